@@ -0,0 +1,66 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Rough timings of the image pipeline on a long, full width receipt.
+//! Compare `cargo bench` with `cargo bench --features rayon`.
+
+use epson::{Command, ImageAdjustments, ImageScale};
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 20;
+
+fn receipt() -> image::GrayImage {
+    image::GrayImage::from_fn(576, 4000, |x, y| {
+        image::Luma([((x * 7 + y * 13) % 256) as u8])
+    })
+}
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let each: Duration = start.elapsed() / ITERATIONS;
+    println!("{:<24} {:>10.2?} per iteration", name, each);
+}
+
+fn main() {
+    let img = receipt();
+
+    bench("pack raster", || {
+        let bytes = Command::Image(img.clone(), ImageScale::Normal)
+            .as_bytes()
+            .unwrap();
+        std::hint::black_box(bytes);
+    });
+
+    bench("adjust tones", || {
+        let mut img = img.clone();
+        ImageAdjustments {
+            gamma: 1.8,
+            ..Default::default()
+        }
+        .apply(&mut img);
+        std::hint::black_box(img);
+    });
+}
+
+// vim: foldmethod=marker
@@ -0,0 +1,570 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Build random, valid, [Command]s from unstructured bytes, for fuzzing
+//! and property tests of the ESC/POS encoder and [decode].
+//!
+//! This mirrors the shape of the `arbitrary` crate, without depending on
+//! it: a fuzz target hands its input to [Unstructured], and asks it for
+//! any type which implements [Arbitrary]. Every value built this way is
+//! within the ranges the encoder accepts, and once the input runs out,
+//! zeros are used for the rest.
+//!
+//! ```rust
+//! use epson::arbitrary::{round_trip, Unstructured};
+//! use epson::Command;
+//!
+//! let data = [7, 42, 1, 2, 3, 4, 5, 6, 7, 8];
+//! let cmd: Command = Unstructured::new(&data).arbitrary();
+//! round_trip(&cmd);
+//! ```
+
+use super::{
+    decode, Alignment, AsbFlags, Barcode, CharacterSet, CodePage, Color, ColumnImageMode, Command,
+    CustomizeValue, CutMode, Decoded, DrawerPin, EffectColor, ImageScale, InfoKind, KanjiCode,
+    KanjiStyle, MaintenanceCounter, NvKey, PaperLayout, QrCode, QrErrorCorrection, QrModel,
+    StatusKind, TransmitStatusKind, UserGlyph,
+};
+
+/// A source of values, reading from a fuzzer's raw input.
+#[derive(Clone, Debug)]
+pub struct Unstructured<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Unstructured<'a> {
+    /// Read values from `data`.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Return true if all of the input has been used.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Return the next byte of input, or 0 once it has run out.
+    pub fn byte(&mut self) -> u8 {
+        match self.data.split_first() {
+            Some((byte, rest)) => {
+                self.data = rest;
+                *byte
+            }
+            None => 0,
+        }
+    }
+
+    /// Return a number less than `n`, which must not be 0.
+    pub fn below(&mut self, n: u32) -> u32 {
+        let value = if n <= 0x100 {
+            self.byte() as u32
+        } else {
+            u32::from_le_bytes(std::array::from_fn(|_| self.byte()))
+        };
+        value % n
+    }
+
+    /// Return a number from `low` to `high`, inclusive.
+    pub fn range(&mut self, low: u8, high: u8) -> u8 {
+        low + self.below((high - low) as u32 + 1) as u8
+    }
+
+    /// Return a random bool.
+    pub fn bool(&mut self) -> bool {
+        self.byte() & 1 != 0
+    }
+
+    /// Return one of `items`, which must not be empty.
+    pub fn choose<T: Clone>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len() as u32) as usize].clone()
+    }
+
+    /// Return a value of any type which implements [Arbitrary].
+    pub fn arbitrary<T: Arbitrary>(&mut self) -> T {
+        T::arbitrary(self)
+    }
+
+    /// Return a black and white image, one pixel per bit of input.
+    pub fn image(&mut self, width: u32, height: u32) -> image::GrayImage {
+        let mut bits = 0u8;
+        let mut img = image::GrayImage::new(width, height);
+        for (i, pixel) in img.pixels_mut().enumerate() {
+            if i % 8 == 0 {
+                bits = self.byte();
+            }
+            *pixel = image::Luma([if bits & (0x80 >> (i % 8)) != 0 {
+                0
+            } else {
+                255
+            }]);
+        }
+        img
+    }
+
+    fn digits(&mut self, len: usize) -> String {
+        (0..len)
+            .map(|_| (b'0' + self.range(0, 9)) as char)
+            .collect()
+    }
+
+    fn bytes(&mut self, max: u32) -> Vec<u8> {
+        (0..self.below(max + 1)).map(|_| self.byte()).collect()
+    }
+}
+
+/// A type which can be built from [Unstructured] input.
+pub trait Arbitrary: Sized {
+    /// Build a valid value from `u`.
+    fn arbitrary(u: &mut Unstructured) -> Self;
+}
+
+impl Arbitrary for bool {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.bool()
+    }
+}
+
+impl Arbitrary for u8 {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.byte()
+    }
+}
+
+impl Arbitrary for Alignment {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.choose(&[Alignment::Left, Alignment::Center, Alignment::Right])
+    }
+}
+
+impl Arbitrary for CutMode {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.choose(&[CutMode::Full, CutMode::Partial])
+    }
+}
+
+impl Arbitrary for Color {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.choose(&[Color::Black, Color::Red])
+    }
+}
+
+impl Arbitrary for EffectColor {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.choose(&[
+            EffectColor::None,
+            EffectColor::Color1,
+            EffectColor::Color2,
+            EffectColor::Color3,
+        ])
+    }
+}
+
+impl Arbitrary for ImageScale {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.choose(&[
+            ImageScale::Normal,
+            ImageScale::DoubleWidth,
+            ImageScale::DoubleHeight,
+            ImageScale::Quadruple,
+        ])
+    }
+}
+
+impl Arbitrary for DrawerPin {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.choose(&[DrawerPin::Pin2, DrawerPin::Pin5])
+    }
+}
+
+impl Arbitrary for MaintenanceCounter {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.choose(&[
+            MaintenanceCounter::PaperFeed,
+            MaintenanceCounter::HeadEnergizing,
+            MaintenanceCounter::Cuts,
+        ])
+    }
+}
+
+impl Arbitrary for StatusKind {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.choose(&[
+            StatusKind::Printer,
+            StatusKind::OfflineCause,
+            StatusKind::ErrorCause,
+            StatusKind::PaperSensor,
+        ])
+    }
+}
+
+impl Arbitrary for TransmitStatusKind {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.choose(&[TransmitStatusKind::Paper, TransmitStatusKind::Drawer])
+    }
+}
+
+impl Arbitrary for InfoKind {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.choose(&[
+            InfoKind::ModelId,
+            InfoKind::TypeId,
+            InfoKind::VersionId,
+            InfoKind::FirmwareVersion,
+            InfoKind::MakerName,
+            InfoKind::ModelName,
+            InfoKind::SerialNumber,
+        ])
+    }
+}
+
+impl Arbitrary for KanjiCode {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.choose(&[KanjiCode::Jis, KanjiCode::ShiftJis])
+    }
+}
+
+impl Arbitrary for KanjiStyle {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        KanjiStyle {
+            double_width: u.bool(),
+            double_height: u.bool(),
+            underline: u.bool(),
+        }
+    }
+}
+
+impl Arbitrary for PaperLayout {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.choose(&[
+            PaperLayout::Receipt,
+            PaperLayout::Label,
+            PaperLayout::BlackMark,
+        ])
+    }
+}
+
+impl Arbitrary for CodePage {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.choose(CodePage::ALL)
+    }
+}
+
+impl Arbitrary for CharacterSet {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.choose(&[CharacterSet::Raw, CharacterSet::Unicode])
+    }
+}
+
+impl Arbitrary for ColumnImageMode {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.choose(&[
+            ColumnImageMode::EightDotSingle,
+            ColumnImageMode::EightDotDouble,
+            ColumnImageMode::TwentyFourDotSingle,
+            ColumnImageMode::TwentyFourDotDouble,
+        ])
+    }
+}
+
+impl Arbitrary for AsbFlags {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        AsbFlags {
+            drawer: u.bool(),
+            online: u.bool(),
+            error: u.bool(),
+            paper: u.bool(),
+        }
+    }
+}
+
+impl Arbitrary for CustomizeValue {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        if u.bool() {
+            CustomizeValue::PrintDensity(u.range(0, 12) as i8 - 6)
+        } else {
+            CustomizeValue::PrintSpeed(u.range(1, 13))
+        }
+    }
+}
+
+impl Arbitrary for NvKey {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        NvKey::new(u.range(32, 126), u.range(32, 126)).unwrap()
+    }
+}
+
+impl Arbitrary for UserGlyph {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        let width = u.range(0, 12) as usize;
+        let columns: Vec<[u8; 3]> = (0..width).map(|_| [u.byte(), u.byte(), u.byte()]).collect();
+        UserGlyph::from_columns(&columns).unwrap()
+    }
+}
+
+impl Arbitrary for Barcode {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        let len = u.choose(&[11, 12]);
+        Barcode::new_upca(&u.digits(len)).unwrap()
+    }
+}
+
+impl Arbitrary for QrModel {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.choose(&[QrModel::Model1, QrModel::Model2, QrModel::Micro])
+    }
+}
+
+impl Arbitrary for QrErrorCorrection {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        u.choose(&[
+            QrErrorCorrection::L,
+            QrErrorCorrection::M,
+            QrErrorCorrection::Q,
+            QrErrorCorrection::H,
+        ])
+    }
+}
+
+impl Arbitrary for QrCode {
+    /// The data is kept short, but may still be too long for the model
+    /// and error correction level, which the encoder rightly refuses.
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        QrCode {
+            model: u.arbitrary(),
+            module_size: u.range(1, 16),
+            error_correction: u.arbitrary(),
+            data: u.bytes(39),
+        }
+    }
+}
+
+impl Arbitrary for Command {
+    fn arbitrary(u: &mut Unstructured) -> Self {
+        match u.below(69) {
+            0 => Command::Init,
+            1 => Command::Cut,
+            2 => Command::LegacyPartialCut,
+            3 => Command::CutWith(u.arbitrary(), u.bool().then(|| u.byte())),
+            4 => Command::Underline(u.arbitrary()),
+            5 => Command::Emphasize(u.arbitrary()),
+            6 => Command::DoubleStrike(u.arbitrary()),
+            7 => Command::Reverse(u.arbitrary()),
+            8 => Command::Rotate90(u.arbitrary()),
+            9 => Command::Smoothing(u.arbitrary()),
+            10 => Command::PrintColor(u.arbitrary()),
+            11 => Command::CharacterColor(u.arbitrary()),
+            12 => Command::BackgroundColor(u.arbitrary()),
+            13 => Command::Shadow(u.bool().then(|| u.arbitrary())),
+            14 => Command::Justification(u.arbitrary()),
+            15 => Command::Speed(u.byte()),
+            16 => Command::Density(u.range(0, 12) as i8 - 6),
+            17 => Command::EnterUserSetting,
+            18 => Command::ExitUserSetting,
+            19 => Command::SetCustomizeValue(u.arbitrary()),
+            20 => Command::Feed(u.byte()),
+            21 => Command::FeedUnits(u.byte()),
+            22 => Command::ReverseFeed(u.byte()),
+            23 => Command::TextSize {
+                width: u.range(1, 8),
+                height: u.range(1, 8),
+            },
+            24 => Command::DoubleWidth(u.arbitrary()),
+            25 => Command::DoubleHeight(u.arbitrary()),
+            26 => Command::LegacyPrintMode {
+                double_width: u.bool(),
+                double_height: u.bool(),
+            },
+            27 => Command::DefaultLineSpacing,
+            28 => Command::LineSpacing(u.byte()),
+            29 => {
+                let mut stops: Vec<u8> = (0..u.below(32)).map(|_| u.range(1, 255)).collect();
+                stops.sort();
+                stops.dedup();
+                Command::SetTabStops(stops)
+            }
+            30 => Command::HorizontalTab,
+            31 => Command::CashDrawer {
+                pin: u.arbitrary(),
+                on_ms: u.byte() & !1,
+                off_ms: u.byte() & !1,
+            },
+            32 => Command::Buzzer {
+                pattern: u.range(1, 10),
+                repetitions: u.range(1, 63),
+            },
+            33 => {
+                let count = u.range(1, 4);
+                Command::DefineUserGlyphs {
+                    first: u.range(32, 126 - count),
+                    glyphs: (0..count).map(|_| u.arbitrary()).collect(),
+                }
+            }
+            34 => Command::UserGlyphs(u.arbitrary()),
+            35 => Command::ClearUserGlyph(u.range(32, 126)),
+            36 => Command::MacroExecute {
+                count: u.byte(),
+                interval: u.byte(),
+                button: u.bool(),
+            },
+            37 => Command::RealTimeStatusRequest(u.arbitrary()),
+            38 => Command::EnableAsb(u.arbitrary()),
+            39 => Command::RequestPrinterId(u.arbitrary()),
+            40 => Command::RequestMaintenanceCounter {
+                counter: u.arbitrary(),
+                cumulative: u.bool(),
+            },
+            41 => Command::ResetMaintenanceCounter(u.arbitrary()),
+            42 => Command::CancelPageData,
+            43 => Command::ClearBuffers,
+            44 => Command::RealTimeDrawerPulse {
+                pin: u.arbitrary(),
+                duration: u.range(1, 8),
+            },
+            45 => Command::KanjiMode(u.arbitrary()),
+            46 => Command::KanjiCodeSystem(u.arbitrary()),
+            47 => Command::KanjiStyle(u.arbitrary()),
+            48 => Command::SetPaperLayout(u.arbitrary()),
+            49 => Command::FeedToLabelOrigin,
+            50 => Command::FeedToCutPosition,
+            51 => Command::FeedToPeelPosition,
+            52 => Command::CodePage(u.arbitrary()),
+            53 => Command::CharacterSet(u.arbitrary()),
+            54 => {
+                let (width, height) = (8 * u.range(1, 8) as u32, u.range(1, 20) as u32);
+                Command::StoreGraphics {
+                    color: u.arbitrary(),
+                    image: u.image(width, height),
+                }
+            }
+            55 => Command::PrintGraphics,
+            56 => {
+                let (width_bytes, height) = (u.range(1, 8) as u16, u.range(1, 20) as u16);
+                Command::RasterBits {
+                    width_bytes,
+                    height,
+                    data: (0..width_bytes * height).map(|_| u.byte()).collect(),
+                }
+            }
+            57 => {
+                let (width, height) = (8 * u.range(1, 8) as u32, u.range(1, 20) as u32);
+                Command::Image(u.image(width, height), u.arbitrary())
+            }
+            58 => {
+                let (width, height) = (8 * u.range(1, 8) as u32, u.range(1, 20) as u32);
+                Command::DefineNvLogo {
+                    key: u.arbitrary(),
+                    image: u.image(width, height),
+                }
+            }
+            59 => Command::PrintNvLogo {
+                key: u.arbitrary(),
+                scale: u.arbitrary(),
+            },
+            60 => Command::DeleteNvLogo(u.arbitrary()),
+            61 => Command::DeleteAllNvLogos,
+            62 => {
+                let mode: ColumnImageMode = u.arbitrary();
+                let width = u.range(1, 40) as u32;
+                if u.bool() {
+                    let height = mode.column_bytes() * 8;
+                    Command::ColumnBand(mode, u.image(width, height))
+                } else {
+                    let height = u.range(1, 60) as u32;
+                    Command::ColumnImage(mode, u.image(width, height))
+                }
+            }
+            63 => {
+                let (width, height) = (u.range(1, 40) as u32, u.range(1, 40) as u32);
+                Command::DefineDownloadedImage(u.image(width, height))
+            }
+            64 => Command::PrintDownloadedImage(u.arbitrary()),
+            65 => Command::TransmitStatus(u.arbitrary()),
+            66 => Command::RequestProcessId(std::array::from_fn(|_| b'0' + u.range(0, 9))),
+            67 => u.choose(&[Command::RecoverAndRestart, Command::RecoverAndCancel]),
+            _ => {
+                if u.bool() {
+                    Command::Barcode(u.arbitrary())
+                } else {
+                    Command::QrCode(u.arbitrary())
+                }
+            }
+        }
+    }
+}
+
+/// Check that `cmd` survives being encoded and decoded: it must decode
+/// without any unknown bytes, encode back to the same bytes, and decode
+/// to the same commands again once in that form. Commands the encoder
+/// refuses, such as a QR Code too large for its model, are skipped.
+///
+/// This panics if any of those don't hold, so it can be called straight
+/// from a fuzz target.
+pub fn round_trip(cmd: &Command) {
+    let Ok(bytes) = cmd.as_bytes() else {
+        return;
+    };
+    let decoded = decode(&bytes);
+    assert!(
+        !decoded.iter().any(|d| matches!(d, Decoded::Unknown(_))),
+        "{:?} decoded to {:?}",
+        cmd,
+        decoded
+    );
+    let encoded = encode(&decoded);
+    assert_eq!(bytes, encoded, "{:?}", cmd);
+    assert_eq!(decoded, decode(&encoded), "{:?}", cmd);
+}
+
+fn encode(items: &[Decoded]) -> Vec<u8> {
+    items
+        .iter()
+        .flat_map(|item| match item {
+            Decoded::Command(cmd) => cmd.as_bytes().unwrap(),
+            Decoded::Text(text) => text.clone(),
+            Decoded::Unknown(bytes) => bytes.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unstructured_exhausted() {
+        let mut u = Unstructured::new(&[1, 0xff]);
+        assert!(u.bool());
+        assert_eq!(5, u.below(10));
+        assert!(u.is_empty());
+        assert_eq!(0, u.byte());
+        assert_eq!(3, u.range(3, 9));
+        // Empty input still builds a valid command.
+        assert_eq!(Command::Init, u.arbitrary());
+    }
+
+    #[test]
+    fn unstructured_image() {
+        let img = Unstructured::new(&[0b1010_0000]).image(4, 2);
+        let pixels: Vec<u8> = img.pixels().map(|p| p.0[0]).collect();
+        assert_eq!(vec![0, 255, 0, 255, 255, 255, 255, 255], pixels);
+    }
+}
+
+// vim: foldmethod=marker
@@ -18,8 +18,30 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE. }}}
 
-use super::{Alignment, CharacterSet, Command, Error as EpsonError, Model};
+#[cfg(feature = "bidi")]
+use super::bidi;
+#[cfg(feature = "textimage")]
+use super::codepage::encodable;
+use super::codepage::Encoder;
+use super::document::{Style, StyleSheet};
+use super::epson_image::{bands, fit_width, IMAGE_BAND_HEIGHT};
+use super::replay::chunks;
+#[cfg(feature = "svg")]
+use super::svg;
+use super::tcp::{self, TcpOptions};
+#[cfg(feature = "textimage")]
+use super::textimage;
+use super::wrap::split_line;
+use super::{
+    wrap_text, Alignment, AsbFlags, Barcode, CharacterSet, CodePage, Color, ColorConversion,
+    ColumnImageMode, Command, CustomizeValue, CutCommand, CutMode, DrawerPin, EffectColor,
+    Error as EpsonError, ImageAdjustments, ImageOptions, ImageScale, InfoKind, JobResult,
+    KanjiCode, KanjiStyle, MaintenanceCounter, MaxWidthBehavior, Model, NvKey, Pacing, PaperLayout,
+    PrintJob, QrCode, Receipt, RuleStyle, StatusKind, StyledText, TransmitStatusKind, UserGlyph,
+};
+use std::time::Instant;
 use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::net::ToSocketAddrs;
 
 /// All possible errors that can be returned from the AsyncWriter struct.
 #[derive(Debug)]
@@ -62,24 +84,101 @@ type Write = dyn AsyncWrite + Unpin + Send;
 pub struct AsyncWriter {
     w: Box<Write>,
     model: Model,
+    cut_command: Option<CutCommand>,
+    code_page: CodePage,
+    character_set: CharacterSet,
+    style_sheet: StyleSheet,
+    style: Style,
+    style_stack: Vec<(Style, CodePage)>,
 }
 
 impl AsyncWriter {
     /// Create a new Writer, wrapping the provided `tokio::io::AsyncWrite`.
     pub async fn open(model: Model, w: Box<Write>) -> Result<Self> {
-        let mut r = Self { w, model };
+        let mut r = Self {
+            w,
+            model,
+            cut_command: None,
+            code_page: CodePage::Pc437,
+            character_set: CharacterSet::Raw,
+            style_sheet: StyleSheet::new(),
+            style: Style::default(),
+            style_stack: vec![],
+        };
         r.init().await?;
         Ok(r)
     }
 
+    /// Connect to the printer at `addr` over TCP, usually on port 9100,
+    /// and create a new AsyncWriter for it, applying [TcpOptions] as
+    /// [crate::Writer::connect_tcp] does, so a printer which is switched
+    /// off fails rather than hanging.
+    ///
+    /// The connection is a [tokio::net::TcpStream], and a write which
+    /// makes no progress for the write timeout fails with
+    /// `ErrorKind::TimedOut`. This must be called from within a tokio
+    /// runtime with its I/O and time drivers enabled.
+    pub async fn connect_tcp(
+        model: Model,
+        addr: impl ToSocketAddrs,
+        options: &TcpOptions,
+    ) -> Result<Self> {
+        let stream = tcp::connect_async(addr, options).await?;
+        Self::open(model, Box::new(stream)).await
+    }
+
     /// initialize the epson printer
-    async fn init(&mut self) -> Result<()> {
+    pub(crate) async fn init(&mut self) -> Result<()> {
         self.write_command(Command::Init).await
     }
 
-    /// cut the printer paper
+    /// Switch the [CodePage] used to print bytes above 0x7F. Models only
+    /// support some code pages, see [Model::get_code_pages].
+    pub async fn code_page(&mut self, page: CodePage) -> Result<()> {
+        if !self.model.supports_code_page(page) {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::CodePage(page)).await
+    }
+
+    /// cut the printer paper, using the [CutCommand] the model expects
     pub async fn cut(&mut self) -> Result<()> {
-        self.write_command(Command::Cut).await
+        let cut = self
+            .cut_command
+            .unwrap_or_else(|| self.model.get_cut_command());
+        if !self.model.supports_cut_mode(cut.mode()) {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(cut.command()).await
+    }
+
+    /// Override the [CutCommand] used by `cut`, rather than the one the
+    /// [Model] expects. Passing `None` goes back to the model's choice.
+    pub fn set_cut_command(&mut self, command: Option<CutCommand>) {
+        self.cut_command = command;
+    }
+
+    /// Partially cut the printer paper, leaving a bit attached.
+    pub async fn partial_cut(&mut self) -> Result<()> {
+        if !self.model.supports_cut_mode(CutMode::Partial) {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::CutWith(CutMode::Partial, None))
+            .await
+    }
+
+    /// Feed the paper to the cutting position plus `feed` motion units,
+    /// and then cut it using the provided [CutMode].
+    pub async fn cut_with_feed(&mut self, mode: CutMode, feed: u8) -> Result<()> {
+        if !self.model.supports_cut_mode(mode) {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::CutWith(mode, Some(feed))).await
+    }
+
+    /// Return the model this writer was opened for.
+    pub fn model(&self) -> Model {
+        self.model
     }
 
     /// Set unicode mode on the printer, if supported.
@@ -121,22 +220,384 @@ impl AsyncWriter {
         self.write_command(Command::DoubleStrike(state)).await
     }
 
+    /// If true, rotate the text printed after this command by 90 degrees
+    /// clockwise. If false, return to normal printing. Not all models
+    /// support rotated text.
+    pub async fn rotate90(&mut self, state: bool) -> Result<()> {
+        if !self.model.supports_rotation() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::Rotate90(state)).await
+    }
+
+    /// If true, smooth the text printed after this command, which makes
+    /// scaled up text look a lot less jagged. If false, disable smoothing.
+    pub async fn smoothing(&mut self, state: bool) -> Result<()> {
+        self.write_command(Command::Smoothing(state)).await
+    }
+
+    /// Select the [Color] of the text printed after this command. This is
+    /// only supported on two-color models.
+    pub async fn print_color(&mut self, color: Color) -> Result<()> {
+        if !self.model.supports_two_color() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::PrintColor(color)).await
+    }
+
+    /// If true, enable Kanji mode, where multibyte character codes are
+    /// printed as Kanji. If false, return to single byte characters. Only
+    /// models with a Kanji ROM support this.
+    pub async fn kanji_mode(&mut self, state: bool) -> Result<()> {
+        if !self.model.supports_kanji() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::KanjiMode(state)).await
+    }
+
+    /// Select the [KanjiCode] used to encode Kanji characters.
+    pub async fn kanji_code(&mut self, code: KanjiCode) -> Result<()> {
+        if !self.model.supports_kanji() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::KanjiCodeSystem(code)).await
+    }
+
+    /// Set the [KanjiStyle] used to print Kanji characters.
+    pub async fn kanji_style(&mut self, style: KanjiStyle) -> Result<()> {
+        if !self.model.supports_kanji() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::KanjiStyle(style)).await
+    }
+
+    /// Select the [EffectColor] of the text printed after this command.
+    /// Only supported on models with character effects.
+    pub async fn character_color(&mut self, color: EffectColor) -> Result<()> {
+        self.write_effect_command(Command::CharacterColor(color))
+            .await
+    }
+
+    /// Select the background [EffectColor] of the text printed after this
+    /// command. Only supported on models with character effects.
+    pub async fn background_color(&mut self, color: EffectColor) -> Result<()> {
+        self.write_effect_command(Command::BackgroundColor(color))
+            .await
+    }
+
+    /// Print a shadow of the provided [EffectColor] behind the text printed
+    /// after this command, or remove it if `None`. Only supported on models
+    /// with character effects.
+    pub async fn shadow(&mut self, color: Option<EffectColor>) -> Result<()> {
+        self.write_effect_command(Command::Shadow(color)).await
+    }
+
     /// Set the horizontal justification of the text printed after this
     /// command.
     pub async fn justify(&mut self, alignment: Alignment) -> Result<()> {
         self.write_command(Command::Justification(alignment)).await
     }
 
+    /// Scale the text printed after this command. Both `width` and `height`
+    /// are multipliers between 1 (normal size) and 8.
+    pub async fn set_text_size(&mut self, width: u8, height: u8) -> Result<()> {
+        self.write_command(Command::TextSize { width, height })
+            .await
+    }
+
+    /// If true, print the text after this command twice as wide. This
+    /// resets the text height to normal.
+    pub async fn double_width(&mut self, state: bool) -> Result<()> {
+        self.write_command(if self.model.supports_text_size() {
+            Command::DoubleWidth(state)
+        } else {
+            Command::LegacyPrintMode {
+                double_width: state,
+                double_height: false,
+            }
+        })
+        .await
+    }
+
+    /// If true, print the text after this command twice as tall. This
+    /// resets the text width to normal.
+    pub async fn double_height(&mut self, state: bool) -> Result<()> {
+        self.write_command(if self.model.supports_text_size() {
+            Command::DoubleHeight(state)
+        } else {
+            Command::LegacyPrintMode {
+                double_width: false,
+                double_height: state,
+            }
+        })
+        .await
+    }
+
+    /// If true, print the text after this command twice as wide and twice
+    /// as tall, otherwise return to normal size text.
+    pub async fn double_size(&mut self, state: bool) -> Result<()> {
+        self.write_command(if self.model.supports_text_size() {
+            let size = if state { 2 } else { 1 };
+            Command::TextSize {
+                width: size,
+                height: size,
+            }
+        } else {
+            Command::LegacyPrintMode {
+                double_width: state,
+                double_height: state,
+            }
+        })
+        .await
+    }
+
+    /// Reset the spacing between lines to the printer's default.
+    pub async fn default_line_spacing(&mut self) -> Result<()> {
+        self.write_command(Command::DefaultLineSpacing).await
+    }
+
+    /// Set the spacing between lines to the provided number of motion
+    /// units (typically dots).
+    pub async fn line_spacing(&mut self, spacing: u8) -> Result<()> {
+        self.write_command(Command::LineSpacing(spacing)).await
+    }
+
+    /// Set the horizontal tab stops, in columns from the start of the line.
+    /// Stops must be ascending, and no more than 32 may be set.
+    pub async fn set_tab_stops(&mut self, stops: Vec<u8>) -> Result<()> {
+        self.write_command(Command::SetTabStops(stops)).await
+    }
+
+    /// Move to the next horizontal tab stop.
+    pub async fn tab(&mut self) -> Result<()> {
+        self.write_command(Command::HorizontalTab).await
+    }
+
+    /// Select the kind of paper loaded in a label printer.
+    pub async fn set_paper_layout(&mut self, layout: PaperLayout) -> Result<()> {
+        self.write_label_command(Command::SetPaperLayout(layout))
+            .await
+    }
+
+    /// Feed label or black mark paper to the start of the next label.
+    pub async fn feed_to_label(&mut self) -> Result<()> {
+        self.write_label_command(Command::FeedToLabelOrigin).await
+    }
+
+    /// Feed label or black mark paper to the cutting position, such as
+    /// before calling [Self::cut].
+    pub async fn feed_to_cut_position(&mut self) -> Result<()> {
+        self.write_label_command(Command::FeedToCutPosition).await
+    }
+
+    /// Feed label paper to the label peeling position.
+    pub async fn feed_to_peel_position(&mut self) -> Result<()> {
+        if !self.model.supports_label_peeler() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_label_command(Command::FeedToPeelPosition).await
+    }
+
     /// Feed the specified number of lines out of the printer.
     pub async fn feed(&mut self, count: u8) -> Result<()> {
         self.write_command(Command::Feed(count)).await
     }
 
-    /// Set the printer speed to the provided value.
+    /// Feed the paper by the specified number of motion units (typically
+    /// dots).
+    pub async fn feed_units(&mut self, units: u8) -> Result<()> {
+        self.write_command(Command::FeedUnits(units)).await
+    }
+
+    /// Feed the paper by the specified distance in millimeters, converted
+    /// to dots using [Model::mm_to_dots].
+    pub async fn feed_mm(&mut self, mm: f32) -> Result<()> {
+        if !mm.is_finite() || mm < 0.0 {
+            return Err(EpsonError::InvalidParameter.into());
+        }
+
+        let mut dots = self.model.mm_to_dots(mm);
+        while dots > 0 {
+            let units = dots.min(u8::MAX as u32);
+            self.write_command(Command::FeedUnits(units as u8)).await?;
+            dots -= units;
+        }
+        Ok(())
+    }
+
+    /// Feed the specified number of lines backwards. Most models can only
+    /// reverse feed a few lines; see [Model::get_max_reverse_feed].
+    pub async fn reverse_feed(&mut self, count: u8) -> Result<()> {
+        let max = self.model.get_max_reverse_feed();
+        if max == 0 {
+            return Err(EpsonError::Unsupported.into());
+        }
+        if count > max {
+            return Err(EpsonError::InvalidParameter.into());
+        }
+        self.write_command(Command::ReverseFeed(count)).await
+    }
+
+    /// Set the printer speed to the provided value. This will return
+    /// an error if the speed is outside of [Model::get_speed_range].
     pub async fn speed(&mut self, speed: u8) -> Result<()> {
+        if !self.model.get_speed_range().contains(&speed) {
+            return Err(EpsonError::InvalidParameter.into());
+        }
+        self.speed_unchecked(speed).await
+    }
+
+    /// Set the printer speed to the provided value, without any model
+    /// checks. Only use this if you know your printer's firmware accepts
+    /// the value.
+    pub async fn speed_unchecked(&mut self, speed: u8) -> Result<()> {
         self.write_command(Command::Speed(speed)).await
     }
 
+    /// Open the cash drawer attached to the provided pin of the drawer
+    /// kick-out connector, using a 50ms pulse.
+    pub async fn open_drawer(&mut self, pin: DrawerPin) -> Result<()> {
+        if !self.model.supports_cash_drawer() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::CashDrawer {
+            pin,
+            on_ms: 50,
+            off_ms: 250,
+        })
+        .await
+    }
+
+    /// Open the cash drawer attached to the provided pin right away, even if
+    /// the printer is busy printing. `duration` is the length of the pulse
+    /// in 100ms units, from 1 through 8.
+    pub async fn pulse_drawer(&mut self, pin: DrawerPin, duration: u8) -> Result<()> {
+        if !self.model.supports_cash_drawer() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::RealTimeDrawerPulse { pin, duration })
+            .await
+    }
+
+    /// Sound the printer's built-in buzzer, playing `pattern` (1 through 10)
+    /// `repetitions` times. Not all models have a buzzer.
+    pub async fn buzzer(&mut self, pattern: u8, repetitions: u8) -> Result<()> {
+        if !self.model.supports_buzzer() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::Buzzer {
+            pattern,
+            repetitions,
+        })
+        .await
+    }
+
+    /// Upload a [UserGlyph] to the printer, to be printed in place of the
+    /// character `code` (between 32 and 126) once enabled with
+    /// [Self::user_glyphs].
+    pub async fn define_glyph(&mut self, code: u8, glyph: UserGlyph) -> Result<()> {
+        self.write_command(Command::DefineUserGlyphs {
+            first: code,
+            glyphs: vec![glyph],
+        })
+        .await
+    }
+
+    /// If true, print user-defined characters in place of the built-in
+    /// ones. If false, go back to the built-in character set.
+    pub async fn user_glyphs(&mut self, state: bool) -> Result<()> {
+        self.write_command(Command::UserGlyphs(state)).await
+    }
+
+    /// Remove the user-defined character for `code`.
+    pub async fn clear_glyph(&mut self, code: u8) -> Result<()> {
+        self.write_command(Command::ClearUserGlyph(code)).await
+    }
+
+    /// Record the commands written by `f` as a macro stored in the printer,
+    /// rather than printing them. The macro can then be replayed with
+    /// [Self::execute_macro].
+    pub async fn record_macro<F>(&mut self, f: F) -> Result<()>
+    where
+        F: AsyncFnOnce(&mut Self) -> Result<()>,
+    {
+        self.write_command(Command::MacroStart).await?;
+        let ret = f(self).await;
+        self.write_command(Command::MacroEnd).await?;
+        ret
+    }
+
+    /// Execute the stored macro `count` times, waiting `interval` (in
+    /// 100ms units) between each, or for the FEED button to be pressed
+    /// if `button` is true.
+    pub async fn execute_macro(&mut self, count: u8, interval: u8, button: bool) -> Result<()> {
+        self.write_command(Command::MacroExecute {
+            count,
+            interval,
+            button,
+        })
+        .await
+    }
+
+    /// Set the print density, relative to the standard density, from -6
+    /// (lightest) through 6 (darkest). This lasts until the printer is
+    /// reset.
+    pub async fn density(&mut self, density: i8) -> Result<()> {
+        self.write_command(Command::Density(density)).await
+    }
+
+    /// Store the provided [CustomizeValue]s in the printer's non-volatile
+    /// memory, entering and leaving user setting mode around them.
+    ///
+    /// Leaving user setting mode resets the printer, so this should be done
+    /// before printing anything. NV memory has a limited number of write
+    /// cycles, so don't do this for every receipt.
+    pub async fn set_customize_values(&mut self, values: &[CustomizeValue]) -> Result<()> {
+        for value in values {
+            value.setting()?;
+        }
+
+        self.write_command(Command::EnterUserSetting).await?;
+        for value in values {
+            self.write_command(Command::SetCustomizeValue(*value))
+                .await?;
+        }
+        self.write_command(Command::ExitUserSetting).await
+    }
+
+    /// Print a greyscale image, prepared and printed as described by the
+    /// provided [ImageOptions]. The other `print_image` methods are
+    /// shorthand for common options.
+    pub async fn print_image_with(
+        &mut self,
+        mut img: image::GrayImage,
+        options: ImageOptions,
+    ) -> Result<()> {
+        options.adjustments.apply(&mut img);
+
+        let max_width = self.model.get_max_image_width() as u32 / options.scale.factors().0 as u32;
+        let mut img = match options.max_width_behavior {
+            MaxWidthBehavior::Error => img,
+            MaxWidthBehavior::Fit => fit_width(img, max_width),
+            MaxWidthBehavior::Crop if img.width() > max_width => {
+                image::imageops::crop_imm(&img, 0, 0, max_width, img.height()).to_image()
+            }
+            MaxWidthBehavior::Crop => img,
+        };
+        self.model.check_image_scaled(&img, options.scale)?;
+        options.dither.apply(&mut img);
+        let img = options.padding.apply(img)?;
+
+        if let Some(align) = options.align {
+            self.justify(align).await?;
+        }
+        self.write_image(img, options.scale).await?;
+        if options.align.is_some() {
+            self.justify(Alignment::Left).await?;
+        }
+        Ok(())
+    }
+
     /// Print a greyscale image.
     ///
     /// Currently, this image must have a width that's 8 bit aligned,
@@ -144,20 +605,582 @@ impl AsyncWriter {
     /// width of the image is constrained by the underling printer model
     /// provided to `Self::open`.
     pub async fn print_image(&mut self, img: image::GrayImage) -> Result<()> {
-        self.model.check_image(&img)?;
-        self.print_image_unchecked(img).await
+        self.print_image_with(img, ImageOptions::default()).await
+    }
+
+    /// Print a greyscale image, which the printer enlarges by `scale`. This
+    /// is useful for small logos, which can be sent at their native size
+    /// rather than being resampled first.
+    pub async fn print_image_scaled(
+        &mut self,
+        img: image::GrayImage,
+        scale: ImageScale,
+    ) -> Result<()> {
+        self.print_image_with(
+            img,
+            ImageOptions {
+                scale,
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// Print `text` in `style`, wrapped at word boundaries to fit the
+    /// paper with [wrap_text]. The previous style is restored afterwards,
+    /// as with [Self::push_style] and [Self::pop_style].
+    pub async fn print_wrapped(&mut self, text: &str, style: &Style) -> Result<()> {
+        self.push_style();
+        self.set_style(style).await?;
+        for line in wrap_text(text, self.model, style) {
+            self.write_text(&line).await?;
+            self.write_all(b"\n").await?;
+        }
+        self.pop_style().await
+    }
+
+    /// Print `text`, converted into the current [CodePage]. When a
+    /// character isn't in the current code page, this switches to one of
+    /// the model's code pages which has it, and characters which none of
+    /// them have are printed as a question mark.
+    pub async fn print_text(&mut self, text: &str) -> Result<()> {
+        #[cfg(feature = "bidi")]
+        let text = &bidi::visual(text);
+        self.write_text(text).await
+    }
+
+    /// Print `text` like [Self::print_text], followed by a line feed.
+    pub async fn print_line(&mut self, text: &str) -> Result<()> {
+        self.print_text(&format!("{}\n", text)).await
+    }
+
+    /// Print formatted text like [Self::print_text], so that
+    /// `write!(pos, "Total: {:>10}\n", total).await` goes through the same
+    /// code page conversion.
+    pub fn write_fmt(
+        &mut self,
+        args: std::fmt::Arguments<'_>,
+    ) -> impl std::future::Future<Output = Result<()>> + '_ {
+        // Format right away, since the arguments can't be held across an
+        // await.
+        let text = args.to_string();
+        async move { self.print_text(&text).await }
+    }
+
+    /// Return an [Encoder] which carries on from the printer's current
+    /// [CodePage] and [CharacterSet].
+    fn encoder(&self) -> Encoder {
+        let unicode = self.character_set == CharacterSet::Unicode;
+        Encoder::resume(self.model, self.code_page, unicode)
+    }
+
+    /// Send `text` converted into the current [CodePage], switching code
+    /// pages as needed, or as UTF-8 once [Self::set_unicode] is on.
+    async fn write_text(&mut self, text: &str) -> Result<()> {
+        for cmd in self.encoder().text(text) {
+            self.write_command(cmd).await?;
+        }
+        Ok(())
+    }
+
+    /// Print `text` like [Self::print_text], except that lines with
+    /// characters none of the model's code pages have are drawn with
+    /// `font` and printed as an image instead. The whole line becomes an
+    /// image, so that it stays on one line.
+    #[cfg(feature = "textimage")]
+    pub async fn print_text_with_fallback(
+        &mut self,
+        text: &str,
+        font: &textimage::Font,
+    ) -> Result<()> {
+        #[cfg(feature = "bidi")]
+        let text = &bidi::visual(text);
+        let pages = self.model.get_code_pages();
+        for line in text.split_inclusive('\n') {
+            if encodable(line, &pages) {
+                self.write_text(line).await?;
+                continue;
+            }
+            let img = textimage::render(
+                font,
+                line.trim_end_matches('\n'),
+                textimage::FONT_A_SIZE,
+                self.model,
+            );
+            self.print_image(img).await?;
+        }
+        Ok(())
+    }
+
+    /// Set the [StyleSheet] used by [Self::apply_style].
+    pub fn set_style_sheet(&mut self, sheet: StyleSheet) {
+        self.style_sheet = sheet;
+    }
+
+    /// Switch to the style called `name` in the [StyleSheet], sending only
+    /// what differs from the current style. The previous style comes back
+    /// with [Self::restore_style]. Returns [EpsonError::InvalidParameter]
+    /// if there's no style by that name.
+    pub async fn apply_style(&mut self, name: &str) -> Result<()> {
+        let style = self
+            .style_sheet
+            .get(name)
+            .ok_or(EpsonError::InvalidParameter)?
+            .clone();
+        self.push_style();
+        self.set_style(&style).await
+    }
+
+    /// Go back to the style in use before the last [Self::apply_style].
+    pub async fn restore_style(&mut self) -> Result<()> {
+        self.pop_style().await
+    }
+
+    /// Return the current text style, as tracked from the commands sent
+    /// so far. Bytes sent with `write_all` aren't tracked.
+    pub fn style(&self) -> &Style {
+        &self.style
+    }
+
+    /// Switch to `style`, sending only the commands for what differs from
+    /// the current style.
+    pub async fn set_style(&mut self, style: &Style) -> Result<()> {
+        for cmd in style.commands_from(&self.style.clone(), self.model) {
+            self.write_command(cmd).await?;
+        }
+        Ok(())
+    }
+
+    /// Save the current style and [CodePage], to be restored with
+    /// [Self::pop_style].
+    pub fn push_style(&mut self) {
+        self.style_stack.push((self.style.clone(), self.code_page));
+    }
+
+    /// Go back to the style and [CodePage] saved by the last
+    /// [Self::push_style], sending only the commands for what differs.
+    pub async fn pop_style(&mut self) -> Result<()> {
+        let Some((style, page)) = self.style_stack.pop() else {
+            return Ok(());
+        };
+        self.set_style(&style).await?;
+        if page != self.code_page {
+            self.code_page(page).await?;
+        }
+        Ok(())
+    }
+
+    /// Go back to the default style: normal sized, left justified, text
+    /// without emphasis or underline.
+    pub async fn reset_style(&mut self) -> Result<()> {
+        self.set_style(&Style::default()).await
+    }
+
+    /// Print `text` in the style called `name`, then restore the previous
+    /// style.
+    pub async fn print_with_style(&mut self, name: &str, text: &str) -> Result<()> {
+        self.apply_style(name).await?;
+        self.print_text(text).await?;
+        self.restore_style().await
+    }
+
+    /// Characters across the paper at the current text width.
+    fn columns(&self) -> usize {
+        self.model.get_columns() / self.style.width.max(1) as usize
+    }
+
+    /// Print `left` and `right` on one line, pushed to either edge of the
+    /// paper, such as a label and a price. `left` is shortened if both
+    /// don't fit across the line at the current text width.
+    pub async fn print_split(&mut self, left: &str, right: &str) -> Result<()> {
+        let line = split_line(left, right, self.columns());
+        self.write_text(&format!("{}\n", line)).await
+    }
+
+    /// Print a separator across the full width of the paper.
+    pub async fn print_rule(&mut self, style: RuleStyle) -> Result<()> {
+        match style.text(self.columns()) {
+            Some(text) => self.write_text(&text).await,
+            None => {
+                let width = self.model.get_max_image_width() as u32;
+                self.print_image(image::GrayImage::new(width, 1)).await
+            }
+        }
+    }
+
+    /// Print `text` as an image, drawn with a TrueType `font` at `size`
+    /// dots tall. This can print any character the font has, whatever
+    /// the printer supports.
+    #[cfg(feature = "textimage")]
+    pub async fn print_text_image(
+        &mut self,
+        font: &textimage::Font,
+        text: &str,
+        size: f32,
+    ) -> Result<()> {
+        self.print_image(textimage::render(font, text, size, self.model))
+            .await
+    }
+
+    /// Print an SVG image, rendered at the full printable width of the
+    /// paper. See [svg] for the parts of SVG which are supported.
+    #[cfg(feature = "svg")]
+    pub async fn print_svg(&mut self, svg: &str) -> Result<()> {
+        let width = self.model.get_max_image_width() as u32;
+        self.print_image(svg::render(svg, width)?).await
+    }
+
+    /// Print [StyledText], sending only the style changes between its
+    /// spans.
+    pub async fn print_styled(&mut self, text: &StyledText) -> Result<()> {
+        let mut encoder = self.encoder();
+        for cmd in text.encode(|text| encoder.text(text)) {
+            self.write_command(cmd).await?;
+        }
+        Ok(())
+    }
+
+    /// Send a [PrintJob] to the printer in a single write, and flush it.
+    /// The job must have been built for the same model as this writer.
+    pub async fn submit(&mut self, job: &PrintJob) -> Result<JobResult> {
+        if job.model() != self.model {
+            return Err(EpsonError::Unsupported.into());
+        }
+        let start = Instant::now();
+        self.write_all(job.as_bytes()).await?;
+        self.flush().await?;
+        for cmd in job.commands() {
+            self.track(cmd);
+        }
+        Ok(JobResult {
+            commands: job.commands().len(),
+            bytes: job.as_bytes().len(),
+            elapsed: start.elapsed(),
+        })
+    }
+
+    /// Print a [Receipt], checking it against the configured model first.
+    pub async fn print_receipt(&mut self, receipt: &Receipt) -> Result<()> {
+        for cmd in receipt.build()? {
+            self.write_command(cmd).await?;
+        }
+        Ok(())
+    }
+
+    /// Print a color image, such as an RGB or RGBA image loaded from disk,
+    /// converting it to greyscale with the provided [ColorConversion].
+    pub async fn print_color_image(
+        &mut self,
+        img: &image::DynamicImage,
+        conversion: ColorConversion,
+    ) -> Result<()> {
+        self.print_image(conversion.convert(img)).await
+    }
+
+    /// Print a greyscale image, after applying the provided
+    /// [ImageAdjustments] to lighten or darken it.
+    pub async fn print_image_adjusted(
+        &mut self,
+        img: image::GrayImage,
+        adjustments: ImageAdjustments,
+    ) -> Result<()> {
+        self.print_image_with(
+            img,
+            ImageOptions {
+                adjustments,
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// Print a greyscale image, scaling it down to the configured model's
+    /// maximum image width first if it's too wide. The aspect ratio is
+    /// kept, so the image will get shorter too.
+    pub async fn print_image_fit(&mut self, img: image::GrayImage) -> Result<()> {
+        self.print_image_with(
+            img,
+            ImageOptions {
+                max_width_behavior: MaxWidthBehavior::Fit,
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// Print an already packed 1-bit raster image, such as a framebuffer
+    /// or a logo stored in ROM, without going through an image. Each row
+    /// is `width_bytes` bytes, most significant bit on the left, and set
+    /// bits are printed black.
+    pub async fn print_raster_bits(
+        &mut self,
+        width_bytes: u16,
+        height: u16,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        if width_bytes as usize * 8 > self.model.get_max_image_width() {
+            return Err(EpsonError::ImageTooLarge.into());
+        }
+        self.write_command(Command::RasterBits {
+            width_bytes,
+            height,
+            data,
+        })
+        .await
+    }
+
+    /// Print a two-color image on models with two-color paper, from a
+    /// plane printed in black and a plane printed in red. Both planes must
+    /// be the same size. See [crate::split_two_color] to build the planes
+    /// from a color image.
+    pub async fn print_two_color_image(
+        &mut self,
+        black: image::GrayImage,
+        red: image::GrayImage,
+    ) -> Result<()> {
+        if !self.model.supports_two_color() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        if black.dimensions() != red.dimensions() {
+            return Err(EpsonError::InvalidParameter.into());
+        }
+        self.model.check_image(&black)?;
+
+        self.write_command(Command::StoreGraphics {
+            color: Color::Black,
+            image: black,
+        })
+        .await?;
+        self.write_command(Command::StoreGraphics {
+            color: Color::Red,
+            image: red,
+        })
+        .await?;
+        self.write_command(Command::PrintGraphics).await
     }
 
     /// Print a grayscale image, without any model checks. This will let you
     /// do all sorts of invalid things. Don't use this if you can avoid it,
     /// it may result in trash being printed.
     pub async fn print_image_unchecked(&mut self, img: image::GrayImage) -> Result<()> {
-        self.write_command(Command::Image(img)).await
+        self.write_image(img, ImageScale::Normal).await
+    }
+
+    /// Print the provided [Barcode].
+    pub async fn print_barcode(&mut self, barcode: Barcode) -> Result<()> {
+        self.write_command(Command::Barcode(barcode)).await
+    }
+
+    /// Print the provided [QrCode]. This will return an error if the
+    /// configured Model does not support the requested [crate::QrModel].
+    pub async fn print_qr(&mut self, qr: QrCode) -> Result<()> {
+        if !self.model.supports_qr_model(qr.model) {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::QrCode(qr)).await
+    }
+
+    /// Store a greyscale image in the printer's non-volatile memory under
+    /// the provided [NvKey]. The image is checked against the model just
+    /// like [Self::print_image].
+    ///
+    /// NV memory has a limited number of write cycles, so this should be
+    /// done once (for instance, at install time), not once per receipt.
+    pub async fn upload_nv_logo(&mut self, key: NvKey, img: image::GrayImage) -> Result<()> {
+        self.model.check_image(&img)?;
+        self.write_command(Command::DefineNvLogo { key, image: img })
+            .await
+    }
+
+    /// Print an image previously stored with [Self::upload_nv_logo].
+    pub async fn print_nv_logo(&mut self, key: NvKey, scale: ImageScale) -> Result<()> {
+        self.write_command(Command::PrintNvLogo { key, scale })
+            .await
+    }
+
+    /// Delete an image previously stored with [Self::upload_nv_logo].
+    pub async fn delete_nv_logo(&mut self, key: NvKey) -> Result<()> {
+        self.write_command(Command::DeleteNvLogo(key)).await
+    }
+
+    /// Print a greyscale image as column-format `ESC *` bit images, rather
+    /// than a raster image. This is slower, but some older or generic
+    /// printers only render `ESC *` reliably.
+    ///
+    /// Single density modes print every column twice as wide, so the image
+    /// should be half as wide as it would be otherwise.
+    pub async fn print_image_columns(
+        &mut self,
+        img: image::GrayImage,
+        mode: ColumnImageMode,
+    ) -> Result<()> {
+        self.model.check_image(&img)?;
+        self.write_command(Command::ColumnImage(mode, img)).await
+    }
+
+    /// Define a downloaded bit image in the printer's RAM, which can then
+    /// be printed any number of times with [Self::print_downloaded_image]
+    /// until the printer is reset. The image may be no more than 2040 dots
+    /// wide and 384 dots tall, and, with each side rounded up to a multiple
+    /// of 8, no more than 98304 dots in total.
+    pub async fn define_downloaded_image(&mut self, img: image::GrayImage) -> Result<()> {
+        self.model.check_image(&img)?;
+        self.write_command(Command::DefineDownloadedImage(img))
+            .await
+    }
+
+    /// Print the image defined with [Self::define_downloaded_image].
+    pub async fn print_downloaded_image(&mut self, scale: ImageScale) -> Result<()> {
+        self.write_command(Command::PrintDownloadedImage(scale))
+            .await
+    }
+
+    /// Ask the printer to send back a real-time status byte of the provided
+    /// [StatusKind]. The response must be read from the underlying
+    /// transport, and can be parsed with [crate::RealTimeStatus::parse].
+    pub async fn request_status(&mut self, kind: StatusKind) -> Result<()> {
+        self.write_command(Command::RealTimeStatusRequest(kind))
+            .await
+    }
+
+    /// Ask the printer to send back a status byte of the provided
+    /// [TransmitStatusKind], once everything sent before it has been
+    /// processed. The response must be read from the underlying transport.
+    pub async fn transmit_status(&mut self, kind: TransmitStatusKind) -> Result<()> {
+        self.write_command(Command::TransmitStatus(kind)).await
+    }
+
+    /// Ask the printer to send back `id`, four ASCII digits, once
+    /// everything sent before it has finished printing. The response must
+    /// be read from the underlying transport.
+    pub async fn request_process_id(&mut self, id: [u8; 4]) -> Result<()> {
+        self.write_command(Command::RequestProcessId(id)).await
+    }
+
+    /// Enable Automatic Status Back (ASB) for the provided [AsbFlags], after
+    /// which the printer will send a 4 byte frame whenever that status
+    /// changes. Frames must be read from the underlying transport, and can
+    /// be parsed with [crate::AsbStatus::parse]. Pass the default flags to
+    /// disable ASB.
+    pub async fn enable_asb(&mut self, flags: AsbFlags) -> Result<()> {
+        self.write_command(Command::EnableAsb(flags)).await
+    }
+
+    /// Ask the printer to send back information of the provided [InfoKind].
+    /// The response must be read from the underlying transport, and can be
+    /// parsed with [crate::PrinterInfo::parse].
+    pub async fn request_printer_id(&mut self, kind: InfoKind) -> Result<()> {
+        self.write_command(Command::RequestPrinterId(kind)).await
+    }
+
+    /// Ask the printer to send back the value of a [MaintenanceCounter]. If
+    /// `cumulative` is true, the lifetime value is requested. The response
+    /// must be read from the underlying transport, and can be parsed with
+    /// [MaintenanceCounter::parse_value].
+    pub async fn request_maintenance_counter(
+        &mut self,
+        counter: MaintenanceCounter,
+        cumulative: bool,
+    ) -> Result<()> {
+        self.write_command(Command::RequestMaintenanceCounter {
+            counter,
+            cumulative,
+        })
+        .await
+    }
+
+    /// Reset a [MaintenanceCounter] to zero. Cumulative counters can't be
+    /// reset.
+    pub async fn reset_maintenance_counter(&mut self, counter: MaintenanceCounter) -> Result<()> {
+        self.write_command(Command::ResetMaintenanceCounter(counter))
+            .await
+    }
+
+    /// Cancel the current job, by clearing the printer's receive and print
+    /// buffers in real time. Anything sent but not yet printed is lost.
+    pub async fn cancel(&mut self) -> Result<()> {
+        self.write_command(Command::ClearBuffers).await
+    }
+
+    /// Recover from a recoverable error, such as a cleared cutter jam or a
+    /// closed cover, and restart printing from the line where the error
+    /// occurred. If `cancel` is true, the interrupted job is cancelled
+    /// instead, by clearing the receive and print buffers.
+    pub async fn recover(&mut self, cancel: bool) -> Result<()> {
+        self.write_command(if cancel {
+            Command::RecoverAndCancel
+        } else {
+            Command::RecoverAndRestart
+        })
+        .await
+    }
+
+    /// Delete all the print data in the page mode print area.
+    pub async fn cancel_page_data(&mut self) -> Result<()> {
+        self.write_command(Command::CancelPageData).await
+    }
+
+    /// Send an image as a series of `GS v 0` raster images, each no taller
+    /// than [IMAGE_BAND_HEIGHT], which print back to back without a gap.
+    async fn write_image(&mut self, img: image::GrayImage, scale: ImageScale) -> Result<()> {
+        if img.height() <= IMAGE_BAND_HEIGHT {
+            return self.write_command(Command::Image(img, scale)).await;
+        }
+        for band in bands(&img, IMAGE_BAND_HEIGHT) {
+            self.write_command(Command::Image(band, scale)).await?;
+        }
+        Ok(())
+    }
+
+    /// Send a character effect command, if the model supports them.
+    async fn write_effect_command(&mut self, cmd: Command) -> Result<()> {
+        if !self.model.supports_character_effects() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(cmd).await
+    }
+
+    /// Send a label paper command, if the model supports label paper.
+    async fn write_label_command(&mut self, cmd: Command) -> Result<()> {
+        if !self.model.supports_label_paper() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(cmd).await
     }
 
     /// Send a raw command to the Epson printer.
     pub async fn write_command(&mut self, cmd: Command) -> Result<()> {
         self.w.write_all(&cmd.as_bytes()?).await?;
+        self.track(&cmd);
+        Ok(())
+    }
+
+    /// Keep track of the style, [CodePage] and [CharacterSet] `cmd`
+    /// leaves the printer in.
+    fn track(&mut self, cmd: &Command) {
+        self.style.track(cmd);
+        match *cmd {
+            Command::Init => {
+                self.code_page = CodePage::Pc437;
+                self.character_set = CharacterSet::Raw;
+            }
+            Command::CodePage(page) => self.code_page = page,
+            Command::CharacterSet(set) => self.character_set = set,
+            _ => {}
+        }
+    }
+
+    /// Replay a previously captured job, such as a `.bin` file of the raw
+    /// bytes pulled out of a packet capture. See [crate::replay].
+    pub async fn replay(&mut self, job: &[u8], pacing: Pacing) -> Result<()> {
+        for chunk in chunks(job) {
+            self.w.write_all(chunk).await?;
+            if let Some(delay) = pacing.delay(chunk.len()) {
+                self.w.flush().await?;
+                tokio::time::sleep(delay).await;
+            }
+        }
+        self.w.flush().await?;
         Ok(())
     }
 
@@ -166,6 +1189,12 @@ impl AsyncWriter {
         self.w.write_all(buf).await?;
         Ok(())
     }
+
+    /// Flush anything buffered to the underlying socket.
+    pub async fn flush(&mut self) -> Result<()> {
+        self.w.flush().await?;
+        Ok(())
+    }
 }
 
 // vim: foldmethod=marker
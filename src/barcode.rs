@@ -0,0 +1,314 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+use super::Error;
+
+/// Barcode symbologies which can be printed with the `GS k` command.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum BarcodeSystem {
+    /// UPC-A, 11 digits of data followed by a single check digit.
+    UpcA = 65,
+}
+
+/// A barcode which has been validated, and is ready to be sent to the
+/// printer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Barcode {
+    system: BarcodeSystem,
+    data: Vec<u8>,
+}
+
+impl Barcode {
+    /// Create a new UPC-A barcode.
+    ///
+    /// If 11 digits are provided, the check digit will be computed and
+    /// appended. If 12 digits are provided, they will be used as-is,
+    /// without checking the final digit. Use [Barcode::new_upca_strict]
+    /// to verify the check digit of a full 12 digit code.
+    pub fn new_upca(digits: &str) -> Result<Self, Error> {
+        let mut data = parse_digits(digits)?;
+
+        match data.len() {
+            11 => data.push(upca_check_digit(&data)),
+            12 => {}
+            _ => return Err(Error::InvalidBarcode),
+        }
+
+        Ok(Self {
+            system: BarcodeSystem::UpcA,
+            data,
+        })
+    }
+
+    /// Create a new UPC-A barcode from a full 12 digit code, returning
+    /// [Error::InvalidCheckDigit] if the final digit does not match the
+    /// check digit computed from the first 11.
+    pub fn new_upca_strict(digits: &str) -> Result<Self, Error> {
+        let data = parse_digits(digits)?;
+        if data.len() != 12 {
+            return Err(Error::InvalidBarcode);
+        }
+        if upca_check_digit(&data[..11]) != data[11] {
+            return Err(Error::InvalidCheckDigit);
+        }
+
+        Ok(Self {
+            system: BarcodeSystem::UpcA,
+            data,
+        })
+    }
+
+    /// Return the symbology of this barcode.
+    pub fn system(&self) -> BarcodeSystem {
+        self.system
+    }
+
+    /// Return the data that will be encoded into this barcode, as ASCII
+    /// bytes.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// QR Code models which can be selected with `GS ( k` function 165.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum QrModel {
+    /// QR Code Model 1, the original specification.
+    Model1 = 49,
+
+    /// QR Code Model 2, which is what nearly everything expects.
+    Model2 = 50,
+
+    /// Micro QR Code, a much smaller symbol for very short payloads,
+    /// such as loyalty card numbers.
+    Micro = 51,
+}
+
+/// QR Code error correction levels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum QrErrorCorrection {
+    /// Recovers roughly 7% of the symbol.
+    L = 48,
+
+    /// Recovers roughly 15% of the symbol.
+    M = 49,
+
+    /// Recovers roughly 25% of the symbol.
+    Q = 50,
+
+    /// Recovers roughly 30% of the symbol. Not supported by Micro QR.
+    H = 51,
+}
+
+/// A QR Code symbol to be rendered by the printer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QrCode {
+    /// QR Code model to use when encoding the symbol.
+    pub model: QrModel,
+
+    /// Width of a single module, in dots. Must be between 1 and 16.
+    pub module_size: u8,
+
+    /// Error correction level of the symbol.
+    pub error_correction: QrErrorCorrection,
+
+    /// Data to encode in the symbol.
+    pub data: Vec<u8>,
+}
+
+impl QrCode {
+    /// Create a new Model 2 QR Code containing the provided data.
+    pub fn new(data: &[u8]) -> Self {
+        Self {
+            model: QrModel::Model2,
+            module_size: 3,
+            error_correction: QrErrorCorrection::M,
+            data: data.to_vec(),
+        }
+    }
+
+    /// Create a new Micro QR Code containing the provided data.
+    pub fn new_micro(data: &[u8]) -> Self {
+        Self {
+            model: QrModel::Micro,
+            ..Self::new(data)
+        }
+    }
+
+    /// Check that the parameters of this symbol are valid.
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        if !(1..=16).contains(&self.module_size) {
+            return Err(Error::InvalidBarcode);
+        }
+
+        if self.model == QrModel::Micro && self.error_correction == QrErrorCorrection::H {
+            return Err(Error::InvalidBarcode);
+        }
+
+        if self.data.is_empty() || self.data.len() > self.capacity() {
+            return Err(Error::InvalidBarcode);
+        }
+
+        Ok(())
+    }
+
+    /// Return the most characters of this symbol's data that fit in the
+    /// largest symbol of its model and error correction level. Model 1 is
+    /// held to the Model 2 limits.
+    fn capacity(&self) -> usize {
+        let mode = if self.data.iter().all(|b| b.is_ascii_digit()) {
+            0
+        } else if self.data.iter().all(|b| QR_ALPHANUMERIC.contains(b)) {
+            1
+        } else {
+            2
+        };
+        let level = match self.error_correction {
+            QrErrorCorrection::L => 0,
+            QrErrorCorrection::M => 1,
+            QrErrorCorrection::Q => 2,
+            QrErrorCorrection::H => 3,
+        };
+        match self.model {
+            QrModel::Model1 | QrModel::Model2 => QR_CAPACITY[level][mode],
+            QrModel::Micro => MICRO_QR_CAPACITY.get(level).map_or(0, |c| c[mode]),
+        }
+    }
+}
+
+/// Characters which can be encoded in QR Code alphanumeric mode.
+const QR_ALPHANUMERIC: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// Numeric, alphanumeric and byte capacity of a version 40 QR Code, for
+/// error correction levels L, M, Q and H.
+const QR_CAPACITY: [[usize; 3]; 4] = [
+    [7089, 4296, 2953],
+    [5596, 3391, 2331],
+    [3993, 2420, 1663],
+    [3057, 1852, 1273],
+];
+
+/// Numeric, alphanumeric and byte capacity of an M4 Micro QR Code, for
+/// error correction levels L, M and Q.
+const MICRO_QR_CAPACITY: [[usize; 3]; 3] = [[35, 21, 15], [30, 18, 13], [21, 13, 9]];
+
+/// Check that every char is an ASCII digit, and return the string
+/// as bytes.
+fn parse_digits(digits: &str) -> Result<Vec<u8>, Error> {
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(Error::InvalidBarcode);
+    }
+    Ok(digits.as_bytes().to_vec())
+}
+
+/// Compute the modulo-10 check digit over the first 11 ASCII digits of a
+/// UPC-A code. Digits in odd positions (1st, 3rd, ...) are weighted by 3.
+fn upca_check_digit(digits: &[u8]) -> u8 {
+    let sum: u32 = digits
+        .iter()
+        .enumerate()
+        .map(|(i, d)| {
+            let d = (d - b'0') as u32;
+            if i % 2 == 0 {
+                d * 3
+            } else {
+                d
+            }
+        })
+        .sum();
+
+    b'0' + ((10 - (sum % 10)) % 10) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upca_computes_check_digit() {
+        let code = Barcode::new_upca("03600029145").unwrap();
+        assert_eq!(b"036000291452", code.data());
+    }
+
+    #[test]
+    fn upca_passes_through_12_digits() {
+        let code = Barcode::new_upca("036000291459").unwrap();
+        assert_eq!(b"036000291459", code.data());
+    }
+
+    #[test]
+    fn upca_strict() {
+        assert!(Barcode::new_upca_strict("036000291452").is_ok());
+        assert_eq!(
+            Err(Error::InvalidCheckDigit),
+            Barcode::new_upca_strict("036000291459")
+        );
+        assert_eq!(
+            Err(Error::InvalidBarcode),
+            Barcode::new_upca_strict("03600029145")
+        );
+    }
+
+    #[test]
+    fn micro_qr_capacity() {
+        assert!(QrCode::new_micro(&[b'1'; 30]).check().is_ok());
+        assert_eq!(
+            Err(Error::InvalidBarcode),
+            QrCode::new_micro(&[b'1'; 31]).check()
+        );
+        assert!(QrCode::new_micro(b"ABCDEFGHIJKLMNOPQR").check().is_ok());
+        assert_eq!(
+            Err(Error::InvalidBarcode),
+            QrCode::new_micro(b"https://example.com").check()
+        );
+
+        let mut qr = QrCode::new_micro(&[b'1'; 35]);
+        qr.error_correction = QrErrorCorrection::L;
+        assert!(qr.check().is_ok());
+    }
+
+    #[test]
+    fn qr_capacity() {
+        assert!(QrCode::new(&[b'a'; 2331]).check().is_ok());
+        assert_eq!(
+            Err(Error::InvalidBarcode),
+            QrCode::new(&[b'a'; 2332]).check()
+        );
+    }
+
+    #[test]
+    fn micro_qr_rejects_ecc_h() {
+        let mut qr = QrCode::new_micro(b"12345");
+        assert!(qr.check().is_ok());
+        qr.error_correction = QrErrorCorrection::H;
+        assert_eq!(Err(Error::InvalidBarcode), qr.check());
+    }
+
+    #[test]
+    fn upca_rejects_bad_input() {
+        assert_eq!(Err(Error::InvalidBarcode), Barcode::new_upca("0360002914"));
+        assert_eq!(Err(Error::InvalidBarcode), Barcode::new_upca("0360002914a"));
+    }
+}
+
+// vim: foldmethod=marker
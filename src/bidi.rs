@@ -0,0 +1,316 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Right to left text, such as Hebrew and Arabic.
+//!
+//! Printers print bytes left to right, in the order they're sent, and
+//! know nothing about the letters they print. Text written right to left
+//! has to be put into visual order first, and Arabic letters replaced by
+//! the presentation form for their position in the word, so the letters
+//! join up. [visual] does both, and is used by `print_text` on the
+//! writers when this feature is enabled.
+//!
+//! This is a simplification of the Unicode Bidirectional Algorithm: each
+//! line has a single direction, taken from its first strong character,
+//! and there is no support for embedding or isolate controls.
+
+use super::width::clusters;
+
+/// Joining behavior of an Arabic character.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Joining {
+    /// Doesn't join to either side, such as hamza or a space.
+    None,
+
+    /// Only joins to the letter before it, such as alef.
+    Right,
+
+    /// Joins to the letters on either side, such as beh.
+    Dual,
+
+    /// Marks which don't affect joining, such as harakat.
+    Transparent,
+}
+
+/// Presentation forms of the letters U+0621 through U+064A: the isolated
+/// form, and how many forms there are (isolated, final, initial and
+/// medial, in that order). A count of 0 means the character has none.
+const FORMS: [(u16, u8); 42] = [
+    (0xfe80, 1),
+    (0xfe81, 2),
+    (0xfe83, 2),
+    (0xfe85, 2),
+    (0xfe87, 2),
+    (0xfe89, 4),
+    (0xfe8d, 2),
+    (0xfe8f, 4),
+    (0xfe93, 2),
+    (0xfe95, 4),
+    (0xfe99, 4),
+    (0xfe9d, 4),
+    (0xfea1, 4),
+    (0xfea5, 4),
+    (0xfea9, 2),
+    (0xfeab, 2),
+    (0xfead, 2),
+    (0xfeaf, 2),
+    (0xfeb1, 4),
+    (0xfeb5, 4),
+    (0xfeb9, 4),
+    (0xfebd, 4),
+    (0xfec1, 4),
+    (0xfec5, 4),
+    (0xfec9, 4),
+    (0xfecd, 4),
+    (0, 0),
+    (0, 0),
+    (0, 0),
+    (0, 0),
+    (0, 0),
+    (0, 0),
+    (0xfed1, 4),
+    (0xfed5, 4),
+    (0xfed9, 4),
+    (0xfedd, 4),
+    (0xfee1, 4),
+    (0xfee5, 4),
+    (0xfee9, 4),
+    (0xfeed, 2),
+    (0xfeef, 2),
+    (0xfef1, 4),
+];
+
+const LAM: char = '\u{644}';
+const TATWEEL: char = '\u{640}';
+
+fn forms(c: char) -> Option<(u16, u8)> {
+    let i = (c as u32).checked_sub(0x621)? as usize;
+    FORMS.get(i).copied().filter(|(_, count)| *count > 0)
+}
+
+fn joining(c: char) -> Joining {
+    match c {
+        '\u{64b}'..='\u{65f}' | '\u{670}' => Joining::Transparent,
+        TATWEEL => Joining::Dual,
+        _ => match forms(c) {
+            Some((_, 4)) => Joining::Dual,
+            Some((_, 2)) => Joining::Right,
+            _ => Joining::None,
+        },
+    }
+}
+
+/// Return the isolated and final forms of lam joined with `alef`, if it's
+/// one of the alefs which form a ligature with lam.
+fn lam_alef(alef: char) -> Option<u16> {
+    Some(match alef {
+        '\u{622}' => 0xfef5,
+        '\u{623}' => 0xfef7,
+        '\u{625}' => 0xfef9,
+        '\u{627}' => 0xfefb,
+        _ => return None,
+    })
+}
+
+/// Replace Arabic letters with the presentation form for their position
+/// in the word, so they join up when printed one after another. Lam
+/// followed by alef becomes a single ligature. Other text is unchanged.
+pub fn shape_arabic(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let joins = |i: usize| joining(chars[i]);
+    let neighbor = |i: usize, forward: bool| {
+        let mut j = i;
+        loop {
+            j = if forward {
+                j.checked_add(1).filter(|j| *j < chars.len())?
+            } else {
+                j.checked_sub(1)?
+            };
+            if joins(j) != Joining::Transparent {
+                return Some(j);
+            }
+        }
+    };
+
+    let mut out = String::with_capacity(text.len());
+    let mut skip = None;
+    for (i, &c) in chars.iter().enumerate() {
+        if skip == Some(i) {
+            continue;
+        }
+        let joining = joins(i);
+        if joining == Joining::Transparent || joining == Joining::None || c == TATWEEL {
+            out.push(c);
+            continue;
+        }
+
+        let before = neighbor(i, false).is_some_and(|j| joins(j) == Joining::Dual);
+        let next = neighbor(i, true);
+        if c == LAM {
+            if let Some((j, ligature)) = next.and_then(|j| Some((j, lam_alef(chars[j])?))) {
+                let form = ligature + before as u16;
+                out.extend(char::from_u32(form as u32));
+                skip = Some(j);
+                continue;
+            }
+        }
+        let after = joining == Joining::Dual
+            && next.is_some_and(|j| matches!(joins(j), Joining::Right | Joining::Dual));
+
+        let (isolated, count) = forms(c).unwrap();
+        let form = match (before, after) {
+            (true, true) if count == 4 => 3,
+            (false, true) if count == 4 => 2,
+            (true, _) => 1,
+            _ => 0,
+        };
+        out.extend(char::from_u32(isolated as u32 + form));
+    }
+    out
+}
+
+/// Direction of a run of text.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Direction {
+    Left,
+    Right,
+}
+
+fn strong(c: char) -> Option<Direction> {
+    match c as u32 {
+        0x0590..=0x08ff | 0xfb1d..=0xfdff | 0xfe70..=0xfeff => Some(Direction::Right),
+        _ if c.is_alphanumeric() => Some(Direction::Left),
+        _ => None,
+    }
+}
+
+fn mirror(cluster: &str) -> &str {
+    match cluster {
+        "(" => ")",
+        ")" => "(",
+        "[" => "]",
+        "]" => "[",
+        "{" => "}",
+        "}" => "{",
+        "<" => ">",
+        ">" => "<",
+        _ => cluster,
+    }
+}
+
+/// Put a single line of logical order text into visual order.
+fn reorder(line: &str) -> String {
+    let clusters: Vec<&str> = clusters(line).collect();
+    let strong_of = |cluster: &str| cluster.chars().next().and_then(strong);
+    let base = clusters
+        .iter()
+        .find_map(|c| strong_of(c))
+        .unwrap_or(Direction::Left);
+
+    // Neutral characters take the direction of the text around them if
+    // it agrees, and the line's direction otherwise.
+    let mut directions: Vec<Option<Direction>> = clusters.iter().map(|c| strong_of(c)).collect();
+    for i in 0..directions.len() {
+        if directions[i].is_some() {
+            continue;
+        }
+        let before = directions[..i].iter().rev().find_map(|d| *d);
+        let after = directions[i..].iter().find_map(|d| *d);
+        directions[i] = Some(match (before, after) {
+            (Some(b), Some(a)) if a == b => a,
+            _ => base,
+        });
+    }
+
+    let mut runs: Vec<(Direction, Vec<&str>)> = vec![];
+    for (cluster, direction) in clusters.iter().zip(directions) {
+        let direction = direction.unwrap_or(base);
+        match runs.last_mut() {
+            Some((d, run)) if *d == direction => run.push(cluster),
+            _ => runs.push((direction, vec![cluster])),
+        }
+    }
+    if base == Direction::Right {
+        runs.reverse();
+    }
+
+    let mut out = String::with_capacity(line.len());
+    for (direction, run) in runs {
+        match direction {
+            Direction::Left => run.iter().for_each(|c| out.push_str(c)),
+            Direction::Right => run.iter().rev().for_each(|c| out.push_str(mirror(c))),
+        }
+    }
+    out
+}
+
+/// Shape Arabic letters with [shape_arabic], and put each line of `text`
+/// into the order it should be printed in, left to right. Lines without
+/// any right to left text are unchanged.
+///
+/// ```rust
+/// assert_eq!("abc םולש", epson::bidi::visual("שלום abc"));
+/// ```
+pub fn visual(text: &str) -> String {
+    text.split_inclusive('\n')
+        .map(|line| {
+            let (line, newline) = match line.strip_suffix('\n') {
+                Some(line) => (line, "\n"),
+                None => (line, ""),
+            };
+            format!("{}{}", reorder(&shape_arabic(line)), newline)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shape_forms() {
+        // seen, lam alef, meem: initial seen, final lam-alef, and an
+        // isolated meem, since alef doesn't join to what follows.
+        assert_eq!("\u{feb3}\u{fefc}\u{fee1}", shape_arabic("سلام"));
+        // beh beh beh: initial, medial, final.
+        assert_eq!("\u{fe91}\u{fe92}\u{fe90}", shape_arabic("ببب"));
+        // Harakat don't break the joining.
+        assert_eq!("\u{fe91}\u{64e}\u{fe90}", shape_arabic("بَب"));
+        assert_eq!("\u{fe8f} \u{fe8f}", shape_arabic("ب ب"));
+        assert_eq!("abc", shape_arabic("abc"));
+    }
+
+    #[test]
+    fn reorder_lines() {
+        assert_eq!("hello world", visual("hello world"));
+        assert_eq!("םולש", visual("שלום"));
+        assert_eq!("abc םולש", visual("שלום abc"));
+        assert_eq!("hi םולש you", visual("hi שלום you"));
+        assert_eq!("123 םולש\nabc\n", visual("שלום 123\nabc\n"));
+        assert_eq!("(ב) א", visual("א (ב)"));
+    }
+
+    #[test]
+    fn visual_arabic() {
+        assert_eq!("\u{fee1}\u{fefc}\u{feb3}", visual("سلام"));
+    }
+}
+
+// vim: foldmethod=marker
@@ -0,0 +1,270 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+#[cfg(feature = "tokio")]
+use super::async_tokio::{AsyncWriter, Error as AsyncWriteError};
+use super::write::Error as WriteError;
+use super::{Model, Writer};
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// Commands waiting to be sent to the printer.
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Buffer {
+    fn lock(&self) -> MutexGuard<'_, Vec<u8>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Return everything in the buffer, leaving it empty.
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.lock())
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for Buffer {
+    fn poll_write(
+        mut self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<io::Result<usize>> {
+        std::task::Poll::Ready(self.write(buf))
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+/// A [Writer] which holds on to everything written to it until the job is
+/// committed, so that an error half way through building a receipt (such
+/// as a template failing to render) doesn't leave half a receipt hanging
+/// out of the printer.
+///
+/// Nothing is sent until [BufferedWriter::commit] (or `flush`) is called.
+/// A job which went wrong can be thrown away with
+/// [BufferedWriter::discard], and anything not yet committed when the
+/// BufferedWriter is dropped is thrown away as well.
+///
+/// ```rust
+/// # use epson::{testing::CaptureWriter, BufferedWriter, Model};
+/// # use std::io::Write;
+/// let capture = CaptureWriter::new();
+/// let mut pos = BufferedWriter::open(Model::T20II, Box::new(capture.clone())).unwrap();
+///
+/// pos.writer().write_all(b"HACK THE PLANET\n").unwrap();
+/// assert!(capture.bytes().is_empty());
+///
+/// pos.commit().unwrap();
+/// assert!(!capture.bytes().is_empty());
+/// ```
+pub struct BufferedWriter {
+    writer: Writer,
+    buffer: Buffer,
+    w: Box<dyn Write>,
+}
+
+impl BufferedWriter {
+    /// Create a new BufferedWriter, which sends committed jobs to `w`.
+    pub fn open(model: Model, w: Box<dyn Write>) -> Result<Self, WriteError> {
+        let buffer = Buffer::default();
+        let writer = Writer::open(model, Box::new(buffer.clone()))?;
+        Ok(Self { writer, buffer, w })
+    }
+
+    /// Return the writer to build the job with.
+    pub fn writer(&mut self) -> &mut Writer {
+        &mut self.writer
+    }
+
+    /// Return the number of bytes waiting to be committed.
+    pub fn pending(&self) -> usize {
+        self.buffer.lock().len()
+    }
+
+    /// Send everything written since the last commit to the printer.
+    ///
+    /// The pending bytes are handed over even if sending them fails, since
+    /// some of them may already have been printed; they're not sent again
+    /// by the next commit.
+    pub fn commit(&mut self) -> Result<(), WriteError> {
+        let pending = self.buffer.take();
+        self.w.write_all(&pending)?;
+        self.w.flush()?;
+        Ok(())
+    }
+
+    /// Throw away everything written since the last commit.
+    ///
+    /// Since the discarded commands may have changed the style or code
+    /// page, the next job starts by initializing the printer again.
+    pub fn discard(&mut self) -> Result<(), WriteError> {
+        self.buffer.take();
+        self.writer.init()
+    }
+}
+
+impl Write for BufferedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.commit().map_err(|err| match err {
+            WriteError::Io(err) => err,
+            WriteError::Epson(err) => io::Error::other(format!("{:?}", err)),
+        })
+    }
+}
+
+/// An [AsyncWriter] which holds on to everything written to it until the
+/// job is committed, like a [BufferedWriter].
+#[cfg(feature = "tokio")]
+pub struct AsyncBufferedWriter {
+    writer: AsyncWriter,
+    buffer: Buffer,
+    w: Box<dyn tokio::io::AsyncWrite + Unpin + Send>,
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncBufferedWriter {
+    /// Create a new AsyncBufferedWriter, which sends committed jobs to
+    /// `w`.
+    pub async fn open(
+        model: Model,
+        w: Box<dyn tokio::io::AsyncWrite + Unpin + Send>,
+    ) -> Result<Self, AsyncWriteError> {
+        let buffer = Buffer::default();
+        let writer = AsyncWriter::open(model, Box::new(buffer.clone())).await?;
+        Ok(Self { writer, buffer, w })
+    }
+
+    /// Return the writer to build the job with.
+    pub fn writer(&mut self) -> &mut AsyncWriter {
+        &mut self.writer
+    }
+
+    /// Return the number of bytes waiting to be committed.
+    pub fn pending(&self) -> usize {
+        self.buffer.lock().len()
+    }
+
+    /// Send everything written since the last commit to the printer.
+    pub async fn commit(&mut self) -> Result<(), AsyncWriteError> {
+        use tokio::io::AsyncWriteExt;
+
+        let pending = self.buffer.take();
+        self.w.write_all(&pending).await?;
+        self.w.flush().await?;
+        Ok(())
+    }
+
+    /// Send everything written since the last commit to the printer, like
+    /// [AsyncBufferedWriter::commit].
+    pub async fn flush(&mut self) -> Result<(), AsyncWriteError> {
+        self.commit().await
+    }
+
+    /// Throw away everything written since the last commit.
+    pub async fn discard(&mut self) -> Result<(), AsyncWriteError> {
+        self.buffer.take();
+        self.writer.init().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::CaptureWriter, Command};
+
+    fn buffered() -> (CaptureWriter, BufferedWriter) {
+        let capture = CaptureWriter::new();
+        let pos = BufferedWriter::open(Model::T20II, Box::new(capture.clone())).unwrap();
+        (capture, pos)
+    }
+
+    #[test]
+    fn commit() {
+        let (capture, mut pos) = buffered();
+        pos.writer().feed(2).unwrap();
+        assert!(capture.bytes().is_empty());
+        assert_eq!(5, pos.pending());
+
+        pos.commit().unwrap();
+        assert_eq!(0, pos.pending());
+        capture.assert_commands(&[Command::Init, Command::Feed(2)]);
+    }
+
+    #[test]
+    fn flush() {
+        let (capture, mut pos) = buffered();
+        pos.write_all(b"HACK THE PLANET\n").unwrap();
+        pos.flush().unwrap();
+        capture.assert_commands(&[Command::Init, Command::Text(b"HACK THE PLANET\n".to_vec())]);
+    }
+
+    #[test]
+    fn discard() {
+        let (capture, mut pos) = buffered();
+        pos.commit().unwrap();
+        capture.clear();
+
+        pos.writer().emphasize(true).unwrap();
+        pos.discard().unwrap();
+        pos.writer().feed(1).unwrap();
+        pos.commit().unwrap();
+        capture.assert_commands(&[Command::Init, Command::Feed(1)]);
+    }
+
+    #[test]
+    fn drop_discards() {
+        let (capture, mut pos) = buffered();
+        pos.writer().feed(1).unwrap();
+        drop(pos);
+        assert!(capture.bytes().is_empty());
+    }
+}
+
+// vim: foldmethod=marker
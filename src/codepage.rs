@@ -0,0 +1,565 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+use super::{Command, Model};
+
+/// Single byte code pages, which can be selected with `ESC t` to decide
+/// how bytes above 0x7F are printed.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum CodePage {
+    /// PC437, USA and standard Europe.
+    Pc437 = 0,
+
+    /// Katakana.
+    Katakana = 1,
+
+    /// PC850, Multilingual.
+    Pc850 = 2,
+
+    /// PC860, Portuguese.
+    Pc860 = 3,
+
+    /// PC863, Canadian-French.
+    Pc863 = 4,
+
+    /// PC865, Nordic.
+    Pc865 = 5,
+
+    /// WPC1252, Windows Latin-1.
+    Wpc1252 = 16,
+
+    /// PC866, Cyrillic #2.
+    Pc866 = 17,
+
+    /// PC852, Latin 2.
+    Pc852 = 18,
+
+    /// PC858, Multilingual with the Euro sign.
+    Pc858 = 19,
+
+    /// Thai character code 42.
+    Thai42 = 20,
+
+    /// Thai character code 11.
+    Thai11 = 21,
+
+    /// Thai character code 18.
+    Thai18 = 26,
+
+    /// TCVN-3, Vietnamese.
+    Tcvn3 = 30,
+
+    /// PC720, Arabic.
+    Pc720 = 32,
+
+    /// PC864, Arabic.
+    Pc864 = 37,
+
+    /// ISO8859-15, Latin 9.
+    Iso8859_15 = 40,
+}
+
+impl CodePage {
+    /// Every code page which can be selected.
+    pub const ALL: &'static [CodePage] = &[
+        CodePage::Pc437,
+        CodePage::Katakana,
+        CodePage::Pc850,
+        CodePage::Pc860,
+        CodePage::Pc863,
+        CodePage::Pc865,
+        CodePage::Wpc1252,
+        CodePage::Pc866,
+        CodePage::Pc852,
+        CodePage::Pc858,
+        CodePage::Thai42,
+        CodePage::Thai11,
+        CodePage::Thai18,
+        CodePage::Tcvn3,
+        CodePage::Pc720,
+        CodePage::Pc864,
+        CodePage::Iso8859_15,
+    ];
+
+    /// Return the byte which prints `c` in this code page, if there is
+    /// one. ASCII is the same in every code page. Control characters
+    /// other than line feeds and tabs have no byte, so that text can't
+    /// smuggle in commands such as a cut or a drawer kick.
+    pub fn encode(&self, c: char) -> Option<u8> {
+        if is_control(c) {
+            return None;
+        }
+        if c.is_ascii() {
+            return Some(c as u8);
+        }
+        let table = self.table()?;
+        let position = table.iter().position(|&u| u != 0 && u as u32 == c as u32)?;
+        Some(0x80 + position as u8)
+    }
+
+    /// Return the character printed for byte `b` in this code page, if
+    /// it's known. Control characters aren't printed, as with
+    /// [Self::encode].
+    pub fn decode(&self, b: u8) -> Option<char> {
+        if is_control(b as char) {
+            return None;
+        }
+        if b.is_ascii() {
+            return Some(b as char);
+        }
+        match self.table()?[b as usize - 0x80] {
+            0 => None,
+            u => char::from_u32(u as u32),
+        }
+    }
+
+    /// Return the characters of bytes 0x80 through 0xFF, where 0 is a
+    /// byte with no known character. Not every code page has a table.
+    fn table(&self) -> Option<&'static [u16; 128]> {
+        Some(match self {
+            CodePage::Pc437 => &PC437,
+            CodePage::Katakana => &KATAKANA,
+            CodePage::Pc850 => &PC850,
+            CodePage::Pc860 => &PC860,
+            CodePage::Pc863 => &PC863,
+            CodePage::Pc865 => &PC865,
+            CodePage::Wpc1252 => &WPC1252,
+            CodePage::Pc866 => &PC866,
+            CodePage::Pc852 => &PC852,
+            CodePage::Pc858 => &PC858,
+            CodePage::Pc720 => &PC720,
+            CodePage::Pc864 => &PC864,
+            CodePage::Iso8859_15 => &ISO8859_15,
+            CodePage::Thai42 | CodePage::Thai11 | CodePage::Thai18 | CodePage::Tcvn3 => {
+                return None
+            }
+        })
+    }
+}
+
+/// Return true if every character of `text` can be printed in at least
+/// one of `pages`.
+#[cfg_attr(not(feature = "textimage"), allow(dead_code))]
+pub(crate) fn encodable(text: &str, pages: &[CodePage]) -> bool {
+    text.chars()
+        .all(|c| c.is_ascii() || pages.iter().any(|page| page.encode(c).is_some()))
+}
+
+/// Return true if `c` is a control character which isn't allowed in
+/// text. Line feeds and tabs are layout, but anything else, such as ESC
+/// or GS, would start a command.
+fn is_control(c: char) -> bool {
+    c.is_ascii_control() && c != '\n' && c != '\t'
+}
+
+/// Encode `text` for printing, starting in the `current` code page.
+///
+/// Characters the current code page can't print switch to the first of
+/// `pages` which can, and characters none of them can print are replaced
+/// with a question mark.
+pub(crate) fn transcode(text: &str, mut current: CodePage, pages: &[CodePage]) -> Vec<Command> {
+    let mut commands = vec![];
+    let mut bytes = vec![];
+
+    for c in text.chars() {
+        if let Some(b) = current.encode(c) {
+            bytes.push(b);
+            continue;
+        }
+        match pages.iter().find_map(|page| Some((*page, page.encode(c)?))) {
+            Some((page, b)) => {
+                if !bytes.is_empty() {
+                    commands.push(Command::Text(std::mem::take(&mut bytes)));
+                }
+                commands.push(Command::CodePage(page));
+                current = page;
+                bytes.push(b);
+            }
+            None => bytes.push(b'?'),
+        }
+    }
+    if !bytes.is_empty() {
+        commands.push(Command::Text(bytes));
+    }
+    commands
+}
+
+/// Encodes text for a model, keeping track of the code page the printer
+/// has been switched to between calls. Builders use it to produce
+/// [Command]s ahead of time, and the writers to print text.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Encoder {
+    current: Option<CodePage>,
+    pages: Vec<CodePage>,
+    unicode: bool,
+}
+
+impl Encoder {
+    /// Encode for `model`, without knowing which code page the printer
+    /// is in. The first text outside of ASCII selects its code page
+    /// explicitly.
+    pub(crate) fn new(model: Model) -> Self {
+        Self {
+            current: None,
+            pages: model.get_code_pages(),
+            unicode: false,
+        }
+    }
+
+    /// Encode for `model` when the printer is known to be in `page`, or
+    /// has UTF-8 turned on with [super::CharacterSet::Unicode].
+    pub(crate) fn resume(model: Model, page: CodePage, unicode: bool) -> Self {
+        Self {
+            current: Some(page),
+            unicode,
+            ..Self::new(model)
+        }
+    }
+
+    /// Keep track of the code page `cmd` leaves the printer in, for
+    /// commands which didn't come from [Self::text].
+    pub(crate) fn track(&mut self, cmd: &Command) {
+        match *cmd {
+            Command::Init => {
+                self.current = Some(CodePage::Pc437);
+                self.unicode = false;
+            }
+            Command::CodePage(page) => self.current = Some(page),
+            Command::CharacterSet(set) => self.unicode = set == super::CharacterSet::Unicode,
+            _ => {}
+        }
+    }
+
+    /// Return the commands which print `text`.
+    pub(crate) fn text(&mut self, text: &str) -> Vec<Command> {
+        if text.is_empty() {
+            return vec![];
+        }
+        if self.unicode || (self.current.is_none() && text.is_ascii()) {
+            // Filter control characters the same way the code pages do.
+            let text: String = text
+                .chars()
+                .map(|c| if is_control(c) { '?' } else { c })
+                .collect();
+            return vec![Command::Text(text.into_bytes())];
+        }
+
+        let mut commands = vec![];
+        let current = match self.current {
+            Some(page) => page,
+            None => {
+                let page = text
+                    .chars()
+                    .find(|c| !c.is_ascii())
+                    .and_then(|c| self.pages.iter().find(|page| page.encode(c).is_some()))
+                    .or(self.pages.first())
+                    .copied()
+                    .unwrap_or(CodePage::Pc437);
+                commands.push(Command::CodePage(page));
+                page
+            }
+        };
+        commands.extend(transcode(text, current, &self.pages));
+        self.current = commands
+            .iter()
+            .rev()
+            .find_map(|cmd| match cmd {
+                Command::CodePage(page) => Some(*page),
+                _ => None,
+            })
+            .or(Some(current));
+        commands
+    }
+}
+
+const PC437: [u16; 128] = [
+    0x00c7, 0x00fc, 0x00e9, 0x00e2, 0x00e4, 0x00e0, 0x00e5, 0x00e7, 0x00ea, 0x00eb, 0x00e8, 0x00ef,
+    0x00ee, 0x00ec, 0x00c4, 0x00c5, 0x00c9, 0x00e6, 0x00c6, 0x00f4, 0x00f6, 0x00f2, 0x00fb, 0x00f9,
+    0x00ff, 0x00d6, 0x00dc, 0x00a2, 0x00a3, 0x00a5, 0x20a7, 0x0192, 0x00e1, 0x00ed, 0x00f3, 0x00fa,
+    0x00f1, 0x00d1, 0x00aa, 0x00ba, 0x00bf, 0x2310, 0x00ac, 0x00bd, 0x00bc, 0x00a1, 0x00ab, 0x00bb,
+    0x2591, 0x2592, 0x2593, 0x2502, 0x2524, 0x2561, 0x2562, 0x2556, 0x2555, 0x2563, 0x2551, 0x2557,
+    0x255d, 0x255c, 0x255b, 0x2510, 0x2514, 0x2534, 0x252c, 0x251c, 0x2500, 0x253c, 0x255e, 0x255f,
+    0x255a, 0x2554, 0x2569, 0x2566, 0x2560, 0x2550, 0x256c, 0x2567, 0x2568, 0x2564, 0x2565, 0x2559,
+    0x2558, 0x2552, 0x2553, 0x256b, 0x256a, 0x2518, 0x250c, 0x2588, 0x2584, 0x258c, 0x2590, 0x2580,
+    0x03b1, 0x00df, 0x0393, 0x03c0, 0x03a3, 0x03c3, 0x00b5, 0x03c4, 0x03a6, 0x0398, 0x03a9, 0x03b4,
+    0x221e, 0x03c6, 0x03b5, 0x2229, 0x2261, 0x00b1, 0x2265, 0x2264, 0x2320, 0x2321, 0x00f7, 0x2248,
+    0x00b0, 0x2219, 0x00b7, 0x221a, 0x207f, 0x00b2, 0x25a0, 0x00a0,
+];
+
+const PC850: [u16; 128] = [
+    0x00c7, 0x00fc, 0x00e9, 0x00e2, 0x00e4, 0x00e0, 0x00e5, 0x00e7, 0x00ea, 0x00eb, 0x00e8, 0x00ef,
+    0x00ee, 0x00ec, 0x00c4, 0x00c5, 0x00c9, 0x00e6, 0x00c6, 0x00f4, 0x00f6, 0x00f2, 0x00fb, 0x00f9,
+    0x00ff, 0x00d6, 0x00dc, 0x00f8, 0x00a3, 0x00d8, 0x00d7, 0x0192, 0x00e1, 0x00ed, 0x00f3, 0x00fa,
+    0x00f1, 0x00d1, 0x00aa, 0x00ba, 0x00bf, 0x00ae, 0x00ac, 0x00bd, 0x00bc, 0x00a1, 0x00ab, 0x00bb,
+    0x2591, 0x2592, 0x2593, 0x2502, 0x2524, 0x00c1, 0x00c2, 0x00c0, 0x00a9, 0x2563, 0x2551, 0x2557,
+    0x255d, 0x00a2, 0x00a5, 0x2510, 0x2514, 0x2534, 0x252c, 0x251c, 0x2500, 0x253c, 0x00e3, 0x00c3,
+    0x255a, 0x2554, 0x2569, 0x2566, 0x2560, 0x2550, 0x256c, 0x00a4, 0x00f0, 0x00d0, 0x00ca, 0x00cb,
+    0x00c8, 0x0131, 0x00cd, 0x00ce, 0x00cf, 0x2518, 0x250c, 0x2588, 0x2584, 0x00a6, 0x00cc, 0x2580,
+    0x00d3, 0x00df, 0x00d4, 0x00d2, 0x00f5, 0x00d5, 0x00b5, 0x00fe, 0x00de, 0x00da, 0x00db, 0x00d9,
+    0x00fd, 0x00dd, 0x00af, 0x00b4, 0x00ad, 0x00b1, 0x2017, 0x00be, 0x00b6, 0x00a7, 0x00f7, 0x00b8,
+    0x00b0, 0x00a8, 0x00b7, 0x00b9, 0x00b3, 0x00b2, 0x25a0, 0x00a0,
+];
+
+const PC860: [u16; 128] = [
+    0x00c7, 0x00fc, 0x00e9, 0x00e2, 0x00e3, 0x00e0, 0x00c1, 0x00e7, 0x00ea, 0x00ca, 0x00e8, 0x00cd,
+    0x00d4, 0x00ec, 0x00c3, 0x00c2, 0x00c9, 0x00c0, 0x00c8, 0x00f4, 0x00f5, 0x00f2, 0x00da, 0x00f9,
+    0x00cc, 0x00d5, 0x00dc, 0x00a2, 0x00a3, 0x00d9, 0x20a7, 0x00d3, 0x00e1, 0x00ed, 0x00f3, 0x00fa,
+    0x00f1, 0x00d1, 0x00aa, 0x00ba, 0x00bf, 0x00d2, 0x00ac, 0x00bd, 0x00bc, 0x00a1, 0x00ab, 0x00bb,
+    0x2591, 0x2592, 0x2593, 0x2502, 0x2524, 0x2561, 0x2562, 0x2556, 0x2555, 0x2563, 0x2551, 0x2557,
+    0x255d, 0x255c, 0x255b, 0x2510, 0x2514, 0x2534, 0x252c, 0x251c, 0x2500, 0x253c, 0x255e, 0x255f,
+    0x255a, 0x2554, 0x2569, 0x2566, 0x2560, 0x2550, 0x256c, 0x2567, 0x2568, 0x2564, 0x2565, 0x2559,
+    0x2558, 0x2552, 0x2553, 0x256b, 0x256a, 0x2518, 0x250c, 0x2588, 0x2584, 0x258c, 0x2590, 0x2580,
+    0x03b1, 0x00df, 0x0393, 0x03c0, 0x03a3, 0x03c3, 0x00b5, 0x03c4, 0x03a6, 0x0398, 0x03a9, 0x03b4,
+    0x221e, 0x03c6, 0x03b5, 0x2229, 0x2261, 0x00b1, 0x2265, 0x2264, 0x2320, 0x2321, 0x00f7, 0x2248,
+    0x00b0, 0x2219, 0x00b7, 0x221a, 0x207f, 0x00b2, 0x25a0, 0x00a0,
+];
+
+const PC863: [u16; 128] = [
+    0x00c7, 0x00fc, 0x00e9, 0x00e2, 0x00c2, 0x00e0, 0x00b6, 0x00e7, 0x00ea, 0x00eb, 0x00e8, 0x00ef,
+    0x00ee, 0x2017, 0x00c0, 0x00a7, 0x00c9, 0x00c8, 0x00ca, 0x00f4, 0x00cb, 0x00cf, 0x00fb, 0x00f9,
+    0x00a4, 0x00d4, 0x00dc, 0x00a2, 0x00a3, 0x00d9, 0x00db, 0x0192, 0x00a6, 0x00b4, 0x00f3, 0x00fa,
+    0x00a8, 0x00b8, 0x00b3, 0x00af, 0x00ce, 0x2310, 0x00ac, 0x00bd, 0x00bc, 0x00be, 0x00ab, 0x00bb,
+    0x2591, 0x2592, 0x2593, 0x2502, 0x2524, 0x2561, 0x2562, 0x2556, 0x2555, 0x2563, 0x2551, 0x2557,
+    0x255d, 0x255c, 0x255b, 0x2510, 0x2514, 0x2534, 0x252c, 0x251c, 0x2500, 0x253c, 0x255e, 0x255f,
+    0x255a, 0x2554, 0x2569, 0x2566, 0x2560, 0x2550, 0x256c, 0x2567, 0x2568, 0x2564, 0x2565, 0x2559,
+    0x2558, 0x2552, 0x2553, 0x256b, 0x256a, 0x2518, 0x250c, 0x2588, 0x2584, 0x258c, 0x2590, 0x2580,
+    0x03b1, 0x00df, 0x0393, 0x03c0, 0x03a3, 0x03c3, 0x00b5, 0x03c4, 0x03a6, 0x0398, 0x03a9, 0x03b4,
+    0x221e, 0x03c6, 0x03b5, 0x2229, 0x2261, 0x00b1, 0x2265, 0x2264, 0x2320, 0x2321, 0x00f7, 0x2248,
+    0x00b0, 0x2219, 0x00b7, 0x221a, 0x207f, 0x00b2, 0x25a0, 0x00a0,
+];
+
+const PC865: [u16; 128] = [
+    0x00c7, 0x00fc, 0x00e9, 0x00e2, 0x00e4, 0x00e0, 0x00e5, 0x00e7, 0x00ea, 0x00eb, 0x00e8, 0x00ef,
+    0x00ee, 0x00ec, 0x00c4, 0x00c5, 0x00c9, 0x00e6, 0x00c6, 0x00f4, 0x00f6, 0x00f2, 0x00fb, 0x00f9,
+    0x00ff, 0x00d6, 0x00dc, 0x00f8, 0x00a3, 0x00d8, 0x20a7, 0x0192, 0x00e1, 0x00ed, 0x00f3, 0x00fa,
+    0x00f1, 0x00d1, 0x00aa, 0x00ba, 0x00bf, 0x2310, 0x00ac, 0x00bd, 0x00bc, 0x00a1, 0x00ab, 0x00a4,
+    0x2591, 0x2592, 0x2593, 0x2502, 0x2524, 0x2561, 0x2562, 0x2556, 0x2555, 0x2563, 0x2551, 0x2557,
+    0x255d, 0x255c, 0x255b, 0x2510, 0x2514, 0x2534, 0x252c, 0x251c, 0x2500, 0x253c, 0x255e, 0x255f,
+    0x255a, 0x2554, 0x2569, 0x2566, 0x2560, 0x2550, 0x256c, 0x2567, 0x2568, 0x2564, 0x2565, 0x2559,
+    0x2558, 0x2552, 0x2553, 0x256b, 0x256a, 0x2518, 0x250c, 0x2588, 0x2584, 0x258c, 0x2590, 0x2580,
+    0x03b1, 0x00df, 0x0393, 0x03c0, 0x03a3, 0x03c3, 0x00b5, 0x03c4, 0x03a6, 0x0398, 0x03a9, 0x03b4,
+    0x221e, 0x03c6, 0x03b5, 0x2229, 0x2261, 0x00b1, 0x2265, 0x2264, 0x2320, 0x2321, 0x00f7, 0x2248,
+    0x00b0, 0x2219, 0x00b7, 0x221a, 0x207f, 0x00b2, 0x25a0, 0x00a0,
+];
+
+const WPC1252: [u16; 128] = [
+    0x20ac, 0x0000, 0x201a, 0x0192, 0x201e, 0x2026, 0x2020, 0x2021, 0x02c6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0x0000, 0x017d, 0x0000, 0x0000, 0x2018, 0x2019, 0x201c, 0x201d, 0x2022, 0x2013, 0x2014,
+    0x02dc, 0x2122, 0x0161, 0x203a, 0x0153, 0x0000, 0x017e, 0x0178, 0x00a0, 0x00a1, 0x00a2, 0x00a3,
+    0x00a4, 0x00a5, 0x00a6, 0x00a7, 0x00a8, 0x00a9, 0x00aa, 0x00ab, 0x00ac, 0x00ad, 0x00ae, 0x00af,
+    0x00b0, 0x00b1, 0x00b2, 0x00b3, 0x00b4, 0x00b5, 0x00b6, 0x00b7, 0x00b8, 0x00b9, 0x00ba, 0x00bb,
+    0x00bc, 0x00bd, 0x00be, 0x00bf, 0x00c0, 0x00c1, 0x00c2, 0x00c3, 0x00c4, 0x00c5, 0x00c6, 0x00c7,
+    0x00c8, 0x00c9, 0x00ca, 0x00cb, 0x00cc, 0x00cd, 0x00ce, 0x00cf, 0x00d0, 0x00d1, 0x00d2, 0x00d3,
+    0x00d4, 0x00d5, 0x00d6, 0x00d7, 0x00d8, 0x00d9, 0x00da, 0x00db, 0x00dc, 0x00dd, 0x00de, 0x00df,
+    0x00e0, 0x00e1, 0x00e2, 0x00e3, 0x00e4, 0x00e5, 0x00e6, 0x00e7, 0x00e8, 0x00e9, 0x00ea, 0x00eb,
+    0x00ec, 0x00ed, 0x00ee, 0x00ef, 0x00f0, 0x00f1, 0x00f2, 0x00f3, 0x00f4, 0x00f5, 0x00f6, 0x00f7,
+    0x00f8, 0x00f9, 0x00fa, 0x00fb, 0x00fc, 0x00fd, 0x00fe, 0x00ff,
+];
+
+const PC866: [u16; 128] = [
+    0x0410, 0x0411, 0x0412, 0x0413, 0x0414, 0x0415, 0x0416, 0x0417, 0x0418, 0x0419, 0x041a, 0x041b,
+    0x041c, 0x041d, 0x041e, 0x041f, 0x0420, 0x0421, 0x0422, 0x0423, 0x0424, 0x0425, 0x0426, 0x0427,
+    0x0428, 0x0429, 0x042a, 0x042b, 0x042c, 0x042d, 0x042e, 0x042f, 0x0430, 0x0431, 0x0432, 0x0433,
+    0x0434, 0x0435, 0x0436, 0x0437, 0x0438, 0x0439, 0x043a, 0x043b, 0x043c, 0x043d, 0x043e, 0x043f,
+    0x2591, 0x2592, 0x2593, 0x2502, 0x2524, 0x2561, 0x2562, 0x2556, 0x2555, 0x2563, 0x2551, 0x2557,
+    0x255d, 0x255c, 0x255b, 0x2510, 0x2514, 0x2534, 0x252c, 0x251c, 0x2500, 0x253c, 0x255e, 0x255f,
+    0x255a, 0x2554, 0x2569, 0x2566, 0x2560, 0x2550, 0x256c, 0x2567, 0x2568, 0x2564, 0x2565, 0x2559,
+    0x2558, 0x2552, 0x2553, 0x256b, 0x256a, 0x2518, 0x250c, 0x2588, 0x2584, 0x258c, 0x2590, 0x2580,
+    0x0440, 0x0441, 0x0442, 0x0443, 0x0444, 0x0445, 0x0446, 0x0447, 0x0448, 0x0449, 0x044a, 0x044b,
+    0x044c, 0x044d, 0x044e, 0x044f, 0x0401, 0x0451, 0x0404, 0x0454, 0x0407, 0x0457, 0x040e, 0x045e,
+    0x00b0, 0x2219, 0x00b7, 0x221a, 0x2116, 0x00a4, 0x25a0, 0x00a0,
+];
+
+const PC852: [u16; 128] = [
+    0x00c7, 0x00fc, 0x00e9, 0x00e2, 0x00e4, 0x016f, 0x0107, 0x00e7, 0x0142, 0x00eb, 0x0150, 0x0151,
+    0x00ee, 0x0179, 0x00c4, 0x0106, 0x00c9, 0x0139, 0x013a, 0x00f4, 0x00f6, 0x013d, 0x013e, 0x015a,
+    0x015b, 0x00d6, 0x00dc, 0x0164, 0x0165, 0x0141, 0x00d7, 0x010d, 0x00e1, 0x00ed, 0x00f3, 0x00fa,
+    0x0104, 0x0105, 0x017d, 0x017e, 0x0118, 0x0119, 0x00ac, 0x017a, 0x010c, 0x015f, 0x00ab, 0x00bb,
+    0x2591, 0x2592, 0x2593, 0x2502, 0x2524, 0x00c1, 0x00c2, 0x011a, 0x015e, 0x2563, 0x2551, 0x2557,
+    0x255d, 0x017b, 0x017c, 0x2510, 0x2514, 0x2534, 0x252c, 0x251c, 0x2500, 0x253c, 0x0102, 0x0103,
+    0x255a, 0x2554, 0x2569, 0x2566, 0x2560, 0x2550, 0x256c, 0x00a4, 0x0111, 0x0110, 0x010e, 0x00cb,
+    0x010f, 0x0147, 0x00cd, 0x00ce, 0x011b, 0x2518, 0x250c, 0x2588, 0x2584, 0x0162, 0x016e, 0x2580,
+    0x00d3, 0x00df, 0x00d4, 0x0143, 0x0144, 0x0148, 0x0160, 0x0161, 0x0154, 0x00da, 0x0155, 0x0170,
+    0x00fd, 0x00dd, 0x0163, 0x00b4, 0x00ad, 0x02dd, 0x02db, 0x02c7, 0x02d8, 0x00a7, 0x00f7, 0x00b8,
+    0x00b0, 0x00a8, 0x02d9, 0x0171, 0x0158, 0x0159, 0x25a0, 0x00a0,
+];
+
+const PC858: [u16; 128] = [
+    0x00c7, 0x00fc, 0x00e9, 0x00e2, 0x00e4, 0x00e0, 0x00e5, 0x00e7, 0x00ea, 0x00eb, 0x00e8, 0x00ef,
+    0x00ee, 0x00ec, 0x00c4, 0x00c5, 0x00c9, 0x00e6, 0x00c6, 0x00f4, 0x00f6, 0x00f2, 0x00fb, 0x00f9,
+    0x00ff, 0x00d6, 0x00dc, 0x00f8, 0x00a3, 0x00d8, 0x00d7, 0x0192, 0x00e1, 0x00ed, 0x00f3, 0x00fa,
+    0x00f1, 0x00d1, 0x00aa, 0x00ba, 0x00bf, 0x00ae, 0x00ac, 0x00bd, 0x00bc, 0x00a1, 0x00ab, 0x00bb,
+    0x2591, 0x2592, 0x2593, 0x2502, 0x2524, 0x00c1, 0x00c2, 0x00c0, 0x00a9, 0x2563, 0x2551, 0x2557,
+    0x255d, 0x00a2, 0x00a5, 0x2510, 0x2514, 0x2534, 0x252c, 0x251c, 0x2500, 0x253c, 0x00e3, 0x00c3,
+    0x255a, 0x2554, 0x2569, 0x2566, 0x2560, 0x2550, 0x256c, 0x00a4, 0x00f0, 0x00d0, 0x00ca, 0x00cb,
+    0x00c8, 0x20ac, 0x00cd, 0x00ce, 0x00cf, 0x2518, 0x250c, 0x2588, 0x2584, 0x00a6, 0x00cc, 0x2580,
+    0x00d3, 0x00df, 0x00d4, 0x00d2, 0x00f5, 0x00d5, 0x00b5, 0x00fe, 0x00de, 0x00da, 0x00db, 0x00d9,
+    0x00fd, 0x00dd, 0x00af, 0x00b4, 0x00ad, 0x00b1, 0x2017, 0x00be, 0x00b6, 0x00a7, 0x00f7, 0x00b8,
+    0x00b0, 0x00a8, 0x00b7, 0x00b9, 0x00b3, 0x00b2, 0x25a0, 0x00a0,
+];
+
+const PC720: [u16; 128] = [
+    0x0080, 0x0081, 0x00e9, 0x00e2, 0x0084, 0x00e0, 0x0086, 0x00e7, 0x00ea, 0x00eb, 0x00e8, 0x00ef,
+    0x00ee, 0x008d, 0x008e, 0x008f, 0x0090, 0x0651, 0x0652, 0x00f4, 0x00a4, 0x0640, 0x00fb, 0x00f9,
+    0x0621, 0x0622, 0x0623, 0x0624, 0x00a3, 0x0625, 0x0626, 0x0627, 0x0628, 0x0629, 0x062a, 0x062b,
+    0x062c, 0x062d, 0x062e, 0x062f, 0x0630, 0x0631, 0x0632, 0x0633, 0x0634, 0x0635, 0x00ab, 0x00bb,
+    0x2591, 0x2592, 0x2593, 0x2502, 0x2524, 0x2561, 0x2562, 0x2556, 0x2555, 0x2563, 0x2551, 0x2557,
+    0x255d, 0x255c, 0x255b, 0x2510, 0x2514, 0x2534, 0x252c, 0x251c, 0x2500, 0x253c, 0x255e, 0x255f,
+    0x255a, 0x2554, 0x2569, 0x2566, 0x2560, 0x2550, 0x256c, 0x2567, 0x2568, 0x2564, 0x2565, 0x2559,
+    0x2558, 0x2552, 0x2553, 0x256b, 0x256a, 0x2518, 0x250c, 0x2588, 0x2584, 0x258c, 0x2590, 0x2580,
+    0x0636, 0x0637, 0x0638, 0x0639, 0x063a, 0x0641, 0x00b5, 0x0642, 0x0643, 0x0644, 0x0645, 0x0646,
+    0x0647, 0x0648, 0x0649, 0x064a, 0x2261, 0x064b, 0x064c, 0x064d, 0x064e, 0x064f, 0x0650, 0x2248,
+    0x00b0, 0x2219, 0x00b7, 0x221a, 0x207f, 0x00b2, 0x25a0, 0x00a0,
+];
+
+const PC864: [u16; 128] = [
+    0x00b0, 0x00b7, 0x2219, 0x221a, 0x2592, 0x2500, 0x2502, 0x253c, 0x2524, 0x252c, 0x251c, 0x2534,
+    0x2510, 0x250c, 0x2514, 0x2518, 0x03b2, 0x221e, 0x03c6, 0x00b1, 0x00bd, 0x00bc, 0x2248, 0x00ab,
+    0x00bb, 0xfef7, 0xfef8, 0x0000, 0x0000, 0xfefb, 0xfefc, 0x0000, 0x00a0, 0x00ad, 0xfe82, 0x00a3,
+    0x00a4, 0xfe84, 0x0000, 0x0000, 0xfe8e, 0xfe8f, 0xfe95, 0xfe99, 0x060c, 0xfe9d, 0xfea1, 0xfea5,
+    0x0660, 0x0661, 0x0662, 0x0663, 0x0664, 0x0665, 0x0666, 0x0667, 0x0668, 0x0669, 0xfed1, 0x061b,
+    0xfeb1, 0xfeb5, 0xfeb9, 0x061f, 0x00a2, 0xfe80, 0xfe81, 0xfe83, 0xfe85, 0xfeca, 0xfe8b, 0xfe8d,
+    0xfe91, 0xfe93, 0xfe97, 0xfe9b, 0xfe9f, 0xfea3, 0xfea7, 0xfea9, 0xfeab, 0xfead, 0xfeaf, 0xfeb3,
+    0xfeb7, 0xfebb, 0xfebf, 0xfec1, 0xfec5, 0xfecb, 0xfecf, 0x00a6, 0x00ac, 0x00f7, 0x00d7, 0xfec9,
+    0x0640, 0xfed3, 0xfed7, 0xfedb, 0xfedf, 0xfee3, 0xfee7, 0xfeeb, 0xfeed, 0xfeef, 0xfef3, 0xfebd,
+    0xfecc, 0xfece, 0xfecd, 0xfee1, 0xfe7d, 0x0651, 0xfee5, 0xfee9, 0xfeec, 0xfef0, 0xfef2, 0xfed0,
+    0xfed5, 0xfef5, 0xfef6, 0xfedd, 0xfed9, 0xfef1, 0x25a0, 0x0000,
+];
+
+const ISO8859_15: [u16; 128] = [
+    0x0080, 0x0081, 0x0082, 0x0083, 0x0084, 0x0085, 0x0086, 0x0087, 0x0088, 0x0089, 0x008a, 0x008b,
+    0x008c, 0x008d, 0x008e, 0x008f, 0x0090, 0x0091, 0x0092, 0x0093, 0x0094, 0x0095, 0x0096, 0x0097,
+    0x0098, 0x0099, 0x009a, 0x009b, 0x009c, 0x009d, 0x009e, 0x009f, 0x00a0, 0x00a1, 0x00a2, 0x00a3,
+    0x20ac, 0x00a5, 0x0160, 0x00a7, 0x0161, 0x00a9, 0x00aa, 0x00ab, 0x00ac, 0x00ad, 0x00ae, 0x00af,
+    0x00b0, 0x00b1, 0x00b2, 0x00b3, 0x017d, 0x00b5, 0x00b6, 0x00b7, 0x017e, 0x00b9, 0x00ba, 0x00bb,
+    0x0152, 0x0153, 0x0178, 0x00bf, 0x00c0, 0x00c1, 0x00c2, 0x00c3, 0x00c4, 0x00c5, 0x00c6, 0x00c7,
+    0x00c8, 0x00c9, 0x00ca, 0x00cb, 0x00cc, 0x00cd, 0x00ce, 0x00cf, 0x00d0, 0x00d1, 0x00d2, 0x00d3,
+    0x00d4, 0x00d5, 0x00d6, 0x00d7, 0x00d8, 0x00d9, 0x00da, 0x00db, 0x00dc, 0x00dd, 0x00de, 0x00df,
+    0x00e0, 0x00e1, 0x00e2, 0x00e3, 0x00e4, 0x00e5, 0x00e6, 0x00e7, 0x00e8, 0x00e9, 0x00ea, 0x00eb,
+    0x00ec, 0x00ed, 0x00ee, 0x00ef, 0x00f0, 0x00f1, 0x00f2, 0x00f3, 0x00f4, 0x00f5, 0x00f6, 0x00f7,
+    0x00f8, 0x00f9, 0x00fa, 0x00fb, 0x00fc, 0x00fd, 0x00fe, 0x00ff,
+];
+
+const KATAKANA: [u16; 128] = [
+    0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000,
+    0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000,
+    0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0xff61, 0xff62, 0xff63,
+    0xff64, 0xff65, 0xff66, 0xff67, 0xff68, 0xff69, 0xff6a, 0xff6b, 0xff6c, 0xff6d, 0xff6e, 0xff6f,
+    0xff70, 0xff71, 0xff72, 0xff73, 0xff74, 0xff75, 0xff76, 0xff77, 0xff78, 0xff79, 0xff7a, 0xff7b,
+    0xff7c, 0xff7d, 0xff7e, 0xff7f, 0xff80, 0xff81, 0xff82, 0xff83, 0xff84, 0xff85, 0xff86, 0xff87,
+    0xff88, 0xff89, 0xff8a, 0xff8b, 0xff8c, 0xff8d, 0xff8e, 0xff8f, 0xff90, 0xff91, 0xff92, 0xff93,
+    0xff94, 0xff95, 0xff96, 0xff97, 0xff98, 0xff99, 0xff9a, 0xff9b, 0xff9c, 0xff9d, 0xff9e, 0xff9f,
+    0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000,
+    0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000,
+    0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_page_round_trip() {
+        for page in CodePage::ALL {
+            for b in 0..=255u8 {
+                if let Some(c) = page.decode(b) {
+                    assert_eq!(Some(b), page.encode(c), "{:?} {:02x}", page, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn code_page_encode() {
+        assert_eq!(Some(0x82), CodePage::Pc437.encode('é'));
+        assert_eq!(Some(0x80), CodePage::Wpc1252.encode('€'));
+        assert_eq!(Some(0xa4), CodePage::Iso8859_15.encode('€'));
+        assert_eq!(Some(0xb1), CodePage::Katakana.encode('ｱ'));
+        assert_eq!(None, CodePage::Pc437.encode('€'));
+        assert_eq!(None, CodePage::Thai42.encode('ก'));
+        assert_eq!(Some(b'A'), CodePage::Thai42.encode('A'));
+    }
+
+    #[test]
+    fn encodable_pages() {
+        let pages = [CodePage::Pc437, CodePage::Pc866];
+        assert!(encodable("café дом", &pages));
+        assert!(!encodable("café €", &pages));
+        assert!(!encodable("日本", &pages));
+    }
+
+    #[test]
+    fn transcode_switches() {
+        let pages = [CodePage::Pc437, CodePage::Pc858, CodePage::Pc866];
+        assert_eq!(
+            vec![
+                Command::Text(b"caf\x82 ".to_vec()),
+                Command::CodePage(CodePage::Pc858),
+                Command::Text(b"\xd5 \x82 ?".to_vec()),
+                Command::CodePage(CodePage::Pc866),
+                Command::Text(b"\xa4".to_vec()),
+            ],
+            transcode("café € é ☃д", CodePage::Pc437, &pages)
+        );
+    }
+
+    #[test]
+    fn encode_control() {
+        assert_eq!(None, CodePage::Pc437.encode('\x1b'));
+        assert_eq!(None, CodePage::Wpc1252.encode('\x1d'));
+        assert_eq!(None, CodePage::Pc437.encode('\x7f'));
+        assert_eq!(Some(b'\n'), CodePage::Pc437.encode('\n'));
+        assert_eq!(Some(b'\t'), CodePage::Pc437.encode('\t'));
+        assert_eq!(
+            vec![Command::Text(b"?p\n".to_vec())],
+            transcode("\x1bp\n", CodePage::Pc437, &[CodePage::Pc437])
+        );
+    }
+
+    #[test]
+    fn encoder_selects_page() {
+        let mut encoder = Encoder::new(Model::T20II);
+        assert_eq!(vec![Command::Text(b"abc".to_vec())], encoder.text("abc"));
+        assert_eq!(vec![Command::Text(b"?p".to_vec())], encoder.text("\x1bp"));
+        assert_eq!(
+            vec![
+                Command::CodePage(CodePage::Pc437),
+                Command::Text(b"caf\x82".to_vec()),
+            ],
+            encoder.text("café")
+        );
+        // Once the page is known, it isn't selected again.
+        assert_eq!(vec![Command::Text(b"\x82".to_vec())], encoder.text("é"));
+
+        let mut encoder = Encoder::resume(Model::T20II, CodePage::Pc437, true);
+        assert_eq!(
+            vec![Command::Text("café".as_bytes().to_vec())],
+            encoder.text("café")
+        );
+        assert_eq!(
+            vec![Command::Text("é?V?".as_bytes().to_vec())],
+            encoder.text("é\x1dV\x00")
+        );
+    }
+}
+
+// vim: foldmethod=marker
@@ -18,7 +18,10 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE. }}}
 
-use super::{Error, ImageBuffer};
+use super::{
+    epson_image::pack_columns, user_glyph::GLYPH_HEIGHT_BYTES, AsbFlags, Barcode, CodePage, Error,
+    ImageBuffer, InfoKind, MaintenanceCounter, QrCode, StatusKind, TransmitStatusKind, UserGlyph,
+};
 
 /// Possible horizontal alignments.
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -34,11 +37,258 @@ pub enum Alignment {
     Center = 1,
 }
 
+/// Commands which can be used to cut the paper. Newer printers all accept
+/// `GS V`, but some older or compatible printers only understand one of
+/// the legacy forms.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CutCommand {
+    /// `GS V`, a full cut.
+    GsV,
+
+    /// `ESC i`, the legacy cut.
+    EscI,
+
+    /// `ESC m`, the legacy partial cut.
+    EscM,
+}
+
+impl CutCommand {
+    /// Return the [Command] which cuts the paper this way.
+    pub(crate) fn command(&self) -> Command {
+        match self {
+            CutCommand::GsV => Command::CutWith(CutMode::Full, None),
+            CutCommand::EscI => Command::Cut,
+            CutCommand::EscM => Command::LegacyPartialCut,
+        }
+    }
+
+    /// Return the [CutMode] this command cuts the paper with.
+    pub(crate) fn mode(&self) -> CutMode {
+        match self {
+            CutCommand::GsV | CutCommand::EscI => CutMode::Full,
+            CutCommand::EscM => CutMode::Partial,
+        }
+    }
+}
+
+/// Ways in which the paper can be cut with the `GS V` command.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CutMode {
+    /// Cut all the way through the paper.
+    Full,
+
+    /// Cut the paper, leaving a small bit attached in the middle.
+    Partial,
+}
+
+/// Pins on the drawer kick-out connector that a cash drawer can be
+/// attached to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum DrawerPin {
+    /// Drawer kick-out connector pin 2.
+    Pin2 = 0,
+
+    /// Drawer kick-out connector pin 5.
+    Pin5 = 1,
+}
+
+/// Print colors which can be selected on two-color models.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum Color {
+    /// The normal, first color. Usually black.
+    Black = 0,
+
+    /// The second color. Usually red.
+    Red = 1,
+}
+
+/// Scaling which the printer can apply to an image when printing it.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum ImageScale {
+    /// Print the image at its normal size.
+    #[default]
+    Normal,
+
+    /// Print every pixel twice as wide.
+    DoubleWidth,
+
+    /// Print every pixel twice as tall.
+    DoubleHeight,
+
+    /// Print every pixel twice as wide and twice as tall.
+    Quadruple,
+}
+
+impl ImageScale {
+    /// Return the horizontal and vertical multipliers for this scale.
+    pub(crate) fn factors(&self) -> (u8, u8) {
+        match self {
+            ImageScale::Normal => (1, 1),
+            ImageScale::DoubleWidth => (2, 1),
+            ImageScale::DoubleHeight => (1, 2),
+            ImageScale::Quadruple => (2, 2),
+        }
+    }
+
+    /// Return the `m` parameter used by `GS v 0` and `GS /`.
+    fn mode(&self) -> u8 {
+        match self {
+            ImageScale::Normal => 0,
+            ImageScale::DoubleWidth => 1,
+            ImageScale::DoubleHeight => 2,
+            ImageScale::Quadruple => 3,
+        }
+    }
+}
+
+/// Colors which can be used for character effects with `GS ( N`, on
+/// models that support multiple colors.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum EffectColor {
+    /// No color; nothing is printed.
+    None = 48,
+
+    /// The first color, usually black.
+    Color1 = 49,
+
+    /// The second color, usually red.
+    Color2 = 50,
+
+    /// The third color.
+    Color3 = 51,
+}
+
+/// Dot densities which can be used to print column-format images with
+/// `ESC *`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum ColumnImageMode {
+    /// 8 dots tall per band, at single (half) horizontal density.
+    EightDotSingle = 0,
+
+    /// 8 dots tall per band, at double (full) horizontal density.
+    EightDotDouble = 1,
+
+    /// 24 dots tall per band, at single (half) horizontal density.
+    TwentyFourDotSingle = 32,
+
+    /// 24 dots tall per band, at double (full) horizontal density.
+    TwentyFourDotDouble = 33,
+}
+
+impl ColumnImageMode {
+    /// Return the number of bytes in a single column of a band.
+    pub(crate) fn column_bytes(&self) -> u32 {
+        match self {
+            ColumnImageMode::EightDotSingle | ColumnImageMode::EightDotDouble => 1,
+            ColumnImageMode::TwentyFourDotSingle | ColumnImageMode::TwentyFourDotDouble => 3,
+        }
+    }
+}
+
+/// Key code used to identify a graphic stored in the printer's
+/// non-volatile (NV) memory. Both bytes must be printable ASCII (32
+/// through 126).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NvKey(u8, u8);
+
+impl NvKey {
+    /// Create a new key from the two provided key code bytes.
+    pub fn new(kc1: u8, kc2: u8) -> Result<Self, Error> {
+        if !(32..=126).contains(&kc1) || !(32..=126).contains(&kc2) {
+            return Err(Error::InvalidParameter);
+        }
+        Ok(Self(kc1, kc2))
+    }
+}
+
+/// Code systems used to encode Kanji characters, once Kanji mode is
+/// enabled.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum KanjiCode {
+    /// JIS code.
+    Jis = 0,
+
+    /// Shift-JIS code.
+    ShiftJis = 1,
+}
+
+/// Print modes which apply to Kanji characters.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct KanjiStyle {
+    /// Print Kanji characters twice as wide.
+    pub double_width: bool,
+
+    /// Print Kanji characters twice as tall.
+    pub double_height: bool,
+
+    /// Underline Kanji characters.
+    pub underline: bool,
+}
+
+/// Customized values which can be stored in the printer's non-volatile
+/// memory with `GS ( E`, while in user setting mode.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CustomizeValue {
+    /// Print density, relative to the standard density, from -6 (lightest)
+    /// through 6 (darkest). 0 is the standard density.
+    PrintDensity(i8),
+
+    /// Print speed level, from 1 (slowest) through 13.
+    PrintSpeed(u8),
+}
+
+impl CustomizeValue {
+    /// Return the `GS ( E` setting number and value of this setting.
+    pub(crate) fn setting(&self) -> Result<(u8, u16), Error> {
+        Ok(match self {
+            CustomizeValue::PrintDensity(density) => {
+                if !(-6..=6).contains(density) {
+                    return Err(Error::InvalidParameter);
+                }
+                // negative values wrap around, so -1 is 65535.
+                (5, *density as i16 as u16)
+            }
+            CustomizeValue::PrintSpeed(speed) => {
+                if !(1..=13).contains(speed) {
+                    return Err(Error::InvalidParameter);
+                }
+                (6, *speed as u16)
+            }
+        })
+    }
+}
+
+/// Kinds of paper a label printer can be loaded with, selected with
+/// `FS ( L` function 33.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum PaperLayout {
+    /// Plain receipt paper, without any positioning marks.
+    Receipt = 48,
+
+    /// Die-cut label paper, positioned using the gaps between labels.
+    Label = 49,
+
+    /// Paper with black marks printed on the back, positioned using the
+    /// marks.
+    BlackMark = 50,
+}
+
 /// All commands that can be encoded to control an Epson printer.
+#[derive(Clone, Debug, PartialEq)]
 pub enum Command {
     /// Initiaize the printer.
     Init,
 
+    /// Text to print, sent to the printer as-is. This should only hold
+    /// characters in the current character set, and line feeds.
+    Text(Vec<u8>),
+
     /// If true, underline the printed text following. If false, remove
     /// text decoration.
     Underline(bool),
@@ -55,24 +305,379 @@ pub enum Command {
     /// remove text decoration.
     Reverse(bool),
 
+    /// If true, rotate the printed text following by 90 degrees clockwise.
+    /// If false, print text normally.
+    Rotate90(bool),
+
+    /// If true, smooth the edges of the printed text following, which
+    /// mostly matters for scaled up text. If false, disable smoothing.
+    Smoothing(bool),
+
+    /// Select the color used to print the following text, on two-color
+    /// models.
+    PrintColor(Color),
+
+    /// Select the color of the printed characters following.
+    CharacterColor(EffectColor),
+
+    /// Select the background color of the printed characters following.
+    BackgroundColor(EffectColor),
+
+    /// If set, print a shadow of the provided color behind the printed
+    /// characters following. If `None`, remove the shadow.
+    Shadow(Option<EffectColor>),
+
     /// Align the text to follow accoridng to the specified horizontal
     /// text alignment.
     Justification(Alignment),
 
-    /// Set the print speed.
+    /// Set the print speed level. The valid levels depend on the model,
+    /// see [crate::Model::get_speed_range].
     Speed(u8),
 
-    /// Cut the thermal printer.
+    /// Set the print density, relative to the standard density, from -6
+    /// (lightest) through 6 (darkest). This lasts until the printer is
+    /// reset; see [CustomizeValue::PrintDensity] to store it.
+    Density(i8),
+
+    /// Enter user setting mode, which is required before changing any
+    /// customized values.
+    EnterUserSetting,
+
+    /// Leave user setting mode. This resets the printer.
+    ExitUserSetting,
+
+    /// Store a customized value in non-volatile memory. This is only
+    /// valid in user setting mode.
+    SetCustomizeValue(CustomizeValue),
+
+    /// Cut the thermal printer using the legacy `ESC i` command.
     Cut,
 
+    /// Partially cut the paper using the legacy `ESC m` command.
+    LegacyPartialCut,
+
+    /// Cut the paper using `GS V` in the provided [CutMode]. If a feed
+    /// distance (in motion units) is provided, the paper is fed to the
+    /// cutting position plus that distance before being cut.
+    CutWith(CutMode, Option<u8>),
+
     /// Feed the specified number of lines.
     Feed(u8),
 
+    /// Feed the paper by the specified number of motion units (typically
+    /// dots), rather than by lines.
+    FeedUnits(u8),
+
+    /// Feed the paper backwards by the specified number of lines.
+    ReverseFeed(u8),
+
+    /// Scale the printed characters following. Both the width and height
+    /// are multipliers, from 1 (normal size) to 8.
+    TextSize {
+        /// Horizontal multiplier, 1 through 8.
+        width: u8,
+
+        /// Vertical multiplier, 1 through 8.
+        height: u8,
+    },
+
+    /// If true, print the following characters twice as wide, using
+    /// `GS !`. This also resets the character height to normal.
+    DoubleWidth(bool),
+
+    /// If true, print the following characters twice as tall, using
+    /// `GS !`. This also resets the character width to normal.
+    DoubleHeight(bool),
+
+    /// Select double width and double height characters using the older
+    /// `ESC !` command, for printers without `GS !`. This also resets the
+    /// font, emphasis and underline.
+    LegacyPrintMode {
+        /// Print characters twice as wide.
+        double_width: bool,
+
+        /// Print characters twice as tall.
+        double_height: bool,
+    },
+
+    /// Reset the line spacing to the printer's default.
+    DefaultLineSpacing,
+
+    /// Set the line spacing to the provided number of motion units
+    /// (typically dots).
+    LineSpacing(u8),
+
+    /// Set the horizontal tab stop positions, in columns from the start of
+    /// the line. Positions must be ascending and non-zero, and at most 32
+    /// may be set. An empty list clears all tab stops.
+    SetTabStops(Vec<u8>),
+
+    /// Move the print position to the next horizontal tab stop.
+    HorizontalTab,
+
+    /// Send a pulse to the drawer kick-out connector, which opens an
+    /// attached cash drawer. The printer times pulses in 2ms increments, so
+    /// the provided times are rounded down to the nearest even number.
+    CashDrawer {
+        /// Connector pin to send the pulse to.
+        pin: DrawerPin,
+
+        /// Time the pulse is on, in milliseconds.
+        on_ms: u8,
+
+        /// Time the pulse is off, in milliseconds.
+        off_ms: u8,
+    },
+
+    /// Sound the built-in buzzer using `ESC ( A`, as found on the TM-T88
+    /// series.
+    Buzzer {
+        /// Sound pattern to play, from 1 through 10.
+        pattern: u8,
+
+        /// Number of times to repeat the pattern, from 1 through 63.
+        repetitions: u8,
+    },
+
+    /// Upload user-defined characters to the printer, replacing the
+    /// character codes starting at `first`. Codes must be between 32 and
+    /// 126.
+    DefineUserGlyphs {
+        /// Character code of the first glyph.
+        first: u8,
+
+        /// Glyphs to define, assigned to consecutive character codes.
+        glyphs: Vec<UserGlyph>,
+    },
+
+    /// If true, print user-defined characters in place of the built-in
+    /// ones. If false, use the built-in character set.
+    UserGlyphs(bool),
+
+    /// Remove the user-defined character for the provided character code.
+    ClearUserGlyph(u8),
+
+    /// Start recording a macro. Commands sent until [Command::MacroEnd]
+    /// are stored in the printer rather than executed.
+    MacroStart,
+
+    /// Stop recording a macro.
+    MacroEnd,
+
+    /// Execute the stored macro.
+    MacroExecute {
+        /// Number of times to execute the macro.
+        count: u8,
+
+        /// Time to wait between executions, in 100ms units.
+        interval: u8,
+
+        /// If true, wait for the FEED button to be pressed before each
+        /// execution, rather than waiting for `interval`.
+        button: bool,
+    },
+
+    /// Request a status byte from the printer, which is processed as soon
+    /// as it is received, even if the printer is busy or offline. The
+    /// response can be parsed with [crate::RealTimeStatus::parse].
+    RealTimeStatusRequest(StatusKind),
+
+    /// Enable Automatic Status Back (ASB), which makes the printer send a
+    /// 4 byte status frame whenever one of the selected statuses changes.
+    /// Frames can be parsed with [crate::AsbStatus::parse].
+    EnableAsb(AsbFlags),
+
+    /// Request a status byte from the printer, which is sent once
+    /// everything before this command has been processed.
+    TransmitStatus(TransmitStatusKind),
+
+    /// Ask the printer to send back the provided process ID once
+    /// everything before this command, including any cut, has finished
+    /// printing. The ID is four ASCII digits. The response is
+    /// `0x37 0x22`, the ID, and a trailing NUL.
+    RequestProcessId([u8; 4]),
+
+    /// Request information about the printer, such as the model or the
+    /// firmware version. The response can be parsed with
+    /// [crate::PrinterInfo::parse].
+    RequestPrinterId(InfoKind),
+
+    /// Request the current value of a maintenance counter. If `cumulative`
+    /// is true, the lifetime value is returned rather than the value since
+    /// it was last reset. The response can be parsed with
+    /// [crate::MaintenanceCounter::parse_value].
+    RequestMaintenanceCounter {
+        /// Counter to request.
+        counter: MaintenanceCounter,
+
+        /// If true, request the lifetime counter.
+        cumulative: bool,
+    },
+
+    /// Reset a maintenance counter to zero.
+    ResetMaintenanceCounter(MaintenanceCounter),
+
+    /// Delete all the print data in the current page mode print area.
+    /// This has no effect in standard mode.
+    CancelPageData,
+
+    /// Clear the receive and print buffers, in real time. Any data which
+    /// has been sent but not yet printed is discarded. The printer responds
+    /// with `0x37 0x25 0x00` once the buffers are cleared.
+    ///
+    /// This is `DLE DC4` function 8.
+    ClearBuffers,
+
+    /// Recover from a recoverable error, such as a cleared cutter jam or a
+    /// closed cover, in real time, and restart printing from the line
+    /// where the error occurred.
+    ///
+    /// This is `DLE ENQ 1`.
+    RecoverAndRestart,
+
+    /// Recover from a recoverable error in real time, clearing the receive
+    /// and print buffers, so the interrupted job is cancelled.
+    ///
+    /// This is `DLE ENQ 2`.
+    RecoverAndCancel,
+
+    /// Send a pulse to the drawer kick-out connector in real time, even if
+    /// the printer is busy with a long job. Unlike [Command::CashDrawer],
+    /// this is not queued behind the data already sent.
+    RealTimeDrawerPulse {
+        /// Connector pin to send the pulse to.
+        pin: DrawerPin,
+
+        /// Time the pulse is on, in 100ms units, from 1 through 8.
+        duration: u8,
+    },
+
+    /// If true, enable Kanji mode, where multibyte character codes are
+    /// printed as Kanji. If false, return to single byte characters.
+    KanjiMode(bool),
+
+    /// Select the [KanjiCode] used to encode Kanji characters.
+    KanjiCodeSystem(KanjiCode),
+
+    /// Set the [KanjiStyle] used to print Kanji characters.
+    KanjiStyle(KanjiStyle),
+
+    /// Select the kind of paper loaded in a label printer. The layout
+    /// dimensions stored in the printer are used.
+    SetPaperLayout(PaperLayout),
+
+    /// Feed label or black mark paper to the print starting position of
+    /// the next label.
+    FeedToLabelOrigin,
+
+    /// Feed label or black mark paper to the cutting position.
+    FeedToCutPosition,
+
+    /// Feed label paper to the label peeling position.
+    FeedToPeelPosition,
+
+    /// Switch the active [CodePage], which decides how bytes above 0x7F
+    /// are printed.
+    CodePage(CodePage),
+
     /// Switch the active character set.
     CharacterSet(CharacterSet),
 
-    /// Print a greyscale image
-    Image(image::ImageBuffer<image::Luma<u8>, Vec<u8>>),
+    /// Store a raster image in the print buffer with `GS ( L` function
+    /// 112, to be printed in the provided [Color]. Several images can be
+    /// stored, one per color, and printed on top of each other with
+    /// [Command::PrintGraphics].
+    StoreGraphics {
+        /// Color the image is printed in.
+        color: Color,
+
+        /// Image to store; dark pixels are printed.
+        image: image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    },
+
+    /// Print the graphics stored in the print buffer with
+    /// [Command::StoreGraphics].
+    PrintGraphics,
+
+    /// Print an already packed 1-bit raster image with `GS v 0`. Each row
+    /// is `width_bytes` bytes, with the most significant bit on the left
+    /// and set bits printed black. `data` must be exactly
+    /// `width_bytes * height` bytes long.
+    RasterBits {
+        /// Width of each row in bytes, or 8 pixels per byte.
+        width_bytes: u16,
+
+        /// Number of rows.
+        height: u16,
+
+        /// Packed pixel data, row by row.
+        data: Vec<u8>,
+    },
+
+    /// Print a greyscale image as a `GS v 0` raster image, enlarged by the
+    /// printer using the provided [ImageScale].
+    Image(image::ImageBuffer<image::Luma<u8>, Vec<u8>>, ImageScale),
+
+    /// Store a greyscale image in the printer's non-volatile memory, to be
+    /// printed later by referring to its [NvKey].
+    DefineNvLogo {
+        /// Key the image will be stored under.
+        key: NvKey,
+
+        /// Image to store.
+        image: image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    },
+
+    /// Print an image previously stored in non-volatile memory.
+    PrintNvLogo {
+        /// Key of the image to print.
+        key: NvKey,
+
+        /// Scaling to apply to the image.
+        scale: ImageScale,
+    },
+
+    /// Delete an image stored in non-volatile memory.
+    DeleteNvLogo(NvKey),
+
+    /// Delete every image stored in non-volatile memory.
+    DeleteAllNvLogos,
+
+    /// A single `ESC *` band of a column-format bit image, 8 or 24 dots
+    /// tall depending on the [ColumnImageMode], without the line feed that
+    /// prints it. Most callers want [Command::ColumnImage] instead.
+    ColumnBand(
+        ColumnImageMode,
+        image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    ),
+
+    /// Print a greyscale image using column-format `ESC *` bit images. The
+    /// image is printed as a series of 8 or 24 dot tall bands, one per line,
+    /// with the line spacing set to the band height while printing, and
+    /// reset to the default afterwards.
+    ///
+    /// This is slower than [Command::Image], but some older printers only
+    /// render `ESC *` reliably.
+    ColumnImage(
+        ColumnImageMode,
+        image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    ),
+
+    /// Define a downloaded bit image, stored in the printer's RAM until it
+    /// is redefined or the printer is reset. The image must be no more
+    /// than 2040 dots wide and 384 dots tall, and, with each side rounded
+    /// up to a multiple of 8, no more than 98304 dots in total.
+    DefineDownloadedImage(image::ImageBuffer<image::Luma<u8>, Vec<u8>>),
+
+    /// Print the image defined by [Command::DefineDownloadedImage].
+    PrintDownloadedImage(ImageScale),
+
+    /// Print a barcode.
+    Barcode(Barcode),
+
+    /// Print a QR Code (or Micro QR Code) symbol.
+    QrCode(QrCode),
 }
 
 /// CharacterSet are the codepages that can be set
@@ -86,32 +691,392 @@ pub enum CharacterSet {
     Unicode = 0x02,
 }
 
+/// Return the `GS ( L` header for a graphics command with `len` bytes of
+/// parameters. `GS ( L` can only describe 65535 bytes of parameters, past
+/// that the `GS 8 L` form with a 32 bit length is needed.
+fn graphics_header(len: usize) -> Vec<u8> {
+    match u16::try_from(len) {
+        Ok(len) => {
+            let [p1, p2] = len.to_le_bytes();
+            vec![0x1d, b'(', b'L', p1, p2]
+        }
+        Err(_) => {
+            let [p1, p2, p3, p4] = (len as u32).to_le_bytes();
+            vec![0x1d, b'8', b'L', p1, p2, p3, p4]
+        }
+    }
+}
+
 impl Command {
     /// Return the command as raw bytes which can be sent to a POS printer.
     pub fn as_bytes(&self) -> Result<Vec<u8>, Error> {
         Ok(match self {
             Command::Init => vec![0x1b, b'@'],
+            Command::Text(text) => text.clone(),
             Command::Cut => vec![0x1b, b'i'],
+            Command::LegacyPartialCut => vec![0x1b, b'm'],
+            Command::CutWith(mode, feed) => match (mode, feed) {
+                (CutMode::Full, None) => vec![0x1d, b'V', 0x00],
+                (CutMode::Partial, None) => vec![0x1d, b'V', 0x01],
+                (CutMode::Full, Some(n)) => vec![0x1d, b'V', 65, *n],
+                (CutMode::Partial, Some(n)) => vec![0x1d, b'V', 66, *n],
+            },
             Command::Underline(state) => vec![0x1b, b'-', if *state { 1 } else { 0 }],
             Command::Emphasize(state) => vec![0x1b, b'E', if *state { 0xFF } else { 0 }],
             Command::DoubleStrike(state) => vec![0x1b, b'G', if *state { 0xFF } else { 0 }],
             Command::Reverse(state) => vec![0x1b, b'B', if *state { 0xFF } else { 0 }],
+            Command::Rotate90(state) => vec![0x1b, b'V', if *state { 1 } else { 0 }],
+            Command::Smoothing(state) => vec![0x1d, b'b', if *state { 1 } else { 0 }],
+            Command::PrintColor(color) => vec![0x1b, b'r', *color as u8],
+            Command::CharacterColor(color) => {
+                vec![0x1d, b'(', b'N', 0x02, 0x00, 0x30, *color as u8]
+            }
+            Command::BackgroundColor(color) => {
+                vec![0x1d, b'(', b'N', 0x02, 0x00, 0x31, *color as u8]
+            }
+            Command::Shadow(color) => match color {
+                Some(color) => vec![0x1d, b'(', b'N', 0x03, 0x00, 0x32, 0x31, *color as u8],
+                None => vec![
+                    0x1d,
+                    b'(',
+                    b'N',
+                    0x03,
+                    0x00,
+                    0x32,
+                    0x30,
+                    EffectColor::None as u8,
+                ],
+            },
             Command::Justification(alignment) => vec![0x1b, b'a', *alignment as u8],
             Command::Feed(count) => vec![0x1b, b'd', *count],
-            Command::Speed(speed) => vec![0x1d, 0x28, 0x4b, 0x02, 0x00, 0x32, speed % 9],
+            Command::FeedUnits(units) => vec![0x1b, b'J', *units],
+            Command::ReverseFeed(count) => vec![0x1b, b'e', *count],
+            Command::Speed(speed) => vec![0x1d, 0x28, 0x4b, 0x02, 0x00, 0x32, *speed],
+            Command::TextSize { width, height } => {
+                if !(1..=8).contains(width) || !(1..=8).contains(height) {
+                    return Err(Error::InvalidParameter);
+                }
+                vec![0x1d, b'!', ((width - 1) << 4) | (height - 1)]
+            }
+            Command::DoubleWidth(state) => vec![0x1d, b'!', if *state { 0x10 } else { 0x00 }],
+            Command::DoubleHeight(state) => vec![0x1d, b'!', if *state { 0x01 } else { 0x00 }],
+            Command::LegacyPrintMode {
+                double_width,
+                double_height,
+            } => vec![
+                0x1b,
+                b'!',
+                (*double_height as u8) << 4 | (*double_width as u8) << 5,
+            ],
+            Command::DefaultLineSpacing => vec![0x1b, b'2'],
+            Command::LineSpacing(spacing) => vec![0x1b, b'3', *spacing],
+            Command::SetTabStops(stops) => {
+                if stops.len() > 32 || stops.contains(&0) || stops.windows(2).any(|w| w[0] >= w[1])
+                {
+                    return Err(Error::InvalidParameter);
+                }
+
+                [0x1b, b'D']
+                    .iter()
+                    .chain(stops)
+                    .chain(&[0x00])
+                    .copied()
+                    .collect()
+            }
+            Command::HorizontalTab => vec![0x09],
+            Command::CashDrawer { pin, on_ms, off_ms } => {
+                vec![0x1b, b'p', *pin as u8, on_ms / 2, off_ms / 2]
+            }
+            Command::Buzzer {
+                pattern,
+                repetitions,
+            } => {
+                if !(1..=10).contains(pattern) || !(1..=63).contains(repetitions) {
+                    return Err(Error::InvalidParameter);
+                }
+                vec![
+                    0x1b,
+                    b'(',
+                    b'A',
+                    0x04,
+                    0x00,
+                    0x30,
+                    0x30 + pattern,
+                    *repetitions,
+                    10,
+                ]
+            }
+            Command::DefineUserGlyphs { first, glyphs } => {
+                let last = (*first as usize + glyphs.len()).saturating_sub(1);
+                if glyphs.is_empty() || *first < 32 || last > 126 {
+                    return Err(Error::InvalidParameter);
+                }
+
+                let mut out = vec![0x1b, b'&', GLYPH_HEIGHT_BYTES, *first, last as u8];
+                for glyph in glyphs {
+                    out.push(glyph.width());
+                    out.extend_from_slice(glyph.data());
+                }
+                out
+            }
+            Command::UserGlyphs(state) => vec![0x1b, b'%', if *state { 1 } else { 0 }],
+            Command::ClearUserGlyph(code) => {
+                if !(32..=126).contains(code) {
+                    return Err(Error::InvalidParameter);
+                }
+                vec![0x1b, b'?', *code]
+            }
+            Command::MacroStart | Command::MacroEnd => vec![0x1d, b':'],
+            Command::MacroExecute {
+                count,
+                interval,
+                button,
+            } => vec![0x1d, b'^', *count, *interval, if *button { 1 } else { 0 }],
+            Command::RealTimeStatusRequest(kind) => vec![0x10, 0x04, *kind as u8],
+            Command::EnableAsb(flags) => vec![0x1d, b'a', flags.bits()],
+            Command::TransmitStatus(kind) => vec![0x1d, b'r', *kind as u8],
+            Command::RequestProcessId(id) => {
+                if !id.iter().all(u8::is_ascii_digit) {
+                    return Err(Error::InvalidParameter);
+                }
+                [&[0x1d, b'(', b'H', 0x06, 0x00, 0x30, 0x30][..], id].concat()
+            }
+            Command::RequestPrinterId(kind) => vec![0x1d, b'I', *kind as u8],
+            Command::RequestMaintenanceCounter {
+                counter,
+                cumulative,
+            } => {
+                let [n1, n2] = counter.id(*cumulative).to_le_bytes();
+                vec![0x1d, b'g', 0x32, 0x00, n1, n2]
+            }
+            Command::ResetMaintenanceCounter(counter) => {
+                let [n1, n2] = counter.id(false).to_le_bytes();
+                vec![0x1d, b'g', 0x30, 0x00, n1, n2]
+            }
+            Command::CancelPageData => vec![0x18],
+            Command::ClearBuffers => {
+                vec![0x10, 0x14, 0x08, 0x01, 0x03, 0x14, 0x01, 0x06, 0x02, 0x08]
+            }
+            Command::RecoverAndRestart => vec![0x10, 0x05, 0x01],
+            Command::RecoverAndCancel => vec![0x10, 0x05, 0x02],
+            Command::RealTimeDrawerPulse { pin, duration } => {
+                if !(1..=8).contains(duration) {
+                    return Err(Error::InvalidParameter);
+                }
+                vec![0x10, 0x14, 0x01, *pin as u8, *duration]
+            }
+            Command::KanjiMode(state) => vec![0x1c, if *state { b'&' } else { b'.' }],
+            Command::KanjiCodeSystem(code) => vec![0x1c, b'C', *code as u8],
+            Command::KanjiStyle(style) => vec![
+                0x1c,
+                b'!',
+                (style.double_width as u8) << 2
+                    | (style.double_height as u8) << 3
+                    | (style.underline as u8) << 7,
+            ],
+            Command::Density(density) => {
+                if !(-6..=6).contains(density) {
+                    return Err(Error::InvalidParameter);
+                }
+                vec![0x1d, 0x28, 0x4b, 0x02, 0x00, 0x31, *density as u8]
+            }
+            Command::EnterUserSetting => vec![0x1d, 0x28, 0x45, 0x03, 0x00, 0x01, b'I', b'N'],
+            Command::ExitUserSetting => {
+                vec![0x1d, 0x28, 0x45, 0x04, 0x00, 0x02, b'O', b'U', b'T']
+            }
+            Command::SetCustomizeValue(value) => {
+                let (a, n) = value.setting()?;
+                let [n1, n2] = n.to_le_bytes();
+                vec![0x1d, 0x28, 0x45, 0x04, 0x00, 0x05, a, n1, n2]
+            }
+            Command::SetPaperLayout(layout) => {
+                vec![0x1c, b'(', b'L', 0x02, 0x00, 0x21, *layout as u8]
+            }
+            Command::FeedToLabelOrigin => vec![0x1d, 0x0c],
+            Command::FeedToCutPosition => vec![0x1c, b'(', b'L', 0x02, 0x00, 0x42, 0x31],
+            Command::FeedToPeelPosition => vec![0x1c, b'(', b'L', 0x02, 0x00, 0x41, 0x31],
+            Command::CodePage(page) => vec![0x1b, b't', *page as u8],
             Command::CharacterSet(page) => vec![0x1C, 0x28, 0x43, 0x02, 0x00, 0x30, *page as u8],
-            Command::Image(img) => {
-                let buf: ImageBuffer = (img.clone()).try_into()?;
+            Command::Image(img, scale) => {
+                let buf: ImageBuffer = img.try_into()?;
 
                 let [w1, w2] = buf.width.to_le_bytes();
                 let [h1, h2] = buf.height.to_le_bytes();
 
+                [0x1d, 0x76, 0x30, scale.mode(), w1, w2, h1, h2]
+                    .iter()
+                    .chain(&buf.pixels)
+                    .copied()
+                    .collect()
+            }
+            Command::RasterBits {
+                width_bytes,
+                height,
+                data,
+            } => {
+                if data.len() != *width_bytes as usize * *height as usize {
+                    return Err(Error::InvalidParameter);
+                }
+
+                let [w1, w2] = width_bytes.to_le_bytes();
+                let [h1, h2] = height.to_le_bytes();
+
                 [0x1d, 0x76, 0x30, 0x00, w1, w2, h1, h2]
                     .iter()
+                    .chain(data)
+                    .copied()
+                    .collect()
+            }
+            Command::StoreGraphics { color, image } => {
+                let buf: ImageBuffer = image.try_into()?;
+
+                let [w1, w2] = buf
+                    .width
+                    .checked_mul(8)
+                    .ok_or(Error::ImageTooLarge)?
+                    .to_le_bytes();
+                let [h1, h2] = buf.height.to_le_bytes();
+                let body = [
+                    0x30,
+                    0x70,
+                    0x30,
+                    0x01,
+                    0x01,
+                    49 + *color as u8,
+                    w1,
+                    w2,
+                    h1,
+                    h2,
+                ];
+
+                graphics_header(body.len() + buf.pixels.len())
+                    .iter()
+                    .chain(&body)
+                    .chain(&buf.pixels)
+                    .copied()
+                    .collect()
+            }
+            Command::PrintGraphics => vec![0x1d, b'(', b'L', 0x02, 0x00, 0x30, 0x32],
+            Command::DefineNvLogo { key, image } => {
+                let buf: ImageBuffer = image.try_into()?;
+
+                let [w1, w2] = buf
+                    .width
+                    .checked_mul(8)
+                    .ok_or(Error::ImageTooLarge)?
+                    .to_le_bytes();
+                let [h1, h2] = buf.height.to_le_bytes();
+                let body = [0x30, 0x43, 0x30, key.0, key.1, 0x01, w1, w2, h1, h2, 0x31];
+
+                graphics_header(body.len() + buf.pixels.len())
+                    .iter()
+                    .chain(&body)
                     .chain(&buf.pixels)
                     .copied()
                     .collect()
             }
+            Command::PrintNvLogo { key, scale } => {
+                let (x, y) = scale.factors();
+                vec![0x1d, b'(', b'L', 0x06, 0x00, 0x30, 0x45, key.0, key.1, x, y]
+            }
+            Command::DeleteNvLogo(key) => {
+                vec![0x1d, b'(', b'L', 0x04, 0x00, 0x30, 0x42, key.0, key.1]
+            }
+            Command::DeleteAllNvLogos => {
+                vec![0x1d, b'(', b'L', 0x05, 0x00, 0x30, 0x41, b'C', b'L', b'R']
+            }
+            Command::ColumnBand(mode, img) => {
+                let width = img.width();
+                let [w1, w2] = u16::try_from(width)
+                    .map_err(|_| Error::ImageTooLarge)?
+                    .to_le_bytes();
+
+                [0x1b, b'*', *mode as u8, w1, w2]
+                    .iter()
+                    .copied()
+                    .chain(pack_columns(img, 0, width, mode.column_bytes()))
+                    .collect()
+            }
+            Command::ColumnImage(mode, img) => {
+                let (width, height) = img.dimensions();
+                let [w1, w2] = u16::try_from(width)
+                    .map_err(|_| Error::ImageTooLarge)?
+                    .to_le_bytes();
+                let band = mode.column_bytes() * 8;
+
+                // 8 dot bands are printed at a third of the vertical
+                // density, so every band is 24 dots tall on paper.
+                let mut out = vec![0x1b, b'3', 24];
+                for y in (0..height).step_by(band as usize) {
+                    out.extend_from_slice(&[0x1b, b'*', *mode as u8, w1, w2]);
+                    out.extend(pack_columns(img, y, width, mode.column_bytes()));
+                    out.push(b'\n');
+                }
+                out.extend_from_slice(&[0x1b, b'2']);
+                out
+            }
+            Command::DefineDownloadedImage(img) => {
+                let (width, height) = img.dimensions();
+                let x = width.div_ceil(8);
+                let y = height.div_ceil(8);
+                if x == 0 || y == 0 || x > 255 || y > 48 || x * y > 1536 {
+                    return Err(Error::ImageTooLarge);
+                }
+
+                [0x1d, b'*', x as u8, y as u8]
+                    .iter()
+                    .chain(&pack_columns(img, 0, x * 8, y))
+                    .copied()
+                    .collect()
+            }
+            Command::PrintDownloadedImage(scale) => vec![0x1d, b'/', scale.mode()],
+            Command::Barcode(barcode) => {
+                let len: u8 = barcode
+                    .data()
+                    .len()
+                    .try_into()
+                    .map_err(|_| Error::InvalidBarcode)?;
+
+                [0x1d, b'k', barcode.system() as u8, len]
+                    .iter()
+                    .chain(barcode.data())
+                    .copied()
+                    .collect()
+            }
+            Command::QrCode(qr) => {
+                qr.check()?;
+
+                let [p1, p2] = ((qr.data.len() + 3) as u16).to_le_bytes();
+
+                [
+                    0x1d,
+                    0x28,
+                    0x6b,
+                    0x04,
+                    0x00,
+                    0x31,
+                    0x41,
+                    qr.model as u8,
+                    0x00,
+                ]
+                .iter()
+                .chain(&[0x1d, 0x28, 0x6b, 0x03, 0x00, 0x31, 0x43, qr.module_size])
+                .chain(&[
+                    0x1d,
+                    0x28,
+                    0x6b,
+                    0x03,
+                    0x00,
+                    0x31,
+                    0x45,
+                    qr.error_correction as u8,
+                ])
+                .chain(&[0x1d, 0x28, 0x6b, p1, p2, 0x31, 0x50, 0x30])
+                .chain(&qr.data)
+                .chain(&[0x1d, 0x28, 0x6b, 0x03, 0x00, 0x31, 0x51, 0x30])
+                .copied()
+                .collect()
+            }
         })
     }
 }
@@ -133,6 +1098,25 @@ mod tests {
     // "Easy" commands
     test_encoding_of!(encode_init, [0x1b, 0x40], || { Command::Init });
     test_encoding_of!(encode_cut, [0x1b, 0x69], || { Command::Cut });
+    test_encoding_of!(encode_text, [0x48, 0x69, 0x0a], || {
+        Command::Text(b"Hi\n".to_vec())
+    });
+    test_encoding_of!(encode_legacy_partial_cut, [0x1b, 0x6d], || {
+        Command::LegacyPartialCut
+    });
+
+    test_encoding_of!(encode_cut_full, [0x1d, 0x56, 0x00], || {
+        Command::CutWith(CutMode::Full, None)
+    });
+    test_encoding_of!(encode_cut_partial, [0x1d, 0x56, 0x01], || {
+        Command::CutWith(CutMode::Partial, None)
+    });
+    test_encoding_of!(encode_cut_full_feed, [0x1d, 0x56, 0x41, 0x10], || {
+        Command::CutWith(CutMode::Full, Some(16))
+    });
+    test_encoding_of!(encode_cut_partial_feed, [0x1d, 0x56, 0x42, 0x10], || {
+        Command::CutWith(CutMode::Partial, Some(16))
+    });
 
     // Underline command
     test_encoding_of!(encode_underline_false, [0x1b, 0x2d, 0x00], || {
@@ -166,6 +1150,52 @@ mod tests {
         Command::Reverse(true)
     });
 
+    // Rotate90 command
+    test_encoding_of!(encode_rotate90_false, [0x1b, 0x56, 0x00], || {
+        Command::Rotate90(false)
+    });
+    test_encoding_of!(encode_rotate90_true, [0x1b, 0x56, 0x01], || {
+        Command::Rotate90(true)
+    });
+
+    // Smoothing command
+    test_encoding_of!(encode_smoothing_false, [0x1d, 0x62, 0x00], || {
+        Command::Smoothing(false)
+    });
+    test_encoding_of!(encode_smoothing_true, [0x1d, 0x62, 0x01], || {
+        Command::Smoothing(true)
+    });
+
+    // PrintColor command
+    test_encoding_of!(encode_print_color_black, [0x1b, 0x72, 0x00], || {
+        Command::PrintColor(Color::Black)
+    });
+    test_encoding_of!(encode_print_color_red, [0x1b, 0x72, 0x01], || {
+        Command::PrintColor(Color::Red)
+    });
+
+    // Character effects
+    test_encoding_of!(
+        encode_character_color,
+        [0x1d, 0x28, 0x4e, 0x02, 0x00, 0x30, 0x32],
+        || { Command::CharacterColor(EffectColor::Color2) }
+    );
+    test_encoding_of!(
+        encode_background_color,
+        [0x1d, 0x28, 0x4e, 0x02, 0x00, 0x31, 0x30],
+        || { Command::BackgroundColor(EffectColor::None) }
+    );
+    test_encoding_of!(
+        encode_shadow_on,
+        [0x1d, 0x28, 0x4e, 0x03, 0x00, 0x32, 0x31, 0x32],
+        || { Command::Shadow(Some(EffectColor::Color2)) }
+    );
+    test_encoding_of!(
+        encode_shadow_off,
+        [0x1d, 0x28, 0x4e, 0x03, 0x00, 0x32, 0x30, 0x30],
+        || { Command::Shadow(None) }
+    );
+
     // Justification command
     test_encoding_of!(encode_justify_left, [0x1b, 0x61, 0x00], || {
         Command::Justification(Alignment::Left)
@@ -180,6 +1210,14 @@ mod tests {
     test_encoding_of!(feed_0, [0x1b, 0x64, 0x00], || { Command::Feed(0) });
     test_encoding_of!(feed_10, [0x1b, 0x64, 0x0a], || { Command::Feed(10) });
 
+    test_encoding_of!(feed_units_24, [0x1b, 0x4a, 0x18], || {
+        Command::FeedUnits(24)
+    });
+
+    test_encoding_of!(reverse_feed_2, [0x1b, 0x65, 0x02], || {
+        Command::ReverseFeed(2)
+    });
+
     test_encoding_of!(speed_1, [0x1d, 0x28, 0x4b, 0x02, 0x00, 0x32, 0x01], || {
         Command::Speed(1)
     });
@@ -187,11 +1225,348 @@ mod tests {
         Command::Speed(8)
     });
 
-    // currently this is modulo
-    test_encoding_of!(speed_9, [0x1d, 0x28, 0x4b, 0x02, 0x00, 0x32, 0x00], || {
+    // speeds are sent as-is, validation happens in the writers
+    test_encoding_of!(speed_9, [0x1d, 0x28, 0x4b, 0x02, 0x00, 0x32, 0x09], || {
         Command::Speed(9)
     });
 
+    test_encoding_of!(text_size_1x1, [0x1d, 0x21, 0x00], || {
+        Command::TextSize {
+            width: 1,
+            height: 1,
+        }
+    });
+    test_encoding_of!(text_size_2x3, [0x1d, 0x21, 0x12], || {
+        Command::TextSize {
+            width: 2,
+            height: 3,
+        }
+    });
+    test_encoding_of!(text_size_8x8, [0x1d, 0x21, 0x77], || {
+        Command::TextSize {
+            width: 8,
+            height: 8,
+        }
+    });
+
+    #[test]
+    fn text_size_out_of_range() {
+        for (width, height) in [(0, 1), (1, 0), (9, 1), (1, 9)] {
+            assert_eq!(
+                Err(Error::InvalidParameter),
+                Command::TextSize { width, height }.as_bytes()
+            );
+        }
+    }
+
+    test_encoding_of!(double_width, [0x1d, 0x21, 0x10], || {
+        Command::DoubleWidth(true)
+    });
+    test_encoding_of!(double_height, [0x1d, 0x21, 0x01], || {
+        Command::DoubleHeight(true)
+    });
+    test_encoding_of!(double_height_off, [0x1d, 0x21, 0x00], || {
+        Command::DoubleHeight(false)
+    });
+    test_encoding_of!(legacy_double_size, [0x1b, 0x21, 0x30], || {
+        Command::LegacyPrintMode {
+            double_width: true,
+            double_height: true,
+        }
+    });
+
+    test_encoding_of!(default_line_spacing, [0x1b, 0x32], || {
+        Command::DefaultLineSpacing
+    });
+    test_encoding_of!(line_spacing_24, [0x1b, 0x33, 0x18], || {
+        Command::LineSpacing(24)
+    });
+
+    test_encoding_of!(tab_stops_clear, [0x1b, 0x44, 0x00], || {
+        Command::SetTabStops(vec![])
+    });
+    test_encoding_of!(tab_stops, [0x1b, 0x44, 0x08, 0x10, 0x20, 0x00], || {
+        Command::SetTabStops(vec![8, 16, 32])
+    });
+    test_encoding_of!(horizontal_tab, [0x09], || { Command::HorizontalTab });
+
+    #[test]
+    fn tab_stops_invalid() {
+        for stops in [vec![0], vec![8, 8], vec![16, 8], (1..=33).collect()] {
+            assert_eq!(
+                Err(Error::InvalidParameter),
+                Command::SetTabStops(stops).as_bytes()
+            );
+        }
+    }
+
+    test_encoding_of!(cash_drawer_pin2, [0x1b, 0x70, 0x00, 0x19, 0x7d], || {
+        Command::CashDrawer {
+            pin: DrawerPin::Pin2,
+            on_ms: 50,
+            off_ms: 250,
+        }
+    });
+    test_encoding_of!(cash_drawer_pin5, [0x1b, 0x70, 0x01, 0x32, 0x64], || {
+        Command::CashDrawer {
+            pin: DrawerPin::Pin5,
+            on_ms: 100,
+            off_ms: 200,
+        }
+    });
+
+    test_encoding_of!(
+        buzzer,
+        [0x1b, 0x28, 0x41, 0x04, 0x00, 0x30, 0x33, 0x02, 0x0a],
+        || {
+            Command::Buzzer {
+                pattern: 3,
+                repetitions: 2,
+            }
+        }
+    );
+
+    #[test]
+    fn buzzer_out_of_range() {
+        for (pattern, repetitions) in [(0, 1), (11, 1), (1, 0), (1, 64)] {
+            assert_eq!(
+                Err(Error::InvalidParameter),
+                Command::Buzzer {
+                    pattern,
+                    repetitions
+                }
+                .as_bytes()
+            );
+        }
+    }
+
+    test_encoding_of!(
+        define_user_glyphs,
+        [
+            0x1b, 0x26, 0x03, 0x41, 0x42, 0x01, 0xff, 0x00, 0x0f, 0x02, 0x80, 0x00, 0x01, 0x01,
+            0x00, 0x80
+        ],
+        || {
+            Command::DefineUserGlyphs {
+                first: b'A',
+                glyphs: vec![
+                    UserGlyph::from_columns(&[[0xff, 0x00, 0x0f]]).unwrap(),
+                    UserGlyph::from_columns(&[[0x80, 0x00, 0x01], [0x01, 0x00, 0x80]]).unwrap(),
+                ],
+            }
+        }
+    );
+    test_encoding_of!(user_glyphs_on, [0x1b, 0x25, 0x01], || {
+        Command::UserGlyphs(true)
+    });
+    test_encoding_of!(clear_user_glyph, [0x1b, 0x3f, 0x41], || {
+        Command::ClearUserGlyph(b'A')
+    });
+
+    #[test]
+    fn define_user_glyphs_out_of_range() {
+        let glyph = UserGlyph::from_columns(&[[0xff, 0xff, 0xff]]).unwrap();
+        for first in [31, 127] {
+            assert_eq!(
+                Err(Error::InvalidParameter),
+                Command::DefineUserGlyphs {
+                    first,
+                    glyphs: vec![glyph.clone()],
+                }
+                .as_bytes()
+            );
+        }
+    }
+
+    test_encoding_of!(macro_start, [0x1d, 0x3a], || { Command::MacroStart });
+    test_encoding_of!(macro_end, [0x1d, 0x3a], || { Command::MacroEnd });
+    test_encoding_of!(macro_execute, [0x1d, 0x5e, 0x03, 0x0a, 0x01], || {
+        Command::MacroExecute {
+            count: 3,
+            interval: 10,
+            button: true,
+        }
+    });
+
+    test_encoding_of!(real_time_status_printer, [0x10, 0x04, 0x01], || {
+        Command::RealTimeStatusRequest(StatusKind::Printer)
+    });
+    test_encoding_of!(real_time_status_paper, [0x10, 0x04, 0x04], || {
+        Command::RealTimeStatusRequest(StatusKind::PaperSensor)
+    });
+
+    test_encoding_of!(enable_asb_all, [0x1d, 0x61, 0x0f], || {
+        Command::EnableAsb(AsbFlags::all())
+    });
+    test_encoding_of!(disable_asb, [0x1d, 0x61, 0x00], || {
+        Command::EnableAsb(AsbFlags::default())
+    });
+
+    test_encoding_of!(transmit_paper_status, [0x1d, 0x72, 0x01], || {
+        Command::TransmitStatus(TransmitStatusKind::Paper)
+    });
+
+    test_encoding_of!(
+        request_process_id,
+        [0x1d, 0x28, 0x48, 0x06, 0x00, 0x30, 0x30, 0x30, 0x30, 0x34, 0x32],
+        || { Command::RequestProcessId(*b"0042") }
+    );
+
+    #[test]
+    fn request_process_id_invalid() {
+        assert_eq!(
+            Err(Error::InvalidParameter),
+            Command::RequestProcessId(*b"12a4").as_bytes()
+        );
+    }
+
+    test_encoding_of!(request_model_id, [0x1d, 0x49, 0x01], || {
+        Command::RequestPrinterId(InfoKind::ModelId)
+    });
+    test_encoding_of!(request_firmware_version, [0x1d, 0x49, 0x41], || {
+        Command::RequestPrinterId(InfoKind::FirmwareVersion)
+    });
+
+    test_encoding_of!(
+        request_maintenance_counter,
+        [0x1d, 0x67, 0x32, 0x00, 0xb2, 0x00],
+        || {
+            Command::RequestMaintenanceCounter {
+                counter: MaintenanceCounter::Cuts,
+                cumulative: true,
+            }
+        }
+    );
+    test_encoding_of!(
+        reset_maintenance_counter,
+        [0x1d, 0x67, 0x30, 0x00, 0x14, 0x00],
+        || { Command::ResetMaintenanceCounter(MaintenanceCounter::PaperFeed) }
+    );
+
+    test_encoding_of!(cancel_page_data, [0x18], || { Command::CancelPageData });
+    test_encoding_of!(recover_and_restart, [0x10, 0x05, 0x01], || {
+        Command::RecoverAndRestart
+    });
+    test_encoding_of!(recover_and_cancel, [0x10, 0x05, 0x02], || {
+        Command::RecoverAndCancel
+    });
+    test_encoding_of!(
+        clear_buffers,
+        [0x10, 0x14, 0x08, 0x01, 0x03, 0x14, 0x01, 0x06, 0x02, 0x08],
+        || { Command::ClearBuffers }
+    );
+
+    test_encoding_of!(
+        real_time_drawer_pulse,
+        [0x10, 0x14, 0x01, 0x01, 0x02],
+        || {
+            Command::RealTimeDrawerPulse {
+                pin: DrawerPin::Pin5,
+                duration: 2,
+            }
+        }
+    );
+
+    #[test]
+    fn real_time_drawer_pulse_out_of_range() {
+        for duration in [0, 9] {
+            assert_eq!(
+                Err(Error::InvalidParameter),
+                Command::RealTimeDrawerPulse {
+                    pin: DrawerPin::Pin2,
+                    duration
+                }
+                .as_bytes()
+            );
+        }
+    }
+
+    test_encoding_of!(kanji_mode_on, [0x1c, 0x26], || { Command::KanjiMode(true) });
+    test_encoding_of!(kanji_mode_off, [0x1c, 0x2e], || {
+        Command::KanjiMode(false)
+    });
+    test_encoding_of!(kanji_code_shift_jis, [0x1c, 0x43, 0x01], || {
+        Command::KanjiCodeSystem(KanjiCode::ShiftJis)
+    });
+    test_encoding_of!(kanji_style, [0x1c, 0x21, 0x8c], || {
+        Command::KanjiStyle(KanjiStyle {
+            double_width: true,
+            double_height: true,
+            underline: true,
+        })
+    });
+
+    test_encoding_of!(
+        density_dark,
+        [0x1d, 0x28, 0x4b, 0x02, 0x00, 0x31, 0x03],
+        || { Command::Density(3) }
+    );
+    test_encoding_of!(
+        density_light,
+        [0x1d, 0x28, 0x4b, 0x02, 0x00, 0x31, 0xfa],
+        || { Command::Density(-6) }
+    );
+    test_encoding_of!(
+        enter_user_setting,
+        [0x1d, 0x28, 0x45, 0x03, 0x00, 0x01, 0x49, 0x4e],
+        || { Command::EnterUserSetting }
+    );
+    test_encoding_of!(
+        exit_user_setting,
+        [0x1d, 0x28, 0x45, 0x04, 0x00, 0x02, 0x4f, 0x55, 0x54],
+        || { Command::ExitUserSetting }
+    );
+    test_encoding_of!(
+        customize_density,
+        [0x1d, 0x28, 0x45, 0x04, 0x00, 0x05, 0x05, 0xff, 0xff],
+        || { Command::SetCustomizeValue(CustomizeValue::PrintDensity(-1)) }
+    );
+    test_encoding_of!(
+        customize_speed,
+        [0x1d, 0x28, 0x45, 0x04, 0x00, 0x05, 0x06, 0x0d, 0x00],
+        || { Command::SetCustomizeValue(CustomizeValue::PrintSpeed(13)) }
+    );
+
+    #[test]
+    fn density_out_of_range() {
+        for density in [-7, 7] {
+            assert_eq!(
+                Err(Error::InvalidParameter),
+                Command::Density(density).as_bytes()
+            );
+            assert_eq!(
+                Err(Error::InvalidParameter),
+                Command::SetCustomizeValue(CustomizeValue::PrintDensity(density)).as_bytes()
+            );
+        }
+    }
+
+    test_encoding_of!(
+        set_paper_layout,
+        [0x1c, 0x28, 0x4c, 0x02, 0x00, 0x21, 0x32],
+        || { Command::SetPaperLayout(PaperLayout::BlackMark) }
+    );
+    test_encoding_of!(feed_to_label_origin, [0x1d, 0x0c], || {
+        Command::FeedToLabelOrigin
+    });
+    test_encoding_of!(
+        feed_to_cut_position,
+        [0x1c, 0x28, 0x4c, 0x02, 0x00, 0x42, 0x31],
+        || { Command::FeedToCutPosition }
+    );
+    test_encoding_of!(
+        feed_to_peel_position,
+        [0x1c, 0x28, 0x4c, 0x02, 0x00, 0x41, 0x31],
+        || { Command::FeedToPeelPosition }
+    );
+
+    test_encoding_of!(code_page_pc437, [0x1b, 0x74, 0x00], || {
+        Command::CodePage(CodePage::Pc437)
+    });
+    test_encoding_of!(code_page_pc858, [0x1b, 0x74, 0x13], || {
+        Command::CodePage(CodePage::Pc858)
+    });
+
     test_encoding_of!(
         encode_char_page_raw,
         [0x1C, 0x28, 0x43, 0x02, 0x00, 0x30, 0x01],
@@ -204,7 +1579,229 @@ mod tests {
         || { Command::CharacterSet(CharacterSet::Unicode) }
     );
 
-    // TODO: test image encoding here
+    test_encoding_of!(
+        encode_barcode_upca,
+        [
+            0x1d, 0x6b, 0x41, 0x0c, b'0', b'3', b'6', b'0', b'0', b'0', b'2', b'9', b'1', b'4',
+            b'5', b'2'
+        ],
+        || { Command::Barcode(Barcode::new_upca("03600029145").unwrap()) }
+    );
+
+    test_encoding_of!(
+        define_nv_logo,
+        [
+            0x1d, 0x28, 0x4c, 0x0d, 0x00, 0x30, 0x43, 0x30, 0x41, 0x31, 0x01, 0x08, 0x00, 0x02,
+            0x00, 0x31, 0xff, 0x00,
+        ],
+        || {
+            let mut img: image::GrayImage =
+                image::ImageBuffer::from_pixel(8, 2, image::Luma([255]));
+            for x in 0..8 {
+                img.put_pixel(x, 0, image::Luma([0]));
+            }
+            Command::DefineNvLogo {
+                key: NvKey::new(b'A', b'1').unwrap(),
+                image: img,
+            }
+        }
+    );
+    test_encoding_of!(
+        print_nv_logo,
+        [0x1d, 0x28, 0x4c, 0x06, 0x00, 0x30, 0x45, 0x41, 0x31, 0x02, 0x01],
+        || {
+            Command::PrintNvLogo {
+                key: NvKey::new(b'A', b'1').unwrap(),
+                scale: ImageScale::DoubleWidth,
+            }
+        }
+    );
+    test_encoding_of!(
+        delete_nv_logo,
+        [0x1d, 0x28, 0x4c, 0x04, 0x00, 0x30, 0x42, 0x41, 0x31],
+        || { Command::DeleteNvLogo(NvKey::new(b'A', b'1').unwrap()) }
+    );
+    test_encoding_of!(
+        delete_all_nv_logos,
+        [0x1d, 0x28, 0x4c, 0x05, 0x00, 0x30, 0x41, 0x43, 0x4c, 0x52],
+        || { Command::DeleteAllNvLogos }
+    );
+
+    #[test]
+    fn nv_key_out_of_range() {
+        assert_eq!(Err(Error::InvalidParameter), NvKey::new(31, b'A'));
+        assert_eq!(Err(Error::InvalidParameter), NvKey::new(b'A', 127));
+    }
+
+    test_encoding_of!(
+        column_image_8_dot,
+        [
+            0x1b, 0x33, 0x18, // line spacing
+            0x1b, 0x2a, 0x01, 0x02, 0x00, 0x80, 0x00, 0x0a, // band 1
+            0x1b, 0x2a, 0x01, 0x02, 0x00, 0x00, 0x01, 0x0a, // band 2
+            0x1b, 0x32, // reset line spacing
+        ],
+        || {
+            let mut img: image::GrayImage =
+                image::ImageBuffer::from_pixel(2, 16, image::Luma([255]));
+            img.put_pixel(0, 0, image::Luma([0]));
+            img.put_pixel(1, 15, image::Luma([0]));
+            Command::ColumnImage(ColumnImageMode::EightDotDouble, img)
+        }
+    );
+    test_encoding_of!(
+        column_band,
+        [0x1b, 0x2a, 0x00, 0x02, 0x00, 0x80, 0x01],
+        || {
+            let mut img: image::GrayImage =
+                image::ImageBuffer::from_pixel(2, 8, image::Luma([255]));
+            img.put_pixel(0, 0, image::Luma([0]));
+            img.put_pixel(1, 7, image::Luma([0]));
+            Command::ColumnBand(ColumnImageMode::EightDotSingle, img)
+        }
+    );
+    test_encoding_of!(
+        column_image_24_dot,
+        [
+            0x1b, 0x33, 0x18, // line spacing
+            0x1b, 0x2a, 0x21, 0x01, 0x00, 0x80, 0x00, 0x01, 0x0a, // band 1
+            0x1b, 0x32, // reset line spacing
+        ],
+        || {
+            let mut img: image::GrayImage =
+                image::ImageBuffer::from_pixel(1, 24, image::Luma([255]));
+            img.put_pixel(0, 0, image::Luma([0]));
+            img.put_pixel(0, 23, image::Luma([0]));
+            Command::ColumnImage(ColumnImageMode::TwentyFourDotDouble, img)
+        }
+    );
+
+    test_encoding_of!(
+        define_downloaded_image,
+        [0x1d, 0x2a, 0x01, 0x01, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,],
+        || {
+            let mut img: image::GrayImage =
+                image::ImageBuffer::from_pixel(8, 8, image::Luma([255]));
+            img.put_pixel(0, 0, image::Luma([0]));
+            img.put_pixel(7, 7, image::Luma([0]));
+            Command::DefineDownloadedImage(img)
+        }
+    );
+    test_encoding_of!(print_downloaded_image, [0x1d, 0x2f, 0x03], || {
+        Command::PrintDownloadedImage(ImageScale::Quadruple)
+    });
+
+    #[test]
+    fn downloaded_image_too_tall() {
+        let img: image::GrayImage = image::ImageBuffer::new(8, 49 * 8);
+        assert_eq!(
+            Err(Error::ImageTooLarge),
+            Command::DefineDownloadedImage(img).as_bytes()
+        );
+    }
+
+    test_encoding_of!(
+        encode_micro_qr,
+        [
+            0x1d, 0x28, 0x6b, 0x04, 0x00, 0x31, 0x41, 0x33, 0x00, // model
+            0x1d, 0x28, 0x6b, 0x03, 0x00, 0x31, 0x43, 0x03, // size
+            0x1d, 0x28, 0x6b, 0x03, 0x00, 0x31, 0x45, 0x31, // ecc
+            0x1d, 0x28, 0x6b, 0x05, 0x00, 0x31, 0x50, 0x30, b'4', b'2', // store
+            0x1d, 0x28, 0x6b, 0x03, 0x00, 0x31, 0x51, 0x30, // print
+        ],
+        || { Command::QrCode(QrCode::new_micro(b"42")) }
+    );
+
+    test_encoding_of!(
+        image,
+        [0x1d, 0x76, 0x30, 0x00, 0x02, 0x00, 0x01, 0x00, 0x80, 0x40],
+        || {
+            let mut img: image::GrayImage =
+                image::ImageBuffer::from_pixel(10, 1, image::Luma([255]));
+            img.put_pixel(0, 0, image::Luma([0]));
+            img.put_pixel(9, 0, image::Luma([0]));
+            Command::Image(img, ImageScale::Normal)
+        }
+    );
+    test_encoding_of!(
+        store_graphics_red,
+        [
+            0x1d, 0x28, 0x4c, 0x0b, 0x00, 0x30, 0x70, 0x30, 0x01, 0x01, 0x32, 0x08, 0x00, 0x01,
+            0x00, 0x81,
+        ],
+        || {
+            let mut img: image::GrayImage =
+                image::ImageBuffer::from_pixel(8, 1, image::Luma([255]));
+            img.put_pixel(0, 0, image::Luma([0]));
+            img.put_pixel(7, 0, image::Luma([0]));
+            Command::StoreGraphics {
+                color: Color::Red,
+                image: img,
+            }
+        }
+    );
+    test_encoding_of!(
+        print_graphics,
+        [0x1d, 0x28, 0x4c, 0x02, 0x00, 0x30, 0x32],
+        || { Command::PrintGraphics }
+    );
+
+    test_encoding_of!(
+        raster_bits,
+        [0x1d, 0x76, 0x30, 0x00, 0x02, 0x00, 0x02, 0x00, 0x80, 0x01, 0xff, 0x00],
+        || {
+            Command::RasterBits {
+                width_bytes: 2,
+                height: 2,
+                data: vec![0x80, 0x01, 0xff, 0x00],
+            }
+        }
+    );
+
+    #[test]
+    fn graphics_too_wide() {
+        // 8192 bytes per row is 65536 dots, which doesn't fit in the u16
+        // width.
+        let img: image::GrayImage = image::ImageBuffer::from_pixel(65536, 1, image::Luma([255]));
+        assert_eq!(
+            Err(Error::ImageTooLarge),
+            Command::StoreGraphics {
+                color: Color::Black,
+                image: img.clone(),
+            }
+            .as_bytes()
+        );
+        assert_eq!(
+            Err(Error::ImageTooLarge),
+            Command::DefineNvLogo {
+                key: NvKey(b'A', b'1'),
+                image: img,
+            }
+            .as_bytes()
+        );
+    }
+
+    #[test]
+    fn raster_bits_wrong_length() {
+        assert_eq!(
+            Err(Error::InvalidParameter),
+            Command::RasterBits {
+                width_bytes: 2,
+                height: 2,
+                data: vec![0x80, 0x01, 0xff],
+            }
+            .as_bytes()
+        );
+    }
+
+    test_encoding_of!(
+        image_quadruple,
+        [0x1d, 0x76, 0x30, 0x03, 0x01, 0x00, 0x01, 0x00, 0xff],
+        || {
+            let img: image::GrayImage = image::ImageBuffer::from_pixel(8, 1, image::Luma([0]));
+            Command::Image(img, ImageScale::Quadruple)
+        }
+    );
 }
 
 // vim: foldmethod=marker
@@ -0,0 +1,1109 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+use super::user_glyph::GLYPH_HEIGHT_BYTES;
+use super::{
+    Alignment, AsbFlags, Barcode, CharacterSet, CodePage, Color, ColumnImageMode, Command,
+    CustomizeValue, CutMode, DrawerPin, EffectColor, ImageScale, InfoKind, KanjiCode, KanjiStyle,
+    MaintenanceCounter, NvKey, PaperLayout, QrCode, QrErrorCorrection, QrModel, StatusKind,
+    TransmitStatusKind, UserGlyph,
+};
+
+/// A single item decoded from an ESC/POS byte stream.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Decoded {
+    /// A command this crate knows how to encode.
+    Command(Command),
+
+    /// A run of bytes to be printed as text, including line feeds and
+    /// carriage returns.
+    Text(Vec<u8>),
+
+    /// Bytes which weren't understood, such as commands this crate doesn't
+    /// support, or commands with invalid parameters.
+    Unknown(Vec<u8>),
+}
+
+/// Streaming decoder which turns raw ESC/POS bytes back into [Command]s.
+///
+/// Bytes are added with [Decoder::push], and decoded items are pulled out
+/// by iterating over the decoder. Iteration stops when the remaining bytes
+/// are only part of a command, and picks up again once more bytes are
+/// pushed. Once the stream is over, [Decoder::finish] decodes whatever is
+/// left.
+///
+/// Commands which share an encoding decode to a single canonical form,
+/// so `DoubleWidth(true)` comes back as `TextSize { width: 2, height: 1 }`,
+/// and composite commands like [Command::ColumnImage] come back as the
+/// commands they're built from. Re-encoding the decoded commands gives
+/// back the bytes this crate sent.
+#[derive(Clone, Debug, Default)]
+pub struct Decoder {
+    buf: Vec<u8>,
+    pos: usize,
+    macro_open: bool,
+}
+
+impl Decoder {
+    /// Create a new, empty, Decoder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add more bytes to the end of the stream.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.drain(..self.pos);
+        self.pos = 0;
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Decode everything left in the stream. Any trailing partial command
+    /// is returned as [Decoded::Unknown].
+    pub fn finish(&mut self) -> Vec<Decoded> {
+        let mut out = vec![];
+        while let Some((_, decoded)) = self.decode_next(true) {
+            out.push(decoded);
+        }
+        out
+    }
+
+    /// Decode the next item, returning it along with the number of bytes
+    /// it was decoded from.
+    fn decode_next(&mut self, eof: bool) -> Option<(usize, Decoded)> {
+        let rest = &self.buf[self.pos..];
+        if rest.is_empty() {
+            return None;
+        }
+
+        let (len, decoded) = match parse(rest, eof) {
+            Ok(parsed) => parsed,
+            Err(Incomplete) if eof => (rest.len(), Decoded::Unknown(rest.to_vec())),
+            Err(Incomplete) => return None,
+        };
+        self.pos += len;
+
+        // GS : both starts and ends a macro definition.
+        let decoded = match decoded {
+            Decoded::Command(Command::MacroStart) if self.macro_open => {
+                self.macro_open = false;
+                Decoded::Command(Command::MacroEnd)
+            }
+            Decoded::Command(Command::MacroStart) => {
+                self.macro_open = true;
+                Decoded::Command(Command::MacroStart)
+            }
+            decoded => decoded,
+        };
+        Some((len, decoded))
+    }
+}
+
+impl Iterator for Decoder {
+    type Item = Decoded;
+
+    fn next(&mut self) -> Option<Decoded> {
+        self.decode_next(false).map(|(_, decoded)| decoded)
+    }
+}
+
+/// Decode a complete ESC/POS byte stream.
+pub fn decode(bytes: &[u8]) -> Vec<Decoded> {
+    let mut decoder = Decoder::new();
+    decoder.push(bytes);
+    decoder.finish()
+}
+
+/// Decode a complete ESC/POS byte stream, along with the range of bytes
+/// each item was decoded from.
+pub(crate) fn decode_ranges(bytes: &[u8]) -> Vec<(std::ops::Range<usize>, Decoded)> {
+    let mut decoder = Decoder::new();
+    decoder.push(bytes);
+
+    let mut out = vec![];
+    let mut start = 0;
+    while let Some((len, decoded)) = decoder.decode_next(true) {
+        out.push((start..start + len, decoded));
+        start += len;
+    }
+    out
+}
+
+/// Returned when the buffer ends part way through a command.
+struct Incomplete;
+
+type Parsed = Result<(usize, Decoded), Incomplete>;
+
+/// Cursor over the bytes of a single command.
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, Incomplete> {
+        let b = *self.buf.get(self.pos).ok_or(Incomplete)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Incomplete> {
+        let bytes = self.buf.get(self.pos..self.pos + n).ok_or(Incomplete)?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, Incomplete> {
+        Ok(u16::from_le_bytes([self.byte()?, self.byte()?]))
+    }
+
+    /// Finish the command, falling back to [Decoded::Unknown] if it wasn't
+    /// understood, or has parameters this crate wouldn't send.
+    fn done(self, cmd: Option<Command>) -> Parsed {
+        Ok((
+            self.pos,
+            match cmd.filter(|cmd| cmd.as_bytes().is_ok()) {
+                Some(cmd) => Decoded::Command(cmd),
+                None => Decoded::Unknown(self.buf[..self.pos].to_vec()),
+            },
+        ))
+    }
+}
+
+fn is_text(b: u8) -> bool {
+    b >= 0x20 || b == b'\n' || b == b'\r'
+}
+
+fn parse(buf: &[u8], eof: bool) -> Parsed {
+    if is_text(buf[0]) {
+        let len = buf.iter().position(|b| !is_text(*b)).unwrap_or(buf.len());
+        return Ok((len, Decoded::Text(buf[..len].to_vec())));
+    }
+
+    let r = Reader { buf, pos: 1 };
+    match buf[0] {
+        0x09 => r.done(Some(Command::HorizontalTab)),
+        0x18 => r.done(Some(Command::CancelPageData)),
+        0x10 => parse_dle(r),
+        0x1b => parse_esc(r),
+        0x1c => parse_fs(r),
+        0x1d => parse_gs(r, eof),
+        _ => r.done(None),
+    }
+}
+
+fn flag(n: u8) -> bool {
+    n & 1 != 0
+}
+
+fn parse_dle(mut r: Reader) -> Parsed {
+    let cmd = match r.byte()? {
+        0x04 => status_kind(r.byte()?).map(Command::RealTimeStatusRequest),
+        0x05 => match r.byte()? {
+            0x01 => Some(Command::RecoverAndRestart),
+            0x02 => Some(Command::RecoverAndCancel),
+            _ => None,
+        },
+        0x14 => match r.byte()? {
+            0x01 => {
+                let pin = drawer_pin(r.byte()?);
+                let duration = r.byte()?;
+                pin.map(|pin| Command::RealTimeDrawerPulse { pin, duration })
+            }
+            0x08 => (r.take(7)? == [0x01, 0x03, 0x14, 0x01, 0x06, 0x02, 0x08])
+                .then_some(Command::ClearBuffers),
+            _ => None,
+        },
+        _ => None,
+    };
+    r.done(cmd)
+}
+
+fn parse_esc(mut r: Reader) -> Parsed {
+    let cmd = match r.byte()? {
+        b'@' => Some(Command::Init),
+        b'i' => Some(Command::Cut),
+        b'm' => Some(Command::LegacyPartialCut),
+        b'-' => Some(Command::Underline(flag(r.byte()?))),
+        b'E' => Some(Command::Emphasize(flag(r.byte()?))),
+        b'G' => Some(Command::DoubleStrike(flag(r.byte()?))),
+        b'B' => Some(Command::Reverse(flag(r.byte()?))),
+        b'V' => Some(Command::Rotate90(flag(r.byte()?))),
+        b'r' => match r.byte()? {
+            0 | 48 => Some(Command::PrintColor(Color::Black)),
+            1 | 49 => Some(Command::PrintColor(Color::Red)),
+            _ => None,
+        },
+        b'a' => match r.byte()? {
+            0 | 48 => Some(Command::Justification(Alignment::Left)),
+            1 | 49 => Some(Command::Justification(Alignment::Center)),
+            2 | 50 => Some(Command::Justification(Alignment::Right)),
+            _ => None,
+        },
+        b'd' => Some(Command::Feed(r.byte()?)),
+        b'J' => Some(Command::FeedUnits(r.byte()?)),
+        b'e' => Some(Command::ReverseFeed(r.byte()?)),
+        b'!' => {
+            let n = r.byte()?;
+            Some(Command::LegacyPrintMode {
+                double_width: n & 0x20 != 0,
+                double_height: n & 0x10 != 0,
+            })
+        }
+        b'2' => Some(Command::DefaultLineSpacing),
+        b'3' => Some(Command::LineSpacing(r.byte()?)),
+        b'D' => {
+            let mut stops = vec![];
+            loop {
+                match r.byte()? {
+                    0 => break,
+                    n => stops.push(n),
+                }
+            }
+            Some(Command::SetTabStops(stops))
+        }
+        b'p' => {
+            let pin = drawer_pin(r.byte()?);
+            let (on, off) = (r.byte()?, r.byte()?);
+            pin.map(|pin| Command::CashDrawer {
+                pin,
+                on_ms: on.saturating_mul(2),
+                off_ms: off.saturating_mul(2),
+            })
+        }
+        b'(' => {
+            let fn_code = r.byte()?;
+            let len = r.u16()? as usize;
+            let body = r.take(len)?;
+            match (fn_code, body) {
+                (b'A', [0x30, p @ 0x31..=0x3a, repetitions, 10]) => Some(Command::Buzzer {
+                    pattern: p - 0x30,
+                    repetitions: *repetitions,
+                }),
+                _ => None,
+            }
+        }
+        b'&' => {
+            let height = r.byte()?;
+            if height != GLYPH_HEIGHT_BYTES {
+                return r.done(None);
+            }
+            let first = r.byte()?;
+            let last = r.byte()?;
+            let mut glyphs = vec![];
+            for _ in first..=last {
+                let width = r.byte()? as usize;
+                let data = r.take(width * height as usize)?;
+                let columns: Vec<[u8; 3]> = data
+                    .chunks(3)
+                    .map(|c| c.try_into().unwrap_or_default())
+                    .collect();
+                glyphs.push(UserGlyph::from_columns(&columns).ok());
+            }
+            glyphs
+                .into_iter()
+                .collect::<Option<Vec<_>>>()
+                .map(|glyphs| Command::DefineUserGlyphs { first, glyphs })
+        }
+        b'%' => Some(Command::UserGlyphs(flag(r.byte()?))),
+        b'?' => Some(Command::ClearUserGlyph(r.byte()?)),
+        b't' => {
+            let n = r.byte()?;
+            CodePage::ALL
+                .iter()
+                .find(|page| **page as u8 == n)
+                .map(|page| Command::CodePage(*page))
+        }
+        b'*' => {
+            let mode = match r.byte()? {
+                0 => ColumnImageMode::EightDotSingle,
+                1 => ColumnImageMode::EightDotDouble,
+                32 => ColumnImageMode::TwentyFourDotSingle,
+                33 => ColumnImageMode::TwentyFourDotDouble,
+                _ => return r.done(None),
+            };
+            let width = r.u16()? as u32;
+            let height_bytes = mode.column_bytes();
+            let data = r.take((width * height_bytes) as usize)?;
+            Some(Command::ColumnBand(
+                mode,
+                unpack_columns(data, width, height_bytes),
+            ))
+        }
+        _ => None,
+    };
+    r.done(cmd)
+}
+
+fn parse_fs(mut r: Reader) -> Parsed {
+    let cmd = match r.byte()? {
+        b'&' => Some(Command::KanjiMode(true)),
+        b'.' => Some(Command::KanjiMode(false)),
+        b'C' => match r.byte()? {
+            0 | 48 => Some(Command::KanjiCodeSystem(KanjiCode::Jis)),
+            1 | 49 => Some(Command::KanjiCodeSystem(KanjiCode::ShiftJis)),
+            _ => None,
+        },
+        b'!' => {
+            let n = r.byte()?;
+            Some(Command::KanjiStyle(KanjiStyle {
+                double_width: n & 0x04 != 0,
+                double_height: n & 0x08 != 0,
+                underline: n & 0x80 != 0,
+            }))
+        }
+        b'(' => {
+            let fn_code = r.byte()?;
+            let len = r.u16()? as usize;
+            let body = r.take(len)?;
+            match (fn_code, body) {
+                (b'L', [0x21, 48]) => Some(Command::SetPaperLayout(PaperLayout::Receipt)),
+                (b'L', [0x21, 49]) => Some(Command::SetPaperLayout(PaperLayout::Label)),
+                (b'L', [0x21, 50]) => Some(Command::SetPaperLayout(PaperLayout::BlackMark)),
+                (b'L', [0x42, 0x31]) => Some(Command::FeedToCutPosition),
+                (b'L', [0x41, 0x31]) => Some(Command::FeedToPeelPosition),
+                (b'C', [0x30, 0x01]) => Some(Command::CharacterSet(CharacterSet::Raw)),
+                (b'C', [0x30, 0x02]) => Some(Command::CharacterSet(CharacterSet::Unicode)),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    r.done(cmd)
+}
+
+fn parse_gs(mut r: Reader, eof: bool) -> Parsed {
+    let cmd = match r.byte()? {
+        b'V' => match r.byte()? {
+            0 | 48 => Some(Command::CutWith(CutMode::Full, None)),
+            1 | 49 => Some(Command::CutWith(CutMode::Partial, None)),
+            65 => Some(Command::CutWith(CutMode::Full, Some(r.byte()?))),
+            66 => Some(Command::CutWith(CutMode::Partial, Some(r.byte()?))),
+            _ => None,
+        },
+        b'b' => Some(Command::Smoothing(flag(r.byte()?))),
+        b'!' => {
+            let n = r.byte()?;
+            Some(Command::TextSize {
+                width: (n >> 4) + 1,
+                height: (n & 0x0f) + 1,
+            })
+            .filter(|_| n & 0x88 == 0)
+        }
+        b':' => Some(Command::MacroStart),
+        b'^' => {
+            let (count, interval, button) = (r.byte()?, r.byte()?, r.byte()?);
+            Some(Command::MacroExecute {
+                count,
+                interval,
+                button: flag(button),
+            })
+        }
+        b'a' => {
+            let n = r.byte()?;
+            Some(Command::EnableAsb(AsbFlags {
+                drawer: n & 0x01 != 0,
+                online: n & 0x02 != 0,
+                error: n & 0x04 != 0,
+                paper: n & 0x08 != 0,
+            }))
+            .filter(|_| n & 0xf0 == 0)
+        }
+        b'I' => info_kind(r.byte()?).map(Command::RequestPrinterId),
+        b'r' => match r.byte()? {
+            1 | 49 => Some(Command::TransmitStatus(TransmitStatusKind::Paper)),
+            2 | 50 => Some(Command::TransmitStatus(TransmitStatusKind::Drawer)),
+            _ => None,
+        },
+        b'g' => {
+            let (fn_code, m, id) = (r.byte()?, r.byte()?, r.u16()?);
+            let counter = match id & 0x7f {
+                20 => Some(MaintenanceCounter::PaperFeed),
+                21 => Some(MaintenanceCounter::HeadEnergizing),
+                50 => Some(MaintenanceCounter::Cuts),
+                _ => None,
+            }
+            .filter(|_| m == 0 && id & !0xff == 0);
+            match (fn_code, counter) {
+                (0x32, Some(counter)) => Some(Command::RequestMaintenanceCounter {
+                    counter,
+                    cumulative: id & 0x80 != 0,
+                }),
+                (0x30, Some(counter)) if id & 0x80 == 0 => {
+                    Some(Command::ResetMaintenanceCounter(counter))
+                }
+                _ => None,
+            }
+        }
+        0x0c => Some(Command::FeedToLabelOrigin),
+        b'v' => {
+            let (zero, m) = (r.byte()?, r.byte()?);
+            let (x, y) = (r.u16()? as u32, r.u16()? as u32);
+            let data = r.take((x * y) as usize)?;
+            image_scale(m)
+                .filter(|_| zero == b'0')
+                .map(|scale| Command::Image(unpack_raster(data, x * 8, y), scale))
+        }
+        b'*' => {
+            let (x, y) = (r.byte()? as u32, r.byte()? as u32);
+            let data = r.take((x * y * 8) as usize)?;
+            Some(Command::DefineDownloadedImage(unpack_columns(
+                data,
+                x * 8,
+                y,
+            )))
+        }
+        b'/' => image_scale(r.byte()?).map(Command::PrintDownloadedImage),
+        b'k' => {
+            let system = r.byte()?;
+            if system < 65 {
+                // The older form of the command is terminated with a NUL.
+                while r.byte()? != 0 {}
+                return r.done(None);
+            }
+            let len = r.byte()? as usize;
+            let data = r.take(len)?;
+            match system {
+                65 => std::str::from_utf8(data)
+                    .ok()
+                    .and_then(|digits| Barcode::new_upca(digits).ok())
+                    .filter(|barcode| barcode.data() == data)
+                    .map(Command::Barcode),
+                _ => None,
+            }
+        }
+        b'(' => {
+            let fn_code = r.byte()?;
+            let len = r.u16()? as usize;
+            let body = r.take(len)?;
+            match fn_code {
+                b'N' => effect(body),
+                b'K' => match body {
+                    [0x32, speed] => Some(Command::Speed(*speed)),
+                    [0x31, density] => Some(Command::Density(*density as i8)),
+                    _ => None,
+                },
+                b'E' => match body {
+                    [0x01, b'I', b'N'] => Some(Command::EnterUserSetting),
+                    [0x02, b'O', b'U', b'T'] => Some(Command::ExitUserSetting),
+                    [0x05, 5, n1, n2] => Some(Command::SetCustomizeValue(
+                        CustomizeValue::PrintDensity(i16::from_le_bytes([*n1, *n2]) as i8),
+                    )),
+                    [0x05, 6, n1, 0] => {
+                        Some(Command::SetCustomizeValue(CustomizeValue::PrintSpeed(*n1)))
+                    }
+                    _ => None,
+                },
+                b'L' => graphics(body),
+                b'H' => match body {
+                    [0x30, 0x30, id @ ..] if id.len() == 4 && id.iter().all(u8::is_ascii_digit) => {
+                        Some(Command::RequestProcessId(id.try_into().unwrap()))
+                    }
+                    _ => None,
+                },
+                b'k' => return parse_qr(r, body, eof),
+                _ => None,
+            }
+        }
+        b'8' => {
+            let fn_code = r.byte()?;
+            let len = u32::from_le_bytes([r.byte()?, r.byte()?, r.byte()?, r.byte()?]);
+            let body = r.take(len as usize)?;
+            match fn_code {
+                b'L' if len > u16::MAX as u32 => graphics(body),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    r.done(cmd)
+}
+
+/// Decode a `GS ( N` character effect command.
+fn effect(body: &[u8]) -> Option<Command> {
+    match body {
+        [0x30, c] => effect_color(*c).map(Command::CharacterColor),
+        [0x31, c] => effect_color(*c).map(Command::BackgroundColor),
+        [0x32, 0x30, 0x30] => Some(Command::Shadow(None)),
+        [0x32, 0x31, c] => effect_color(*c).map(|c| Command::Shadow(Some(c))),
+        _ => None,
+    }
+}
+
+/// Decode the body of a `GS ( L` or `GS 8 L` graphics command.
+fn graphics(body: &[u8]) -> Option<Command> {
+    match body {
+        [0x30, 0x32] => Some(Command::PrintGraphics),
+        [0x30, 0x41, b'C', b'L', b'R'] => Some(Command::DeleteAllNvLogos),
+        [0x30, 0x42, k1, k2] => NvKey::new(*k1, *k2).ok().map(Command::DeleteNvLogo),
+        [0x30, 0x45, k1, k2, x, y] => {
+            let scale = match (x, y) {
+                (1, 1) => ImageScale::Normal,
+                (2, 1) => ImageScale::DoubleWidth,
+                (1, 2) => ImageScale::DoubleHeight,
+                (2, 2) => ImageScale::Quadruple,
+                _ => return None,
+            };
+            let key = NvKey::new(*k1, *k2).ok()?;
+            Some(Command::PrintNvLogo { key, scale })
+        }
+        [0x30, 0x43, 0x30, k1, k2, 0x01, x1, x2, y1, y2, 0x31, data @ ..] => {
+            let image = raster_image(data, u16::from_le_bytes([*x1, *x2]), *y1, *y2)?;
+            let key = NvKey::new(*k1, *k2).ok()?;
+            Some(Command::DefineNvLogo { key, image })
+        }
+        [0x30, 0x70, 0x30, 0x01, 0x01, c, x1, x2, y1, y2, data @ ..] => {
+            let color = match c {
+                49 => Color::Black,
+                50 => Color::Red,
+                _ => return None,
+            };
+            let image = raster_image(data, u16::from_le_bytes([*x1, *x2]), *y1, *y2)?;
+            Some(Command::StoreGraphics { color, image })
+        }
+        _ => None,
+    }
+}
+
+/// Unpack raster data for a graphics command, which gives the width in
+/// dots. Only whole bytes of width can be encoded again.
+fn raster_image(
+    data: &[u8],
+    width: u16,
+    y1: u8,
+    y2: u8,
+) -> Option<image::ImageBuffer<image::Luma<u8>, Vec<u8>>> {
+    let height = u16::from_le_bytes([y1, y2]) as u32;
+    let width = width as u32;
+    if !width.is_multiple_of(8) || data.len() as u32 != width / 8 * height {
+        return None;
+    }
+    Some(unpack_raster(data, width, height))
+}
+
+/// Decode a `GS ( k` QR Code command. The crate always sends the five
+/// functions needed to print a symbol back to back, so look for all of
+/// them together, and otherwise treat the function as unknown.
+fn parse_qr(r: Reader, first: &[u8], eof: bool) -> Parsed {
+    let [0x31, 0x41, model, 0x00] = first else {
+        return r.done(None);
+    };
+
+    let mut next = Reader {
+        buf: r.buf,
+        pos: r.pos,
+    };
+    match parse_qr_functions(&mut next, *model) {
+        Ok(Some(qr)) => next.done(Some(Command::QrCode(qr))),
+        Err(Incomplete) if !eof => Err(Incomplete),
+        _ => r.done(None),
+    }
+}
+
+/// Read the `GS ( k` functions which follow the model selection.
+fn parse_qr_functions(r: &mut Reader, model: u8) -> Result<Option<QrCode>, Incomplete> {
+    let Some([0x31, 0x43, module_size]) = qr_function(r)? else {
+        return Ok(None);
+    };
+    let Some([0x31, 0x45, ecc]) = qr_function(r)? else {
+        return Ok(None);
+    };
+    let Some([0x31, 0x50, 0x30, data @ ..]) = qr_function(r)? else {
+        return Ok(None);
+    };
+    let Some([0x31, 0x51, 0x30]) = qr_function(r)? else {
+        return Ok(None);
+    };
+    Ok(qr_code(model, *module_size, *ecc, data))
+}
+
+/// Read the parameters of a single `GS ( k` function, if the next command
+/// is one.
+fn qr_function<'a>(r: &mut Reader<'a>) -> Result<Option<&'a [u8]>, Incomplete> {
+    if r.take(3)? != [0x1d, b'(', b'k'] {
+        return Ok(None);
+    }
+    let len = r.u16()? as usize;
+    r.take(len).map(Some)
+}
+
+fn qr_code(model: u8, module_size: u8, ecc: u8, data: &[u8]) -> Option<QrCode> {
+    let model = match model {
+        49 => QrModel::Model1,
+        50 => QrModel::Model2,
+        51 => QrModel::Micro,
+        _ => return None,
+    };
+    let error_correction = match ecc {
+        48 => QrErrorCorrection::L,
+        49 => QrErrorCorrection::M,
+        50 => QrErrorCorrection::Q,
+        51 => QrErrorCorrection::H,
+        _ => return None,
+    };
+    Some(QrCode {
+        model,
+        module_size,
+        error_correction,
+        data: data.to_vec(),
+    })
+}
+
+fn status_kind(n: u8) -> Option<StatusKind> {
+    Some(match n {
+        1 => StatusKind::Printer,
+        2 => StatusKind::OfflineCause,
+        3 => StatusKind::ErrorCause,
+        4 => StatusKind::PaperSensor,
+        _ => return None,
+    })
+}
+
+fn info_kind(n: u8) -> Option<InfoKind> {
+    Some(match n {
+        1 | 49 => InfoKind::ModelId,
+        2 | 50 => InfoKind::TypeId,
+        3 | 51 => InfoKind::VersionId,
+        65 => InfoKind::FirmwareVersion,
+        66 => InfoKind::MakerName,
+        67 => InfoKind::ModelName,
+        68 => InfoKind::SerialNumber,
+        _ => return None,
+    })
+}
+
+fn drawer_pin(n: u8) -> Option<DrawerPin> {
+    match n {
+        0 | 48 => Some(DrawerPin::Pin2),
+        1 | 49 => Some(DrawerPin::Pin5),
+        _ => None,
+    }
+}
+
+fn image_scale(n: u8) -> Option<ImageScale> {
+    match n {
+        0 | 48 => Some(ImageScale::Normal),
+        1 | 49 => Some(ImageScale::DoubleWidth),
+        2 | 50 => Some(ImageScale::DoubleHeight),
+        3 | 51 => Some(ImageScale::Quadruple),
+        _ => None,
+    }
+}
+
+fn effect_color(n: u8) -> Option<EffectColor> {
+    match n {
+        48 => Some(EffectColor::None),
+        49 => Some(EffectColor::Color1),
+        50 => Some(EffectColor::Color2),
+        51 => Some(EffectColor::Color3),
+        _ => None,
+    }
+}
+
+/// Unpack row-major raster data, 8 pixels per byte, into an image where
+/// set bits are black.
+fn unpack_raster(data: &[u8], width: u32, height: u32) -> image::GrayImage {
+    let row_bytes = width.div_ceil(8);
+    image::GrayImage::from_fn(width, height, |x, y| {
+        let byte = data[(y * row_bytes + x / 8) as usize];
+        image::Luma([if byte & (0x80 >> (x % 8)) != 0 {
+            0
+        } else {
+            255
+        }])
+    })
+}
+
+/// Unpack column-major data, `height_bytes` bytes per column, into an
+/// image where set bits are black.
+fn unpack_columns(data: &[u8], width: u32, height_bytes: u32) -> image::GrayImage {
+    image::GrayImage::from_fn(width, height_bytes * 8, |x, y| {
+        let byte = data[(x * height_bytes + y / 8) as usize];
+        image::Luma([if byte & (0x80 >> (y % 8)) != 0 {
+            0
+        } else {
+            255
+        }])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arbitrary::{round_trip, Unstructured};
+
+    fn encode(items: &[Decoded]) -> Vec<u8> {
+        items
+            .iter()
+            .flat_map(|item| match item {
+                Decoded::Command(cmd) => cmd.as_bytes().unwrap(),
+                Decoded::Text(text) => text.clone(),
+                Decoded::Unknown(bytes) => bytes.clone(),
+            })
+            .collect()
+    }
+
+    fn checkerboard(width: u32, height: u32) -> image::GrayImage {
+        image::GrayImage::from_fn(width, height, |x, y| {
+            image::Luma([if (x + y) % 3 == 0 { 0 } else { 255 }])
+        })
+    }
+
+    fn assert_round_trip(cmd: Command) {
+        let bytes = cmd.as_bytes().unwrap();
+        let decoded = decode(&bytes);
+        assert!(
+            !decoded.iter().any(|d| matches!(d, Decoded::Unknown(_))),
+            "{:?} decoded to {:?}",
+            cmd,
+            decoded
+        );
+        assert_eq!(bytes, encode(&decoded), "{:?}", cmd);
+    }
+
+    #[test]
+    fn decode_round_trip() {
+        let commands = vec![
+            Command::Init,
+            Command::Cut,
+            Command::LegacyPartialCut,
+            Command::CutWith(CutMode::Partial, Some(3)),
+            Command::Underline(true),
+            Command::Emphasize(true),
+            Command::DoubleStrike(false),
+            Command::Reverse(true),
+            Command::Rotate90(true),
+            Command::Smoothing(true),
+            Command::PrintColor(Color::Red),
+            Command::CharacterColor(EffectColor::Color2),
+            Command::BackgroundColor(EffectColor::Color1),
+            Command::Shadow(None),
+            Command::Shadow(Some(EffectColor::Color3)),
+            Command::Justification(Alignment::Center),
+            Command::Speed(4),
+            Command::Density(-3),
+            Command::EnterUserSetting,
+            Command::ExitUserSetting,
+            Command::SetCustomizeValue(CustomizeValue::PrintDensity(-2)),
+            Command::SetCustomizeValue(CustomizeValue::PrintSpeed(9)),
+            Command::Feed(4),
+            Command::FeedUnits(30),
+            Command::ReverseFeed(2),
+            Command::TextSize {
+                width: 3,
+                height: 8,
+            },
+            Command::DoubleWidth(true),
+            Command::LegacyPrintMode {
+                double_width: true,
+                double_height: false,
+            },
+            Command::DefaultLineSpacing,
+            Command::LineSpacing(24),
+            Command::SetTabStops(vec![8, 16, 24]),
+            Command::HorizontalTab,
+            Command::CashDrawer {
+                pin: DrawerPin::Pin5,
+                on_ms: 100,
+                off_ms: 200,
+            },
+            Command::Buzzer {
+                pattern: 10,
+                repetitions: 3,
+            },
+            Command::DefineUserGlyphs {
+                first: 65,
+                glyphs: vec![
+                    UserGlyph::from_columns(&[[0xff, 0x00, 0x81]; 12]).unwrap(),
+                    UserGlyph::from_columns(&[[0x18, 0x3c, 0x7e]; 5]).unwrap(),
+                ],
+            },
+            Command::UserGlyphs(true),
+            Command::ClearUserGlyph(65),
+            Command::MacroExecute {
+                count: 2,
+                interval: 10,
+                button: true,
+            },
+            Command::RealTimeStatusRequest(StatusKind::PaperSensor),
+            Command::EnableAsb(AsbFlags::all()),
+            Command::RequestPrinterId(InfoKind::SerialNumber),
+            Command::RequestMaintenanceCounter {
+                counter: MaintenanceCounter::Cuts,
+                cumulative: true,
+            },
+            Command::ResetMaintenanceCounter(MaintenanceCounter::HeadEnergizing),
+            Command::CancelPageData,
+            Command::ClearBuffers,
+            Command::RealTimeDrawerPulse {
+                pin: DrawerPin::Pin2,
+                duration: 4,
+            },
+            Command::KanjiMode(true),
+            Command::KanjiMode(false),
+            Command::KanjiCodeSystem(KanjiCode::ShiftJis),
+            Command::KanjiStyle(KanjiStyle {
+                double_width: true,
+                double_height: false,
+                underline: true,
+            }),
+            Command::SetPaperLayout(PaperLayout::BlackMark),
+            Command::FeedToLabelOrigin,
+            Command::FeedToCutPosition,
+            Command::FeedToPeelPosition,
+            Command::CodePage(CodePage::Pc858),
+            Command::CharacterSet(CharacterSet::Unicode),
+            Command::StoreGraphics {
+                color: Color::Red,
+                image: checkerboard(16, 5),
+            },
+            Command::PrintGraphics,
+            Command::RasterBits {
+                width_bytes: 2,
+                height: 2,
+                data: vec![0xde, 0xad, 0xbe, 0xef],
+            },
+            Command::Image(checkerboard(24, 7), ImageScale::Quadruple),
+            Command::DefineNvLogo {
+                key: NvKey::new(b'A', b'1').unwrap(),
+                image: checkerboard(8, 3),
+            },
+            Command::PrintNvLogo {
+                key: NvKey::new(b'A', b'1').unwrap(),
+                scale: ImageScale::DoubleHeight,
+            },
+            Command::DeleteNvLogo(NvKey::new(b'A', b'1').unwrap()),
+            Command::DeleteAllNvLogos,
+            Command::ColumnBand(ColumnImageMode::TwentyFourDotDouble, checkerboard(10, 24)),
+            Command::ColumnImage(ColumnImageMode::EightDotSingle, checkerboard(10, 20)),
+            Command::DefineDownloadedImage(checkerboard(16, 16)),
+            Command::PrintDownloadedImage(ImageScale::DoubleWidth),
+            Command::Barcode(Barcode::new_upca("03600029145").unwrap()),
+            Command::QrCode(QrCode::new(b"https://example.com")),
+            Command::QrCode(QrCode::new_micro(b"12345")),
+        ];
+        for cmd in commands {
+            assert_round_trip(cmd);
+        }
+    }
+
+    #[test]
+    fn decode_canonical() {
+        assert_eq!(
+            vec![Decoded::Command(Command::TextSize {
+                width: 2,
+                height: 1
+            })],
+            decode(&Command::DoubleWidth(true).as_bytes().unwrap())
+        );
+    }
+
+    #[test]
+    fn decode_text() {
+        assert_eq!(
+            vec![
+                Decoded::Command(Command::Init),
+                Decoded::Text(b"HACK THE\r\nPLANET\n".to_vec()),
+                Decoded::Command(Command::HorizontalTab),
+                Decoded::Text(b"!".to_vec()),
+            ],
+            decode(b"\x1b@HACK THE\r\nPLANET\n\t!")
+        );
+    }
+
+    #[test]
+    fn decode_unknown() {
+        assert_eq!(
+            vec![
+                Decoded::Unknown(vec![0x1b, b'Z']),
+                Decoded::Text(b"A".to_vec()),
+                Decoded::Unknown(vec![0x07]),
+                Decoded::Unknown(vec![0x1d, b'(', b'z', 0x02, 0x00, 0x01, 0x02]),
+                Decoded::Unknown(vec![0x1d, b'k', 0x00, b'1', b'2', 0x00]),
+                Decoded::Text(b"B".to_vec()),
+                Decoded::Unknown(vec![0x1b, b'a', 0x07]),
+                Decoded::Unknown(vec![0x1b, b'd']),
+            ],
+            decode(b"\x1bZA\x07\x1d(z\x02\x00\x01\x02\x1dk\x0012\x00B\x1ba\x07\x1bd")
+        );
+    }
+
+    #[test]
+    fn decode_macro() {
+        let bytes = [
+            Command::MacroStart.as_bytes().unwrap(),
+            Command::Feed(1).as_bytes().unwrap(),
+            Command::MacroEnd.as_bytes().unwrap(),
+            Command::MacroStart.as_bytes().unwrap(),
+        ]
+        .concat();
+        assert_eq!(
+            vec![
+                Decoded::Command(Command::MacroStart),
+                Decoded::Command(Command::Feed(1)),
+                Decoded::Command(Command::MacroEnd),
+                Decoded::Command(Command::MacroStart),
+            ],
+            decode(&bytes)
+        );
+    }
+
+    #[test]
+    fn decode_partial_qr() {
+        let bytes = Command::QrCode(QrCode::new(b"hello")).as_bytes().unwrap();
+        let decoded = decode(&bytes[..20]);
+        assert_eq!(Decoded::Unknown(bytes[..9].to_vec()), decoded[0]);
+        assert_eq!(&bytes[..20], &encode(&decoded)[..]);
+    }
+
+    #[test]
+    fn decode_streaming() {
+        let bytes = [
+            b"Hello\n".to_vec(),
+            Command::Image(checkerboard(16, 4), ImageScale::Normal)
+                .as_bytes()
+                .unwrap(),
+            Command::QrCode(QrCode::new(b"hello")).as_bytes().unwrap(),
+            Command::Cut.as_bytes().unwrap(),
+        ]
+        .concat();
+        let expected = decode(&bytes);
+        assert_eq!(4, expected.len());
+
+        for chunk in [1, 2, 3, 7, 64] {
+            let mut decoder = Decoder::new();
+            let mut decoded = vec![];
+            for bytes in bytes.chunks(chunk) {
+                decoder.push(bytes);
+                decoded.extend(&mut decoder);
+            }
+            decoded.extend(decoder.finish());
+
+            // Text may be split into several runs, depending on how it
+            // arrived.
+            assert_eq!(bytes, encode(&decoded));
+            assert_eq!(&expected[1..], &decoded[decoded.len() - 3..]);
+        }
+    }
+
+    #[test]
+    fn decode_truncated() {
+        let mut decoder = Decoder::new();
+        decoder.push(&[0x1b, b'd']);
+        assert_eq!(None, decoder.next());
+        decoder.push(&[0x03]);
+        assert_eq!(Some(Decoded::Command(Command::Feed(3))), decoder.next());
+        assert_eq!(None, decoder.next());
+        assert!(decoder.finish().is_empty());
+    }
+    /// Small xorshift generator, so the randomized tests are repeatable.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u32) -> u32 {
+            (self.next() % n as u64) as u32
+        }
+
+        fn range(&mut self, low: u8, high: u8) -> u8 {
+            low + self.below((high - low) as u32 + 1) as u8
+        }
+
+        fn byte(&mut self) -> u8 {
+            self.next() as u8
+        }
+
+        fn bool(&mut self) -> bool {
+            self.next() & 1 != 0
+        }
+
+        /// Return a random, valid, command.
+        fn command(&mut self) -> Command {
+            let data: Vec<u8> = (0..512).map(|_| self.byte()).collect();
+            Unstructured::new(&data).arbitrary()
+        }
+
+        /// Return a random run of text, without any control codes.
+        fn text(&mut self) -> Vec<u8> {
+            (0..self.range(1, 20))
+                .map(|_| match self.below(10) {
+                    0 => b'\n',
+                    1 => b'\r',
+                    _ => self.range(0x20, 0xff),
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn decode_random_round_trip() {
+        let mut rng = Rng(0x5eed_1849);
+        for _ in 0..5000 {
+            round_trip(&rng.command());
+        }
+    }
+
+    #[test]
+    fn decode_random_stream() {
+        let mut rng = Rng(0x5eed_1850);
+        for _ in 0..200 {
+            let mut bytes = vec![];
+            for _ in 0..rng.range(1, 20) {
+                if rng.bool() {
+                    bytes.extend(rng.text());
+                } else if let Ok(cmd) = rng.command().as_bytes() {
+                    bytes.extend(cmd);
+                }
+            }
+            let decoded = decode(&bytes);
+            assert_eq!(bytes, encode(&decoded));
+
+            // Decoding in random chunks gives back the same items, other
+            // than text being split into more runs.
+            let mut decoder = Decoder::new();
+            let mut chunked = vec![];
+            let mut rest = &bytes[..];
+            while !rest.is_empty() {
+                let (chunk, tail) = rest.split_at((rng.range(1, 64) as usize).min(rest.len()));
+                decoder.push(chunk);
+                chunked.extend(&mut decoder);
+                rest = tail;
+            }
+            chunked.extend(decoder.finish());
+
+            let without_text = |items: &[Decoded]| -> Vec<Decoded> {
+                items
+                    .iter()
+                    .filter(|d| !matches!(d, Decoded::Text(_)))
+                    .cloned()
+                    .collect()
+            };
+            assert_eq!(bytes, encode(&chunked));
+            assert_eq!(without_text(&decoded), without_text(&chunked));
+        }
+    }
+}
+
+// vim: foldmethod=marker
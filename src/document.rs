@@ -0,0 +1,594 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Declarative receipt documents, so that receipt layouts can live in
+//! configuration files and be edited without rebuilding the program that
+//! prints them.
+//!
+//! A [Document] is a list of [Block]s, and can be loaded from JSON with
+//! [Document::from_json]:
+//!
+//! ```json
+//! {
+//!   "blocks": [
+//!     { "type": "text", "text": "HACK THE PLANET", "align": "center", "bold": true },
+//!     { "type": "table", "rows": [["Coffee", "$3.50"], ["Bagel", "$2.25"]] },
+//!     { "type": "qr", "data": "https://example.com/r/1234" },
+//!     { "type": "cut" }
+//!   ]
+//! }
+//! ```
+//!
+//! or from YAML with [Document::from_yaml]:
+//!
+//! ```yaml
+//! blocks:
+//!   - {type: text, text: HACK THE PLANET, align: center, bold: true}
+//!   - type: table
+//!     rows:
+//!       - [Coffee, $3.50]
+//!       - [Bagel, $2.25]
+//!   - {type: qr, data: "https://example.com/r/1234"}
+//!   - type: cut
+//! ```
+//!
+//! Documents are printed with [render].
+
+use super::json::Value;
+use super::width::{text_width, truncate};
+use super::write::Error as WriteError;
+use super::yaml;
+use super::{Alignment, Barcode, Command, Error, Model, QrCode, Writer};
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+/// Style to print text with, used by [Block::Text] and [StyleSheet]. The
+/// default is plain, left justified, normal sized text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Style {
+    /// Print the text in bold.
+    pub bold: bool,
+
+    /// Underline the text.
+    pub underline: bool,
+
+    /// Justification of the text.
+    pub align: Alignment,
+
+    /// Character width multiplier, from 1 through 8.
+    pub width: u8,
+
+    /// Character height multiplier, from 1 through 8.
+    pub height: u8,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            bold: false,
+            underline: false,
+            align: Alignment::Left,
+            width: 1,
+            height: 1,
+        }
+    }
+}
+
+impl Style {
+    /// Update the style to match the printer's after `cmd` is sent.
+    pub(crate) fn track(&mut self, cmd: &Command) {
+        match *cmd {
+            Command::Init => *self = Style::default(),
+            Command::Emphasize(bold) => self.bold = bold,
+            Command::Underline(underline) => self.underline = underline,
+            Command::Justification(align) => self.align = align,
+            Command::TextSize { width, height } => (self.width, self.height) = (width, height),
+            Command::DoubleWidth(state) => (self.width, self.height) = (1 + state as u8, 1),
+            Command::DoubleHeight(state) => (self.width, self.height) = (1, 1 + state as u8),
+            Command::LegacyPrintMode {
+                double_width,
+                double_height,
+            } => {
+                self.bold = false;
+                self.underline = false;
+                (self.width, self.height) = (1 + double_width as u8, 1 + double_height as u8);
+            }
+            _ => {}
+        }
+    }
+
+    /// Return the commands which change the printer from the `previous`
+    /// style to this one on `model`. Models without `GS !` can only double
+    /// the size of characters, with `ESC !`.
+    pub(crate) fn commands_from(&self, previous: &Style, model: Model) -> Vec<Command> {
+        let mut commands = vec![];
+        let mut previous = previous.clone();
+        let resize = (self.width, self.height) != (previous.width, previous.height);
+        if resize && !model.supports_text_size() {
+            // ESC ! also turns off emphasis and underline, so it goes
+            // first, and they're turned back on after it.
+            let cmd = Command::LegacyPrintMode {
+                double_width: self.width > 1,
+                double_height: self.height > 1,
+            };
+            previous.track(&cmd);
+            commands.push(cmd);
+        }
+        if self.bold != previous.bold {
+            commands.push(Command::Emphasize(self.bold));
+        }
+        if self.underline != previous.underline {
+            commands.push(Command::Underline(self.underline));
+        }
+        if self.align != previous.align {
+            commands.push(Command::Justification(self.align));
+        }
+        if resize && model.supports_text_size() {
+            commands.push(Command::TextSize {
+                width: self.width,
+                height: self.height,
+            });
+        }
+        commands
+    }
+}
+
+/// Named [Style]s, such as "h1", "total" or "fineprint", so formatting
+/// decisions live in one place. Styles are applied by name with
+/// [Writer::apply_style] once the sheet is set with
+/// [Writer::set_style_sheet].
+///
+/// ```rust
+/// # use epson::{document::Style, Alignment, StyleSheet};
+/// let sheet = StyleSheet::new()
+///     .style("h1", Style { bold: true, align: Alignment::Center, width: 2, height: 2, ..Default::default() })
+///     .style("total", Style { bold: true, ..Default::default() });
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StyleSheet {
+    styles: HashMap<String, Style>,
+}
+
+impl StyleSheet {
+    /// Create a new, empty, style sheet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a style called `name`, replacing any style with that name.
+    pub fn style(mut self, name: &str, style: Style) -> Self {
+        self.styles.insert(name.to_owned(), style);
+        self
+    }
+
+    /// Return the style called `name`, if there is one.
+    pub fn get(&self, name: &str) -> Option<&Style> {
+        self.styles.get(name)
+    }
+}
+
+/// A single piece of a [Document].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Block {
+    /// A paragraph of text, wrapped to fit the paper.
+    Text {
+        /// Text to print.
+        text: String,
+
+        /// Style to print the text in.
+        style: Style,
+    },
+
+    /// An image loaded from disk when the document is rendered, scaled
+    /// down to fit the paper if it's too wide.
+    Image(PathBuf),
+
+    /// A barcode.
+    Barcode(Barcode),
+
+    /// A QR Code.
+    QrCode(QrCode),
+
+    /// Rows of text laid out in columns. Each entry of `widths` is the
+    /// number of characters in that column; if it's empty, the columns
+    /// split the line evenly. The last column is right justified, which
+    /// suits prices.
+    Table {
+        /// Width of each column, in characters.
+        widths: Vec<usize>,
+
+        /// Cells of each row.
+        rows: Vec<Vec<String>>,
+    },
+
+    /// Feed the paper by a number of lines.
+    Feed(u8),
+
+    /// Cut the paper.
+    Cut,
+}
+
+/// A receipt layout, described as data rather than code.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    /// Blocks of the document, printed in order.
+    pub blocks: Vec<Block>,
+}
+
+impl Document {
+    /// Parse a document from JSON. See the [module](self) documentation
+    /// for the format. Returns [Error::InvalidDocument] if the JSON isn't
+    /// a valid document, or a barcode error if a barcode is invalid.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Self::from_value(&Value::parse(json).map_err(|_| Error::InvalidDocument)?)
+    }
+
+    /// Parse a document from YAML, with the same structure as the JSON
+    /// format. Only a subset of YAML is understood: anchors, aliases, tags
+    /// and multiple documents are rejected with [Error::InvalidDocument].
+    pub fn from_yaml(yaml: &str) -> Result<Self, Error> {
+        Self::from_value(&yaml::parse(yaml).map_err(|_| Error::InvalidDocument)?)
+    }
+
+    fn from_value(doc: &Value) -> Result<Self, Error> {
+        let blocks = doc
+            .get("blocks")
+            .and_then(Value::as_array)
+            .ok_or(Error::InvalidDocument)?;
+        Ok(Self {
+            blocks: blocks.iter().map(parse_block).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+fn parse_block(block: &Value) -> Result<Block, Error> {
+    let string = |key| {
+        block
+            .get(key)
+            .and_then(Value::as_str)
+            .ok_or(Error::InvalidDocument)
+    };
+    let whole = |value: &Value, max: f64| {
+        value
+            .as_f64()
+            .filter(|n| n.fract() == 0.0 && (0.0..=max).contains(n))
+    };
+    let number = |key, default: u8, range: RangeInclusive<u8>| match block.get(key) {
+        None => Ok(default),
+        Some(value) => whole(value, 255.0)
+            .map(|n| n as u8)
+            .filter(|n| range.contains(n))
+            .ok_or(Error::InvalidDocument),
+    };
+    let flag = |key| match block.get(key) {
+        None => Ok(false),
+        Some(value) => value.as_bool().ok_or(Error::InvalidDocument),
+    };
+
+    Ok(match string("type")? {
+        "text" => Block::Text {
+            text: string("text")?.to_owned(),
+            style: Style {
+                bold: flag("bold")?,
+                underline: flag("underline")?,
+                align: match block.get("align") {
+                    None => Alignment::Left,
+                    Some(align) => match align.as_str() {
+                        Some("left") => Alignment::Left,
+                        Some("center") => Alignment::Center,
+                        Some("right") => Alignment::Right,
+                        _ => return Err(Error::InvalidDocument),
+                    },
+                },
+                width: number("width", 1, 1..=8)?,
+                height: number("height", 1, 1..=8)?,
+            },
+        },
+        "image" => Block::Image(string("path")?.into()),
+        "barcode" => Block::Barcode(Barcode::new_upca(string("upca")?)?),
+        "qr" => Block::QrCode(QrCode::new(string("data")?.as_bytes())),
+        "table" => {
+            let widths = match block.get("widths") {
+                None => vec![],
+                Some(widths) => widths
+                    .as_array()
+                    .ok_or(Error::InvalidDocument)?
+                    .iter()
+                    .map(|w| whole(w, u16::MAX as f64).map(|w| w as usize))
+                    .collect::<Option<_>>()
+                    .ok_or(Error::InvalidDocument)?,
+            };
+            let rows = block
+                .get("rows")
+                .and_then(Value::as_array)
+                .ok_or(Error::InvalidDocument)?
+                .iter()
+                .map(|row| {
+                    row.as_array()?
+                        .iter()
+                        .map(|cell| cell.as_str().map(str::to_owned))
+                        .collect::<Option<_>>()
+                })
+                .collect::<Option<_>>()
+                .ok_or(Error::InvalidDocument)?;
+            Block::Table { widths, rows }
+        }
+        "feed" => Block::Feed(number("lines", 1, 0..=255)?),
+        "cut" => Block::Cut,
+        _ => return Err(Error::InvalidDocument),
+    })
+}
+
+/// Lay out one row of a [Block::Table], `columns` characters wide.
+fn table_row(widths: &[usize], row: &[String], columns: usize) -> String {
+    let count = row.len().max(1);
+    let mut line = String::new();
+    for (i, cell) in row.iter().enumerate() {
+        let width = widths.get(i).copied().unwrap_or(columns / count);
+        let cell = truncate(cell, width);
+        let pad = " ".repeat(width - text_width(cell));
+        if i + 1 == row.len() && i > 0 {
+            line.push_str(&pad);
+            line.push_str(cell);
+        } else {
+            line.push_str(cell);
+            line.push_str(&pad);
+        }
+    }
+    line.truncate(line.trim_end().len());
+    line.push('\n');
+    line
+}
+
+/// Print a [Document] to a [Writer].
+pub fn render(doc: &Document, w: &mut Writer) -> Result<(), WriteError> {
+    let columns = w.model().get_columns();
+    for block in &doc.blocks {
+        match block {
+            Block::Text { text, style } => w.print_wrapped(text, style)?,
+            Block::Image(path) => {
+                let img = image::open(path).map_err(|e| match e {
+                    image::ImageError::IoError(e) => e,
+                    e => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+                })?;
+                w.print_image_fit(img.into_luma8())?;
+            }
+            Block::Barcode(barcode) => w.print_barcode(barcode.clone())?,
+            Block::QrCode(qr) => w.print_qr(qr.clone())?,
+            Block::Table { widths, rows } => {
+                for row in rows {
+                    w.print_text(&table_row(widths, row, columns))?;
+                }
+            }
+            Block::Feed(lines) => w.feed(*lines)?,
+            Block::Cut => w.cut()?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureWriter;
+    use crate::{Command, CutMode, Model};
+
+    #[test]
+    fn track_style() {
+        let mut style = Style::default();
+        style.track(&Command::Emphasize(true));
+        style.track(&Command::Justification(Alignment::Center));
+        style.track(&Command::DoubleWidth(true));
+        assert_eq!(
+            Style {
+                bold: true,
+                align: Alignment::Center,
+                width: 2,
+                ..Default::default()
+            },
+            style
+        );
+
+        style.track(&Command::LegacyPrintMode {
+            double_width: false,
+            double_height: true,
+        });
+        assert!(!style.bold);
+        assert_eq!((1, 2), (style.width, style.height));
+
+        style.track(&Command::Init);
+        assert_eq!(Style::default(), style);
+    }
+
+    #[test]
+    fn document_from_json() {
+        let doc = Document::from_json(
+            r#"{"blocks": [
+                {"type": "text", "text": "Hi", "align": "center", "bold": true, "width": 2},
+                {"type": "table", "widths": [10, 6], "rows": [["Coffee", "$3.50"]]},
+                {"type": "feed", "lines": 3},
+                {"type": "cut"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                Block::Text {
+                    text: "Hi".to_owned(),
+                    style: Style {
+                        bold: true,
+                        align: Alignment::Center,
+                        width: 2,
+                        ..Default::default()
+                    },
+                },
+                Block::Table {
+                    widths: vec![10, 6],
+                    rows: vec![vec!["Coffee".to_owned(), "$3.50".to_owned()]],
+                },
+                Block::Feed(3),
+                Block::Cut,
+            ],
+            doc.blocks
+        );
+    }
+
+    #[test]
+    fn document_from_yaml() {
+        let yaml = Document::from_yaml(
+            "# Morning receipt
+blocks:
+  - type: text
+    text: Hi
+    align: center
+    bold: true
+    width: 2
+  - {type: table, widths: [10, 6], rows: [[Coffee, $3.50]]}
+  - type: feed
+    lines: 3
+  - type: cut
+",
+        );
+        let json = Document::from_json(
+            r#"{"blocks": [
+                {"type": "text", "text": "Hi", "align": "center", "bold": true, "width": 2},
+                {"type": "table", "widths": [10, 6], "rows": [["Coffee", "$3.50"]]},
+                {"type": "feed", "lines": 3},
+                {"type": "cut"}
+            ]}"#,
+        );
+        assert_eq!(json, yaml);
+        assert_eq!(
+            Err(Error::InvalidDocument),
+            Document::from_yaml("blocks: *all\n")
+        );
+        assert_eq!(
+            Err(Error::InvalidDocument),
+            Document::from_yaml("blocks:\n  - type: feed\n    lines: yes\n")
+        );
+    }
+
+    #[test]
+    fn document_invalid() {
+        for json in [
+            "",
+            "{}",
+            r#"{"blocks": [{"type": "sparkles"}]}"#,
+            r#"{"blocks": [{"type": "text"}]}"#,
+            r#"{"blocks": [{"type": "text", "text": "Hi", "align": "up"}]}"#,
+            r#"{"blocks": [{"type": "feed", "lines": 300}]}"#,
+            r#"{"blocks": [{"type": "text", "text": "Hi", "width": 0}]}"#,
+            r#"{"blocks": [{"type": "text", "text": "Hi", "height": 9}]}"#,
+            r#"{"blocks": [{"type": "table", "widths": [-1], "rows": []}]}"#,
+            r#"{"blocks": [{"type": "table", "widths": [2.5], "rows": []}]}"#,
+        ] {
+            assert_eq!(Err(Error::InvalidDocument), Document::from_json(json));
+        }
+        assert_eq!(
+            Err(Error::InvalidBarcode),
+            Document::from_json(r#"{"blocks": [{"type": "barcode", "upca": "123"}]}"#)
+        );
+    }
+
+    #[test]
+    fn style_changes() {
+        let h1 = Style {
+            bold: true,
+            align: Alignment::Center,
+            width: 2,
+            height: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![
+                Command::Emphasize(true),
+                Command::Justification(Alignment::Center),
+                Command::TextSize {
+                    width: 2,
+                    height: 2
+                },
+            ],
+            h1.commands_from(&Style::default(), Model::T20II)
+        );
+        assert!(h1.commands_from(&h1, Model::T20II).is_empty());
+
+        // Without GS !, sizes are doubled with ESC !, which turns
+        // emphasis off.
+        assert_eq!(
+            vec![
+                Command::LegacyPrintMode {
+                    double_width: true,
+                    double_height: true
+                },
+                Command::Emphasize(true),
+                Command::Justification(Alignment::Center),
+            ],
+            h1.commands_from(&Style::default(), Model::Generic)
+        );
+        let bold = Style {
+            bold: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![
+                Command::LegacyPrintMode {
+                    double_width: false,
+                    double_height: false
+                },
+                Command::Emphasize(true),
+                Command::Justification(Alignment::Left),
+            ],
+            bold.commands_from(&h1, Model::Generic)
+        );
+    }
+
+    #[test]
+    fn table_layout() {
+        let row = vec!["Coffee".to_owned(), "$3.50".to_owned()];
+        assert_eq!("Coffee             $3.50\n", table_row(&[], &row, 24));
+        assert_eq!("Cof $3.50\n", table_row(&[3, 6], &row, 24));
+        let row = vec!["珈琲".to_owned(), "¥350".to_owned()];
+        assert_eq!("珈琲  ¥350\n", table_row(&[6, 4], &row, 24));
+    }
+
+    #[test]
+    fn document_render() {
+        let capture = CaptureWriter::new();
+        let mut w = Writer::open(Model::T20II, Box::new(capture.clone())).unwrap();
+        let doc = Document {
+            blocks: vec![
+                Block::Text {
+                    text: "Hi".to_owned(),
+                    style: Style::default(),
+                },
+                Block::Cut,
+            ],
+        };
+        render(&doc, &mut w).unwrap();
+        assert_eq!("Hi\n", capture.text());
+        assert_eq!(
+            Some(&Command::CutWith(CutMode::Full, None)),
+            capture.commands().last()
+        );
+    }
+}
+
+// vim: foldmethod=marker
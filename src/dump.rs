@@ -0,0 +1,140 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+use super::decode::decode_ranges;
+use super::{Command, Decoded};
+use std::fmt::Write;
+
+/// Number of bytes shown on each line of a dump. Longer commands are
+/// shortened.
+const DUMP_BYTES: usize = 8;
+
+/// Disassemble raw ESC/POS bytes into an annotated, human readable
+/// listing, with the offset, bytes and decoded command on each line.
+///
+/// ```rust
+/// let listing = epson::dump(b"\x1b@Hi\n\x1bd\x03");
+/// assert_eq!(
+///     listing,
+///     "00000000  1b 40                      Init\n\
+///      00000002  48 69 0a                   Text \"Hi\\n\"\n\
+///      00000005  1b 64 03                   Feed(3)\n",
+/// );
+/// ```
+pub fn dump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (range, decoded) in decode_ranges(bytes) {
+        let raw = &bytes[range.clone()];
+        let mut hex: Vec<String> = raw
+            .iter()
+            .take(DUMP_BYTES)
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        if raw.len() > DUMP_BYTES {
+            hex.push("..".to_owned());
+        }
+
+        let _ = writeln!(
+            out,
+            "{:08x}  {:<width$} {}",
+            range.start,
+            hex.join(" "),
+            describe(&decoded),
+            width = DUMP_BYTES * 3 + 2,
+        );
+    }
+    out
+}
+
+/// Describe a decoded item, summarizing image data rather than listing
+/// every pixel.
+fn describe(decoded: &Decoded) -> String {
+    let cmd = match decoded {
+        Decoded::Text(text) => return format!("Text \"{}\"", text.escape_ascii()),
+        Decoded::Unknown(_) => return "Unknown".to_owned(),
+        Decoded::Command(cmd) => cmd,
+    };
+
+    let size = |img: &image::GrayImage| format!("{}x{}", img.width(), img.height());
+    match cmd {
+        Command::Image(img, scale) => format!("Image({}, {:?})", size(img), scale),
+        Command::RasterBits {
+            width_bytes,
+            height,
+            ..
+        } => format!("RasterBits({}x{})", *width_bytes as u32 * 8, height),
+        Command::StoreGraphics { color, image } => {
+            format!("StoreGraphics({:?}, {})", color, size(image))
+        }
+        Command::DefineNvLogo { key, image } => {
+            format!("DefineNvLogo({:?}, {})", key, size(image))
+        }
+        Command::ColumnBand(mode, img) => format!("ColumnBand({:?}, {})", mode, size(img)),
+        Command::ColumnImage(mode, img) => format!("ColumnImage({:?}, {})", mode, size(img)),
+        Command::DefineDownloadedImage(img) => format!("DefineDownloadedImage({})", size(img)),
+        Command::QrCode(qr) => format!(
+            "QrCode({:?}, size {}, {:?}, \"{}\")",
+            qr.model,
+            qr.module_size,
+            qr.error_correction,
+            qr.data.escape_ascii()
+        ),
+        Command::Barcode(barcode) => format!(
+            "Barcode({:?}, \"{}\")",
+            barcode.system(),
+            barcode.data().escape_ascii()
+        ),
+        cmd => format!("{:?}", cmd),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ImageScale;
+
+    #[test]
+    fn dump_image() {
+        let img = image::GrayImage::new(16, 2);
+        let bytes = Command::Image(img, ImageScale::Normal).as_bytes().unwrap();
+        assert_eq!(
+            "00000000  1d 76 30 00 02 00 02 00 .. Image(16x2, Normal)\n",
+            dump(&bytes)
+        );
+    }
+
+    #[test]
+    fn dump_unknown() {
+        assert_eq!(
+            "00000000  1b 5a                      Unknown\n\
+             00000002  1b                         Unknown\n",
+            dump(b"\x1bZ\x1b")
+        );
+    }
+
+    #[test]
+    fn dump_qr() {
+        let qr = crate::QrCode::new(b"hi");
+        let listing = dump(&Command::QrCode(qr).as_bytes().unwrap());
+        assert!(listing.ends_with("QrCode(Model2, size 3, M, \"hi\")\n"));
+    }
+}
+
+// vim: foldmethod=marker
@@ -0,0 +1,869 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+use super::font::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+use super::preview::Metrics;
+use super::{
+    Alignment, Barcode, BarcodeSystem, CharacterSet, Color, ColumnImageMode, Command, Decoded,
+    Decoder, ImageScale, Model, NvKey, QrCode, QrModel,
+};
+use image::{GrayImage, Luma};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// Width of a character in Font A, in dots.
+const CHAR_WIDTH: u32 = 12;
+
+/// Height of a character in Font A, in dots.
+const CHAR_HEIGHT: u32 = 24;
+
+/// Default line spacing, in dots.
+const DEFAULT_LINE_SPACING: u32 = 30;
+
+/// Width of a single barcode module, in dots.
+const BARCODE_MODULE_WIDTH: u32 = 3;
+
+/// Height of a barcode, in dots.
+const BARCODE_HEIGHT: u32 = 162;
+
+const WHITE: u8 = 255;
+
+/// Virtual printer which renders the commands written to it into an image
+/// of the printed receipt, rather than onto paper.
+///
+/// Text, text styles, justification, feeds, images and barcodes are all
+/// drawn. Text is drawn with a small built-in font which only covers
+/// printable ASCII, QR Codes are drawn as a placeholder of about the
+/// right size, and red ink is drawn in gray. Cuts are drawn as a dashed
+/// line across the receipt.
+///
+/// Cloning an Emulator returns another handle to the same receipt, so one
+/// handle can be given to a [super::Writer], and the other used to look at
+/// the result.
+///
+/// ```rust
+/// # use epson::{Emulator, Model, Writer};
+/// # use std::io::Write;
+/// let emulator = Emulator::new(Model::T20II);
+/// let mut pos = Writer::open(Model::T20II, Box::new(emulator.clone())).unwrap();
+/// pos.write_all(b"HACK THE PLANET\n").unwrap();
+///
+/// let receipt = emulator.image();
+/// assert_eq!(576, receipt.width());
+/// ```
+#[derive(Clone, Debug)]
+pub struct Emulator {
+    inner: Arc<Mutex<State>>,
+    dots_per_mm: f32,
+}
+
+impl Emulator {
+    /// Create a new Emulator, printing on the paper width of the provided
+    /// model.
+    pub fn new(model: Model) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(State::new(model.get_max_image_width() as u32))),
+            dots_per_mm: model.dots_per_mm(),
+        }
+    }
+
+    /// Return an image of the receipt printed so far, as if the stream
+    /// ended here. Any text still waiting for a line feed is included.
+    pub fn image(&self) -> GrayImage {
+        self.finished().into_image()
+    }
+
+    /// Return how much paper the receipt printed so far uses, as if the
+    /// stream ended here. This is useful as a dry run, to check the
+    /// length of a receipt before printing it.
+    pub fn metrics(&self) -> Metrics {
+        let state = self.finished();
+        let dots = state.y.max(
+            (state.paper.len() as u32)
+                .checked_div(state.width)
+                .unwrap_or(0),
+        );
+        Metrics {
+            dots,
+            length_mm: dots as f32 / self.dots_per_mm,
+            lines: state.lines,
+            cuts: state.cuts,
+        }
+    }
+
+    /// Return a copy of the state, with the rest of the stream handled
+    /// and any pending line printed.
+    fn finished(&self) -> State {
+        let mut state = self.lock().clone();
+        for decoded in state.decoder.finish() {
+            state.handle(decoded);
+        }
+        state.print_line();
+        state
+    }
+
+    /// Decode and render more of the command stream.
+    pub(crate) fn push(&self, buf: &[u8]) {
+        let mut state = self.lock();
+        state.decoder.push(buf);
+        while let Some(decoded) = state.decoder.next() {
+            state.handle(decoded);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        // The state is only changed while decoding, which can't panic part
+        // way through an update, so a poisoned lock is still usable.
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Write for Emulator {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.push(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for Emulator {
+    fn poll_write(
+        mut self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        std::task::Poll::Ready(self.write(buf))
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+/// Print modes which apply to text, reset by [Command::Init].
+#[derive(Clone, Debug)]
+struct Style {
+    emphasize: bool,
+    underline: bool,
+    reverse: bool,
+    width: u8,
+    height: u8,
+    ink: u8,
+    align: Alignment,
+    line_spacing: u32,
+    tab_stops: Vec<u8>,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            emphasize: false,
+            underline: false,
+            reverse: false,
+            width: 1,
+            height: 1,
+            ink: 0,
+            align: Alignment::Left,
+            line_spacing: DEFAULT_LINE_SPACING,
+            tab_stops: (1..32).map(|n| n * 8).collect(),
+        }
+    }
+}
+
+/// Everything the virtual printer has printed or stored so far.
+#[derive(Clone, Debug)]
+struct State {
+    decoder: Decoder,
+    style: Style,
+    unicode: bool,
+
+    /// Text bytes which are only part of a UTF-8 character.
+    partial: Vec<u8>,
+
+    /// Pieces of the line waiting to be printed, left to right.
+    line: Vec<GrayImage>,
+
+    /// Number of characters in the line, used for tab stops.
+    line_chars: u32,
+
+    width: u32,
+    paper: Vec<u8>,
+    y: u32,
+
+    /// Number of lines fed, and cuts made, for [Metrics].
+    lines: u32,
+    cuts: u32,
+
+    graphics: [Option<GrayImage>; 2],
+    downloaded: Option<GrayImage>,
+    logos: Vec<(NvKey, GrayImage)>,
+}
+
+impl State {
+    fn new(width: u32) -> Self {
+        Self {
+            decoder: Decoder::new(),
+            style: Style::default(),
+            unicode: false,
+            partial: vec![],
+            line: vec![],
+            line_chars: 0,
+            width,
+            paper: vec![],
+            y: 0,
+            lines: 0,
+            cuts: 0,
+            graphics: [None, None],
+            downloaded: None,
+            logos: vec![],
+        }
+    }
+
+    fn into_image(mut self) -> GrayImage {
+        self.grow(self.y);
+        let height = (self.paper.len() as u32)
+            .checked_div(self.width)
+            .unwrap_or(0);
+        GrayImage::from_raw(self.width, height, self.paper).unwrap()
+    }
+
+    fn handle(&mut self, decoded: Decoded) {
+        match decoded {
+            Decoded::Text(text) => self.text(&text),
+            Decoded::Unknown(_) => {}
+            Decoded::Command(cmd) => self.command(cmd),
+        }
+    }
+
+    fn command(&mut self, cmd: Command) {
+        match cmd {
+            Command::Init => {
+                self.style = Style::default();
+                self.unicode = false;
+            }
+            Command::Underline(state) => self.style.underline = state,
+            Command::Emphasize(state) | Command::DoubleStrike(state) => {
+                self.style.emphasize = state
+            }
+            Command::Reverse(state) => self.style.reverse = state,
+            Command::PrintColor(color) => {
+                self.style.ink = match color {
+                    Color::Black => 0,
+                    Color::Red => 128,
+                }
+            }
+            Command::Justification(align) => self.style.align = align,
+            Command::TextSize { width, height } => {
+                self.style.width = width;
+                self.style.height = height;
+            }
+            Command::DoubleWidth(state) => self.style.width = if state { 2 } else { 1 },
+            Command::DoubleHeight(state) => self.style.height = if state { 2 } else { 1 },
+            Command::LegacyPrintMode {
+                double_width,
+                double_height,
+            } => {
+                self.style.width = if double_width { 2 } else { 1 };
+                self.style.height = if double_height { 2 } else { 1 };
+            }
+            Command::DefaultLineSpacing => self.style.line_spacing = DEFAULT_LINE_SPACING,
+            Command::LineSpacing(spacing) => self.style.line_spacing = spacing as u32,
+            Command::SetTabStops(stops) => self.style.tab_stops = stops,
+            Command::HorizontalTab => self.tab(),
+            Command::CharacterSet(set) => self.unicode = set == CharacterSet::Unicode,
+            Command::Feed(lines) => {
+                self.print_line();
+                self.lines += lines as u32;
+                self.y += lines as u32 * self.style.line_spacing;
+            }
+            Command::FeedUnits(units) => {
+                self.print_line();
+                self.y += units as u32;
+            }
+            Command::ReverseFeed(lines) => {
+                self.print_line();
+                self.y = self
+                    .y
+                    .saturating_sub(lines as u32 * self.style.line_spacing);
+            }
+            Command::Cut | Command::LegacyPartialCut => self.cut(0),
+            Command::CutWith(_, feed) => self.cut(feed.unwrap_or(0) as u32),
+            Command::Image(img, scale) => {
+                self.print_line();
+                self.print_image(&scaled(&img, scale));
+            }
+            Command::RasterBits {
+                width_bytes,
+                height,
+                data,
+            } => {
+                self.print_line();
+                self.print_image(&raster_bits_image(width_bytes, height, &data));
+            }
+            Command::StoreGraphics { color, image } => {
+                self.graphics[color as usize] = Some(image);
+            }
+            Command::PrintGraphics => {
+                self.print_line();
+                if let Some(img) = two_color_graphics(&self.graphics) {
+                    self.print_image(&img);
+                }
+            }
+            Command::DefineNvLogo { key, image } => {
+                self.logos.retain(|(k, _)| *k != key);
+                self.logos.push((key, image));
+            }
+            Command::PrintNvLogo { key, scale } => {
+                self.print_line();
+                if let Some((_, img)) = self.logos.iter().find(|(k, _)| *k == key) {
+                    let img = scaled(img, scale);
+                    self.print_image(&img);
+                }
+            }
+            Command::DeleteNvLogo(key) => self.logos.retain(|(k, _)| *k != key),
+            Command::DeleteAllNvLogos => self.logos.clear(),
+            Command::DefineDownloadedImage(img) => self.downloaded = Some(img),
+            Command::PrintDownloadedImage(scale) => {
+                if let Some(img) = &self.downloaded {
+                    let img = scaled(img, scale);
+                    self.line.push(img);
+                }
+            }
+            Command::ColumnBand(mode, img) => {
+                // Single density bands are printed at half the horizontal
+                // density, and 8 dot bands at a third of the vertical.
+                let sx = match mode {
+                    ColumnImageMode::EightDotSingle | ColumnImageMode::TwentyFourDotSingle => 2,
+                    _ => 1,
+                };
+                let sy = 3 / mode.column_bytes();
+                let img = GrayImage::from_fn(img.width() * sx, img.height() * sy, |x, y| {
+                    *img.get_pixel(x / sx, y / sy)
+                });
+                self.line.push(img);
+            }
+            Command::ColumnImage(..) => {
+                // Encoded as the commands it's built from, so it never
+                // comes out of the decoder.
+            }
+            Command::Barcode(barcode) => {
+                self.print_line();
+                self.print_image(&barcode_image(&barcode));
+            }
+            Command::QrCode(qr) => {
+                self.print_line();
+                self.print_image(&qr_placeholder(&qr));
+            }
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &[u8]) {
+        for &b in text {
+            match b {
+                b'\n' => {
+                    self.partial.clear();
+                    self.line_feed();
+                }
+                b'\r' => {}
+                _ if !self.unicode => self.char(if b.is_ascii() { b as char } else { '?' }),
+                _ => {
+                    self.partial.push(b);
+                    match std::str::from_utf8(&self.partial) {
+                        Ok(s) => {
+                            let c = s.chars().next().unwrap_or('?');
+                            self.partial.clear();
+                            self.char(c);
+                        }
+                        Err(e) if e.error_len().is_some() => {
+                            self.partial.clear();
+                            self.char('?');
+                        }
+                        Err(_) => {}
+                    }
+                }
+            }
+        }
+    }
+
+    fn char(&mut self, c: char) {
+        let style = &self.style;
+        let (sx, sy) = (style.width as u32, style.height as u32);
+        let (ink, paper) = if style.reverse {
+            (WHITE, style.ink)
+        } else {
+            (style.ink, WHITE)
+        };
+
+        let mut cell = GrayImage::from_pixel(CHAR_WIDTH * sx, CHAR_HEIGHT * sy, Luma([paper]));
+        let glyph = font::glyph(c);
+        // Each font pixel is drawn 2 dots wide and 3 tall, to roughly
+        // match the 12x24 Font A. Emphasized text is a dot wider.
+        let dot_width = if style.emphasize { 3 } else { 2 };
+        for gx in 0..GLYPH_WIDTH {
+            for gy in 0..GLYPH_HEIGHT {
+                if !font::is_set(glyph, gx, gy) {
+                    continue;
+                }
+                for dx in 0..dot_width * sx {
+                    for dy in 0..3 * sy {
+                        let x = (1 + gx * 2) * sx + dx;
+                        let y = (1 + gy * 3) * sy + dy;
+                        if x < cell.width() {
+                            cell.put_pixel(x, y, Luma([ink]));
+                        }
+                    }
+                }
+            }
+        }
+        if style.underline {
+            for x in 0..cell.width() {
+                for y in 0..sy {
+                    cell.put_pixel(x, cell.height() - 1 - y, Luma([ink]));
+                }
+            }
+        }
+
+        self.push_piece(cell, 1);
+    }
+
+    fn tab(&mut self) {
+        let Some(stop) = self
+            .style
+            .tab_stops
+            .iter()
+            .map(|stop| *stop as u32)
+            .find(|stop| *stop > self.line_chars)
+        else {
+            return;
+        };
+        let chars = stop - self.line_chars;
+        let space = GrayImage::from_pixel(CHAR_WIDTH * chars, 1, Luma([WHITE]));
+        self.push_piece(space, chars);
+    }
+
+    /// Add a piece of text to the line, printing the line first if the
+    /// piece won't fit.
+    fn push_piece(&mut self, piece: GrayImage, chars: u32) {
+        let used: u32 = self.line.iter().map(|p| p.width()).sum();
+        if used + piece.width() > self.width && !self.line.is_empty() {
+            self.line_feed();
+        }
+        self.line.push(piece);
+        self.line_chars += chars;
+    }
+
+    /// Print the line, and advance the paper by at least the line
+    /// spacing.
+    fn line_feed(&mut self) {
+        let height = self.print_pieces();
+        self.lines += 1;
+        self.y += height.max(self.style.line_spacing);
+    }
+
+    /// Print any pending pieces of a line, as if a line feed were sent.
+    fn print_line(&mut self) {
+        if !self.line.is_empty() {
+            self.line_feed();
+        }
+    }
+
+    /// Draw the pieces of the line onto the paper, aligned to their
+    /// bottom edge, and return the height of the line.
+    fn print_pieces(&mut self) -> u32 {
+        let line = std::mem::take(&mut self.line);
+        self.line_chars = 0;
+
+        let width: u32 = line.iter().map(|p| p.width()).sum();
+        let height = line.iter().map(|p| p.height()).max().unwrap_or(0);
+        let mut x = self.offset(width);
+        for piece in &line {
+            self.draw(piece, x, self.y + height - piece.height());
+            x += piece.width();
+        }
+        height
+    }
+
+    fn print_image(&mut self, img: &GrayImage) {
+        let x = self.offset(img.width());
+        self.draw(img, x, self.y);
+        self.y += img.height();
+    }
+
+    /// Return the left edge of something `width` dots wide, following
+    /// the current justification.
+    fn offset(&self, width: u32) -> u32 {
+        let free = self.width.saturating_sub(width);
+        match self.style.align {
+            Alignment::Left => 0,
+            Alignment::Center => free / 2,
+            Alignment::Right => free,
+        }
+    }
+
+    fn cut(&mut self, feed: u32) {
+        self.print_line();
+        self.cuts += 1;
+        self.y += feed;
+        self.grow(self.y + 1);
+        let row = (self.y * self.width) as usize;
+        for (x, pixel) in self.paper[row..row + self.width as usize]
+            .iter_mut()
+            .enumerate()
+        {
+            if x % 8 < 4 {
+                *pixel = 0;
+            }
+        }
+        self.y += 1;
+    }
+
+    /// Make sure the paper is at least `height` dots long.
+    fn grow(&mut self, height: u32) {
+        let len = (height * self.width) as usize;
+        if self.paper.len() < len {
+            self.paper.resize(len, WHITE);
+        }
+    }
+
+    /// Draw an image onto the paper. Only dark pixels are drawn, so
+    /// overlapping pieces combine like ink. Anything past the edge of the
+    /// paper is dropped.
+    fn draw(&mut self, img: &GrayImage, x: u32, y: u32) {
+        self.grow(y + img.height());
+        for (ix, iy, pixel) in img.enumerate_pixels() {
+            if x + ix >= self.width {
+                continue;
+            }
+            let i = ((y + iy) * self.width + x + ix) as usize;
+            self.paper[i] = self.paper[i].min(pixel.0[0]);
+        }
+    }
+}
+
+/// Combine the stored black and red graphics buffers, drawing red in
+/// gray.
+pub(crate) fn two_color_graphics(graphics: &[Option<GrayImage>; 2]) -> Option<GrayImage> {
+    let [black, red] = graphics;
+    let (width, height) = match (black, red) {
+        (Some(img), _) | (None, Some(img)) => img.dimensions(),
+        (None, None) => return None,
+    };
+    let dark = |img: &Option<GrayImage>, x, y| {
+        img.as_ref()
+            .and_then(|img| img.get_pixel_checked(x, y))
+            .is_some_and(|p| p.0[0] < 128)
+    };
+    Some(GrayImage::from_fn(width, height, |x, y| {
+        Luma([if dark(black, x, y) {
+            0
+        } else if dark(red, x, y) {
+            128
+        } else {
+            WHITE
+        }])
+    }))
+}
+
+/// Unpack pre-packed raster data, 8 pixels per byte, into an image.
+pub(crate) fn raster_bits_image(width_bytes: u16, height: u16, data: &[u8]) -> GrayImage {
+    GrayImage::from_fn(width_bytes as u32 * 8, height as u32, |x, y| {
+        let byte = data[(y * width_bytes as u32 + x / 8) as usize];
+        Luma([if byte & (0x80 >> (x % 8)) != 0 {
+            0
+        } else {
+            WHITE
+        }])
+    })
+}
+
+/// Scale an image the way the printer would.
+pub(crate) fn scaled(img: &GrayImage, scale: ImageScale) -> GrayImage {
+    let (sx, sy) = scale.factors();
+    let (sx, sy) = (sx as u32, sy as u32);
+    GrayImage::from_fn(img.width() * sx, img.height() * sy, |x, y| {
+        *img.get_pixel(x / sx, y / sy)
+    })
+}
+
+/// Left-hand (odd parity) UPC-A digit patterns, one bit per module, most
+/// significant bit first. Right-hand digits are the complement.
+const UPC_LEFT: [u8; 10] = [
+    0b0001101, 0b0011001, 0b0010011, 0b0111101, 0b0100011, 0b0110001, 0b0101111, 0b0111011,
+    0b0110111, 0b0001011,
+];
+
+/// Draw the bars of a barcode, without any human readable text.
+pub(crate) fn barcode_image(barcode: &Barcode) -> GrayImage {
+    let mut modules: Vec<bool> = vec![];
+    let mut push = |bits: u8, count: u32| {
+        for i in (0..count).rev() {
+            modules.push(bits & (1 << i) != 0);
+        }
+    };
+
+    match barcode.system() {
+        BarcodeSystem::UpcA => {
+            let digits: Vec<usize> = barcode.data().iter().map(|d| (d - b'0') as usize).collect();
+            push(0b101, 3);
+            for digit in &digits[..6] {
+                push(UPC_LEFT[*digit], 7);
+            }
+            push(0b01010, 5);
+            for digit in &digits[6..] {
+                push(!UPC_LEFT[*digit] & 0x7f, 7);
+            }
+            push(0b101, 3);
+        }
+    }
+
+    GrayImage::from_fn(
+        modules.len() as u32 * BARCODE_MODULE_WIDTH,
+        BARCODE_HEIGHT,
+        |x, _| {
+            Luma([if modules[(x / BARCODE_MODULE_WIDTH) as usize] {
+                0
+            } else {
+                WHITE
+            }])
+        },
+    )
+}
+
+/// Number of bytes which fit in QR Code versions 1 through 10, at the
+/// lowest error correction level.
+const QR_CAPACITY: [usize; 10] = [17, 32, 53, 78, 106, 134, 154, 192, 230, 271];
+
+/// Draw a placeholder of about the size of the QR Code, with finder
+/// patterns in three corners.
+pub(crate) fn qr_placeholder(qr: &QrCode) -> GrayImage {
+    let modules = match qr.model {
+        QrModel::Micro => 17,
+        QrModel::Model1 | QrModel::Model2 => {
+            let version = QR_CAPACITY
+                .iter()
+                .position(|capacity| *capacity >= qr.data.len())
+                .unwrap_or_else(|| 9 + (qr.data.len() - 271) / 30)
+                + 1;
+            17 + 4 * version.min(40) as u32
+        }
+    };
+
+    let finder = |x: u32, y: u32| {
+        let ring = x.abs_diff(3).max(y.abs_diff(3));
+        ring != 2
+    };
+    let size = modules * qr.module_size as u32;
+    GrayImage::from_fn(size, size, |x, y| {
+        let (mx, my) = (x / qr.module_size as u32, y / qr.module_size as u32);
+        let dark = match (mx, my) {
+            (0..=6, 0..=6) => finder(mx, my),
+            (0..=6, _) if my >= modules - 7 && qr.model != QrModel::Micro => {
+                finder(mx, my - (modules - 7))
+            }
+            (_, 0..=6) if mx >= modules - 7 && qr.model != QrModel::Micro => {
+                finder(mx - (modules - 7), my)
+            }
+            _ if mx < 8 && my < 8 => false,
+            _ => (mx * 7 + my * 3) % 5 < 2,
+        };
+        Luma([if dark { 0 } else { WHITE }])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CutMode;
+
+    fn render(bytes: &[u8]) -> GrayImage {
+        let mut emulator = Emulator::new(Model::T20II);
+        emulator.write_all(bytes).unwrap();
+        emulator.image()
+    }
+
+    fn commands(cmds: &[Command]) -> Vec<u8> {
+        cmds.iter().flat_map(|c| c.as_bytes().unwrap()).collect()
+    }
+
+    /// Return the left-most and right-most dark columns of the image.
+    fn ink_columns(img: &GrayImage) -> Option<(u32, u32)> {
+        let dark: Vec<u32> = (0..img.width())
+            .filter(|x| (0..img.height()).any(|y| img.get_pixel(*x, y).0[0] < 128))
+            .collect();
+        Some((*dark.first()?, *dark.last()?))
+    }
+
+    #[test]
+    fn emulator_text() {
+        let img = render(b"HI\n");
+        assert_eq!((576, DEFAULT_LINE_SPACING), img.dimensions());
+        let (left, right) = ink_columns(&img).unwrap();
+        assert!(left < CHAR_WIDTH);
+        assert!((CHAR_WIDTH..2 * CHAR_WIDTH).contains(&right));
+    }
+
+    #[test]
+    fn emulator_pending_line() {
+        assert_eq!(render(b"HI\n"), render(b"HI"));
+    }
+
+    #[test]
+    fn emulator_justification() {
+        let bytes = [
+            commands(&[Command::Justification(Alignment::Right)]),
+            b"HI\n".to_vec(),
+        ]
+        .concat();
+        let (_, right) = ink_columns(&render(&bytes)).unwrap();
+        assert!(right > 576 - CHAR_WIDTH);
+    }
+
+    #[test]
+    fn emulator_text_size() {
+        let bytes = [
+            commands(&[Command::TextSize {
+                width: 2,
+                height: 3,
+            }]),
+            b"H\n".to_vec(),
+        ]
+        .concat();
+        let img = render(&bytes);
+        assert_eq!(CHAR_HEIGHT * 3, img.height());
+        let (_, right) = ink_columns(&img).unwrap();
+        assert!(right >= CHAR_WIDTH);
+    }
+
+    #[test]
+    fn emulator_wraps() {
+        let img = render(&[b'A'; 49]);
+        assert_eq!(2 * DEFAULT_LINE_SPACING, img.height());
+    }
+
+    #[test]
+    fn emulator_feed() {
+        let bytes = commands(&[Command::LineSpacing(10), Command::Feed(3)]);
+        assert_eq!(30, render(&bytes).height());
+    }
+
+    #[test]
+    fn emulator_image() {
+        let img = GrayImage::from_pixel(16, 4, Luma([0]));
+        let bytes = commands(&[
+            Command::Justification(Alignment::Center),
+            Command::Image(img, ImageScale::DoubleHeight),
+        ]);
+        let receipt = render(&bytes);
+        assert_eq!(8, receipt.height());
+        assert_eq!(Some((280, 295)), ink_columns(&receipt));
+    }
+
+    #[test]
+    fn emulator_column_image() {
+        let img = GrayImage::from_pixel(10, 30, Luma([0]));
+        let bytes = commands(&[Command::ColumnImage(
+            ColumnImageMode::TwentyFourDotDouble,
+            img,
+        )]);
+        let receipt = render(&bytes);
+        assert_eq!(48, receipt.height());
+        assert_eq!(Some((0, 9)), ink_columns(&receipt));
+    }
+
+    #[test]
+    fn emulator_column_image_8_dot() {
+        let img = GrayImage::from_pixel(10, 16, Luma([0]));
+        let bytes = commands(&[Command::ColumnImage(ColumnImageMode::EightDotSingle, img)]);
+        let receipt = render(&bytes);
+        assert_eq!(48, receipt.height());
+        assert_eq!(Some((0, 19)), ink_columns(&receipt));
+        assert!((0..48).all(|y| receipt.get_pixel(0, y).0 == [0]));
+    }
+
+    #[test]
+    fn emulator_barcode() {
+        let barcode = Barcode::new_upca("03600029145").unwrap();
+        let receipt = render(&commands(&[Command::Barcode(barcode)]));
+        assert_eq!(BARCODE_HEIGHT, receipt.height());
+        assert_eq!(
+            Some((0, 95 * BARCODE_MODULE_WIDTH - 1)),
+            ink_columns(&receipt)
+        );
+    }
+
+    #[test]
+    fn emulator_qr() {
+        let receipt = render(&commands(&[Command::QrCode(QrCode::new(b"hello"))]));
+        assert_eq!(21 * 3, receipt.height());
+    }
+
+    #[test]
+    fn emulator_cut() {
+        let receipt = render(&commands(&[Command::CutWith(CutMode::Full, Some(20))]));
+        assert_eq!(21, receipt.height());
+        assert_eq!(0, receipt.get_pixel(0, 20).0[0]);
+    }
+
+    #[test]
+    fn emulator_streaming() {
+        let bytes = [
+            b"Hello\n".to_vec(),
+            commands(&[
+                Command::Emphasize(true),
+                Command::Image(GrayImage::from_pixel(8, 8, Luma([0])), ImageScale::Normal),
+            ]),
+            b"World".to_vec(),
+        ]
+        .concat();
+
+        let mut emulator = Emulator::new(Model::T20II);
+        for byte in &bytes {
+            emulator.write_all(&[*byte]).unwrap();
+        }
+        assert_eq!(render(&bytes), emulator.image());
+    }
+
+    #[test]
+    fn emulator_writer() {
+        let emulator = Emulator::new(Model::T20II);
+        let mut pos = super::super::Writer::open(Model::T20II, Box::new(emulator.clone())).unwrap();
+        pos.feed(2).unwrap();
+        pos.print_image(GrayImage::from_pixel(8, 8, Luma([0])))
+            .unwrap();
+        assert_eq!(2 * DEFAULT_LINE_SPACING + 8, emulator.image().height());
+    }
+}
+
+// vim: foldmethod=marker
@@ -20,6 +20,9 @@
 
 use super::Error;
 
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
 /// INTERNAL only type to represent an image buffer. The API is only
 /// the standard crate Image type(s).
 ///
@@ -35,29 +38,35 @@ impl TryFrom<image::ImageBuffer<image::Luma<u8>, Vec<u8>>> for ImageBuffer {
     type Error = Error;
 
     fn try_from(img: image::ImageBuffer<image::Luma<u8>, Vec<u8>>) -> Result<Self, Error> {
+        (&img).try_into()
+    }
+}
+
+impl TryFrom<&image::ImageBuffer<image::Luma<u8>, Vec<u8>>> for ImageBuffer {
+    type Error = Error;
+
+    fn try_from(img: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>) -> Result<Self, Error> {
         let (mut width, height) = img.dimensions();
 
         if width % 8 != 0 {
             width += 8 - (width % 8);
         }
 
-        let mut pixels = vec![];
+        let width = width / 8;
+        let mut pixels = vec![0; width as usize * height as usize];
 
-        for y in 0..height {
-            for x in (0..width).step_by(8) {
-                let mut block: u8 = 0;
-                for bit in 0..8 {
-                    if let Some(pixel) = img.get_pixel_checked(x + bit, y) {
-                        if pixel.0[0] <= 128 {
-                            block |= 1 << (7 - bit)
-                        }
-                    }
-                }
-                pixels.push(block);
-            }
+        if !pixels.is_empty() {
+            let (out, raw) = (width as usize, img.width() as usize);
+
+            // rows are packed independently, so they can be packed in
+            // parallel when the rayon feature is enabled.
+            #[cfg(not(feature = "rayon"))]
+            let rows = pixels.chunks_mut(out).zip(img.as_raw().chunks(raw));
+            #[cfg(feature = "rayon")]
+            let rows = pixels.par_chunks_mut(out).zip(img.as_raw().par_chunks(raw));
+            rows.for_each(|(out, row)| pack_row(row, out));
         }
 
-        let width = width / 8;
         let width: u16 = width.try_into().map_err(|_| Error::ImageTooLarge)?;
         let height: u16 = height.try_into().map_err(|_| Error::ImageTooLarge)?;
 
@@ -69,4 +78,123 @@ impl TryFrom<image::ImageBuffer<image::Luma<u8>, Vec<u8>>> for ImageBuffer {
     }
 }
 
+/// Tallest raster image sent in a single `GS v 0` command. Taller images
+/// are sent as a series of bands, so the printer doesn't need to buffer
+/// the whole image before it starts printing.
+pub(crate) const IMAGE_BAND_HEIGHT: u32 = 256;
+
+/// Split `img` into bands of no more than `band_height` rows, from top to
+/// bottom.
+pub(crate) fn bands(
+    img: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    band_height: u32,
+) -> Vec<image::ImageBuffer<image::Luma<u8>, Vec<u8>>> {
+    let (width, height) = img.dimensions();
+    (0..height)
+        .step_by(band_height as usize)
+        .map(|y| {
+            image::imageops::crop_imm(img, 0, y, width, band_height.min(height - y)).to_image()
+        })
+        .collect()
+}
+
+/// Scale `img` down to no more than `max_width` pixels wide, keeping the
+/// aspect ratio. Images which already fit are returned untouched.
+pub(crate) fn fit_width(
+    img: image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    max_width: u32,
+) -> image::ImageBuffer<image::Luma<u8>, Vec<u8>> {
+    let (width, height) = img.dimensions();
+    if width <= max_width {
+        return img;
+    }
+
+    let height = ((height as u64 * max_width as u64) / width as u64).max(1) as u32;
+    image::imageops::resize(
+        &img,
+        max_width,
+        height,
+        image::imageops::FilterType::Triangle,
+    )
+}
+
+/// Pack a single row of pixels into `out`, 8 pixels per byte with the
+/// leftmost pixel in the most significant bit. Dark pixels are set, and
+/// pixels past the end of the row are left white.
+fn pack_row(row: &[u8], out: &mut [u8]) {
+    for (x, pixel) in row.iter().enumerate() {
+        if *pixel <= 128 {
+            out[x / 8] |= 1 << (7 - x % 8);
+        }
+    }
+}
+
+/// Pack a region of an image into column format, where each byte
+/// represents 8 vertically stacked pixels (most significant bit on top),
+/// and each column is `height_bytes` bytes tall. Columns are emitted left
+/// to right, starting at row `y`. Pixels outside of the image are white.
+pub(crate) fn pack_columns(
+    img: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+    y: u32,
+    width: u32,
+    height_bytes: u32,
+) -> Vec<u8> {
+    let mut pixels = vec![];
+
+    for x in 0..width {
+        for byte in 0..height_bytes {
+            let mut block: u8 = 0;
+            for bit in 0..8 {
+                if let Some(pixel) = img.get_pixel_checked(x, y + byte * 8 + bit) {
+                    if pixel.0[0] <= 128 {
+                        block |= 1 << (7 - bit)
+                    }
+                }
+            }
+            pixels.push(block);
+        }
+    }
+
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bands_reassemble() {
+        let img = image::GrayImage::from_fn(20, 600, |x, y| {
+            image::Luma([if (x * 7 + y * 3) % 5 == 0 { 0 } else { 255 }])
+        });
+        let whole: ImageBuffer = (&img).try_into().unwrap();
+
+        let bands = bands(&img, IMAGE_BAND_HEIGHT);
+        assert_eq!(
+            vec![256, 256, 88],
+            bands.iter().map(|band| band.height()).collect::<Vec<_>>()
+        );
+
+        let mut pixels = vec![];
+        for band in bands {
+            let band: ImageBuffer = band.try_into().unwrap();
+            assert_eq!(whole.width, band.width);
+            pixels.extend(band.pixels);
+        }
+        assert_eq!(whole.pixels, pixels);
+    }
+
+    #[test]
+    fn fit_width_scales_down() {
+        let img = image::GrayImage::new(1024, 300);
+        assert_eq!((512, 150), fit_width(img, 512).dimensions());
+    }
+
+    #[test]
+    fn fit_width_leaves_small_images() {
+        let img = image::GrayImage::new(100, 300);
+        assert_eq!((100, 300), fit_width(img, 512).dimensions());
+    }
+}
+
 // vim: foldmethod=marker
@@ -0,0 +1,416 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Render a command stream as HTML, with the text laid out in a
+//! monospace column and images embedded as PNG data URLs.
+
+use super::emulator::{
+    barcode_image, qr_placeholder, raster_bits_image, scaled, two_color_graphics,
+};
+use super::preview::encode_png;
+use super::{Alignment, CharacterSet, Color, Command, Decoded, Decoder, Error, Model};
+use std::fmt::Write;
+
+/// Stylesheet for the classes used in the rendered HTML, scoped to the
+/// receipt.
+const STYLE: &str = "<style>\
+.epson-receipt{font-family:monospace;white-space:pre;line-height:1.25;}\
+.epson-receipt .line{min-height:1.25em;}\
+.epson-receipt .bold{font-weight:bold;}\
+.epson-receipt .underline{text-decoration:underline;}\
+.epson-receipt .inverse{background:black;color:white;}\
+.epson-receipt .red{color:#c00;}\
+.epson-receipt .sized{display:inline-block;transform-origin:left bottom;}\
+.epson-receipt .cut{border:none;border-top:1px dashed gray;}\
+.epson-receipt img{image-rendering:pixelated;vertical-align:bottom;}\
+</style>";
+
+/// Print modes which are turned into a span around the text.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Style {
+    bold: bool,
+    underline: bool,
+    inverse: bool,
+    red: bool,
+    width: u8,
+    height: u8,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            bold: false,
+            underline: false,
+            inverse: false,
+            red: false,
+            width: 1,
+            height: 1,
+        }
+    }
+}
+
+impl Style {
+    /// Return the opening tag for text in this style, if it isn't the
+    /// default.
+    fn open(&self) -> Option<String> {
+        if *self == Style::default() {
+            return None;
+        }
+
+        let classes: Vec<&str> = [
+            (self.bold, "bold"),
+            (self.underline, "underline"),
+            (self.inverse, "inverse"),
+            (self.red, "red"),
+            (self.width != 1 || self.height != 1, "sized"),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, class)| *class)
+        .collect();
+
+        let mut tag = format!("<span class=\"{}\"", classes.join(" "));
+        if self.width != 1 || self.height != 1 {
+            let _ = write!(
+                tag,
+                " style=\"font-size:{}em;transform:scaleX({})\"",
+                self.height,
+                self.width as f32 / self.height as f32
+            );
+        }
+        tag.push('>');
+        Some(tag)
+    }
+}
+
+struct Html {
+    model: Model,
+    out: String,
+    line: String,
+    style: Style,
+    span: bool,
+    align: Alignment,
+    unicode: bool,
+    partial: Vec<u8>,
+    graphics: [Option<image::GrayImage>; 2],
+}
+
+/// Render raw ESC/POS bytes into an HTML fragment.
+pub(crate) fn render(bytes: &[u8], model: Model) -> Result<String, Error> {
+    let mut html = Html {
+        model,
+        out: format!(
+            "{}<div class=\"epson-receipt\" style=\"width:{}ch\">\n",
+            STYLE,
+            model.get_columns()
+        ),
+        line: String::new(),
+        style: Style::default(),
+        span: false,
+        align: Alignment::Left,
+        unicode: false,
+        partial: vec![],
+        graphics: [None, None],
+    };
+
+    let mut decoder = Decoder::new();
+    decoder.push(bytes);
+    for decoded in decoder.finish() {
+        html.handle(decoded)?;
+    }
+    html.end_line();
+    html.out.push_str("</div>\n");
+    Ok(html.out)
+}
+
+impl Html {
+    fn handle(&mut self, decoded: Decoded) -> Result<(), Error> {
+        match decoded {
+            Decoded::Text(text) => self.text(&text),
+            Decoded::Unknown(_) => {}
+            Decoded::Command(cmd) => self.command(cmd)?,
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, cmd: Command) -> Result<(), Error> {
+        let mut style = self.style;
+        match cmd {
+            Command::Init => {
+                style = Style::default();
+                self.align = Alignment::Left;
+                self.unicode = false;
+            }
+            Command::Emphasize(state) | Command::DoubleStrike(state) => style.bold = state,
+            Command::Underline(state) => style.underline = state,
+            Command::Reverse(state) => style.inverse = state,
+            Command::PrintColor(color) => style.red = color == Color::Red,
+            Command::TextSize { width, height } => {
+                style.width = width;
+                style.height = height;
+            }
+            Command::DoubleWidth(state) => style.width = if state { 2 } else { 1 },
+            Command::DoubleHeight(state) => style.height = if state { 2 } else { 1 },
+            Command::LegacyPrintMode {
+                double_width,
+                double_height,
+            } => {
+                style.width = if double_width { 2 } else { 1 };
+                style.height = if double_height { 2 } else { 1 };
+            }
+            Command::Justification(align) => self.align = align,
+            Command::CharacterSet(set) => self.unicode = set == CharacterSet::Unicode,
+            Command::HorizontalTab => self.line.push('\t'),
+            Command::Feed(lines) => {
+                self.end_line();
+                for _ in 0..lines {
+                    self.out.push_str("<div class=\"line\"></div>\n");
+                }
+            }
+            Command::FeedUnits(units) => {
+                self.end_line();
+                let _ = writeln!(
+                    self.out,
+                    "<div style=\"height:{:.2}mm\"></div>",
+                    self.model.dots_to_mm(units as u32)
+                );
+            }
+            Command::Cut | Command::LegacyPartialCut | Command::CutWith(..) => {
+                self.end_line();
+                self.out.push_str("<hr class=\"cut\">\n");
+            }
+            Command::Image(img, scale) => self.image(&scaled(&img, scale), "")?,
+            Command::RasterBits {
+                width_bytes,
+                height,
+                data,
+            } => self.image(&raster_bits_image(width_bytes, height, &data), "")?,
+            Command::StoreGraphics { color, image } => self.graphics[color as usize] = Some(image),
+            Command::PrintGraphics => {
+                if let Some(img) = two_color_graphics(&self.graphics) {
+                    self.image(&img, "")?;
+                }
+            }
+            Command::ColumnBand(_, img) => {
+                self.close_span();
+                let _ = write!(self.line, "{}", img_tag(&img, self.model, "")?);
+            }
+            Command::Barcode(barcode) => {
+                let alt = String::from_utf8_lossy(barcode.data()).into_owned();
+                self.image(&barcode_image(&barcode), &alt)?;
+            }
+            Command::QrCode(qr) => {
+                let alt = format!("QR Code: {}", String::from_utf8_lossy(&qr.data));
+                self.image(&qr_placeholder(&qr), &alt)?;
+            }
+            _ => {}
+        }
+
+        if style != self.style {
+            self.close_span();
+            self.style = style;
+        }
+        Ok(())
+    }
+
+    fn text(&mut self, text: &[u8]) {
+        for &b in text {
+            match b {
+                b'\n' => {
+                    self.partial.clear();
+                    self.end_line();
+                }
+                b'\r' => {}
+                _ if !self.unicode => self.char(if b.is_ascii() { b as char } else { '?' }),
+                _ => {
+                    self.partial.push(b);
+                    match std::str::from_utf8(&self.partial) {
+                        Ok(s) => {
+                            let c = s.chars().next().unwrap_or('?');
+                            self.partial.clear();
+                            self.char(c);
+                        }
+                        Err(e) if e.error_len().is_some() => {
+                            self.partial.clear();
+                            self.char('?');
+                        }
+                        Err(_) => {}
+                    }
+                }
+            }
+        }
+    }
+
+    fn char(&mut self, c: char) {
+        if !self.span {
+            if let Some(open) = self.style.open() {
+                self.line.push_str(&open);
+                self.span = true;
+            }
+        }
+        match c {
+            '&' => self.line.push_str("&amp;"),
+            '<' => self.line.push_str("&lt;"),
+            '>' => self.line.push_str("&gt;"),
+            '"' => self.line.push_str("&quot;"),
+            c => self.line.push(c),
+        }
+    }
+
+    fn close_span(&mut self) {
+        if self.span {
+            self.line.push_str("</span>");
+            self.span = false;
+        }
+    }
+
+    /// Write out the pending line, if there is one.
+    fn end_line(&mut self) {
+        self.close_span();
+        let line = std::mem::take(&mut self.line);
+        let _ = writeln!(
+            self.out,
+            "<div class=\"line\"{}>{}</div>",
+            self.align_attr(),
+            line
+        );
+    }
+
+    /// Write out an image on a line of its own.
+    fn image(&mut self, img: &image::GrayImage, alt: &str) -> Result<(), Error> {
+        if !self.line.is_empty() {
+            self.end_line();
+        }
+        let tag = img_tag(img, self.model, alt)?;
+        let _ = writeln!(self.out, "<div{}>{}</div>", self.align_attr(), tag);
+        Ok(())
+    }
+
+    fn align_attr(&self) -> &'static str {
+        match self.align {
+            Alignment::Left => "",
+            Alignment::Center => " style=\"text-align:center\"",
+            Alignment::Right => " style=\"text-align:right\"",
+        }
+    }
+}
+
+/// Return an `img` tag embedding the image, sized to print at the
+/// model's resolution.
+fn img_tag(img: &image::GrayImage, model: Model, alt: &str) -> Result<String, Error> {
+    Ok(format!(
+        "<img alt=\"{}\" style=\"width:{:.2}mm\" src=\"data:image/png;base64,{}\">",
+        alt.replace('&', "&amp;")
+            .replace('"', "&quot;")
+            .replace('<', "&lt;"),
+        model.dots_to_mm(img.width()),
+        base64(&encode_png(img)?)
+    ))
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(cmds: &[Command]) -> Vec<u8> {
+        cmds.iter().flat_map(|c| c.as_bytes().unwrap()).collect()
+    }
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!("", base64(b""));
+        assert_eq!("Zg==", base64(b"f"));
+        assert_eq!("Zm8=", base64(b"fo"));
+        assert_eq!("Zm9v", base64(b"foo"));
+        assert_eq!("Zm9vYmFy", base64(b"foobar"));
+    }
+
+    #[test]
+    fn html_styles() {
+        let bytes = [
+            b"A&B ".to_vec(),
+            commands(&[Command::Emphasize(true), Command::Underline(true)]),
+            b"bold".to_vec(),
+            commands(&[Command::Emphasize(false), Command::Underline(false)]),
+            b" plain\n".to_vec(),
+        ]
+        .concat();
+        let html = render(&bytes, Model::T20II).unwrap();
+        assert!(html.contains(
+            "<div class=\"line\">A&amp;B <span class=\"bold underline\">bold</span> plain</div>"
+        ));
+    }
+
+    #[test]
+    fn html_alignment() {
+        let bytes = [
+            commands(&[Command::Justification(Alignment::Center)]),
+            b"hi\n".to_vec(),
+        ]
+        .concat();
+        let html = render(&bytes, Model::T20II).unwrap();
+        assert!(html.contains("<div class=\"line\" style=\"text-align:center\">hi</div>"));
+    }
+
+    #[test]
+    fn html_size() {
+        let bytes = [
+            commands(&[Command::TextSize {
+                width: 2,
+                height: 2,
+            }]),
+            b"big\n".to_vec(),
+        ]
+        .concat();
+        let html = render(&bytes, Model::T20II).unwrap();
+        assert!(html.contains(
+            "<span class=\"sized\" style=\"font-size:2em;transform:scaleX(1)\">big</span>"
+        ));
+    }
+
+    #[test]
+    fn html_barcode() {
+        let barcode = crate::Barcode::new_upca("03600029145").unwrap();
+        let html = render(&commands(&[Command::Barcode(barcode)]), Model::T20II).unwrap();
+        assert!(html.contains("<img alt=\"036000291452\" style=\"width:35.62mm\""));
+    }
+}
+
+// vim: foldmethod=marker
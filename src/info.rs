@@ -0,0 +1,265 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+use super::Error;
+
+/// Kinds of printer information which can be requested with `GS I`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum InfoKind {
+    /// Single byte model ID.
+    ModelId = 1,
+
+    /// Single byte type ID, describing installed options.
+    TypeId = 2,
+
+    /// Single byte firmware version ID.
+    VersionId = 3,
+
+    /// Firmware version, as text.
+    FirmwareVersion = 65,
+
+    /// Manufacturer name, as text.
+    MakerName = 66,
+
+    /// Model name, as text.
+    ModelName = 67,
+
+    /// Serial number, as text.
+    SerialNumber = 68,
+}
+
+impl InfoKind {
+    /// Return true if the printer responds to this request with a text
+    /// block, rather than a single byte.
+    pub fn is_text(&self) -> bool {
+        (*self as u8) >= 65
+    }
+}
+
+/// Options installed in the printer, as returned by [InfoKind::TypeId].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TypeId {
+    /// True if two byte (multibyte) character codes are supported.
+    pub multibyte: bool,
+
+    /// True if an autocutter is installed.
+    pub autocutter: bool,
+
+    /// True if a customer display (DM-D) is connected.
+    pub customer_display: bool,
+}
+
+/// Response to a printer information request.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PrinterInfo {
+    /// Response to [InfoKind::ModelId].
+    ModelId(u8),
+
+    /// Response to [InfoKind::TypeId].
+    TypeId(TypeId),
+
+    /// Response to [InfoKind::VersionId].
+    VersionId(u8),
+
+    /// Response to [InfoKind::FirmwareVersion].
+    FirmwareVersion(String),
+
+    /// Response to [InfoKind::MakerName].
+    MakerName(String),
+
+    /// Response to [InfoKind::ModelName].
+    ModelName(String),
+
+    /// Response to [InfoKind::SerialNumber].
+    SerialNumber(String),
+}
+
+impl PrinterInfo {
+    /// Parse the bytes returned by the printer in response to a printer
+    /// information request of the provided [InfoKind].
+    ///
+    /// Single byte responses must be exactly one byte. Text responses are
+    /// framed by a leading `0x5F` and a trailing NUL, both of which must be
+    /// included in `buf`.
+    pub fn parse(kind: InfoKind, buf: &[u8]) -> Result<Self, Error> {
+        if kind.is_text() {
+            let text = match buf {
+                [0x5f, text @ .., 0x00] => text,
+                _ => return Err(Error::InvalidStatus),
+            };
+            let text = String::from_utf8(text.to_vec()).map_err(|_| Error::InvalidStatus)?;
+
+            return Ok(match kind {
+                InfoKind::FirmwareVersion => PrinterInfo::FirmwareVersion(text),
+                InfoKind::MakerName => PrinterInfo::MakerName(text),
+                InfoKind::ModelName => PrinterInfo::ModelName(text),
+                _ => PrinterInfo::SerialNumber(text),
+            });
+        }
+
+        let byte = match buf {
+            [byte] => *byte,
+            _ => return Err(Error::InvalidStatus),
+        };
+
+        Ok(match kind {
+            InfoKind::ModelId => PrinterInfo::ModelId(byte),
+            InfoKind::TypeId => {
+                if byte & 0b1001_0000 != 0 {
+                    return Err(Error::InvalidStatus);
+                }
+                PrinterInfo::TypeId(TypeId {
+                    multibyte: byte & 0b001 != 0,
+                    autocutter: byte & 0b010 != 0,
+                    customer_display: byte & 0b100 != 0,
+                })
+            }
+            _ => PrinterInfo::VersionId(byte),
+        })
+    }
+}
+
+/// Who made the printer, what it is, and which firmware it runs, as
+/// returned by [crate::Printer::identity].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Identity {
+    /// Manufacturer name, such as "EPSON".
+    pub maker: String,
+
+    /// Model name, such as "TM-T20II".
+    pub model_name: String,
+
+    /// Firmware version.
+    pub firmware_version: String,
+
+    /// Serial number, if the printer reports one. Older models don't.
+    pub serial_number: Option<String>,
+}
+
+impl Identity {
+    /// Return the text from a text [PrinterInfo] response.
+    pub(crate) fn text(info: PrinterInfo) -> Result<String, Error> {
+        match info {
+            PrinterInfo::FirmwareVersion(text)
+            | PrinterInfo::MakerName(text)
+            | PrinterInfo::ModelName(text)
+            | PrinterInfo::SerialNumber(text) => Ok(text),
+            _ => Err(Error::InvalidStatus),
+        }
+    }
+}
+
+/// Maintenance counters kept by the printer, which can be read with
+/// `GS g 2` and reset with `GS g 0`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MaintenanceCounter {
+    /// Distance of paper fed, in lines.
+    PaperFeed,
+
+    /// Number of times the print head has been energized.
+    HeadEnergizing,
+
+    /// Number of cuts made by the autocutter.
+    Cuts,
+}
+
+impl MaintenanceCounter {
+    /// Return the counter's `GS g` identifier. Cumulative counters are
+    /// kept for the lifetime of the printer, and can't be reset.
+    pub(crate) fn id(&self, cumulative: bool) -> u16 {
+        let id = match self {
+            MaintenanceCounter::PaperFeed => 20,
+            MaintenanceCounter::HeadEnergizing => 21,
+            MaintenanceCounter::Cuts => 50,
+        };
+        if cumulative {
+            id + 128
+        } else {
+            id
+        }
+    }
+
+    /// Parse the printer's response to a maintenance counter request,
+    /// which is a decimal number framed by a leading `0x5F` and a trailing
+    /// NUL, both of which must be included in `buf`.
+    pub fn parse_value(buf: &[u8]) -> Result<u32, Error> {
+        let digits = match buf {
+            [0x5f, digits @ .., 0x00] => digits,
+            _ => return Err(Error::InvalidStatus),
+        };
+        std::str::from_utf8(digits)
+            .ok()
+            .and_then(|digits| digits.parse().ok())
+            .ok_or(Error::InvalidStatus)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_maintenance_counter() {
+        assert_eq!(Ok(12345), MaintenanceCounter::parse_value(b"_12345\0"));
+        assert_eq!(
+            Err(Error::InvalidStatus),
+            MaintenanceCounter::parse_value(b"_12a45\0")
+        );
+        assert_eq!(
+            Err(Error::InvalidStatus),
+            MaintenanceCounter::parse_value(b"12345")
+        );
+    }
+
+    #[test]
+    fn parse_single_byte_info() {
+        assert_eq!(
+            PrinterInfo::ModelId(0x20),
+            PrinterInfo::parse(InfoKind::ModelId, &[0x20]).unwrap()
+        );
+        assert_eq!(
+            PrinterInfo::TypeId(TypeId {
+                multibyte: false,
+                autocutter: true,
+                customer_display: false,
+            }),
+            PrinterInfo::parse(InfoKind::TypeId, &[0x02]).unwrap()
+        );
+        assert_eq!(
+            Err(Error::InvalidStatus),
+            PrinterInfo::parse(InfoKind::VersionId, &[0x01, 0x02])
+        );
+    }
+
+    #[test]
+    fn parse_text_info() {
+        assert_eq!(
+            PrinterInfo::MakerName("EPSON".to_owned()),
+            PrinterInfo::parse(InfoKind::MakerName, b"_EPSON\0").unwrap()
+        );
+        assert_eq!(
+            Err(Error::InvalidStatus),
+            PrinterInfo::parse(InfoKind::ModelName, b"TM-T20II\0")
+        );
+    }
+}
+
+// vim: foldmethod=marker
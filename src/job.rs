@@ -0,0 +1,215 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+use super::{decode, Command, Decoded, Error, Model, Receipt};
+use std::time::Duration;
+
+/// A complete print job, such as a receipt, built and checked ahead of
+/// time and sent to the printer in one go.
+///
+/// Each [Command] is checked against the [Model] as it's added, so an
+/// unsupported command fails while the job is being built, rather than
+/// half way through printing it. Once built, the job can be submitted to
+/// a [super::Writer] with [super::Writer::submit], kept around to be
+/// retried, or saved for later with [PrintJob::to_bytes].
+///
+/// ```rust
+/// # use epson::{testing::CaptureWriter, Command, Model, PrintJob, Writer};
+/// let mut job = PrintJob::new(Model::T20II);
+/// job.push(Command::Text(b"HACK THE PLANET\n".to_vec())).unwrap();
+/// job.push(Command::Cut).unwrap();
+///
+/// let capture = CaptureWriter::new();
+/// let mut pos = Writer::open(Model::T20II, Box::new(capture.clone())).unwrap();
+/// let result = pos.submit(&job).unwrap();
+/// assert_eq!(2, result.commands);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrintJob {
+    model: Model,
+    commands: Vec<Command>,
+    bytes: Vec<u8>,
+}
+
+/// What happened when a [PrintJob] was sent to the printer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JobResult {
+    /// Number of commands sent.
+    pub commands: usize,
+
+    /// Number of bytes sent.
+    pub bytes: usize,
+
+    /// How long it took to send the job.
+    pub elapsed: Duration,
+}
+
+impl PrintJob {
+    /// Start a new, empty, job for the provided model.
+    pub fn new(model: Model) -> Self {
+        Self {
+            model,
+            commands: vec![],
+            bytes: vec![],
+        }
+    }
+
+    /// Build a job from `commands`, checking each of them.
+    pub fn from_commands(
+        model: Model,
+        commands: impl IntoIterator<Item = Command>,
+    ) -> Result<Self, Error> {
+        let mut job = Self::new(model);
+        job.extend(commands)?;
+        Ok(job)
+    }
+
+    /// Build a job which prints `receipt`.
+    pub fn from_receipt(receipt: &Receipt) -> Result<Self, Error> {
+        Self::from_commands(receipt.model(), receipt.build()?)
+    }
+
+    /// Read back a job saved with [PrintJob::to_bytes]. Any bytes which
+    /// can't be decoded, or commands the model doesn't support, are
+    /// rejected.
+    pub fn from_bytes(model: Model, bytes: &[u8]) -> Result<Self, Error> {
+        let mut job = Self::new(model);
+        for decoded in decode(bytes) {
+            job.push(match decoded {
+                Decoded::Command(cmd) => cmd,
+                Decoded::Text(text) => Command::Text(text),
+                Decoded::Unknown(_) => return Err(Error::InvalidParameter),
+            })?;
+        }
+        Ok(job)
+    }
+
+    /// Add a command to the end of the job, if the model supports it.
+    /// Nothing is added if it doesn't.
+    pub fn push(&mut self, cmd: Command) -> Result<(), Error> {
+        self.model.check_command(&cmd)?;
+        self.bytes.extend(cmd.as_bytes()?);
+        self.commands.push(cmd);
+        Ok(())
+    }
+
+    /// Add each of `commands` to the end of the job. Commands before the
+    /// first unsupported one are kept.
+    pub fn extend(&mut self, commands: impl IntoIterator<Item = Command>) -> Result<(), Error> {
+        for cmd in commands {
+            self.push(cmd)?;
+        }
+        Ok(())
+    }
+
+    /// Return the model this job was built for.
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Return the commands in this job.
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    /// Return the job as ESC/POS bytes, exactly as it's sent to the
+    /// printer.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Return the job as ESC/POS bytes, to be saved and read back with
+    /// [PrintJob::from_bytes], or sent with [super::replay].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+
+    /// Return true if the job has no commands.
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::CaptureWriter, CutMode, Writer};
+
+    #[test]
+    fn unsupported() {
+        let mut job = PrintJob::new(Model::T20II);
+        assert_eq!(
+            Err(Error::Unsupported),
+            job.push(Command::PrintColor(crate::Color::Red))
+        );
+        assert!(job.is_empty());
+    }
+
+    #[test]
+    fn round_trip() {
+        let job = PrintJob::from_commands(
+            Model::T20II,
+            [
+                Command::Init,
+                Command::Emphasize(true),
+                Command::Text(b"HACK THE PLANET\n".to_vec()),
+                Command::Feed(2),
+                Command::CutWith(CutMode::Full, None),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            job,
+            PrintJob::from_bytes(Model::T20II, &job.to_bytes()).unwrap()
+        );
+    }
+
+    #[test]
+    fn from_bytes_unknown() {
+        assert_eq!(
+            Err(Error::InvalidParameter),
+            PrintJob::from_bytes(Model::T20II, &[0x1b, 0xff])
+        );
+    }
+
+    #[test]
+    fn submit() {
+        let receipt = Receipt::new(Model::T20II).header("Cafe").cut();
+        let job = PrintJob::from_receipt(&receipt).unwrap();
+
+        let capture = CaptureWriter::new();
+        let mut pos = Writer::open(Model::T20II, Box::new(capture.clone())).unwrap();
+        capture.clear();
+
+        let result = pos.submit(&job).unwrap();
+        assert_eq!(job.commands().len(), result.commands);
+        assert_eq!(job.as_bytes(), capture.bytes());
+        assert!(!pos.style().bold);
+    }
+
+    #[test]
+    fn submit_wrong_model() {
+        let job = PrintJob::new(Model::T88VI);
+        let mut pos = Writer::open(Model::T20II, Box::new(CaptureWriter::new())).unwrap();
+        assert!(pos.submit(&job).is_err());
+    }
+}
+
+// vim: foldmethod=marker
@@ -42,6 +42,12 @@
 //! | ----- | ------------------------ | ------------------------------- |
 //! | T20II | [models::Model::T20II]   | Epson TM-T20II Thermal Printer  |
 //! | T30II | [models::Model::T30II]   | Epson TM-T30II Thermal Printer  |
+//! | T88VI | [models::Model::T88VI]   | Epson TM-T88VI Thermal Printer  |
+//! | T88VII | [models::Model::T88VII] | Epson TM-T88VII Thermal Printer |
+//! | L90   | [models::Model::L90]     | Epson TM-L90 Label Printer      |
+//! | T82   | [models::Model::T82]     | Epson TM-T82 Thermal Printer    |
+//! | T82II | [models::Model::T82II]   | Epson TM-T82II Thermal Printer  |
+//! | T82III | [models::Model::T82III] | Epson TM-T82III Thermal Printer |
 //!
 //! # Writing to a `std::io::Write`
 //!
@@ -49,13 +55,15 @@
 //! but maybe something like a Serial device?), you can use a [Writer] to
 //! handle writing commands to the printer.
 //!
-//! ```rust
+//! ```rust,no_run
+//! # use epson::Model;
+//! # use std::net::TcpStream;
 //! // IP address of the printer
 //! let stream = TcpStream::connect("192.168.0.12:9100").unwrap();
 //! let mut pos = epson::Writer::open(Model::T20II, Box::new(stream)).unwrap();
 //!
 //! pos.speed(5).unwrap();
-//! pos.write_all(b"HACK THE PLANET\n").unwrap();
+//! pos.print_line("HACK THE PLANET").unwrap();
 //! pos.feed(5).unwrap();
 //! pos.cut().unwrap();
 //! ```
@@ -68,31 +76,126 @@
 //!
 //! This requires the `tokio` feature.
 //!
-//! ```rust
+//! ```rust,ignore
 //! let stream = TcpStream::connect("192.168.0.12:9100").await.unwrap();
 //! let mut pos = epson::AsyncWriter::open(Model::T20II, Box::new(stream)).await.unwrap();
 //!
 //! pos.speed(5).await.unwrap();
-//! pos.write_all(b"HACK THE PLANET\n").await.unwrap();
+//! pos.print_line("HACK THE PLANET").await.unwrap();
 //! pos.feed(5).await.unwrap();
 //! pos.cut().await.unwrap();
 //! ```
+//!
+//! # Printing vector images
+//!
+//! With the `svg` feature, `Writer::print_svg` rasterizes an SVG at the
+//! printer's exact resolution, [Model::get_max_image_width] dots wide,
+//! so logos print sharp rather than scaled from a bitmap.
+//!
+//! ```rust,ignore
+//! let logo = std::fs::read_to_string("logo.svg").unwrap();
+//! pos.print_svg(&logo).unwrap();
+//! ```
+//!
+//! The built-in renderer handles shapes, paths, transforms, fills and
+//! strokes, which covers most logos; see the `svg` module for the
+//! details. Text must be converted to paths first. For anything more
+//! elaborate, render the SVG with a crate such as `resvg` to an image
+//! that wide, and print it with [Writer::print_color_image].
 
+#[cfg(any(test, feature = "arbitrary"))]
+pub mod arbitrary;
+mod barcode;
+#[cfg(feature = "bidi")]
+pub mod bidi;
+mod buffered;
+mod codepage;
 mod commands;
+mod decode;
+pub mod document;
+mod dump;
+mod emulator;
 mod epson_image;
+mod font;
+mod html;
+mod info;
+mod job;
+mod json;
 mod models;
+#[cfg(feature = "pdf")]
+mod pdf;
+mod preprocess;
+pub mod preview;
+mod printer;
+mod profile;
+#[cfg(any(feature = "textimage", feature = "svg"))]
+mod raster;
+mod receipt;
+mod replay;
+#[cfg(feature = "spooler")]
+pub mod spooler;
+mod status;
+mod styled;
+#[cfg(feature = "svg")]
+pub mod svg;
+mod tcp;
+#[cfg(feature = "template")]
+pub mod template;
+pub mod testing;
+#[cfg(feature = "textimage")]
+pub mod textimage;
+pub mod transport;
+mod user_glyph;
+mod width;
+mod wrap;
 mod write;
+mod yaml;
 
 #[cfg(feature = "tokio")]
 mod async_tokio;
 
-pub use commands::{Alignment, CharacterSet, Command};
+pub use barcode::{Barcode, BarcodeSystem, QrCode, QrErrorCorrection, QrModel};
+pub use buffered::BufferedWriter;
+pub use codepage::CodePage;
+pub use commands::{
+    Alignment, CharacterSet, Color, ColumnImageMode, Command, CustomizeValue, CutCommand, CutMode,
+    DrawerPin, EffectColor, ImageScale, KanjiCode, KanjiStyle, NvKey, PaperLayout,
+};
+pub use decode::{decode, Decoded, Decoder};
+pub use document::{Document, StyleSheet};
+pub use dump::dump;
+pub use emulator::Emulator;
 use epson_image::ImageBuffer;
-pub use models::Model;
+pub use info::{Identity, InfoKind, MaintenanceCounter, PrinterInfo, TypeId};
+pub use job::{JobResult, PrintJob};
+pub use models::{Capabilities, Model, PaperWidth, PaperWidthModel};
+pub use preprocess::{
+    split_two_color, ColorConversion, Dither, ImageAdjustments, ImageOptions, LumaWeighting,
+    MaxWidthBehavior, Padding,
+};
+pub use printer::Printer;
+pub use profile::CapabilityProfile;
+pub use receipt::{Footer, Header, Paginator, Receipt, Section, SectionedWriter, Sections};
+pub use replay::{replay, Pacing};
+pub use status::{
+    AsbFlags, AsbStatus, DrawerStatus, ErrorStatus, OfflineStatus, PaperStatus, PrinterStatus,
+    RealTimeStatus, StatusKind, TransmitStatusKind,
+};
+pub use styled::{Span, StyledText};
+pub use tcp::TcpOptions;
+pub use user_glyph::UserGlyph;
+pub use width::text_width;
+pub use wrap::{wrap_text, RuleStyle};
 pub use write::Writer;
 
 #[cfg(feature = "tokio")]
 pub use async_tokio::{AsyncWriter, Error as AsyncWriterError};
+#[cfg(feature = "tokio")]
+pub use buffered::AsyncBufferedWriter;
+#[cfg(feature = "tokio")]
+pub use printer::AsyncPrinter;
+#[cfg(feature = "tokio")]
+pub use receipt::AsyncSectionedWriter;
 
 /// Possible error states that we can get returned from the crate
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -110,6 +213,40 @@ pub enum Error {
     /// This is returned if the requested function is not supported by the
     /// configured Model.
     Unsupported,
+
+    /// This is returned when a parameter to a command is outside of the
+    /// range the printer accepts.
+    InvalidParameter,
+
+    /// This is returned when a status or information response from the
+    /// printer could not be parsed.
+    InvalidStatus,
+
+    /// This is returned when the data or parameters provided for a barcode
+    /// or 2D code are not valid for the requested symbology, such as the
+    /// wrong number of digits.
+    InvalidBarcode,
+
+    /// This is returned when a barcode's check digit does not match the
+    /// one computed from the rest of the data.
+    InvalidCheckDigit,
+
+    /// This is returned when a printer capability profile could not be
+    /// parsed, or does not contain the requested printer.
+    InvalidProfile,
+
+    /// This is returned when a [Document] could not be parsed.
+    InvalidDocument,
+
+    /// This is returned when a template could not be parsed, or the data
+    /// it's rendered with is invalid or missing a value it uses.
+    InvalidTemplate,
+
+    /// This is returned when a font could not be read.
+    InvalidFont,
+
+    /// This is returned when an SVG image could not be parsed.
+    InvalidSvg,
 }
 
 impl std::error::Error for Error {}
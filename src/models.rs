@@ -18,7 +18,98 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE. }}}
 
-use super::{CharacterSet, Error};
+use super::{
+    CapabilityProfile, CharacterSet, CodePage, Command, CutCommand, CutMode, Error, ImageScale,
+    QrModel,
+};
+
+/// Code pages supported by the international models.
+const STANDARD_CODE_PAGES: &[CodePage] = &[
+    CodePage::Pc437,
+    CodePage::Katakana,
+    CodePage::Pc850,
+    CodePage::Pc860,
+    CodePage::Pc863,
+    CodePage::Pc865,
+    CodePage::Wpc1252,
+    CodePage::Pc866,
+    CodePage::Pc852,
+    CodePage::Pc858,
+];
+
+/// Code pages supported by the Asia-Pacific models, which add Thai,
+/// Vietnamese and Arabic to the standard set.
+const APAC_CODE_PAGES: &[CodePage] = &[
+    CodePage::Pc437,
+    CodePage::Katakana,
+    CodePage::Pc850,
+    CodePage::Pc860,
+    CodePage::Pc863,
+    CodePage::Pc865,
+    CodePage::Wpc1252,
+    CodePage::Pc866,
+    CodePage::Pc852,
+    CodePage::Pc858,
+    CodePage::Thai42,
+    CodePage::Thai11,
+    CodePage::Thai18,
+    CodePage::Tcvn3,
+    CodePage::Pc720,
+    CodePage::Pc864,
+];
+
+/// Width of the paper roll loaded into the printer. Most models ship
+/// configured for 80mm paper, but can be switched to 58mm paper with the
+/// paper width guides and a memory switch.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PaperWidth {
+    /// 58mm paper, of which 48mm is printable.
+    Mm58,
+
+    /// 80mm paper, which is the default for all supported models.
+    Mm80,
+}
+
+impl PaperWidth {
+    /// Return the width of the paper roll, in millimeters.
+    pub fn mm(&self) -> f32 {
+        match self {
+            PaperWidth::Mm58 => 58.0,
+            PaperWidth::Mm80 => 80.0,
+        }
+    }
+
+    /// Return the printable width of the paper, in millimeters.
+    pub fn printable_mm(&self) -> f32 {
+        match self {
+            PaperWidth::Mm58 => 48.0,
+            PaperWidth::Mm80 => 72.0,
+        }
+    }
+}
+
+/// One of the built-in [Model]s, configured for a paper width other than
+/// its default. This can only be created by [Model::with_paper_width],
+/// which makes sure the model it wraps is never itself configured for
+/// another paper width.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PaperWidthModel {
+    base: &'static Model,
+    width: PaperWidth,
+}
+
+impl PaperWidthModel {
+    /// Return the model, as configured for its default paper width.
+    pub fn base(&self) -> Model {
+        *self.base
+    }
+
+    /// Return the paper width the model is configured for.
+    pub fn paper_width(&self) -> PaperWidth {
+        self.width
+    }
+}
 
 /// Maintained and understood models of Epson Printers.
 #[non_exhaustive]
@@ -37,9 +128,156 @@ pub enum Model {
 
     /// TM-T30II Epson brand thermal printer.
     T30II,
+
+    /// TM-T88VI Epson brand thermal printer.
+    T88VI,
+
+    /// TM-T88VII Epson brand thermal printer.
+    T88VII,
+
+    /// TM-L90 Epson brand thermal label printer. This can be loaded with
+    /// receipt, label or black mark paper.
+    L90,
+
+    /// TM-T82 Epson brand thermal printer, sold in the Asia-Pacific region
+    /// with a region-specific set of code pages.
+    T82,
+
+    /// TM-T82II Epson brand thermal printer.
+    T82II,
+
+    /// TM-T82III Epson brand thermal printer.
+    T82III,
+
+    /// One of the models above, configured for a paper width other than
+    /// its default. This is created by [Model::with_paper_width].
+    WithPaperWidth(PaperWidthModel),
+
+    /// A printer which isn't listed above, described by a
+    /// [CapabilityProfile].
+    Custom(CapabilityProfile),
+}
+
+/// Everything known about what a [Model] can do, gathered into one place
+/// so code can branch on features rather than on model names. This is
+/// returned by [Model::capabilities].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Capabilities {
+    /// Width of the loaded paper.
+    pub paper_width: PaperWidth,
+
+    /// Maximum image width, in pixels.
+    pub max_image_width: usize,
+
+    /// Number of dots printed per millimeter of paper.
+    pub dots_per_mm: f32,
+
+    /// Number of characters which fit on a line.
+    pub columns: usize,
+
+    /// Maximum number of lines which can be fed in reverse.
+    pub max_reverse_feed: u8,
+
+    /// Range of accepted print speeds.
+    pub speed_range: std::ops::RangeInclusive<u8>,
+
+    /// Command used to cut the paper.
+    pub cut_command: CutCommand,
+
+    /// Code pages which can be selected with `ESC t`.
+    pub code_pages: Vec<CodePage>,
+
+    /// If the [CharacterSet::Unicode] character set is supported.
+    pub supports_unicode: bool,
+
+    /// If QR Code Model 2 symbols can be printed.
+    pub supports_qr: bool,
+
+    /// If Micro QR Code symbols can be printed.
+    pub supports_micro_qr: bool,
+
+    /// If the paper can be cut all the way through.
+    pub supports_full_cut: bool,
+
+    /// If the paper can be partially cut.
+    pub supports_partial_cut: bool,
+
+    /// If there is a cash drawer kick-out connector.
+    pub supports_cash_drawer: bool,
+
+    /// If text can be scaled with `GS !`.
+    pub supports_text_size: bool,
+
+    /// If text can be rotated 90 degrees.
+    pub supports_rotation: bool,
+
+    /// If there is a built-in buzzer.
+    pub supports_buzzer: bool,
+
+    /// If two-color paper is supported.
+    pub supports_two_color: bool,
+
+    /// If kanji fonts are present.
+    pub supports_kanji: bool,
+
+    /// If label or black mark paper can be loaded.
+    pub supports_label_paper: bool,
+
+    /// If there is a label peeler.
+    pub supports_label_peeler: bool,
+
+    /// If character color and shadow effects are supported.
+    pub supports_character_effects: bool,
+
+    /// If Epson ePOS is supported.
+    pub supports_epos: bool,
+}
+
+/// Return the number of dots which can be printed on `paper`, in whole
+/// bytes, limited to the `max` the printer can print.
+fn paper_limited_width(max: usize, dots_per_mm: f32, paper: PaperWidth) -> usize {
+    match paper {
+        PaperWidth::Mm80 => max,
+        _ => {
+            let dots = (paper.printable_mm() * dots_per_mm) as usize;
+            max.min(dots - dots % 8)
+        }
+    }
 }
 
 impl Model {
+    /// Return this model, configured for a specific [PaperWidth]. This
+    /// will lower the maximum image width and number of columns for
+    /// narrower paper.
+    pub fn with_paper_width(self, width: PaperWidth) -> Model {
+        let model: &'static Model = match self {
+            Model::Generic => &Model::Generic,
+            Model::T20II => &Model::T20II,
+            Model::T30II => &Model::T30II,
+            Model::T88VI => &Model::T88VI,
+            Model::T88VII => &Model::T88VII,
+            Model::L90 => &Model::L90,
+            Model::T82 => &Model::T82,
+            Model::T82II => &Model::T82II,
+            Model::T82III => &Model::T82III,
+            Model::WithPaperWidth(configured) => configured.base,
+            Model::Custom(profile) => return Model::Custom(profile.paper_width(width)),
+        };
+        match width {
+            PaperWidth::Mm80 => *model,
+            _ => Model::WithPaperWidth(PaperWidthModel { base: model, width }),
+        }
+    }
+
+    /// Return the [PaperWidth] this model is configured for.
+    pub fn paper_width(&self) -> PaperWidth {
+        match self {
+            Model::WithPaperWidth(configured) => configured.width,
+            Model::Custom(profile) => profile.paper_width,
+            _ => PaperWidth::Mm80,
+        }
+    }
+
     /// Return the maximum number of pixels that is wise to send to the printer.
     ///
     /// This is in *pixels*, not bytes, and images from the image crate can
@@ -56,6 +294,97 @@ impl Model {
             // the T30II has 12 pixels per column, 48 columns, so 576
             // pixels.
             Model::T30II => 576,
+
+            // the T88 series are 180 dpi, and print 72mm wide, or 512
+            // pixels.
+            Model::T88VI => 512,
+            Model::T88VII => 512,
+
+            // the L90 is 203 dpi, and prints 72mm wide on 80mm media, or
+            // 576 pixels (TM-L90 Technical Reference Guide, "Printing
+            // Specifications").
+            Model::L90 => 576,
+
+            // the T82 family has 12 pixels per column, 48 columns, so 576
+            // pixels.
+            Model::T82 | Model::T82II | Model::T82III => 576,
+            // narrower paper is printed on the same head, so take as many
+            // dots as fit on the paper, in whole bytes.
+            Model::WithPaperWidth(configured) => paper_limited_width(
+                configured.base.get_max_image_width(),
+                configured.base.dots_per_mm(),
+                configured.width,
+            ),
+            Model::Custom(profile) => paper_limited_width(
+                profile.max_image_width,
+                profile.dots_per_mm,
+                profile.paper_width,
+            ),
+        }
+    }
+
+    /// Return the number of dots printed per millimeter of paper.
+    pub fn dots_per_mm(&self) -> f32 {
+        match self {
+            // 203 dpi, or 8 dots per mm.
+            Model::Generic => 8.0,
+            Model::T20II => 8.0,
+            Model::T30II => 8.0,
+            Model::L90 => 8.0,
+            Model::T82 | Model::T82II | Model::T82III => 8.0,
+            Model::WithPaperWidth(configured) => configured.base.dots_per_mm(),
+            Model::Custom(profile) => profile.dots_per_mm,
+
+            // 180 dpi.
+            Model::T88VI => 180.0 / 25.4,
+            Model::T88VII => 180.0 / 25.4,
+        }
+    }
+
+    /// Return the resolution of the print head in dots per inch.
+    pub fn dpi(&self) -> u32 {
+        (self.dots_per_mm() * 25.4).round() as u32
+    }
+
+    /// Convert a distance in millimeters into the nearest whole number of
+    /// dots. Negative distances are clamped to 0.
+    pub fn mm_to_dots(&self, mm: f32) -> u32 {
+        (mm * self.dots_per_mm()).round() as u32
+    }
+
+    /// Convert a number of dots into a distance in millimeters.
+    pub fn dots_to_mm(&self, dots: u32) -> f32 {
+        dots as f32 / self.dots_per_mm()
+    }
+
+    /// Return the maximum number of lines the printer can feed backwards.
+    /// Models that can't reverse feed at all return 0.
+    pub fn get_max_reverse_feed(&self) -> u8 {
+        match self {
+            Model::Generic => 0,
+            Model::T20II => 2,
+            Model::T30II => 2,
+            Model::T88VI => 2,
+            Model::T88VII => 2,
+            Model::L90 => 0,
+            Model::T82 | Model::T82II | Model::T82III => 2,
+            Model::WithPaperWidth(configured) => configured.base.get_max_reverse_feed(),
+            Model::Custom(profile) => profile.max_reverse_feed,
+        }
+    }
+
+    /// Return the range of print speed levels accepted by the model.
+    pub fn get_speed_range(&self) -> std::ops::RangeInclusive<u8> {
+        match self {
+            Model::Generic => 1..=9,
+            Model::T20II => 1..=9,
+            Model::T30II => 1..=9,
+            Model::T88VI => 1..=9,
+            Model::T88VII => 1..=9,
+            Model::L90 => 1..=9,
+            Model::T82 | Model::T82II | Model::T82III => 1..=9,
+            Model::WithPaperWidth(configured) => configured.base.get_speed_range(),
+            Model::Custom(profile) => profile.speed_min..=profile.speed_max,
         }
     }
 
@@ -66,11 +395,253 @@ impl Model {
             CharacterSet::Unicode => match self {
                 Model::T20II => false,
                 Model::T30II => true,
+                Model::T88VI => true,
+                Model::T88VII => true,
+                Model::L90 => false,
+                Model::T82 | Model::T82II | Model::T82III => false,
+                Model::WithPaperWidth(configured) => configured.base.supports_character_set(c),
+                Model::Custom(profile) => profile.supports_character_set(c),
                 Model::Generic => false,
             },
         }
     }
 
+    /// Return the [CodePage]s which the model can print.
+    pub fn get_code_pages(&self) -> Vec<CodePage> {
+        match self {
+            Model::Generic => vec![CodePage::Pc437],
+            Model::T20II => STANDARD_CODE_PAGES.to_vec(),
+            Model::T30II => STANDARD_CODE_PAGES.to_vec(),
+            Model::T88VI => STANDARD_CODE_PAGES.to_vec(),
+            Model::T88VII => STANDARD_CODE_PAGES.to_vec(),
+            Model::L90 => STANDARD_CODE_PAGES.to_vec(),
+            Model::T82 | Model::T82II | Model::T82III => APAC_CODE_PAGES.to_vec(),
+            Model::WithPaperWidth(configured) => configured.base.get_code_pages(),
+            Model::Custom(_) => CodePage::ALL
+                .iter()
+                .copied()
+                .filter(|page| self.supports_code_page(*page))
+                .collect(),
+        }
+    }
+
+    /// Return the level of support for a specific [CodePage].
+    pub fn supports_code_page(&self, page: CodePage) -> bool {
+        match self {
+            Model::Custom(profile) => profile.supports_code_page(page),
+            _ => self.get_code_pages().contains(&page),
+        }
+    }
+
+    /// Return the level of support for a specific [CutMode]. All of the
+    /// listed models have an autocutter which can do either.
+    pub fn supports_cut_mode(&self, mode: CutMode) -> bool {
+        match self {
+            Model::Custom(profile) => match mode {
+                CutMode::Full => profile.full_cut,
+                CutMode::Partial => profile.partial_cut,
+            },
+            Model::WithPaperWidth(configured) => configured.base.supports_cut_mode(mode),
+            _ => true,
+        }
+    }
+
+    /// Return the [CutCommand] the printer expects. All of the listed
+    /// models understand `GS V`, but unknown printers are sent the legacy
+    /// `ESC i`.
+    pub fn get_cut_command(&self) -> CutCommand {
+        match self {
+            Model::Generic => CutCommand::EscI,
+            Model::Custom(profile) => profile.cut_command,
+            Model::WithPaperWidth(configured) => configured.base.get_cut_command(),
+            _ => CutCommand::GsV,
+        }
+    }
+
+    /// Return if the printer has a cash drawer kick-out connector. All of
+    /// the listed models do.
+    pub fn supports_cash_drawer(&self) -> bool {
+        match self {
+            Model::Custom(profile) => profile.cash_drawer,
+            Model::WithPaperWidth(configured) => configured.base.supports_cash_drawer(),
+            _ => true,
+        }
+    }
+
+    /// Return the level of support for a specific QR Code model. Micro QR
+    /// in particular is only available on some newer firmware.
+    pub fn supports_qr_model(&self, m: QrModel) -> bool {
+        match m {
+            QrModel::Model1 | QrModel::Model2 => match self {
+                Model::T20II => true,
+                Model::T30II => true,
+                Model::T88VI => true,
+                Model::T88VII => true,
+                Model::L90 => true,
+                Model::T82 | Model::T82II | Model::T82III => true,
+                Model::WithPaperWidth(configured) => configured.base.supports_qr_model(m),
+                Model::Custom(profile) => profile.supports_qr_model(m),
+                Model::Generic => false,
+            },
+            QrModel::Micro => match self {
+                Model::T20II => false,
+                Model::T30II => false,
+                Model::T88VI => true,
+                Model::T88VII => true,
+                Model::L90 => false,
+                Model::T82 | Model::T82II | Model::T82III => false,
+                Model::WithPaperWidth(configured) => configured.base.supports_qr_model(m),
+                Model::Custom(profile) => profile.supports_qr_model(m),
+                Model::Generic => false,
+            },
+        }
+    }
+
+    /// Return true if the model can scale characters with `GS !`. Models
+    /// without it can only double the size of characters using `ESC !`.
+    pub fn supports_text_size(&self) -> bool {
+        match self {
+            Model::T20II => true,
+            Model::T30II => true,
+            Model::T88VI => true,
+            Model::T88VII => true,
+            Model::L90 => true,
+            Model::T82 | Model::T82II | Model::T82III => true,
+            Model::WithPaperWidth(configured) => configured.base.supports_text_size(),
+            Model::Custom(profile) => profile.text_size,
+            Model::Generic => false,
+        }
+    }
+
+    /// Return true if the model can rotate text by 90 degrees.
+    pub fn supports_rotation(&self) -> bool {
+        match self {
+            Model::T20II => true,
+            Model::T30II => true,
+            Model::T88VI => true,
+            Model::T88VII => true,
+            Model::L90 => true,
+            Model::T82 | Model::T82II | Model::T82III => true,
+            Model::WithPaperWidth(configured) => configured.base.supports_rotation(),
+            Model::Custom(profile) => profile.rotation,
+            Model::Generic => false,
+        }
+    }
+
+    /// Return true if the model has a built-in buzzer which can be sounded
+    /// with `ESC ( A`. Models which drive an external buzzer through the
+    /// drawer kick-out connector should use [crate::Command::CashDrawer]
+    /// instead.
+    pub fn supports_buzzer(&self) -> bool {
+        match self {
+            Model::T20II => false,
+            Model::T30II => false,
+            Model::T88VI => true,
+            Model::T88VII => true,
+            Model::L90 => false,
+            Model::T82 | Model::T82II | Model::T82III => false,
+            Model::WithPaperWidth(configured) => configured.base.supports_buzzer(),
+            Model::Custom(profile) => profile.buzzer,
+            Model::Generic => false,
+        }
+    }
+
+    /// Return true if the model can print in two colors, such as black and
+    /// red.
+    pub fn supports_two_color(&self) -> bool {
+        match self {
+            Model::T20II => false,
+            Model::T30II => false,
+            Model::T88VI => true,
+            Model::T88VII => true,
+            Model::L90 => false,
+            Model::T82 | Model::T82II | Model::T82III => false,
+            Model::WithPaperWidth(configured) => configured.base.supports_two_color(),
+            Model::Custom(profile) => profile.two_color,
+            Model::Generic => false,
+        }
+    }
+
+    /// Return true if the model has a Kanji ROM, and can print Japanese
+    /// text in Kanji mode.
+    pub fn supports_kanji(&self) -> bool {
+        match self {
+            Model::T20II => false,
+            Model::T30II => false,
+            Model::T88VI => false,
+            Model::T88VII => false,
+            Model::L90 => false,
+            Model::T82 | Model::T82II | Model::T82III => true,
+            Model::WithPaperWidth(configured) => configured.base.supports_kanji(),
+            Model::Custom(profile) => profile.kanji,
+            Model::Generic => false,
+        }
+    }
+
+    /// Return true if the model can be loaded with label or black mark
+    /// paper, and supports the label positioning commands.
+    pub fn supports_label_paper(&self) -> bool {
+        match self {
+            Model::T20II => false,
+            Model::T30II => false,
+            Model::T88VI => false,
+            Model::T88VII => false,
+            Model::L90 => true,
+            Model::T82 | Model::T82II | Model::T82III => false,
+            Model::WithPaperWidth(configured) => configured.base.supports_label_paper(),
+            Model::Custom(profile) => profile.label_paper,
+            Model::Generic => false,
+        }
+    }
+
+    /// Return true if the model can be fitted with a label peeler, which
+    /// peels labels from the liner as they are printed.
+    pub fn supports_label_peeler(&self) -> bool {
+        match self {
+            Model::T20II => false,
+            Model::T30II => false,
+            Model::T88VI => false,
+            Model::T88VII => false,
+            Model::L90 => true,
+            Model::T82 | Model::T82II | Model::T82III => false,
+            Model::WithPaperWidth(configured) => configured.base.supports_label_peeler(),
+            Model::Custom(profile) => profile.label_peeler,
+            Model::Generic => false,
+        }
+    }
+
+    /// Return true if the model supports character color, background
+    /// color and shadow effects with `GS ( N`.
+    pub fn supports_character_effects(&self) -> bool {
+        match self {
+            Model::T20II => false,
+            Model::T30II => false,
+            Model::T88VI => true,
+            Model::T88VII => true,
+            Model::L90 => false,
+            Model::T82 | Model::T82II | Model::T82III => false,
+            Model::WithPaperWidth(configured) => configured.base.supports_character_effects(),
+            Model::Custom(profile) => profile.character_effects,
+            Model::Generic => false,
+        }
+    }
+
+    /// Return true if the model can be driven over the network using
+    /// Epson's ePOS-Print XML protocol, in addition to raw ESC/POS.
+    pub fn supports_epos(&self) -> bool {
+        match self {
+            Model::T20II => false,
+            Model::T30II => false,
+            Model::T88VI => true,
+            Model::T88VII => true,
+            Model::L90 => false,
+            Model::T82 | Model::T82II | Model::T82III => false,
+            Model::WithPaperWidth(configured) => configured.base.supports_epos(),
+            Model::Custom(profile) => profile.epos,
+            Model::Generic => false,
+        }
+    }
+
     /// Return the number of printable columns in normal text mode. Some models
     /// may support different column capacities. When the time comes, add
     /// a mode inside the enum.
@@ -79,12 +650,105 @@ impl Model {
             Model::Generic => 48,
             Model::T20II => 48,
             Model::T30II => 48,
+            Model::T88VI => 42,
+            Model::T88VII => 42,
+            Model::L90 => 48,
+            Model::T82 | Model::T82II | Model::T82III => 48,
+            Model::WithPaperWidth(configured) => {
+                let base = configured.base;
+                base.get_columns() * self.get_max_image_width() / base.get_max_image_width()
+            }
+            // a profile with no image width has nothing to scale by.
+            Model::Custom(profile) => (profile.columns * self.get_max_image_width())
+                .checked_div(profile.max_image_width)
+                .unwrap_or(profile.columns),
+        }
+    }
+
+    /// Return all of the model's capabilities at once.
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            paper_width: self.paper_width(),
+            max_image_width: self.get_max_image_width(),
+            dots_per_mm: self.dots_per_mm(),
+            columns: self.get_columns(),
+            max_reverse_feed: self.get_max_reverse_feed(),
+            speed_range: self.get_speed_range(),
+            cut_command: self.get_cut_command(),
+            code_pages: self.get_code_pages(),
+            supports_unicode: self.supports_character_set(CharacterSet::Unicode),
+            supports_qr: self.supports_qr_model(QrModel::Model2),
+            supports_micro_qr: self.supports_qr_model(QrModel::Micro),
+            supports_full_cut: self.supports_cut_mode(CutMode::Full),
+            supports_partial_cut: self.supports_cut_mode(CutMode::Partial),
+            supports_cash_drawer: self.supports_cash_drawer(),
+            supports_text_size: self.supports_text_size(),
+            supports_rotation: self.supports_rotation(),
+            supports_buzzer: self.supports_buzzer(),
+            supports_two_color: self.supports_two_color(),
+            supports_kanji: self.supports_kanji(),
+            supports_label_paper: self.supports_label_paper(),
+            supports_label_peeler: self.supports_label_peeler(),
+            supports_character_effects: self.supports_character_effects(),
+            supports_epos: self.supports_epos(),
+        }
+    }
+
+    /// Check that this model supports `cmd`, and that it can be encoded.
+    pub(crate) fn check_command(&self, cmd: &Command) -> Result<(), Error> {
+        let supported = match cmd {
+            Command::CharacterSet(c) => self.supports_character_set(*c),
+            Command::CodePage(page) => self.supports_code_page(*page),
+            Command::Cut => self.supports_cut_mode(CutMode::Full),
+            Command::LegacyPartialCut => self.supports_cut_mode(CutMode::Partial),
+            Command::CutWith(mode, _) => self.supports_cut_mode(*mode),
+            Command::TextSize { width, height } => {
+                (*width, *height) == (1, 1) || self.supports_text_size()
+            }
+            Command::Rotate90(_) => self.supports_rotation(),
+            Command::PrintColor(_) => self.supports_two_color(),
+            Command::KanjiMode(_) | Command::KanjiCodeSystem(_) | Command::KanjiStyle(_) => {
+                self.supports_kanji()
+            }
+            Command::SetPaperLayout(_)
+            | Command::FeedToLabelOrigin
+            | Command::FeedToCutPosition => self.supports_label_paper(),
+            Command::FeedToPeelPosition => self.supports_label_peeler(),
+            Command::CashDrawer { .. } | Command::RealTimeDrawerPulse { .. } => {
+                self.supports_cash_drawer()
+            }
+            Command::Buzzer { .. } => self.supports_buzzer(),
+            Command::CharacterColor(_) | Command::BackgroundColor(_) | Command::Shadow(_) => {
+                self.supports_character_effects()
+            }
+            Command::QrCode(qr) => self.supports_qr_model(qr.model),
+            Command::Image(img, scale) => {
+                self.check_image_scaled(img, *scale)?;
+                true
+            }
+            _ => true,
+        };
+        if !supported {
+            return Err(Error::Unsupported);
         }
+        cmd.as_bytes()?;
+        Ok(())
     }
 
     /// Check to ensure that the Image is printable.
     pub(crate) fn check_image(&self, img: &image::GrayImage) -> Result<(), Error> {
+        self.check_image_scaled(img, ImageScale::Normal)
+    }
+
+    /// Check to ensure that the Image is printable once the printer has
+    /// enlarged it by `scale`.
+    pub(crate) fn check_image_scaled(
+        &self,
+        img: &image::GrayImage,
+        scale: ImageScale,
+    ) -> Result<(), Error> {
         let (width, _) = img.dimensions();
+        let width = width * scale.factors().0 as u32;
 
         if width
             > self
@@ -101,4 +765,113 @@ impl Model {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paper_width_58mm() {
+        let model = Model::T20II.with_paper_width(PaperWidth::Mm58);
+        assert_eq!(PaperWidth::Mm58, model.paper_width());
+        assert_eq!(384, model.get_max_image_width());
+        assert_eq!(32, model.get_columns());
+        assert!(model.supports_rotation());
+    }
+
+    #[test]
+    fn paper_width_not_nested() {
+        let model = Model::T20II
+            .with_paper_width(PaperWidth::Mm58)
+            .with_paper_width(PaperWidth::Mm58);
+        let Model::WithPaperWidth(configured) = model else {
+            panic!("expected a paper width configuration");
+        };
+        assert_eq!(Model::T20II, configured.base());
+        assert_eq!(PaperWidth::Mm58, configured.paper_width());
+    }
+
+    #[test]
+    fn paper_width_58mm_180dpi() {
+        let model = Model::T88VI.with_paper_width(PaperWidth::Mm58);
+        assert_eq!(336, model.get_max_image_width());
+        assert!(model.supports_qr_model(QrModel::Micro));
+    }
+
+    #[test]
+    fn paper_width_80mm() {
+        let model = Model::T20II
+            .with_paper_width(PaperWidth::Mm58)
+            .with_paper_width(PaperWidth::Mm80);
+        assert_eq!(Model::T20II, model);
+        assert_eq!(576, model.get_max_image_width());
+    }
+
+    #[test]
+    fn custom_paper_width_58mm() {
+        let model = Model::Custom(CapabilityProfile::new().max_image_width(576))
+            .with_paper_width(PaperWidth::Mm58);
+        assert_eq!(PaperWidth::Mm58, model.paper_width());
+        assert_eq!(384, model.get_max_image_width());
+        assert_eq!(32, model.get_columns());
+        assert_eq!(vec![CodePage::Pc437], model.get_code_pages());
+    }
+
+    #[test]
+    fn custom_zero_image_width() {
+        let model = Model::Custom(CapabilityProfile::new().max_image_width(0).columns(32));
+        assert_eq!(0, model.get_max_image_width());
+        assert_eq!(32, model.get_columns());
+
+        let mut emulator = crate::Emulator::new(model);
+        std::io::Write::write_all(&mut emulator, b"HACK THE PLANET\n").unwrap();
+        emulator.metrics();
+        emulator.image();
+    }
+
+    #[test]
+    fn kanji() {
+        assert!(Model::T82II.supports_kanji());
+        assert!(Model::T82III
+            .with_paper_width(PaperWidth::Mm58)
+            .supports_kanji());
+        assert!(!Model::T20II.supports_kanji());
+    }
+
+    #[test]
+    fn unit_conversions() {
+        assert_eq!(203, Model::T20II.dpi());
+        assert_eq!(180, Model::T88VI.dpi());
+        assert_eq!(80, Model::T20II.mm_to_dots(10.0));
+        assert_eq!(71, Model::T88VI.mm_to_dots(10.0));
+        assert_eq!(0, Model::T20II.mm_to_dots(-1.0));
+        assert_eq!(10.0, Model::T20II.dots_to_mm(80));
+    }
+
+    #[test]
+    fn capabilities() {
+        let caps = Model::T88VI.capabilities();
+        assert_eq!(512, caps.max_image_width);
+        assert_eq!(42, caps.columns);
+        assert!(caps.supports_micro_qr);
+        assert!(caps.supports_partial_cut);
+        assert!(caps.code_pages.contains(&CodePage::Pc858));
+
+        assert_eq!(576, Model::L90.get_max_image_width());
+
+        let caps = Model::Custom(CapabilityProfile::new().cash_drawer(false)).capabilities();
+        assert!(!caps.supports_qr);
+        assert!(!caps.supports_cash_drawer);
+    }
+
+    #[test]
+    fn paper_width_check_image() {
+        let model = Model::T20II.with_paper_width(PaperWidth::Mm58);
+        assert!(model.check_image(&image::GrayImage::new(384, 8)).is_ok());
+        assert_eq!(
+            Err(Error::ImageTooLarge),
+            model.check_image(&image::GrayImage::new(576, 8))
+        );
+    }
+}
+
 // vim: foldmethod=marker
@@ -0,0 +1,129 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Minimal PDF writer, used to wrap a rendered receipt in a single page
+//! document at its true physical size.
+
+use flate2::{write::ZlibEncoder, Compression};
+use std::io::Write;
+
+/// PDF points per millimeter.
+const POINTS_PER_MM: f32 = 72.0 / 25.4;
+
+/// Write a single page PDF `page_mm` wide, with the image centered
+/// across the page, drawn at `dots_per_mm`. The page is as tall as the
+/// image.
+pub(crate) fn image_pdf(img: &image::GrayImage, page_mm: f32, dots_per_mm: f32) -> Vec<u8> {
+    let (width, height) = img.dimensions();
+    let image_w = width as f32 / dots_per_mm * POINTS_PER_MM;
+    let image_h = height as f32 / dots_per_mm * POINTS_PER_MM;
+    let page_w = (page_mm * POINTS_PER_MM).max(image_w);
+    let left = (page_w - image_w) / 2.0;
+
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    // Writing into a Vec can't fail.
+    encoder.write_all(img.as_raw()).unwrap();
+    let pixels = encoder.finish().unwrap();
+
+    let content = format!(
+        "q {:.3} 0 0 {:.3} {:.3} 0 cm /Im0 Do Q",
+        image_w, image_h, left
+    );
+
+    let objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.3} {:.3}] \
+             /Resources << /XObject << /Im0 4 0 R >> >> /Contents 5 0 R >>",
+            page_w, image_h
+        )
+        .into_bytes(),
+        stream(
+            &format!(
+                "/Type /XObject /Subtype /Image /Width {} /Height {} \
+                 /ColorSpace /DeviceGray /BitsPerComponent 8 /Filter /FlateDecode",
+                width, height
+            ),
+            &pixels,
+        ),
+        stream("", content.as_bytes()),
+    ];
+
+    let mut out = b"%PDF-1.4\n".to_vec();
+    let mut offsets = vec![];
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        out.extend_from_slice(object);
+        out.extend_from_slice(b"\nendobj\n");
+    }
+
+    let xref = out.len();
+    out.extend_from_slice(format!("xref\n0 {}\n", objects.len() + 1).as_bytes());
+    out.extend_from_slice(b"0000000000 65535 f \n");
+    for offset in offsets {
+        out.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    out.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .as_bytes(),
+    );
+    out
+}
+
+/// Return a stream object with the provided dictionary entries.
+fn stream(dict: &str, data: &[u8]) -> Vec<u8> {
+    let mut out = format!("<< {} /Length {} >>\nstream\n", dict, data.len()).into_bytes();
+    out.extend_from_slice(data);
+    out.extend_from_slice(b"\nendstream");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_pdf_structure() {
+        let img = image::GrayImage::from_pixel(576, 80, image::Luma([255]));
+        let pdf = image_pdf(&img, 80.0, 8.0);
+        let text = String::from_utf8_lossy(&pdf);
+
+        assert!(text.starts_with("%PDF-1.4\n"));
+        assert!(text.ends_with("%%EOF\n"));
+        // 80mm wide, and 10mm tall.
+        assert!(text.contains("/MediaBox [0 0 226.772 28.346]"));
+        assert!(text.contains("/Width 576 /Height 80"));
+
+        // Every xref entry points at the start of its object.
+        let xref = text.rfind("xref\n").unwrap();
+        for (i, line) in text[xref..].lines().skip(3).take(5).enumerate() {
+            let offset: usize = line[..10].parse().unwrap();
+            assert!(text[offset..].starts_with(&format!("{} 0 obj", i + 1)));
+        }
+    }
+}
+
+// vim: foldmethod=marker
@@ -0,0 +1,426 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+use super::{Alignment, Error, ImageScale};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// How the red, green and blue channels of a color image are combined
+/// into a single gray level.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum LumaWeighting {
+    /// Weight each channel by how bright it looks to the eye (ITU-R BT.601),
+    /// which is what most image tools do.
+    #[default]
+    Luminosity,
+
+    /// Give each channel the same weight.
+    Average,
+}
+
+impl LumaWeighting {
+    fn luma(&self, r: u8, g: u8, b: u8) -> f32 {
+        let (r, g, b) = (r as f32, g as f32, b as f32);
+        match self {
+            LumaWeighting::Luminosity => 0.299 * r + 0.587 * g + 0.114 * b,
+            LumaWeighting::Average => (r + g + b) / 3.0,
+        }
+    }
+}
+
+/// Conversion of a color (and possibly transparent) image into the
+/// greyscale image the printer works with.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColorConversion {
+    /// How channels are combined into a gray level.
+    pub weighting: LumaWeighting,
+
+    /// Color that transparent pixels are composited onto. If this is
+    /// `None`, the alpha channel is ignored.
+    pub background: Option<image::Rgb<u8>>,
+}
+
+impl Default for ColorConversion {
+    fn default() -> Self {
+        Self {
+            weighting: LumaWeighting::Luminosity,
+            background: Some(image::Rgb([255, 255, 255])),
+        }
+    }
+}
+
+impl ColorConversion {
+    /// Convert `img` into a greyscale image.
+    pub fn convert(&self, img: &image::DynamicImage) -> image::GrayImage {
+        let img = img.to_rgba8();
+        image::GrayImage::from_fn(img.width(), img.height(), |x, y| {
+            let [r, g, b, a] = img.get_pixel(x, y).0;
+            let luma = self.weighting.luma(r, g, b);
+            let luma = match self.background {
+                Some(image::Rgb([br, bg, bb])) => {
+                    let alpha = a as f32 / 255.0;
+                    luma * alpha + self.weighting.luma(br, bg, bb) * (1.0 - alpha)
+                }
+                None => luma,
+            };
+            image::Luma([luma.round().clamp(0.0, 255.0) as u8])
+        })
+    }
+}
+
+/// Split a color image into the black and red planes of a two-color
+/// printer. Strongly red pixels go on the red plane, and any other dark
+/// pixels on the black plane. Transparent pixels are left blank.
+pub fn split_two_color(img: &image::DynamicImage) -> (image::GrayImage, image::GrayImage) {
+    let img = img.to_rgba8();
+    let (width, height) = img.dimensions();
+    let mut black = image::GrayImage::from_pixel(width, height, image::Luma([255]));
+    let mut red = black.clone();
+
+    for (x, y, pixel) in img.enumerate_pixels() {
+        let [r, g, b, a] = pixel.0;
+        if a < 128 {
+            continue;
+        }
+        if r > 128 && g < 96 && b < 96 {
+            red.put_pixel(x, y, image::Luma([0]));
+        } else if LumaWeighting::Luminosity.luma(r, g, b) <= 128.0 {
+            black.put_pixel(x, y, image::Luma([0]));
+        }
+    }
+
+    (black, red)
+}
+
+/// Tone adjustments applied to a greyscale image before it's reduced to
+/// black and white. Thermal printers tend to crush dark tones, so photos
+/// usually benefit from a gamma above 1.0.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ImageAdjustments {
+    /// Gamma correction. Values above 1.0 lighten the midtones, values
+    /// below 1.0 darken them.
+    pub gamma: f32,
+
+    /// Brightness offset, from -1.0 (black) to 1.0 (white).
+    pub brightness: f32,
+
+    /// Contrast multiplier around the middle gray. 1.0 leaves the image
+    /// unchanged.
+    pub contrast: f32,
+
+    /// If true, flip black and white after the other adjustments, so
+    /// white-on-black artwork prints black-on-white.
+    pub invert: bool,
+}
+
+impl Default for ImageAdjustments {
+    fn default() -> Self {
+        Self {
+            gamma: 1.0,
+            brightness: 0.0,
+            contrast: 1.0,
+            invert: false,
+        }
+    }
+}
+
+impl ImageAdjustments {
+    /// Apply the adjustments to every pixel of `img`.
+    pub fn apply(&self, img: &mut image::GrayImage) {
+        if *self == Self::default() {
+            return;
+        }
+
+        let mut table = [0u8; 256];
+        for (level, out) in table.iter_mut().enumerate() {
+            let v = (level as f32 / 255.0).powf(1.0 / self.gamma);
+            let v = (v - 0.5) * self.contrast + 0.5 + self.brightness;
+            *out = (v * 255.0).round().clamp(0.0, 255.0) as u8;
+            if self.invert {
+                *out = 255 - *out;
+            }
+        }
+
+        #[cfg(not(feature = "rayon"))]
+        let pixels = img.iter_mut();
+        #[cfg(feature = "rayon")]
+        let pixels = img.par_iter_mut();
+        pixels.for_each(|pixel| *pixel = table[*pixel as usize]);
+    }
+}
+
+/// How greyscale images are reduced to the black and white dots the
+/// printer can print.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum Dither {
+    /// Every pixel darker than middle gray is printed black. This is best
+    /// for line art, text and barcodes.
+    #[default]
+    Threshold,
+
+    /// Floyd-Steinberg error diffusion, which keeps the shading of photos
+    /// at the cost of some noise. Each row depends on the one above it, so
+    /// this always runs on a single thread.
+    FloydSteinberg,
+}
+
+impl Dither {
+    /// Reduce every pixel of `img` to black or white.
+    pub fn apply(&self, img: &mut image::GrayImage) {
+        match self {
+            Dither::Threshold => {}
+            Dither::FloydSteinberg => {
+                let (width, height) = img.dimensions();
+                let (w, h) = (width as usize, height as usize);
+                let mut levels: Vec<f32> = img.pixels().map(|p| p.0[0] as f32).collect();
+
+                for y in 0..h {
+                    for x in 0..w {
+                        let old = levels[y * w + x];
+                        let new = if old <= 128.0 { 0.0 } else { 255.0 };
+                        levels[y * w + x] = new;
+
+                        let error = old - new;
+                        let mut spread = |dx: isize, dy: usize, weight: f32| {
+                            let nx = x as isize + dx;
+                            if nx >= 0 && (nx as usize) < w && y + dy < h {
+                                levels[(y + dy) * w + nx as usize] += error * weight;
+                            }
+                        };
+                        spread(1, 0, 7.0 / 16.0);
+                        spread(-1, 1, 3.0 / 16.0);
+                        spread(0, 1, 5.0 / 16.0);
+                        spread(1, 1, 1.0 / 16.0);
+                    }
+                }
+
+                for (pixel, level) in img.pixels_mut().zip(levels) {
+                    pixel.0[0] = level as u8;
+                }
+            }
+        }
+    }
+}
+
+/// What to do with an image which is wider than the printer can print.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum MaxWidthBehavior {
+    /// Return [crate::Error::ImageTooLarge].
+    #[default]
+    Error,
+
+    /// Scale the image down to fit, keeping the aspect ratio.
+    Fit,
+
+    /// Cut off the right hand side of the image.
+    Crop,
+}
+
+/// Where white columns are added to an image whose width isn't a multiple
+/// of 8, since the printer works on whole bytes of pixels.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum Padding {
+    /// Pad on the right hand side.
+    #[default]
+    Right,
+
+    /// Pad on the left hand side.
+    Left,
+
+    /// Split the padding between both sides, which keeps centered artwork
+    /// centered.
+    Both,
+
+    /// Don't pad, and return [crate::Error::ImageNot8BitAligned] instead.
+    Strict,
+}
+
+impl Padding {
+    /// Pad `img` out to a multiple of 8 pixels wide.
+    pub(crate) fn apply(&self, img: image::GrayImage) -> Result<image::GrayImage, Error> {
+        let (width, height) = img.dimensions();
+        let extra = (8 - width % 8) % 8;
+        if extra == 0 {
+            return Ok(img);
+        }
+
+        let left = match self {
+            // the raster conversion already pads on the right
+            Padding::Right => return Ok(img),
+            Padding::Strict => return Err(Error::ImageNot8BitAligned),
+            Padding::Left => extra,
+            Padding::Both => extra / 2,
+        };
+
+        let mut out = image::GrayImage::from_pixel(width + extra, height, image::Luma([255]));
+        image::imageops::replace(&mut out, &img, left as i64, 0);
+        Ok(out)
+    }
+}
+
+/// Options controlling how an image is prepared and printed, used by
+/// `print_image_with` on both writers.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct ImageOptions {
+    /// How the image is reduced to black and white.
+    pub dither: Dither,
+
+    /// How much the printer enlarges the image.
+    pub scale: ImageScale,
+
+    /// Tone adjustments, including inversion, applied before dithering.
+    pub adjustments: ImageAdjustments,
+
+    /// Alignment of the image on the paper. The alignment is set back to
+    /// [Alignment::Left] after the image is printed. If this is `None`,
+    /// the current alignment is used.
+    pub align: Option<Alignment>,
+
+    /// What to do if the image is too wide.
+    pub max_width_behavior: MaxWidthBehavior,
+
+    /// Where to pad the image if it isn't a multiple of 8 pixels wide.
+    pub padding: Padding,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(conversion: ColorConversion, rgba: [u8; 4]) -> u8 {
+        let img = image::RgbaImage::from_pixel(1, 1, image::Rgba(rgba));
+        conversion
+            .convert(&image::DynamicImage::ImageRgba8(img))
+            .get_pixel(0, 0)
+            .0[0]
+    }
+
+    #[test]
+    fn luminosity() {
+        assert_eq!(76, pixel(ColorConversion::default(), [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn average() {
+        let conversion = ColorConversion {
+            weighting: LumaWeighting::Average,
+            ..Default::default()
+        };
+        assert_eq!(85, pixel(conversion, [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn alpha_composited() {
+        assert_eq!(255, pixel(ColorConversion::default(), [0, 0, 0, 0]));
+
+        let conversion = ColorConversion {
+            background: None,
+            ..Default::default()
+        };
+        assert_eq!(0, pixel(conversion, [0, 0, 0, 0]));
+    }
+
+    fn adjusted(adjustments: ImageAdjustments, level: u8) -> u8 {
+        let mut img = image::GrayImage::from_pixel(1, 1, image::Luma([level]));
+        adjustments.apply(&mut img);
+        img.get_pixel(0, 0).0[0]
+    }
+
+    #[test]
+    fn two_color() {
+        let mut img = image::RgbaImage::from_pixel(3, 1, image::Rgba([255, 255, 255, 255]));
+        img.put_pixel(0, 0, image::Rgba([0, 0, 0, 255]));
+        img.put_pixel(1, 0, image::Rgba([220, 20, 20, 255]));
+
+        let (black, red) = split_two_color(&image::DynamicImage::ImageRgba8(img));
+        assert_eq!(vec![0, 255, 255], black.into_raw());
+        assert_eq!(vec![255, 0, 255], red.into_raw());
+    }
+
+    #[test]
+    fn padding() {
+        let img = image::GrayImage::from_pixel(6, 1, image::Luma([0]));
+
+        let padded = Padding::Left.apply(img.clone()).unwrap();
+        assert_eq!(vec![255, 255, 0, 0, 0, 0, 0, 0], padded.into_raw());
+
+        let padded = Padding::Both.apply(img.clone()).unwrap();
+        assert_eq!(vec![255, 0, 0, 0, 0, 0, 0, 255], padded.into_raw());
+
+        assert_eq!(
+            (6, 1),
+            Padding::Right.apply(img.clone()).unwrap().dimensions()
+        );
+        assert_eq!(Err(Error::ImageNot8BitAligned), Padding::Strict.apply(img));
+    }
+
+    #[test]
+    fn adjustments_default() {
+        for level in [0, 64, 128, 255] {
+            assert_eq!(level, adjusted(ImageAdjustments::default(), level));
+        }
+    }
+
+    #[test]
+    fn adjustments() {
+        let gamma = ImageAdjustments {
+            gamma: 2.0,
+            ..Default::default()
+        };
+        assert_eq!(181, adjusted(gamma, 128));
+        assert_eq!(0, adjusted(gamma, 0));
+
+        let brightness = ImageAdjustments {
+            brightness: 0.25,
+            ..Default::default()
+        };
+        assert_eq!(192, adjusted(brightness, 128));
+
+        let contrast = ImageAdjustments {
+            contrast: 2.0,
+            ..Default::default()
+        };
+        assert_eq!(0, adjusted(contrast, 32));
+        assert_eq!(255, adjusted(contrast, 192));
+    }
+
+    #[test]
+    fn floyd_steinberg() {
+        let mut img = image::GrayImage::from_pixel(16, 16, image::Luma([128 + 64]));
+        Dither::FloydSteinberg.apply(&mut img);
+        assert!(img.pixels().all(|p| p.0[0] == 0 || p.0[0] == 255));
+
+        let black = img.pixels().filter(|p| p.0[0] == 0).count();
+        assert!((48..=80).contains(&black), "{} black pixels", black);
+    }
+
+    #[test]
+    fn adjustments_invert() {
+        let invert = ImageAdjustments {
+            invert: true,
+            ..Default::default()
+        };
+        assert_eq!(255, adjusted(invert, 0));
+        assert_eq!(55, adjusted(invert, 200));
+    }
+}
+
+// vim: foldmethod=marker
@@ -0,0 +1,236 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Render receipts to images, to show what they will look like before
+//! they're printed.
+
+use super::{Command, Emulator, Error, Model};
+
+/// Something which can be rendered into a preview: either the raw bytes
+/// that would be sent to the printer, or the commands to encode.
+#[derive(Clone, Copy, Debug)]
+pub enum Source<'a> {
+    /// Raw bytes, as they would be sent to the printer.
+    Bytes(&'a [u8]),
+
+    /// Commands, which are encoded before being rendered.
+    Commands(&'a [Command]),
+}
+
+impl<'a> From<&'a [u8]> for Source<'a> {
+    fn from(bytes: &'a [u8]) -> Self {
+        Source::Bytes(bytes)
+    }
+}
+
+impl<'a> From<&'a Vec<u8>> for Source<'a> {
+    fn from(bytes: &'a Vec<u8>) -> Self {
+        Source::Bytes(bytes)
+    }
+}
+
+impl<'a> From<&'a [Command]> for Source<'a> {
+    fn from(commands: &'a [Command]) -> Self {
+        Source::Commands(commands)
+    }
+}
+
+impl<'a> From<&'a Vec<Command>> for Source<'a> {
+    fn from(commands: &'a Vec<Command>) -> Self {
+        Source::Commands(commands)
+    }
+}
+
+/// How much paper a receipt uses, returned by [measure] and
+/// [Emulator::metrics].
+///
+/// This is an estimate, from the same emulation as [render]: it doesn't
+/// include paper fed by the printer itself, such as the distance between
+/// the print head and the cutter.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Metrics {
+    /// Length of the receipt, in dots.
+    pub dots: u32,
+
+    /// Length of the receipt, in millimeters.
+    pub length_mm: f32,
+
+    /// Number of lines fed, including blank lines.
+    pub lines: u32,
+
+    /// Number of times the paper is cut.
+    pub cuts: u32,
+}
+
+fn emulate<'a>(source: impl Into<Source<'a>>, model: Model) -> Result<Emulator, Error> {
+    let emulator = Emulator::new(model);
+    match source.into() {
+        Source::Bytes(bytes) => emulator.push(bytes),
+        Source::Commands(commands) => {
+            for cmd in commands {
+                emulator.push(&cmd.as_bytes()?);
+            }
+        }
+    }
+    Ok(emulator)
+}
+
+/// Render a receipt to a grayscale image, as it would be printed on the
+/// provided model. See [Emulator] for what is drawn.
+pub fn render<'a>(source: impl Into<Source<'a>>, model: Model) -> Result<image::GrayImage, Error> {
+    Ok(emulate(source, model)?.image())
+}
+
+/// Estimate how much paper a receipt would use on the provided model,
+/// without printing it, such as to warn about very long receipts.
+///
+/// ```rust
+/// # use epson::{preview, Command, Model};
+/// let metrics = preview::measure(&vec![Command::Feed(8)], Model::T20II).unwrap();
+/// assert_eq!(8, metrics.lines);
+/// assert_eq!(30.0, metrics.length_mm);
+/// ```
+pub fn measure<'a>(source: impl Into<Source<'a>>, model: Model) -> Result<Metrics, Error> {
+    Ok(emulate(source, model)?.metrics())
+}
+
+/// Render a receipt to a PNG file, as it would be printed on the provided
+/// model.
+///
+/// ```rust
+/// # use epson::{preview, Command, Model};
+/// let png = preview::render_to_png(b"HACK THE PLANET\n".as_slice(), Model::T20II).unwrap();
+/// assert_eq!(b"\x89PNG", &png[..4]);
+/// ```
+pub fn render_to_png<'a>(source: impl Into<Source<'a>>, model: Model) -> Result<Vec<u8>, Error> {
+    let mut img = render(source, model)?;
+    if img.height() == 0 {
+        // PNGs can't be empty, so draw an empty receipt as a blank row.
+        img = image::GrayImage::from_pixel(img.width(), 1, image::Luma([255]));
+    }
+
+    encode_png(&img)
+}
+
+/// Encode an image as a PNG file.
+pub(crate) fn encode_png(img: &image::GrayImage) -> Result<Vec<u8>, Error> {
+    let mut png = vec![];
+    img.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|_| Error::ImageTooLarge)?;
+    Ok(png)
+}
+
+/// Render a receipt to an HTML fragment, laid out in a monospace column as
+/// wide as the model's line of text, with bold, underlined and inverted
+/// text in spans, and images and barcodes embedded as PNGs. The fragment
+/// includes a stylesheet scoped to the `epson-receipt` class.
+pub fn render_to_html<'a>(source: impl Into<Source<'a>>, model: Model) -> Result<String, Error> {
+    match source.into() {
+        Source::Bytes(bytes) => super::html::render(bytes, model),
+        Source::Commands(commands) => {
+            let mut bytes = vec![];
+            for cmd in commands {
+                bytes.extend(cmd.as_bytes()?);
+            }
+            super::html::render(&bytes, model)
+        }
+    }
+}
+
+/// Render a receipt to a single page PDF, as it would be printed on the
+/// provided model. The page is as wide as the model's paper, and as long
+/// as the receipt, so it prints out at true size.
+///
+/// This requires the `pdf` feature.
+#[cfg(feature = "pdf")]
+pub fn render_to_pdf<'a>(source: impl Into<Source<'a>>, model: Model) -> Result<Vec<u8>, Error> {
+    let img = render(source, model)?;
+    Ok(super::pdf::image_pdf(
+        &img,
+        model.paper_width().mm(),
+        model.dots_per_mm(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measure_receipt() {
+        let commands = vec![
+            Command::Text(b"one\ntwo\n\nthree".to_vec()),
+            Command::Image(image::GrayImage::new(8, 100), crate::ImageScale::Normal),
+            Command::CutWith(crate::CutMode::Full, Some(20)),
+        ];
+        assert_eq!(
+            Metrics {
+                dots: 4 * 30 + 100 + 20 + 1,
+                length_mm: 241.0 / 8.0,
+                lines: 4,
+                cuts: 1,
+            },
+            measure(&commands, Model::T20II).unwrap()
+        );
+    }
+
+    #[test]
+    fn render_sources_match() {
+        let commands = vec![Command::Emphasize(true), Command::Feed(2)];
+        let bytes: Vec<u8> = commands
+            .iter()
+            .flat_map(|c| c.as_bytes().unwrap())
+            .collect();
+        assert_eq!(
+            render(&bytes, Model::T20II).unwrap(),
+            render(&commands, Model::T20II).unwrap()
+        );
+    }
+
+    #[test]
+    fn render_invalid_command() {
+        let commands = vec![Command::Density(20)];
+        assert_eq!(
+            Err(Error::InvalidParameter),
+            render(&commands, Model::T20II).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn render_empty_png() {
+        assert!(render_to_png(b"".as_slice(), Model::T20II).is_ok());
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn render_pdf() {
+        let pdf = render_to_pdf(b"HI\n".as_slice(), Model::T20II).unwrap();
+        assert!(pdf.starts_with(b"%PDF-"));
+    }
+
+    #[test]
+    fn render_png() {
+        let png = render_to_png(b"HI\n".as_slice(), Model::T20II).unwrap();
+        let img = image::load_from_memory(&png).unwrap().to_luma8();
+        assert_eq!(render(b"HI\n".as_slice(), Model::T20II).unwrap(), img);
+    }
+}
+
+// vim: foldmethod=marker
@@ -0,0 +1,867 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+#[cfg(feature = "tokio")]
+use super::async_tokio::{AsyncWriter, Error as AsyncWriteError};
+use super::tcp::{self, TcpOptions};
+use super::write::Error as WriteError;
+use super::{
+    AsbStatus, DrawerStatus, Error, ErrorStatus, Identity, InfoKind, Model, OfflineStatus,
+    PaperStatus, PrinterInfo, PrinterStatus, RealTimeStatus, StatusKind, TransmitStatusKind,
+    Writer,
+};
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// How long to wait for the printer to answer a request, by default.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long to wait before reading again, when there's nothing to read.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// How long a single read from a [Printer::connect_tcp] connection may
+/// block, so that requests time out on time, and printing isn't held up
+/// waiting for the connection.
+const READ_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// How often to check the cash drawer, while waiting for it to close.
+const DRAWER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A connection shared between a printer and the [Writer] it writes
+/// through.
+struct Shared<T>(Arc<Mutex<T>>);
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, T> {
+        lock(&self.0)
+    }
+}
+
+fn lock<T>(io: &Mutex<T>) -> MutexGuard<'_, T> {
+    io.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl<T: Write> Write for Shared<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.lock().flush()
+    }
+}
+
+/// Return the next process ID to send with `GS ( H`, as four ASCII
+/// digits.
+fn next_process_id(counter: &mut u16) -> [u8; 4] {
+    *counter = (*counter + 1) % 10000;
+    format!("{:04}", counter).into_bytes().try_into().unwrap()
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "no response from printer")
+}
+
+fn closed() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "printer closed the connection",
+    )
+}
+
+/// Return true if a read failing with `err` should be tried again.
+fn retry(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted
+    )
+}
+
+/// A printer which can be asked about its status, as well as printed to.
+///
+/// A [Writer] can only send to the printer, so it can't tell whether the
+/// paper has run out or the cover is open. A Printer wraps a connection
+/// which can be read from as well as written to, such as a `TcpStream` or
+/// a [crate::transport::usb::UsbPrinter], and reads back the printer's
+/// answers to `DLE EOT`, `GS r` and `GS I` requests.
+///
+/// Reads from the connection must give up when the printer has nothing
+/// to say, by failing with [io::ErrorKind::WouldBlock] or
+/// [io::ErrorKind::TimedOut], or requests can never time out. A
+/// `TcpStream` blocks forever unless it has a read timeout, so connect
+/// with [Printer::connect_tcp], which sets one, or set a short one with
+/// `TcpStream::set_read_timeout`. The connection is shared with
+/// [Self::writer], which waits while a read is blocked, so keep the read
+/// timeout short.
+///
+/// ```rust
+/// # use epson::{testing::MockPrinter, Model, Printer};
+/// # use std::io::Write;
+/// let mock = MockPrinter::new().with_paper_lines(1);
+/// let mut printer = Printer::open(Model::T20II, mock).unwrap();
+/// printer.writer().write_all(b"HACK THE PLANET\n").unwrap();
+/// assert!(printer.is_paper_out().unwrap());
+/// ```
+pub struct Printer<T> {
+    io: Arc<Mutex<T>>,
+    writer: Writer,
+    timeout: Duration,
+    process_id: u16,
+}
+
+impl Printer<TcpStream> {
+    /// Connect to the printer at `addr` over TCP, usually on port 9100,
+    /// applying [TcpOptions] as [Writer::connect_tcp] does. The read
+    /// timeout is replaced by a short one, so that requests give up after
+    /// [Self::with_timeout] rather than blocking.
+    pub fn connect_tcp(
+        model: Model,
+        addr: impl ToSocketAddrs,
+        options: &TcpOptions,
+    ) -> Result<Self, WriteError> {
+        let options = TcpOptions {
+            read_timeout: Some(READ_TIMEOUT),
+            ..*options
+        };
+        Self::open(model, tcp::connect(addr, &options)?)
+    }
+}
+
+impl<T: Read + Write + 'static> Printer<T> {
+    /// Create a new Printer, talking to the printer over `io`.
+    pub fn open(model: Model, io: T) -> Result<Self, WriteError> {
+        let io = Arc::new(Mutex::new(io));
+        let writer = Writer::open(model, Box::new(Shared(io.clone())))?;
+        Ok(Self {
+            io,
+            writer,
+            timeout: DEFAULT_TIMEOUT,
+            process_id: 0,
+        })
+    }
+
+    /// Set how long to wait for the printer to answer a request before
+    /// failing with [io::ErrorKind::TimedOut]. The default is 2 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Return the writer used to print.
+    pub fn writer(&mut self) -> &mut Writer {
+        &mut self.writer
+    }
+
+    /// Read exactly enough bytes from the printer to fill `buf`, waiting
+    /// up to the timeout for them to arrive. The timeout is only checked
+    /// between reads, so each read must give up on its own, as described
+    /// on [Printer].
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), WriteError> {
+        let start = Instant::now();
+        let mut read = 0;
+        while read < buf.len() {
+            let result = lock(&self.io).read(&mut buf[read..]);
+            match result {
+                Ok(0) => return Err(closed().into()),
+                Ok(n) => {
+                    read += n;
+                    continue;
+                }
+                Err(err) if retry(&err) => {}
+                Err(err) => return Err(err.into()),
+            }
+            if start.elapsed() >= self.timeout {
+                return Err(timed_out().into());
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> Result<u8, WriteError> {
+        let mut byte = [0u8];
+        self.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    /// Read a text response, from the leading `0x5F` through the trailing
+    /// NUL.
+    fn read_text(&mut self) -> Result<Vec<u8>, WriteError> {
+        let mut buf = vec![self.read_byte()?];
+        if buf[0] != 0x5f {
+            return Err(Error::InvalidStatus.into());
+        }
+        loop {
+            let byte = self.read_byte()?;
+            buf.push(byte);
+            if byte == 0x00 {
+                return Ok(buf);
+            }
+        }
+    }
+
+    /// Ask the printer for a real-time status of the provided kind. This is
+    /// answered right away, even if the printer is busy or offline. Any
+    /// Automatic Status Back frames received while waiting are skipped.
+    pub fn real_time_status(&mut self, kind: StatusKind) -> Result<RealTimeStatus, WriteError> {
+        self.writer.request_status(kind)?;
+        loop {
+            let byte = self.read_byte()?;
+            if AsbStatus::is_frame_start(byte) {
+                self.read_exact(&mut [0u8; 3])?;
+                continue;
+            }
+            return Ok(RealTimeStatus::parse(kind, byte)?);
+        }
+    }
+
+    /// Return the general status of the printer.
+    pub fn status(&mut self) -> Result<PrinterStatus, WriteError> {
+        match self.real_time_status(StatusKind::Printer)? {
+            RealTimeStatus::Printer(status) => Ok(status),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Return why the printer is offline, if it is.
+    pub fn offline_status(&mut self) -> Result<OfflineStatus, WriteError> {
+        match self.real_time_status(StatusKind::OfflineCause)? {
+            RealTimeStatus::Offline(status) => Ok(status),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Return which errors the printer is reporting.
+    pub fn error_status(&mut self) -> Result<ErrorStatus, WriteError> {
+        match self.real_time_status(StatusKind::ErrorCause)? {
+            RealTimeStatus::Error(status) => Ok(status),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Return the state of the paper roll sensors.
+    pub fn paper_status(&mut self) -> Result<PaperStatus, WriteError> {
+        match self.real_time_status(StatusKind::PaperSensor)? {
+            RealTimeStatus::Paper(status) => Ok(status),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Return true if the paper roll has run out.
+    pub fn is_paper_out(&mut self) -> Result<bool, WriteError> {
+        Ok(self.paper_status()?.paper_end)
+    }
+
+    /// Return the state of the drawer kick-out connector, using a
+    /// real-time status request, so this works even while the printer is
+    /// busy printing.
+    pub fn drawer_status(&mut self) -> Result<DrawerStatus, WriteError> {
+        Ok(self.status()?.drawer())
+    }
+
+    /// Return true if the cash drawer is open, according to pin 3 of the
+    /// drawer kick-out connector.
+    pub fn drawer_open(&mut self) -> Result<bool, WriteError> {
+        Ok(self.drawer_status()?.pin3_high)
+    }
+
+    /// Check the cash drawer until it's closed, for up to `timeout`.
+    /// Returns false if the drawer was left open.
+    pub fn wait_for_drawer_closed(&mut self, timeout: Duration) -> Result<bool, WriteError> {
+        let start = Instant::now();
+        while self.drawer_open()? {
+            if start.elapsed() >= timeout {
+                return Ok(false);
+            }
+            std::thread::sleep(DRAWER_POLL_INTERVAL);
+        }
+        Ok(true)
+    }
+
+    /// Return the state of the paper roll sensors once everything sent so
+    /// far has been processed, using `GS r`.
+    pub fn transmitted_paper_status(&mut self) -> Result<PaperStatus, WriteError> {
+        self.writer.transmit_status(TransmitStatusKind::Paper)?;
+        Ok(PaperStatus::parse_transmitted(self.read_byte()?)?)
+    }
+
+    /// Clear a recoverable error, such as a cutter jam which has been
+    /// cleared or a cover which has been closed, and return the errors
+    /// still being reported afterwards. If `cancel` is false, the
+    /// interrupted job is resumed from the line where the error occurred;
+    /// otherwise it's cancelled. Nothing is sent if there's no recoverable
+    /// error. Unrecoverable errors need the printer to be power cycled.
+    pub fn recover(&mut self, cancel: bool) -> Result<ErrorStatus, WriteError> {
+        let status = self.error_status()?;
+        if !status.recoverable && !status.autocutter {
+            return Ok(status);
+        }
+        self.writer.recover(cancel)?;
+        self.error_status()
+    }
+
+    /// Ask the printer for its maker, model name, firmware version and
+    /// serial number, for inventory or support. Printers which don't
+    /// report a serial number wait out the timeout before returning.
+    pub fn identity(&mut self) -> Result<Identity, WriteError> {
+        let serial_number = match self.info(InfoKind::SerialNumber) {
+            Ok(info) => Some(Identity::text(info)?),
+            Err(WriteError::Io(err)) if err.kind() == io::ErrorKind::TimedOut => None,
+            Err(err) => return Err(err),
+        };
+        Ok(Identity {
+            maker: Identity::text(self.info(InfoKind::MakerName)?)?,
+            model_name: Identity::text(self.info(InfoKind::ModelName)?)?,
+            firmware_version: Identity::text(self.info(InfoKind::FirmwareVersion)?)?,
+            serial_number,
+        })
+    }
+
+    /// Send everything written so far, and wait up to `timeout` for the
+    /// printer to finish printing it, including any cut. Use this before
+    /// opening the cash drawer or moving on, to be sure the receipt is out.
+    pub fn flush_and_wait(&mut self, timeout: Duration) -> Result<(), WriteError> {
+        let id = next_process_id(&mut self.process_id);
+        self.writer.request_process_id(id)?;
+        self.writer.flush()?;
+
+        let saved = std::mem::replace(&mut self.timeout, timeout);
+        let result = self.wait_for_process(id);
+        self.timeout = saved;
+        result
+    }
+
+    /// Read responses until the printer sends back process ID `id`,
+    /// skipping ASB frames and responses to earlier requests.
+    fn wait_for_process(&mut self, id: [u8; 4]) -> Result<(), WriteError> {
+        loop {
+            let byte = self.read_byte()?;
+            if AsbStatus::is_frame_start(byte) {
+                self.read_exact(&mut [0u8; 3])?;
+                continue;
+            }
+            if byte != 0x37 {
+                return Err(Error::InvalidStatus.into());
+            }
+            let mut response = [0u8; 6];
+            self.read_exact(&mut response)?;
+            match response {
+                [0x22, d1, d2, d3, d4, 0x00] if [d1, d2, d3, d4] == id => return Ok(()),
+                [0x22, .., 0x00] => continue,
+                _ => return Err(Error::InvalidStatus.into()),
+            }
+        }
+    }
+
+    /// Ask the printer for information about itself, such as its model
+    /// name or serial number.
+    pub fn info(&mut self, kind: InfoKind) -> Result<PrinterInfo, WriteError> {
+        self.writer.request_printer_id(kind)?;
+        let buf = if kind.is_text() {
+            self.read_text()?
+        } else {
+            vec![self.read_byte()?]
+        };
+        Ok(PrinterInfo::parse(kind, &buf)?)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T: tokio::io::AsyncWrite + Unpin> tokio::io::AsyncWrite for Shared<T> {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<io::Result<usize>> {
+        std::pin::Pin::new(&mut *self.lock()).poll_write(cx, buf)
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        std::pin::Pin::new(&mut *self.lock()).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        std::pin::Pin::new(&mut *self.lock()).poll_shutdown(cx)
+    }
+}
+
+/// A printer which can be asked about its status, as well as printed to,
+/// over a `tokio::io::AsyncRead` and `tokio::io::AsyncWrite` connection.
+/// See [Printer].
+#[cfg(feature = "tokio")]
+pub struct AsyncPrinter<T> {
+    io: Arc<Mutex<T>>,
+    writer: AsyncWriter,
+    timeout: Duration,
+    process_id: u16,
+}
+
+#[cfg(feature = "tokio")]
+impl<T> AsyncPrinter<T>
+where
+    T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    /// Create a new AsyncPrinter, talking to the printer over `io`.
+    pub async fn open(model: Model, io: T) -> Result<Self, AsyncWriteError> {
+        let io = Arc::new(Mutex::new(io));
+        let writer = AsyncWriter::open(model, Box::new(Shared(io.clone()))).await?;
+        Ok(Self {
+            io,
+            writer,
+            timeout: DEFAULT_TIMEOUT,
+            process_id: 0,
+        })
+    }
+
+    /// Set how long to wait for the printer to answer a request before
+    /// failing with [io::ErrorKind::TimedOut]. The default is 2 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Return the writer used to print.
+    pub fn writer(&mut self) -> &mut AsyncWriter {
+        &mut self.writer
+    }
+
+    /// Read exactly enough bytes from the printer to fill `buf`, waiting
+    /// up to the timeout for them to arrive.
+    async fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), AsyncWriteError> {
+        let read_all = async {
+            let mut read = 0;
+            while read < buf.len() {
+                let mut rb = tokio::io::ReadBuf::new(&mut buf[read..]);
+                let result = std::future::poll_fn(|cx| {
+                    std::pin::Pin::new(&mut *lock(&self.io)).poll_read(cx, &mut rb)
+                })
+                .await;
+                match result {
+                    Ok(()) if rb.filled().is_empty() => return Err(closed()),
+                    Ok(()) => {
+                        read += rb.filled().len();
+                        continue;
+                    }
+                    Err(err) if retry(&err) => {}
+                    Err(err) => return Err(err),
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            Ok(())
+        };
+        match tokio::time::timeout(self.timeout, read_all).await {
+            Ok(result) => Ok(result?),
+            Err(_) => Err(timed_out().into()),
+        }
+    }
+
+    async fn read_byte(&mut self) -> Result<u8, AsyncWriteError> {
+        let mut byte = [0u8];
+        self.read_exact(&mut byte).await?;
+        Ok(byte[0])
+    }
+
+    /// Read a text response, from the leading `0x5F` through the trailing
+    /// NUL.
+    async fn read_text(&mut self) -> Result<Vec<u8>, AsyncWriteError> {
+        let mut buf = vec![self.read_byte().await?];
+        if buf[0] != 0x5f {
+            return Err(Error::InvalidStatus.into());
+        }
+        loop {
+            let byte = self.read_byte().await?;
+            buf.push(byte);
+            if byte == 0x00 {
+                return Ok(buf);
+            }
+        }
+    }
+
+    /// Ask the printer for a real-time status of the provided kind. See
+    /// [Printer::real_time_status].
+    pub async fn real_time_status(
+        &mut self,
+        kind: StatusKind,
+    ) -> Result<RealTimeStatus, AsyncWriteError> {
+        self.writer.request_status(kind).await?;
+        loop {
+            let byte = self.read_byte().await?;
+            if AsbStatus::is_frame_start(byte) {
+                self.read_exact(&mut [0u8; 3]).await?;
+                continue;
+            }
+            return Ok(RealTimeStatus::parse(kind, byte)?);
+        }
+    }
+
+    /// Return the general status of the printer.
+    pub async fn status(&mut self) -> Result<PrinterStatus, AsyncWriteError> {
+        match self.real_time_status(StatusKind::Printer).await? {
+            RealTimeStatus::Printer(status) => Ok(status),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Return why the printer is offline, if it is.
+    pub async fn offline_status(&mut self) -> Result<OfflineStatus, AsyncWriteError> {
+        match self.real_time_status(StatusKind::OfflineCause).await? {
+            RealTimeStatus::Offline(status) => Ok(status),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Return which errors the printer is reporting.
+    pub async fn error_status(&mut self) -> Result<ErrorStatus, AsyncWriteError> {
+        match self.real_time_status(StatusKind::ErrorCause).await? {
+            RealTimeStatus::Error(status) => Ok(status),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Return the state of the paper roll sensors.
+    pub async fn paper_status(&mut self) -> Result<PaperStatus, AsyncWriteError> {
+        match self.real_time_status(StatusKind::PaperSensor).await? {
+            RealTimeStatus::Paper(status) => Ok(status),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Return true if the paper roll has run out.
+    pub async fn is_paper_out(&mut self) -> Result<bool, AsyncWriteError> {
+        Ok(self.paper_status().await?.paper_end)
+    }
+
+    /// Return the state of the drawer kick-out connector. See
+    /// [Printer::drawer_status].
+    pub async fn drawer_status(&mut self) -> Result<DrawerStatus, AsyncWriteError> {
+        Ok(self.status().await?.drawer())
+    }
+
+    /// Return true if the cash drawer is open, according to pin 3 of the
+    /// drawer kick-out connector.
+    pub async fn drawer_open(&mut self) -> Result<bool, AsyncWriteError> {
+        Ok(self.drawer_status().await?.pin3_high)
+    }
+
+    /// Check the cash drawer until it's closed, for up to `timeout`.
+    /// Returns false if the drawer was left open.
+    pub async fn wait_for_drawer_closed(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<bool, AsyncWriteError> {
+        let start = Instant::now();
+        while self.drawer_open().await? {
+            if start.elapsed() >= timeout {
+                return Ok(false);
+            }
+            tokio::time::sleep(DRAWER_POLL_INTERVAL).await;
+        }
+        Ok(true)
+    }
+
+    /// Return the state of the paper roll sensors once everything sent so
+    /// far has been processed, using `GS r`.
+    pub async fn transmitted_paper_status(&mut self) -> Result<PaperStatus, AsyncWriteError> {
+        self.writer
+            .transmit_status(TransmitStatusKind::Paper)
+            .await?;
+        Ok(PaperStatus::parse_transmitted(self.read_byte().await?)?)
+    }
+
+    /// Clear a recoverable error, and return the errors still being
+    /// reported afterwards. See [Printer::recover].
+    pub async fn recover(&mut self, cancel: bool) -> Result<ErrorStatus, AsyncWriteError> {
+        let status = self.error_status().await?;
+        if !status.recoverable && !status.autocutter {
+            return Ok(status);
+        }
+        self.writer.recover(cancel).await?;
+        self.error_status().await
+    }
+
+    /// Ask the printer for its maker, model name, firmware version and
+    /// serial number. See [Printer::identity].
+    pub async fn identity(&mut self) -> Result<Identity, AsyncWriteError> {
+        let serial_number = match self.info(InfoKind::SerialNumber).await {
+            Ok(info) => Some(Identity::text(info)?),
+            Err(AsyncWriteError::Tokio(err)) if err.kind() == io::ErrorKind::TimedOut => None,
+            Err(err) => return Err(err),
+        };
+        Ok(Identity {
+            maker: Identity::text(self.info(InfoKind::MakerName).await?)?,
+            model_name: Identity::text(self.info(InfoKind::ModelName).await?)?,
+            firmware_version: Identity::text(self.info(InfoKind::FirmwareVersion).await?)?,
+            serial_number,
+        })
+    }
+
+    /// Send everything written so far, and wait up to `timeout` for the
+    /// printer to finish printing it. See [Printer::flush_and_wait].
+    pub async fn flush_and_wait(&mut self, timeout: Duration) -> Result<(), AsyncWriteError> {
+        let id = next_process_id(&mut self.process_id);
+        self.writer.request_process_id(id).await?;
+        self.writer.flush().await?;
+
+        let saved = std::mem::replace(&mut self.timeout, timeout);
+        let result = self.wait_for_process(id).await;
+        self.timeout = saved;
+        result
+    }
+
+    /// Read responses until the printer sends back process ID `id`,
+    /// skipping ASB frames and responses to earlier requests.
+    async fn wait_for_process(&mut self, id: [u8; 4]) -> Result<(), AsyncWriteError> {
+        loop {
+            let byte = self.read_byte().await?;
+            if AsbStatus::is_frame_start(byte) {
+                self.read_exact(&mut [0u8; 3]).await?;
+                continue;
+            }
+            if byte != 0x37 {
+                return Err(Error::InvalidStatus.into());
+            }
+            let mut response = [0u8; 6];
+            self.read_exact(&mut response).await?;
+            match response {
+                [0x22, d1, d2, d3, d4, 0x00] if [d1, d2, d3, d4] == id => return Ok(()),
+                [0x22, .., 0x00] => continue,
+                _ => return Err(Error::InvalidStatus.into()),
+            }
+        }
+    }
+
+    /// Ask the printer for information about itself, such as its model
+    /// name or serial number.
+    pub async fn info(&mut self, kind: InfoKind) -> Result<PrinterInfo, AsyncWriteError> {
+        self.writer.request_printer_id(kind).await?;
+        let buf = if kind.is_text() {
+            self.read_text().await?
+        } else {
+            vec![self.read_byte().await?]
+        };
+        Ok(PrinterInfo::parse(kind, &buf)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockPrinter;
+
+    fn printer(mock: &MockPrinter) -> Printer<MockPrinter> {
+        Printer::open(Model::T20II, mock.clone())
+            .unwrap()
+            .with_timeout(Duration::from_millis(50))
+    }
+
+    #[test]
+    fn printer_status() {
+        let mock = MockPrinter::new().with_paper_lines(2);
+        let mut printer = printer(&mock);
+        assert!(!printer.status().unwrap().offline);
+        assert!(!printer.is_paper_out().unwrap());
+        assert!(!printer.drawer_open().unwrap());
+
+        printer.writer().write_all(b"one\ntwo\n").unwrap();
+        mock.set_drawer_open(true);
+        assert!(printer.is_paper_out().unwrap());
+        assert!(printer.transmitted_paper_status().unwrap().paper_end);
+        assert!(printer.offline_status().unwrap().paper_end_stop);
+        assert!(printer.drawer_open().unwrap());
+    }
+
+    /// Connection which ignores what's written, and reads back canned
+    /// responses.
+    struct Canned(std::collections::VecDeque<u8>);
+
+    impl Write for Canned {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Read for Canned {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.is_empty() {
+                true => Err(io::ErrorKind::WouldBlock.into()),
+                false => self.0.read(buf),
+            }
+        }
+    }
+
+    #[test]
+    fn printer_skips_asb() {
+        // An ASB frame, arriving before the answer.
+        let canned = Canned([0x10, 0x00, 0x00, 0x00, 0x1a].into());
+        let mut printer = Printer::open(Model::T20II, canned).unwrap();
+        assert!(printer.error_status().unwrap().autocutter);
+    }
+
+    #[test]
+    fn printer_tcp_timeout() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut printer = Printer::connect_tcp(Model::T20II, addr, &TcpOptions::default())
+            .unwrap()
+            .with_timeout(Duration::from_millis(200));
+        let (peer, _) = listener.accept().unwrap();
+
+        // The printer never answers.
+        let start = Instant::now();
+        let err = printer.status().unwrap_err();
+        assert!(matches!(err, WriteError::Io(ref err) if err.kind() == io::ErrorKind::TimedOut));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        // The printer closing the connection is reported, rather than
+        // waited out.
+        peer.shutdown(std::net::Shutdown::Write).unwrap();
+        let err = printer.status().unwrap_err();
+        assert!(
+            matches!(err, WriteError::Io(ref err) if err.kind() == io::ErrorKind::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn printer_flush_and_wait() {
+        let mock = MockPrinter::new();
+        let mut printer = printer(&mock);
+        printer.writer().write_all(b"one\n").unwrap();
+        printer.flush_and_wait(Duration::from_secs(1)).unwrap();
+        printer.flush_and_wait(Duration::from_secs(1)).unwrap();
+        assert_eq!("one\n", mock.printed_text());
+        assert_eq!(2, printer.process_id);
+
+        // A stale response is skipped, then the right one is waited for.
+        let canned = Canned(
+            b"\x37\x220001\x00\x37\x220002\x00"
+                .iter()
+                .copied()
+                .collect(),
+        );
+        let mut printer = Printer::open(Model::T20II, canned).unwrap();
+        printer.process_id = 1;
+        printer.flush_and_wait(Duration::from_millis(50)).unwrap();
+        assert!(printer.flush_and_wait(Duration::from_millis(50)).is_err());
+    }
+
+    #[test]
+    fn printer_recover() {
+        let mock = MockPrinter::new();
+        let mut printer = printer(&mock);
+        assert!(!printer.recover(false).unwrap().any());
+
+        let jam = ErrorStatus {
+            recoverable: false,
+            autocutter: true,
+            unrecoverable: false,
+            auto_recoverable: false,
+        };
+        mock.set_error(jam);
+        assert!(printer.status().unwrap().offline);
+        assert!(!printer.recover(false).unwrap().any());
+        assert!(!printer.status().unwrap().offline);
+
+        // Unrecoverable errors are left alone.
+        let broken = ErrorStatus {
+            unrecoverable: true,
+            ..jam
+        };
+        mock.set_error(broken);
+        assert_eq!(broken, printer.recover(true).unwrap());
+    }
+
+    #[test]
+    fn printer_drawer() {
+        let mock = MockPrinter::new();
+        let mut printer = printer(&mock);
+        assert_eq!(
+            DrawerStatus { pin3_high: false },
+            printer.drawer_status().unwrap()
+        );
+        assert!(printer.wait_for_drawer_closed(Duration::ZERO).unwrap());
+
+        mock.set_drawer_open(true);
+        assert!(printer.drawer_open().unwrap());
+        assert!(!printer
+            .wait_for_drawer_closed(Duration::from_millis(150))
+            .unwrap());
+
+        let closer = mock.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            closer.set_drawer_open(false);
+        });
+        assert!(printer
+            .wait_for_drawer_closed(Duration::from_secs(5))
+            .unwrap());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn printer_identity() {
+        let mock = MockPrinter::new()
+            .with_info(PrinterInfo::MakerName("EPSON".to_owned()))
+            .with_info(PrinterInfo::ModelName("TM-T20II".to_owned()))
+            .with_info(PrinterInfo::FirmwareVersion("1.00 ESC/POS".to_owned()));
+        let mut printer = printer(&mock);
+        let mut expected = Identity {
+            maker: "EPSON".to_owned(),
+            model_name: "TM-T20II".to_owned(),
+            firmware_version: "1.00 ESC/POS".to_owned(),
+            serial_number: None,
+        };
+        assert_eq!(expected, printer.identity().unwrap());
+
+        let mock = mock.with_info(PrinterInfo::SerialNumber("X5E1234567".to_owned()));
+        let mut printer = self::printer(&mock);
+        expected.serial_number = Some("X5E1234567".to_owned());
+        assert_eq!(expected, printer.identity().unwrap());
+    }
+
+    #[test]
+    fn printer_info() {
+        let mock = MockPrinter::new().with_info(PrinterInfo::ModelName("TM-T20II".to_owned()));
+        let mut printer = printer(&mock);
+        assert_eq!(
+            PrinterInfo::ModelName("TM-T20II".to_owned()),
+            printer.info(InfoKind::ModelName).unwrap()
+        );
+        assert!(matches!(
+            printer.info(InfoKind::SerialNumber),
+            Err(WriteError::Io(err)) if err.kind() == io::ErrorKind::TimedOut
+        ));
+    }
+}
+
+// vim: foldmethod=marker
@@ -0,0 +1,439 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+use super::{CharacterSet, CodePage, CutCommand, CutMode, PaperWidth, QrModel};
+
+#[cfg(feature = "escpos-printer-db")]
+use super::{json::Value, Error};
+
+/// Capabilities of a printer which isn't one of the [super::Model]
+/// variants. This starts off with the same safe defaults as
+/// [super::Model::Generic], and can be adjusted to describe the printer,
+/// before being used as a [super::Model::Custom].
+///
+/// ```
+/// use epson::{CapabilityProfile, CodePage, Model, QrModel};
+///
+/// let model = Model::Custom(
+///     CapabilityProfile::new()
+///         .max_image_width(576)
+///         .columns(48)
+///         .code_pages(&[CodePage::Pc437, CodePage::Pc858])
+///         .qr_models(&[QrModel::Model2]),
+/// );
+/// assert!(model.supports_code_page(CodePage::Pc858));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CapabilityProfile {
+    pub(crate) max_image_width: usize,
+    pub(crate) dots_per_mm: f32,
+    pub(crate) columns: usize,
+    pub(crate) paper_width: PaperWidth,
+    pub(crate) max_reverse_feed: u8,
+    pub(crate) speed_min: u8,
+    pub(crate) speed_max: u8,
+    code_pages: u64,
+    qr_models: u8,
+    pub(crate) full_cut: bool,
+    pub(crate) partial_cut: bool,
+    pub(crate) cut_command: CutCommand,
+    pub(crate) cash_drawer: bool,
+    pub(crate) unicode: bool,
+    pub(crate) text_size: bool,
+    pub(crate) rotation: bool,
+    pub(crate) buzzer: bool,
+    pub(crate) two_color: bool,
+    pub(crate) kanji: bool,
+    pub(crate) label_paper: bool,
+    pub(crate) label_peeler: bool,
+    pub(crate) character_effects: bool,
+    pub(crate) epos: bool,
+}
+
+impl Default for CapabilityProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CapabilityProfile {
+    /// Create a new profile, with the capabilities of
+    /// [super::Model::Generic].
+    pub fn new() -> Self {
+        Self {
+            max_image_width: 512,
+            dots_per_mm: 8.0,
+            columns: 48,
+            paper_width: PaperWidth::Mm80,
+            max_reverse_feed: 0,
+            speed_min: 1,
+            speed_max: 9,
+            code_pages: 1 << CodePage::Pc437 as u8,
+            qr_models: 0,
+            full_cut: true,
+            partial_cut: true,
+            cut_command: CutCommand::EscI,
+            cash_drawer: true,
+            unicode: false,
+            text_size: false,
+            rotation: false,
+            buzzer: false,
+            two_color: false,
+            kanji: false,
+            label_paper: false,
+            label_peeler: false,
+            character_effects: false,
+            epos: false,
+        }
+    }
+
+    /// Set the maximum image width, in pixels, when loaded with 80mm
+    /// paper.
+    pub fn max_image_width(mut self, width: usize) -> Self {
+        self.max_image_width = width;
+        self
+    }
+
+    /// Set the number of dots printed per millimeter of paper.
+    pub fn dots_per_mm(mut self, dots: f32) -> Self {
+        self.dots_per_mm = dots;
+        self
+    }
+
+    /// Set the number of characters which fit on a line, when loaded with
+    /// 80mm paper.
+    pub fn columns(mut self, columns: usize) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// Set the width of the paper loaded into the printer.
+    pub fn paper_width(mut self, width: PaperWidth) -> Self {
+        self.paper_width = width;
+        self
+    }
+
+    /// Set the maximum number of lines the printer can feed in reverse.
+    pub fn max_reverse_feed(mut self, lines: u8) -> Self {
+        self.max_reverse_feed = lines;
+        self
+    }
+
+    /// Set the range of print speeds the printer accepts.
+    pub fn speed_range(mut self, range: std::ops::RangeInclusive<u8>) -> Self {
+        self.speed_min = *range.start();
+        self.speed_max = *range.end();
+        self
+    }
+
+    /// Set the [CodePage]s the printer can print, replacing the defaults.
+    pub fn code_pages(mut self, pages: &[CodePage]) -> Self {
+        self.code_pages = pages.iter().fold(0, |acc, page| acc | 1 << *page as u8);
+        self
+    }
+
+    /// Set the [QrModel]s the printer can print, replacing the defaults.
+    /// By default, no QR codes are supported.
+    pub fn qr_models(mut self, models: &[QrModel]) -> Self {
+        self.qr_models = models.iter().fold(0, |acc, m| acc | qr_model_bit(*m));
+        self
+    }
+
+    /// Set the [CutMode]s the printer can perform, replacing the defaults.
+    /// Pass an empty slice for a printer without an autocutter.
+    pub fn cut_modes(mut self, modes: &[CutMode]) -> Self {
+        self.full_cut = modes.contains(&CutMode::Full);
+        self.partial_cut = modes.contains(&CutMode::Partial);
+        self
+    }
+
+    /// Set the [CutCommand] used to cut the paper. By default this is the
+    /// legacy `ESC i`, like [super::Model::Generic].
+    pub fn cut_command(mut self, command: CutCommand) -> Self {
+        self.cut_command = command;
+        self
+    }
+
+    /// Set if the printer has a cash drawer kick-out connector.
+    pub fn cash_drawer(mut self, state: bool) -> Self {
+        self.cash_drawer = state;
+        self
+    }
+
+    /// Set if the printer supports the [CharacterSet::Unicode] character
+    /// set.
+    pub fn unicode(mut self, state: bool) -> Self {
+        self.unicode = state;
+        self
+    }
+
+    /// Set if the printer supports text scaling with `GS !`.
+    pub fn text_size(mut self, state: bool) -> Self {
+        self.text_size = state;
+        self
+    }
+
+    /// Set if the printer supports 90 degree rotation.
+    pub fn rotation(mut self, state: bool) -> Self {
+        self.rotation = state;
+        self
+    }
+
+    /// Set if the printer has a buzzer.
+    pub fn buzzer(mut self, state: bool) -> Self {
+        self.buzzer = state;
+        self
+    }
+
+    /// Set if the printer supports two-color paper.
+    pub fn two_color(mut self, state: bool) -> Self {
+        self.two_color = state;
+        self
+    }
+
+    /// Set if the printer has kanji fonts.
+    pub fn kanji(mut self, state: bool) -> Self {
+        self.kanji = state;
+        self
+    }
+
+    /// Set if the printer can be loaded with label or black mark paper.
+    pub fn label_paper(mut self, state: bool) -> Self {
+        self.label_paper = state;
+        self
+    }
+
+    /// Set if the printer has a label peeler.
+    pub fn label_peeler(mut self, state: bool) -> Self {
+        self.label_peeler = state;
+        self
+    }
+
+    /// Set if the printer supports character color and shadow effects.
+    pub fn character_effects(mut self, state: bool) -> Self {
+        self.character_effects = state;
+        self
+    }
+
+    /// Set if the printer supports Epson ePOS.
+    pub fn epos(mut self, state: bool) -> Self {
+        self.epos = state;
+        self
+    }
+
+    pub(crate) fn supports_code_page(&self, page: CodePage) -> bool {
+        self.code_pages & (1 << page as u8) != 0
+    }
+
+    pub(crate) fn supports_qr_model(&self, m: QrModel) -> bool {
+        self.qr_models & qr_model_bit(m) != 0
+    }
+
+    pub(crate) fn supports_character_set(&self, c: CharacterSet) -> bool {
+        match c {
+            CharacterSet::Raw => true,
+            CharacterSet::Unicode => self.unicode,
+        }
+    }
+}
+
+#[cfg(feature = "escpos-printer-db")]
+impl CapabilityProfile {
+    /// Load the capabilities of the printer `name` from the
+    /// `capabilities.json` file of the community maintained
+    /// [escpos-printer-db](https://github.com/receipt-print-hq/escpos-printer-db).
+    ///
+    /// Code pages are only carried over if they're in the same `ESC t` slot
+    /// as the matching [CodePage], and anything the database doesn't know
+    /// keeps the [CapabilityProfile::new] default.
+    pub fn from_escpos_printer_db(json: &str, name: &str) -> Result<Self, Error> {
+        let db = Value::parse(json).map_err(|_| Error::InvalidProfile)?;
+        let printer = db
+            .get("profiles")
+            .and_then(|profiles| profiles.get(name))
+            .ok_or(Error::InvalidProfile)?;
+
+        let mut profile = Self::new();
+
+        if let Some(media) = printer.get("media") {
+            if let Some(dpi) = media.get("dpi").and_then(Value::as_f64) {
+                profile.dots_per_mm = (dpi / 25.4) as f32;
+            }
+            if let Some(width) = media
+                .get("width")
+                .and_then(|width| width.get("pixels"))
+                .and_then(Value::as_f64)
+            {
+                profile.max_image_width = width as usize;
+            }
+        }
+
+        if let Some(columns) = printer
+            .get("fonts")
+            .and_then(|fonts| fonts.get("0"))
+            .and_then(|font| font.get("columns"))
+            .and_then(Value::as_f64)
+        {
+            profile.columns = columns as usize;
+        }
+
+        if let Some(features) = printer.get("features") {
+            let feature = |key| features.get(key).and_then(Value::as_bool);
+            if let Some(state) = feature("paperFullCut") {
+                profile.full_cut = state;
+            }
+            if let Some(state) = feature("paperPartCut") {
+                profile.partial_cut = state;
+            }
+            // the database's cut features are for `GS V`, so only fall
+            // back to the legacy commands when it can't do a full cut.
+            profile.cut_command = match (profile.full_cut, profile.partial_cut) {
+                (true, _) => CutCommand::GsV,
+                (false, true) => CutCommand::EscM,
+                (false, false) => profile.cut_command,
+            };
+            if let Some(true) = feature("qrCode") {
+                profile = profile.qr_models(&[QrModel::Model1, QrModel::Model2]);
+            }
+            if let Some(state) = feature("pulseBel") {
+                profile.buzzer = state;
+            }
+        }
+
+        if let Some(colors) = printer.get("colors").and_then(Value::as_object) {
+            profile.two_color = colors.len() > 1;
+        }
+
+        if let Some(pages) = printer.get("codePages").and_then(Value::as_object) {
+            profile.code_pages = pages
+                .iter()
+                .filter_map(|(slot, name)| {
+                    let page = code_page_by_name(name.as_str()?)?;
+                    (slot.parse::<u8>().ok()? == page as u8).then_some(page)
+                })
+                .fold(0, |acc, page| acc | 1 << page as u8);
+        }
+
+        Ok(profile)
+    }
+}
+
+/// Map an escpos-printer-db encoding name onto a [CodePage].
+#[cfg(feature = "escpos-printer-db")]
+fn code_page_by_name(name: &str) -> Option<CodePage> {
+    Some(match name {
+        "CP437" => CodePage::Pc437,
+        "CP932" | "Katakana" => CodePage::Katakana,
+        "CP850" => CodePage::Pc850,
+        "CP860" => CodePage::Pc860,
+        "CP863" => CodePage::Pc863,
+        "CP865" => CodePage::Pc865,
+        "CP1252" => CodePage::Wpc1252,
+        "CP866" => CodePage::Pc866,
+        "CP852" => CodePage::Pc852,
+        "CP858" => CodePage::Pc858,
+        "TIS_42" => CodePage::Thai42,
+        "TIS_11" => CodePage::Thai11,
+        "TIS_18" => CodePage::Thai18,
+        "TCVN-3-1" => CodePage::Tcvn3,
+        "CP720" => CodePage::Pc720,
+        "CP864" => CodePage::Pc864,
+        "ISO_8859-15" => CodePage::Iso8859_15,
+        _ => return None,
+    })
+}
+
+fn qr_model_bit(m: QrModel) -> u8 {
+    1 << (m as u8 - QrModel::Model1 as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_defaults() {
+        let profile = CapabilityProfile::new();
+        assert!(profile.supports_code_page(CodePage::Pc437));
+        assert!(!profile.supports_code_page(CodePage::Pc858));
+        assert!(!profile.supports_qr_model(QrModel::Model2));
+        assert!(profile.full_cut);
+    }
+
+    #[test]
+    fn profile_sets() {
+        let profile = CapabilityProfile::new()
+            .code_pages(&[CodePage::Pc858, CodePage::Iso8859_15])
+            .qr_models(&[QrModel::Micro])
+            .cut_modes(&[CutMode::Partial]);
+        assert!(!profile.supports_code_page(CodePage::Pc437));
+        assert!(profile.supports_code_page(CodePage::Pc858));
+        assert!(profile.supports_code_page(CodePage::Iso8859_15));
+        assert!(!profile.supports_qr_model(QrModel::Model2));
+        assert!(profile.supports_qr_model(QrModel::Micro));
+        assert!(!profile.full_cut);
+        assert!(profile.partial_cut);
+    }
+
+    #[cfg(feature = "escpos-printer-db")]
+    #[test]
+    fn profile_from_escpos_printer_db() {
+        let json = r#"{
+            "profiles": {
+                "TM-T88V": {
+                    "name": "Epson TM-T88V",
+                    "vendor": "Epson",
+                    "features": {"paperFullCut": false, "paperPartCut": true, "qrCode": true},
+                    "media": {"dpi": 180, "width": {"mm": 80, "pixels": 512}},
+                    "fonts": {"0": {"name": "Font A", "columns": 42}},
+                    "colors": {"0": "black", "1": "red"},
+                    "codePages": {"0": "CP437", "2": "CP850", "16": "CP1252", "99": "CP858"}
+                },
+                "TM-T20II": {
+                    "features": {"paperFullCut": true, "paperPartCut": true}
+                }
+            }
+        }"#;
+        let profile = CapabilityProfile::from_escpos_printer_db(json, "TM-T88V").unwrap();
+        assert_eq!(512, profile.max_image_width);
+        assert_eq!(42, profile.columns);
+        assert!(!profile.full_cut);
+        assert!(profile.partial_cut);
+        assert_eq!(CutCommand::EscM, profile.cut_command);
+        assert!(profile.two_color);
+        assert!(profile.supports_qr_model(QrModel::Model2));
+        assert!(profile.supports_code_page(CodePage::Wpc1252));
+        assert!(!profile.supports_code_page(CodePage::Pc858));
+
+        let profile = CapabilityProfile::from_escpos_printer_db(json, "TM-T20II").unwrap();
+        assert_eq!(CutCommand::GsV, profile.cut_command);
+
+        assert_eq!(
+            Err(Error::InvalidProfile),
+            CapabilityProfile::from_escpos_printer_db(json, "TM-T20")
+        );
+        assert_eq!(
+            Err(Error::InvalidProfile),
+            CapabilityProfile::from_escpos_printer_db("{", "TM-T88V")
+        );
+    }
+}
+
+// vim: foldmethod=marker
@@ -0,0 +1,137 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Scanline filling of outlines, shared by the code which draws text and
+//! vector images into bitmaps to be printed.
+
+/// Number of samples taken along each axis of a pixel.
+pub(crate) const SAMPLES: usize = 4;
+
+/// A line of an outline, `[x0, y0, x1, y1]`, in pixels.
+pub(crate) type Line = [f32; 4];
+
+/// How to decide which parts of an outline are inside it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum FillRule {
+    /// Inside if the outline winds around the point any number of times.
+    NonZero,
+
+    /// Inside if the outline crosses an odd number of times between the
+    /// point and the outside.
+    EvenOdd,
+}
+
+/// Fill the area enclosed by `lines` in `img`. For each pixel touched,
+/// `paint` is called with the pixel's value and how many of its
+/// `SAMPLES * SAMPLES` samples are inside.
+pub(crate) fn fill(
+    img: &mut image::GrayImage,
+    lines: &[Line],
+    rule: FillRule,
+    mut paint: impl FnMut(&mut u8, usize),
+) {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for [x0, y0, x1, y1] in lines {
+        min_x = min_x.min(x0.min(*x1));
+        max_x = max_x.max(x0.max(*x1));
+        min_y = min_y.min(y0.min(*y1));
+        max_y = max_y.max(y0.max(*y1));
+    }
+    let left = min_x.floor().max(0.0) as usize;
+    let right = (max_x.ceil().max(0.0) as usize).min(img.width() as usize);
+    let top = min_y.floor().max(0.0) as usize;
+    let bottom = (max_y.ceil().max(0.0) as usize).min(img.height() as usize);
+    if left >= right || top >= bottom {
+        return;
+    }
+
+    let samples = (right - left) * SAMPLES;
+    let mut coverage = vec![0usize; right - left];
+    let mut crossings = vec![];
+    for py in top..bottom {
+        coverage.fill(0);
+        for sy in 0..SAMPLES {
+            let y = py as f32 + (sy as f32 + 0.5) / SAMPLES as f32;
+            crossings.clear();
+            for &[x0, y0, x1, y1] in lines {
+                if (y0 <= y) != (y1 <= y) {
+                    let x = x0 + (y - y0) / (y1 - y0) * (x1 - x0);
+                    crossings.push((x, if y1 > y0 { 1 } else { -1 }));
+                }
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let sample = |x: f32| {
+                (((x - left as f32) * SAMPLES as f32 - 0.5).ceil().max(0.0) as usize).min(samples)
+            };
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                let inside = match rule {
+                    FillRule::NonZero => winding != 0,
+                    FillRule::EvenOdd => winding % 2 != 0,
+                };
+                if inside {
+                    for s in sample(pair[0].0)..sample(pair[1].0) {
+                        coverage[s / SAMPLES] += 1;
+                    }
+                }
+            }
+        }
+        for (i, &covered) in coverage.iter().enumerate() {
+            if covered > 0 {
+                let pixel = img.get_pixel_mut((left + i) as u32, py as u32);
+                paint(&mut pixel.0[0], covered);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x0: f32, y0: f32, x1: f32, y1: f32) -> Vec<Line> {
+        vec![
+            [x0, y0, x1, y0],
+            [x1, y0, x1, y1],
+            [x1, y1, x0, y1],
+            [x0, y1, x0, y0],
+        ]
+    }
+
+    #[test]
+    fn fill_rules() {
+        // Two squares wound the same way, one inside the other.
+        let mut lines = square(0.0, 0.0, 8.0, 8.0);
+        lines.extend(square(2.0, 2.0, 6.0, 6.0));
+
+        for (rule, center) in [(FillRule::NonZero, 0), (FillRule::EvenOdd, 255)] {
+            let mut img = image::GrayImage::from_pixel(8, 8, image::Luma([255]));
+            fill(&mut img, &lines, rule, |pixel, covered| {
+                *pixel = 255 - (255 * covered / (SAMPLES * SAMPLES)) as u8
+            });
+            assert_eq!(0, img.get_pixel(0, 0).0[0]);
+            assert_eq!(center, img.get_pixel(4, 4).0[0]);
+        }
+    }
+}
+
+// vim: foldmethod=marker
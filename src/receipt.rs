@@ -0,0 +1,752 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+#[cfg(feature = "tokio")]
+use super::async_tokio::{AsyncWriter, Error as AsyncWriteError};
+use super::codepage::Encoder;
+use super::document::Style;
+use super::epson_image::fit_width;
+use super::wrap::{split_line, wrap_text};
+use super::write::Error as WriteError;
+use super::{Alignment, Barcode, Command, CutMode, Dither, Error, Model, QrCode, Writer};
+use image::GrayImage;
+
+/// Builder for the layout most receipts share: a header, line items, a
+/// rule, totals, a QR Code and a cut, without having to toggle each
+/// style by hand.
+///
+/// Lines are laid out in the model's columns of Font A text. The receipt
+/// can be turned into a list of [Command]s with [Receipt::build], or
+/// printed with [super::Writer::print_receipt].
+///
+/// ```rust
+/// # use epson::{Model, Receipt};
+/// let receipt = Receipt::new(Model::T20II)
+///     .header("HACK THE PLANET")
+///     .line_item("Coffee", "$3.50")
+///     .line_item("Bagel", "$2.25")
+///     .rule()
+///     .total("Total", "$5.75")
+///     .qr("https://example.com/r/1234")
+///     .cut();
+///
+/// let commands = receipt.build().unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Receipt {
+    model: Model,
+    commands: Vec<Command>,
+    copies: usize,
+    watermark: Option<String>,
+    encoder: Encoder,
+}
+
+impl Receipt {
+    /// Start a new, empty, receipt for the provided model.
+    pub fn new(model: Model) -> Self {
+        Self {
+            model,
+            commands: vec![],
+            copies: 1,
+            watermark: None,
+            encoder: Encoder::new(model),
+        }
+    }
+
+    /// Return the model this receipt is laid out for.
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Number of columns of normal sized text on a line.
+    fn columns(&self) -> usize {
+        self.model.get_columns()
+    }
+
+    fn push(mut self, commands: impl IntoIterator<Item = Command>) -> Self {
+        for cmd in commands {
+            self.encoder.track(&cmd);
+            self.commands.push(cmd);
+        }
+        self
+    }
+
+    /// Return the commands which print `text` on a line of its own,
+    /// converted into the model's code pages.
+    fn line(&mut self, text: &str) -> Vec<Command> {
+        self.encoder.text(&format!("{}\n", text))
+    }
+
+    /// Add a centered heading, in bold double sized text.
+    pub fn header(mut self, text: &str) -> Self {
+        let line = self.line(text);
+        self.push([
+            Command::Justification(Alignment::Center),
+            Command::TextSize {
+                width: 2,
+                height: 2,
+            },
+            Command::Emphasize(true),
+        ])
+        .push(line)
+        .push([
+            Command::Emphasize(false),
+            Command::TextSize {
+                width: 1,
+                height: 1,
+            },
+            Command::Justification(Alignment::Left),
+        ])
+    }
+
+    /// Add a line of plain text.
+    pub fn text(mut self, text: &str) -> Self {
+        let line = self.line(text);
+        self.push(line)
+    }
+
+    /// Add a line with the description on the left and the amount on the
+    /// right. Descriptions which don't fit are shortened.
+    pub fn line_item(mut self, description: &str, amount: &str) -> Self {
+        let line = split_line(description, amount, self.columns());
+        let line = self.line(&line);
+        self.push(line)
+    }
+
+    /// Add a dashed line across the receipt.
+    pub fn rule(mut self) -> Self {
+        let line = self.line(&"-".repeat(self.columns()));
+        self.push(line)
+    }
+
+    /// Add a line item in bold, for totals.
+    pub fn total(mut self, label: &str, amount: &str) -> Self {
+        let line = split_line(label, amount, self.columns());
+        let line = self.line(&line);
+        self.push([Command::Emphasize(true)])
+            .push(line)
+            .push([Command::Emphasize(false)])
+    }
+
+    /// Add a centered QR Code holding the provided data, such as a URL.
+    pub fn qr(self, data: &str) -> Self {
+        self.push([
+            Command::Justification(Alignment::Center),
+            Command::QrCode(QrCode::new(data.as_bytes())),
+            Command::Justification(Alignment::Left),
+        ])
+    }
+
+    /// Add a centered barcode.
+    pub fn barcode(self, barcode: Barcode) -> Self {
+        self.push([
+            Command::Justification(Alignment::Center),
+            Command::Barcode(barcode),
+            Command::Justification(Alignment::Left),
+        ])
+    }
+
+    /// Add a centered image, such as a logo, scaled down to fit the paper
+    /// if it's too wide.
+    pub fn image(self, img: GrayImage) -> Self {
+        let mut img = fit_width(img, self.model.get_max_image_width() as u32);
+        Dither::default().apply(&mut img);
+        self.push([
+            Command::Justification(Alignment::Center),
+            Command::Image(img, Default::default()),
+            Command::Justification(Alignment::Left),
+        ])
+    }
+
+    /// Add the lines of a [Section], such as a [Header] or [Footer].
+    pub fn section(self, section: &dyn Section) -> Self {
+        section.append(self)
+    }
+
+    /// Add centered lines of plain text.
+    fn centered(mut self, lines: &[String]) -> Self {
+        if lines.is_empty() {
+            return self;
+        }
+        let text: Vec<Command> = lines.iter().flat_map(|line| self.line(line)).collect();
+        self.push(
+            std::iter::once(Command::Justification(Alignment::Center))
+                .chain(text)
+                .chain(std::iter::once(Command::Justification(Alignment::Left))),
+        )
+    }
+
+    /// Feed the paper by `lines` lines.
+    pub fn feed(self, lines: u8) -> Self {
+        self.push([Command::Feed(lines)])
+    }
+
+    /// Cut the paper, using the [super::CutCommand] the model expects.
+    pub fn cut(self) -> Self {
+        let cut = self.model.get_cut_command().command();
+        self.push([cut])
+    }
+
+    /// Print `copies` copies of the receipt, cut apart. The receipt is
+    /// only built once, and repeated.
+    pub fn copies(self, copies: usize) -> Self {
+        Self { copies, ..self }
+    }
+
+    /// Print a centered, bold line of `text`, such as "COPY", at the top
+    /// of every copy after the first.
+    pub fn copy_watermark(self, text: &str) -> Self {
+        Self {
+            watermark: Some(text.to_owned()),
+            ..self
+        }
+    }
+
+    /// Return the commands which print this receipt, and its copies. This
+    /// will return an error if the receipt uses anything the model doesn't
+    /// support.
+    pub fn build(&self) -> Result<Vec<Command>, Error> {
+        for cmd in &self.commands {
+            let supported = match cmd {
+                Command::QrCode(qr) => self.model.supports_qr_model(qr.model),
+                Command::CutWith(mode, _) => self.model.supports_cut_mode(*mode),
+                Command::Cut | Command::LegacyPartialCut => {
+                    self.model.supports_cut_mode(CutMode::Full)
+                }
+                _ => true,
+            };
+            if !supported {
+                return Err(Error::Unsupported);
+            }
+        }
+
+        let ends_with_cut = matches!(
+            self.commands.last(),
+            Some(Command::Cut | Command::LegacyPartialCut | Command::CutWith(..))
+        );
+        let mut commands = vec![];
+        for copy in 0..self.copies {
+            if copy > 0 {
+                if !ends_with_cut {
+                    commands.push(self.model.get_cut_command().command());
+                }
+                if let Some(watermark) = &self.watermark {
+                    commands.extend([
+                        Command::Justification(Alignment::Center),
+                        Command::Emphasize(true),
+                    ]);
+                    // Each copy selects its own code page, so the watermark
+                    // can't rely on the one the previous copy left.
+                    commands.extend(Encoder::new(self.model).text(&format!("{}\n", watermark)));
+                    commands.extend([
+                        Command::Emphasize(false),
+                        Command::Justification(Alignment::Left),
+                    ]);
+                }
+            }
+            commands.extend(self.commands.iter().cloned());
+        }
+        Ok(commands)
+    }
+}
+
+/// Split long content, such as a warehouse pick list, across several
+/// pages cut apart from each other. Each page repeats the header lines,
+/// and ends with a "continued" line and its page number.
+///
+/// ```rust
+/// # use epson::{Model, Paginator};
+/// let items: Vec<String> = (1..=100).map(|i| format!("SKU {:05}  x1", i)).collect();
+/// let receipt = Paginator::new(Model::T20II, 40)
+///     .header("PICK LIST")
+///     .paginate(&items);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Paginator {
+    model: Model,
+    lines_per_page: usize,
+    header: Vec<String>,
+    continued: String,
+    page_numbers: bool,
+}
+
+impl Paginator {
+    /// Create a paginator for `model`, putting at most `lines_per_page`
+    /// lines of content on each page.
+    pub fn new(model: Model, lines_per_page: usize) -> Self {
+        Self {
+            model,
+            lines_per_page: lines_per_page.max(1),
+            header: vec![],
+            continued: "continued...".to_owned(),
+            page_numbers: true,
+        }
+    }
+
+    /// Add a line to print in bold at the top of every page, such as
+    /// column headings.
+    pub fn header(mut self, line: &str) -> Self {
+        self.header.push(line.to_owned());
+        self
+    }
+
+    /// Set the line printed at the bottom of every page but the last. The
+    /// default is "continued...".
+    pub fn continued(self, text: &str) -> Self {
+        Self {
+            continued: text.to_owned(),
+            ..self
+        }
+    }
+
+    /// Choose whether to print "page x of y" at the bottom of each page.
+    /// The default is to.
+    pub fn page_numbers(self, page_numbers: bool) -> Self {
+        Self {
+            page_numbers,
+            ..self
+        }
+    }
+
+    /// Lay out `lines` of content, one printed line each, onto pages.
+    pub fn paginate(&self, lines: &[String]) -> Receipt {
+        let pages: Vec<&[String]> = match lines.len() {
+            0 => vec![&[]],
+            _ => lines.chunks(self.lines_per_page).collect(),
+        };
+        let total = pages.len();
+
+        let mut receipt = Receipt::new(self.model);
+        for (page, lines) in pages.into_iter().enumerate() {
+            if !self.header.is_empty() {
+                receipt = receipt.push([Command::Emphasize(true)]);
+                receipt = self.header.iter().fold(receipt, |r, line| r.text(line));
+                receipt = receipt.push([Command::Emphasize(false)]);
+            }
+            receipt = lines.iter().fold(receipt, |r, line| r.text(line));
+
+            let mut footer = vec![];
+            if page + 1 < total {
+                footer.push(self.continued.clone());
+            }
+            if self.page_numbers {
+                footer.push(format!("page {} of {}", page + 1, total));
+            }
+            receipt = receipt.centered(&footer).cut();
+        }
+        receipt
+    }
+
+    /// Wrap `text` to the paper, and lay it out onto pages.
+    pub fn paginate_text(&self, text: &str) -> Receipt {
+        self.paginate(&wrap_text(text, self.model, &Style::default()))
+    }
+}
+
+/// A reusable part of a receipt, such as a [Header] or [Footer], which
+/// can be added to any [Receipt], or to every receipt printed with a
+/// [SectionedWriter].
+pub trait Section: Send + Sync {
+    /// Add this section to the end of `receipt`.
+    fn append(&self, receipt: Receipt) -> Receipt;
+}
+
+/// The top of a receipt: a logo, the name of the business and its
+/// address, all centered.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Header {
+    logo: Option<GrayImage>,
+    title: Option<String>,
+    lines: Vec<String>,
+}
+
+impl Header {
+    /// Create a new, empty, header.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a header with a logo, followed by the lines of an address.
+    pub fn with_logo_and_address(logo: GrayImage, address: &[&str]) -> Self {
+        address
+            .iter()
+            .fold(Self::new().logo(logo), |header, line| header.line(line))
+    }
+
+    /// Print an image, such as a logo, at the top of the header.
+    pub fn logo(self, logo: GrayImage) -> Self {
+        Self {
+            logo: Some(logo),
+            ..self
+        }
+    }
+
+    /// Print a title, such as the name of the business, in large text
+    /// under the logo.
+    pub fn title(self, title: &str) -> Self {
+        Self {
+            title: Some(title.to_owned()),
+            ..self
+        }
+    }
+
+    /// Add a line of text, such as part of the address.
+    pub fn line(mut self, line: &str) -> Self {
+        self.lines.push(line.to_owned());
+        self
+    }
+}
+
+impl Section for Header {
+    fn append(&self, mut receipt: Receipt) -> Receipt {
+        if let Some(logo) = &self.logo {
+            receipt = receipt.image(logo.clone());
+        }
+        if let Some(title) = &self.title {
+            receipt = receipt.header(title);
+        }
+        receipt.centered(&self.lines).feed(1)
+    }
+}
+
+/// The bottom of a receipt: a centered message, and a QR Code such as a
+/// link to a survey or the receipt online.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Footer {
+    lines: Vec<String>,
+    qr: Option<String>,
+}
+
+impl Footer {
+    /// Create a new, empty, footer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a footer with a QR Code holding `data`, followed by a
+    /// message, which may be more than one line.
+    pub fn with_qr_and_message(data: &str, message: &str) -> Self {
+        Self::new().qr(data).message(message)
+    }
+
+    /// Add a message, which may be more than one line.
+    pub fn message(mut self, message: &str) -> Self {
+        self.lines.extend(message.lines().map(str::to_owned));
+        self
+    }
+
+    /// Print a QR Code holding `data`, such as a URL.
+    pub fn qr(self, data: &str) -> Self {
+        Self {
+            qr: Some(data.to_owned()),
+            ..self
+        }
+    }
+}
+
+impl Section for Footer {
+    fn append(&self, receipt: Receipt) -> Receipt {
+        let receipt = receipt.feed(1);
+        let receipt = match &self.qr {
+            Some(data) => receipt.qr(data),
+            None => receipt,
+        };
+        receipt.centered(&self.lines)
+    }
+}
+
+/// Headers and footers which go around every receipt, used by a
+/// [SectionedWriter].
+#[derive(Default)]
+pub struct Sections {
+    headers: Vec<Box<dyn Section>>,
+    footers: Vec<Box<dyn Section>>,
+}
+
+impl Sections {
+    /// Create a new, empty, set of sections.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a section to print before each receipt.
+    pub fn header(mut self, section: impl Section + 'static) -> Self {
+        self.headers.push(Box::new(section));
+        self
+    }
+
+    /// Add a section to print after each receipt.
+    pub fn footer(mut self, section: impl Section + 'static) -> Self {
+        self.footers.push(Box::new(section));
+        self
+    }
+
+    /// Return `body` with the headers before it, and the footers and a
+    /// cut after it.
+    pub fn wrap(&self, body: &Receipt) -> Receipt {
+        let mut receipt = Receipt {
+            copies: body.copies,
+            watermark: body.watermark.clone(),
+            ..Receipt::new(body.model)
+        };
+        for header in &self.headers {
+            receipt = header.append(receipt);
+        }
+        receipt.commands.extend(body.commands.iter().cloned());
+        for footer in &self.footers {
+            receipt = footer.append(receipt);
+        }
+        receipt.cut()
+    }
+}
+
+/// A [Writer] which prints the same [Sections] around every receipt, so
+/// that headers and footers are set up once. Receipts printed with it
+/// shouldn't cut the paper themselves, since the cut comes after the
+/// footers.
+pub struct SectionedWriter {
+    writer: Writer,
+    sections: Sections,
+}
+
+impl SectionedWriter {
+    /// Wrap `writer`, printing `sections` around every receipt.
+    pub fn new(writer: Writer, sections: Sections) -> Self {
+        Self { writer, sections }
+    }
+
+    /// Print a receipt, wrapped in the headers and footers.
+    pub fn print_receipt(&mut self, receipt: &Receipt) -> Result<(), WriteError> {
+        self.writer.print_receipt(&self.sections.wrap(receipt))
+    }
+
+    /// Return the underlying writer, for anything other than receipts.
+    pub fn writer(&mut self) -> &mut Writer {
+        &mut self.writer
+    }
+
+    /// Stop wrapping receipts, and return the underlying writer.
+    pub fn into_inner(self) -> Writer {
+        self.writer
+    }
+}
+
+/// An [AsyncWriter] which prints the same [Sections] around every
+/// receipt, like a [SectionedWriter].
+#[cfg(feature = "tokio")]
+pub struct AsyncSectionedWriter {
+    writer: AsyncWriter,
+    sections: Sections,
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncSectionedWriter {
+    /// Wrap `writer`, printing `sections` around every receipt.
+    pub fn new(writer: AsyncWriter, sections: Sections) -> Self {
+        Self { writer, sections }
+    }
+
+    /// Print a receipt, wrapped in the headers and footers.
+    pub async fn print_receipt(&mut self, receipt: &Receipt) -> Result<(), AsyncWriteError> {
+        self.writer
+            .print_receipt(&self.sections.wrap(receipt))
+            .await
+    }
+
+    /// Return the underlying writer, for anything other than receipts.
+    pub fn writer(&mut self) -> &mut AsyncWriter {
+        &mut self.writer
+    }
+
+    /// Stop wrapping receipts, and return the underlying writer.
+    pub fn into_inner(self) -> AsyncWriter {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(text: &str) -> Command {
+        Command::Text(format!("{}\n", text).into_bytes())
+    }
+
+    #[test]
+    fn receipt_build() {
+        let commands = Receipt::new(Model::T20II)
+            .line_item("Coffee", "$3.50")
+            .rule()
+            .cut()
+            .build()
+            .unwrap();
+        assert_eq!(
+            vec![
+                Command::Text(format!("Coffee{}$3.50\n", " ".repeat(37)).into_bytes()),
+                Command::Text(format!("{}\n", "-".repeat(48)).into_bytes()),
+                Command::CutWith(CutMode::Full, None),
+            ],
+            commands
+        );
+    }
+
+    #[test]
+    fn receipt_code_page() {
+        let commands = Receipt::new(Model::T20II)
+            .text("Caf\u{e9}")
+            .text("\u{e9}")
+            .build()
+            .unwrap();
+        // é is 0x82 in PC437, selected before the first line which needs it.
+        assert_eq!(
+            vec![
+                Command::CodePage(crate::CodePage::Pc437),
+                Command::Text(b"Caf\x82\n".to_vec()),
+                Command::Text(b"\x82\n".to_vec()),
+            ],
+            commands
+        );
+    }
+
+    #[test]
+    fn receipt_58mm() {
+        let model = Model::T20II.with_paper_width(crate::PaperWidth::Mm58);
+        let commands = Receipt::new(model).rule().build().unwrap();
+        assert_eq!(
+            vec![Command::Text(
+                format!("{}\n", "-".repeat(model.get_columns())).into_bytes()
+            )],
+            commands
+        );
+    }
+
+    #[test]
+    fn receipt_sections() {
+        let sections = Sections::new()
+            .header(Header::new().title("Cafe").line("1 Main St"))
+            .footer(Footer::new().message("Thanks!"));
+        let body = Receipt::new(Model::T20II).text("Coffee");
+        let expected = Receipt::new(Model::T20II)
+            .header("Cafe")
+            .push([
+                Command::Justification(Alignment::Center),
+                line("1 Main St"),
+                Command::Justification(Alignment::Left),
+                Command::Feed(1),
+                line("Coffee"),
+                Command::Feed(1),
+                Command::Justification(Alignment::Center),
+                line("Thanks!"),
+                Command::Justification(Alignment::Left),
+            ])
+            .cut();
+        assert_eq!(expected, sections.wrap(&body));
+    }
+
+    #[test]
+    fn receipt_image_fits() {
+        let commands = Receipt::new(Model::T20II)
+            .section(&Header::with_logo_and_address(
+                GrayImage::new(1000, 100),
+                &[],
+            ))
+            .build()
+            .unwrap();
+        match &commands[1] {
+            Command::Image(img, _) => assert_eq!((576, 57), img.dimensions()),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn receipt_copies() {
+        let commands = Receipt::new(Model::T20II)
+            .text("Coffee")
+            .copies(3)
+            .copy_watermark("COPY")
+            .build()
+            .unwrap();
+        let watermark = [
+            Command::CutWith(CutMode::Full, None),
+            Command::Justification(Alignment::Center),
+            Command::Emphasize(true),
+            line("COPY"),
+            Command::Emphasize(false),
+            Command::Justification(Alignment::Left),
+        ];
+        let coffee = line("Coffee");
+        let mut expected = vec![coffee.clone()];
+        for _ in 0..2 {
+            expected.extend(watermark.iter().cloned());
+            expected.push(coffee.clone());
+        }
+        assert_eq!(expected, commands);
+
+        let cut = Receipt::new(Model::T20II).cut().copies(2).build().unwrap();
+        assert_eq!(vec![Command::CutWith(CutMode::Full, None); 2], cut);
+    }
+
+    #[test]
+    fn paginate_pages() {
+        let lines: Vec<String> = (1..=5).map(|i| i.to_string()).collect();
+        let receipt = Paginator::new(Model::T20II, 2)
+            .header("ITEM")
+            .paginate(&lines);
+        let page = |lines: &[&str], footer: &[&str]| {
+            let receipt = Receipt::new(Model::T20II)
+                .push([Command::Emphasize(true)])
+                .text("ITEM")
+                .push([Command::Emphasize(false)]);
+            let footer: Vec<String> = footer.iter().map(|s| s.to_string()).collect();
+            lines
+                .iter()
+                .fold(receipt, |r, line| r.text(line))
+                .centered(&footer)
+                .cut()
+                .commands
+        };
+        let expected = [
+            page(&["1", "2"], &["continued...", "page 1 of 3"]),
+            page(&["3", "4"], &["continued...", "page 2 of 3"]),
+            page(&["5"], &["page 3 of 3"]),
+        ]
+        .concat();
+        assert_eq!(expected, receipt.build().unwrap());
+    }
+
+    #[test]
+    fn paginate_empty() {
+        let receipt = Paginator::new(Model::T20II, 2)
+            .page_numbers(false)
+            .paginate(&[]);
+        assert_eq!(
+            vec![Command::CutWith(CutMode::Full, None)],
+            receipt.build().unwrap()
+        );
+    }
+
+    #[test]
+    fn receipt_unsupported() {
+        let receipt = Receipt::new(Model::Generic).qr("https://example.com");
+        assert_eq!(Err(Error::Unsupported), receipt.build());
+    }
+}
+
+// vim: foldmethod=marker
@@ -0,0 +1,143 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+use super::decode::decode_ranges;
+use std::io::Write;
+use std::time::Duration;
+
+/// How quickly a captured job is sent to the printer when it's replayed.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Pacing {
+    /// Send the job as fast as the printer will take it.
+    #[default]
+    Unpaced,
+
+    /// Wait this long after sending each command or run of text.
+    PerCommand(Duration),
+
+    /// Send the job at roughly this many bytes per second, such as to
+    /// reproduce a slow serial link.
+    BytesPerSecond(u32),
+}
+
+impl Pacing {
+    /// Return how long to wait after sending `len` bytes.
+    pub(crate) fn delay(&self, len: usize) -> Option<Duration> {
+        match self {
+            Pacing::Unpaced => None,
+            Pacing::PerCommand(delay) => Some(*delay),
+            Pacing::BytesPerSecond(rate) => {
+                Some(Duration::from_secs_f64(len as f64 / (*rate).max(1) as f64))
+            }
+        }
+    }
+}
+
+/// Split a captured job into the chunks it should be sent in: one for
+/// each command or run of text, exactly as they were captured.
+pub(crate) fn chunks(job: &[u8]) -> impl Iterator<Item = &[u8]> {
+    decode_ranges(job)
+        .into_iter()
+        .map(move |(range, _)| &job[range])
+}
+
+/// Replay a previously captured job, such as a `.bin` file of the raw
+/// bytes pulled out of a packet capture, to a [super::Writer] or any
+/// other `std::io::Write`.
+///
+/// The job is sent byte for byte as it was captured, split at command
+/// boundaries, and flushed after each command so pacing applies to what
+/// the printer sees.
+///
+/// ```rust,no_run
+/// # use epson::{replay, Model, Pacing, Writer};
+/// # use std::{net::TcpStream, time::Duration};
+/// let job = std::fs::read("job.bin").unwrap();
+/// let stream = TcpStream::connect("192.168.0.12:9100").unwrap();
+/// let mut pos = Writer::open(Model::T20II, Box::new(stream)).unwrap();
+/// replay(&mut pos, &job, Pacing::PerCommand(Duration::from_millis(10))).unwrap();
+/// ```
+pub fn replay<W: Write + ?Sized>(w: &mut W, job: &[u8], pacing: Pacing) -> std::io::Result<()> {
+    for chunk in chunks(job) {
+        w.write_all(chunk)?;
+        if let Some(delay) = pacing.delay(chunk.len()) {
+            w.flush()?;
+            std::thread::sleep(delay);
+        }
+    }
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CaptureWriter;
+    use crate::Command;
+
+    /// Writer which records the size of every write.
+    struct Writes(Vec<usize>);
+
+    impl Write for Writes {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.push(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn job() -> Vec<u8> {
+        [
+            Command::Init.as_bytes().unwrap(),
+            b"Hello\n".to_vec(),
+            // Not something this crate sends, but replayed as-is.
+            vec![0x1b, b'E', 0x01],
+            vec![0x1b, b'Z'],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn replay_exact_bytes() {
+        let mut capture = CaptureWriter::new();
+        replay(&mut capture, &job(), Pacing::Unpaced).unwrap();
+        capture.assert_bytes(&job());
+    }
+
+    #[test]
+    fn replay_splits_commands() {
+        let mut writes = Writes(vec![]);
+        replay(&mut writes, &job(), Pacing::PerCommand(Duration::ZERO)).unwrap();
+        assert_eq!(vec![2, 6, 3, 2], writes.0);
+    }
+
+    #[test]
+    fn pacing_delay() {
+        assert_eq!(None, Pacing::Unpaced.delay(100));
+        assert_eq!(
+            Some(Duration::from_millis(500)),
+            Pacing::BytesPerSecond(200).delay(100)
+        );
+    }
+}
+
+// vim: foldmethod=marker
@@ -0,0 +1,575 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! A print queue, so that orders survive the printer being switched off,
+//! out of paper or unplugged for a few minutes.
+//!
+//! Jobs submitted to a [PrintQueue] are saved to a directory before
+//! [PrintQueue::submit] returns, and printed in order by a background
+//! thread. If printing fails, the connection is dropped, and the job is
+//! retried on a new connection after [QueueOptions::retry_delay]. Jobs
+//! which were still waiting when the queue (or the whole program) was
+//! stopped are picked up again when the queue is next opened with the
+//! same directory.
+//!
+//! ```rust,no_run
+//! # use epson::{spooler::{PrintQueue, QueueOptions}, Model, PrintJob, Receipt};
+//! # use std::{io::Write, net::TcpStream, time::Duration};
+//! let queue = PrintQueue::open(
+//!     Model::T20II,
+//!     "/var/spool/epson",
+//!     &QueueOptions::default(),
+//!     || Ok(Box::new(TcpStream::connect("192.168.0.12:9100")?) as Box<dyn Write>),
+//! )
+//! .unwrap();
+//!
+//! let receipt = Receipt::new(Model::T20II).header("Order 42").cut();
+//! let id = queue.submit(PrintJob::from_receipt(&receipt).unwrap()).unwrap();
+//! queue.wait(id, Duration::from_secs(60));
+//! ```
+
+use super::write::Error as WriteError;
+use super::{Error, Model, PrintJob, Writer};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Settings for how a [PrintQueue] retries jobs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueueOptions {
+    /// How long to wait before trying to print a job again, after
+    /// printing it failed.
+    pub retry_delay: Duration,
+
+    /// How many times to try printing a job before giving up on it and
+    /// marking it [JobState::Failed]. If `None`, jobs are retried until
+    /// they print, so that no order is ever lost.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for QueueOptions {
+    fn default() -> Self {
+        Self {
+            retry_delay: Duration::from_secs(1),
+            max_attempts: None,
+        }
+    }
+}
+
+/// Identifies a job submitted to a [PrintQueue]. IDs increase in the
+/// order jobs were submitted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JobId(u64);
+
+impl std::fmt::Display for JobId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Where a job submitted to a [PrintQueue] is up to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum JobState {
+    /// Waiting for the jobs ahead of it to print.
+    Queued,
+
+    /// Being sent to the printer, or waiting to be retried.
+    Printing,
+
+    /// Sent to the printer.
+    Done,
+
+    /// Given up on after [QueueOptions::max_attempts] tries. The job is
+    /// kept in the queue's directory, with a `.failed` extension.
+    Failed,
+}
+
+impl JobState {
+    /// Return true if the queue is done with the job, whether or not it
+    /// printed.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobState::Done | JobState::Failed)
+    }
+}
+
+/// Opens a new connection to the printer.
+type Connect = Box<dyn FnMut() -> io::Result<Box<dyn Write>> + Send>;
+
+struct State {
+    queue: VecDeque<(JobId, PrintJob)>,
+    states: BTreeMap<JobId, JobState>,
+    next_id: u64,
+    stopped: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Set the state of a job, and wake anyone waiting on it.
+    fn set(&self, id: JobId, state: JobState) {
+        self.lock().states.insert(id, state);
+        self.changed.notify_all();
+    }
+
+    /// Sleep for `delay`, returning true if the queue was stopped in the
+    /// meantime.
+    fn sleep(&self, delay: Duration) -> bool {
+        let state = self.lock();
+        let (state, _) = self
+            .changed
+            .wait_timeout_while(state, delay, |state| !state.stopped)
+            .unwrap_or_else(|e| e.into_inner());
+        state.stopped
+    }
+}
+
+/// Queue of [PrintJob]s, saved to disk and printed in order by a
+/// background thread. See the [module documentation](self) for an
+/// example.
+///
+/// Dropping the queue stops the background thread once the job being
+/// sent (if any) is written. Jobs which haven't printed stay on disk.
+pub struct PrintQueue {
+    model: Model,
+    dir: PathBuf,
+    shared: Arc<Shared>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl PrintQueue {
+    /// Open the queue saved in `dir`, creating it if needed, and start
+    /// printing any jobs left in it. `connect` is called to connect to the
+    /// printer, and again to reconnect whenever printing fails.
+    pub fn open<F>(
+        model: Model,
+        dir: impl AsRef<Path>,
+        options: &QueueOptions,
+        connect: F,
+    ) -> Result<Self, WriteError>
+    where
+        F: FnMut() -> io::Result<Box<dyn Write>> + Send + 'static,
+    {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let mut state = State {
+            queue: VecDeque::new(),
+            states: BTreeMap::new(),
+            next_id: 1,
+            stopped: false,
+        };
+        // Failed and half-written jobs keep their IDs too, so a new job
+        // can never overwrite one of them.
+        for (id, _) in saved_files(&dir, &["job", "failed", "tmp"])? {
+            state.next_id = state.next_id.max(id.0 + 1);
+        }
+        for (id, path) in saved_jobs(&dir)? {
+            match PrintJob::from_bytes(model, &fs::read(&path)?) {
+                Ok(job) => {
+                    state.queue.push_back((id, job));
+                    state.states.insert(id, JobState::Queued);
+                }
+                Err(_) => {
+                    fs::rename(&path, path.with_extension("failed"))?;
+                    state.states.insert(id, JobState::Failed);
+                }
+            }
+        }
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(state),
+            changed: Condvar::new(),
+        });
+        let worker = Worker {
+            model,
+            dir: dir.clone(),
+            options: options.clone(),
+            shared: shared.clone(),
+            connect: Box::new(connect),
+        };
+        Ok(Self {
+            model,
+            dir,
+            shared,
+            worker: Some(thread::spawn(move || worker.run())),
+        })
+    }
+
+    /// Save `job` to the queue, to be printed after the jobs already in
+    /// it. The job must have been built for the queue's model.
+    pub fn submit(&self, job: PrintJob) -> Result<JobId, WriteError> {
+        if job.model() != self.model {
+            return Err(Error::Unsupported.into());
+        }
+
+        let mut state = self.shared.lock();
+        let id = JobId(state.next_id);
+        let path = job_path(&self.dir, id);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, job.as_bytes())?;
+        fs::rename(&tmp, &path)?;
+
+        state.next_id += 1;
+        state.queue.push_back((id, job));
+        state.states.insert(id, JobState::Queued);
+        self.shared.changed.notify_all();
+        Ok(id)
+    }
+
+    /// Return the state of the job `id`, or `None` if there's no such job.
+    pub fn state(&self, id: JobId) -> Option<JobState> {
+        self.shared.lock().states.get(&id).copied()
+    }
+
+    /// Return the state of every job the queue knows about, in the order
+    /// they were submitted.
+    pub fn jobs(&self) -> Vec<(JobId, JobState)> {
+        let state = self.shared.lock();
+        state.states.iter().map(|(id, s)| (*id, *s)).collect()
+    }
+
+    /// Return the number of jobs which haven't been printed yet.
+    pub fn pending(&self) -> usize {
+        self.shared.lock().queue.len()
+    }
+
+    /// Wait up to `timeout` for the job `id` to be printed or given up on,
+    /// and return its state.
+    pub fn wait(&self, id: JobId, timeout: Duration) -> Option<JobState> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.lock();
+        loop {
+            let current = state.states.get(&id).copied();
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if current.is_none_or(|s| s.is_finished()) || remaining.is_zero() {
+                return current;
+            }
+            state = self
+                .shared
+                .changed
+                .wait_timeout(state, remaining)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+}
+
+impl Drop for PrintQueue {
+    fn drop(&mut self) {
+        self.shared.lock().stopped = true;
+        self.shared.changed.notify_all();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn job_path(dir: &Path, id: JobId) -> PathBuf {
+    dir.join(format!("{:020}.job", id.0))
+}
+
+/// Return the jobs saved in `dir`, oldest first.
+fn saved_jobs(dir: &Path) -> io::Result<Vec<(JobId, PathBuf)>> {
+    saved_files(dir, &["job"])
+}
+
+/// Return the files in `dir` named after a job with one of `extensions`,
+/// oldest first.
+fn saved_files(dir: &Path, extensions: &[&str]) -> io::Result<Vec<(JobId, PathBuf)>> {
+    let mut jobs = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let wanted = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| extensions.contains(&ext));
+        if !wanted {
+            continue;
+        }
+        let id = path
+            .file_stem()
+            .and_then(|stem| stem.to_str()?.parse().ok());
+        if let Some(id) = id {
+            jobs.push((JobId(id), path));
+        }
+    }
+    jobs.sort();
+    Ok(jobs)
+}
+
+/// Background thread which prints the jobs in the queue.
+struct Worker {
+    model: Model,
+    dir: PathBuf,
+    options: QueueOptions,
+    shared: Arc<Shared>,
+    connect: Connect,
+}
+
+impl Worker {
+    fn run(mut self) {
+        // The Writer isn't Send, so it's only ever opened on this thread.
+        let mut writer = None;
+        loop {
+            let (id, job) = {
+                let state = self.shared.lock();
+                let state = self
+                    .shared
+                    .changed
+                    .wait_while(state, |state| state.queue.is_empty() && !state.stopped)
+                    .unwrap_or_else(|e| e.into_inner());
+                if state.stopped {
+                    return;
+                }
+                state.queue[0].clone()
+            };
+            self.shared.set(id, JobState::Printing);
+
+            let Some(done) = self.attempt(&mut writer, &job) else {
+                self.shared.set(id, JobState::Queued);
+                return;
+            };
+
+            let path = job_path(&self.dir, id);
+            let _ = if done {
+                fs::remove_file(&path)
+            } else {
+                fs::rename(&path, path.with_extension("failed"))
+            };
+            self.shared.lock().queue.pop_front();
+            self.shared.set(
+                id,
+                if done {
+                    JobState::Done
+                } else {
+                    JobState::Failed
+                },
+            );
+        }
+    }
+
+    /// Try to print `job` until it prints or the attempts run out,
+    /// returning whether it printed, or `None` if the queue was stopped.
+    fn attempt(&mut self, writer: &mut Option<Writer>, job: &PrintJob) -> Option<bool> {
+        let mut attempts = 0;
+        loop {
+            if self.print(writer, job).is_ok() {
+                return Some(true);
+            }
+            *writer = None;
+            attempts += 1;
+            if self.options.max_attempts.is_some_and(|max| attempts >= max) {
+                return Some(false);
+            }
+            if self.shared.sleep(self.options.retry_delay) {
+                return None;
+            }
+        }
+    }
+
+    fn print(&mut self, writer: &mut Option<Writer>, job: &PrintJob) -> Result<(), WriteError> {
+        let writer = match writer {
+            Some(writer) => writer,
+            None => writer.insert(Writer::open(self.model, (self.connect)()?)?),
+        };
+        writer.submit(job)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::CaptureWriter, Command};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("epson-spooler-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn options() -> QueueOptions {
+        QueueOptions {
+            retry_delay: Duration::from_millis(1),
+            max_attempts: None,
+        }
+    }
+
+    fn job(text: &str) -> PrintJob {
+        PrintJob::from_commands(Model::T20II, [Command::Text(text.as_bytes().to_vec())]).unwrap()
+    }
+
+    fn capture(capture: &CaptureWriter) -> impl FnMut() -> io::Result<Box<dyn Write>> {
+        let capture = capture.clone();
+        move || Ok(Box::new(capture.clone()) as Box<dyn Write>)
+    }
+
+    #[test]
+    fn prints_in_order() {
+        let dir = temp_dir("order");
+        let printed = CaptureWriter::new();
+        let queue = PrintQueue::open(Model::T20II, &dir, &options(), capture(&printed)).unwrap();
+
+        let first = queue.submit(job("one\n")).unwrap();
+        let second = queue.submit(job("two\n")).unwrap();
+        assert!(first < second);
+        assert_eq!(
+            Some(JobState::Done),
+            queue.wait(second, Duration::from_secs(5))
+        );
+        assert_eq!(Some(JobState::Done), queue.state(first));
+        assert_eq!(0, queue.pending());
+
+        printed.assert_commands(&[
+            Command::Init,
+            Command::Text(b"one\n".to_vec()),
+            Command::Text(b"two\n".to_vec()),
+        ]);
+        assert!(saved_jobs(&dir).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn retries() {
+        let dir = temp_dir("retry");
+        let printed = CaptureWriter::new();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let mut connect = capture(&printed);
+        let counter = attempts.clone();
+        let queue = PrintQueue::open(Model::T20II, &dir, &options(), move || {
+            if counter.fetch_add(1, Ordering::SeqCst) < 3 {
+                return Err(io::ErrorKind::ConnectionRefused.into());
+            }
+            connect()
+        })
+        .unwrap();
+
+        let id = queue.submit(job("one\n")).unwrap();
+        assert_eq!(Some(JobState::Done), queue.wait(id, Duration::from_secs(5)));
+        assert_eq!(4, attempts.load(Ordering::SeqCst));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn gives_up() {
+        let dir = temp_dir("fail");
+        let options = QueueOptions {
+            max_attempts: Some(2),
+            ..options()
+        };
+        let queue = PrintQueue::open(Model::T20II, &dir, &options, || {
+            Err(io::ErrorKind::ConnectionRefused.into())
+        })
+        .unwrap();
+
+        let id = queue.submit(job("one\n")).unwrap();
+        assert_eq!(
+            Some(JobState::Failed),
+            queue.wait(id, Duration::from_secs(5))
+        );
+        assert!(job_path(&dir, id).with_extension("failed").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn survives_restart() {
+        let dir = temp_dir("restart");
+        let queue = PrintQueue::open(Model::T20II, &dir, &options(), || {
+            Err(io::ErrorKind::ConnectionRefused.into())
+        })
+        .unwrap();
+        let id = queue.submit(job("one\n")).unwrap();
+        drop(queue);
+
+        let printed = CaptureWriter::new();
+        let queue = PrintQueue::open(Model::T20II, &dir, &options(), capture(&printed)).unwrap();
+        assert_eq!(Some(JobState::Done), queue.wait(id, Duration::from_secs(5)));
+        assert!(queue.submit(job("two\n")).unwrap() > id);
+        printed.assert_commands(&[Command::Init, Command::Text(b"one\n".to_vec())]);
+        drop(queue);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_failed_ids() {
+        let dir = temp_dir("failed-ids");
+        let options = QueueOptions {
+            max_attempts: Some(1),
+            ..options()
+        };
+        let queue = PrintQueue::open(Model::T20II, &dir, &options, || {
+            Err(io::ErrorKind::ConnectionRefused.into())
+        })
+        .unwrap();
+        let failed = queue.submit(job("one\n")).unwrap();
+        assert_eq!(
+            Some(JobState::Failed),
+            queue.wait(failed, Duration::from_secs(5))
+        );
+        drop(queue);
+
+        let failed_path = job_path(&dir, failed).with_extension("failed");
+        let saved = fs::read(&failed_path).unwrap();
+        let queue = PrintQueue::open(Model::T20II, &dir, &options, || {
+            Err(io::ErrorKind::ConnectionRefused.into())
+        })
+        .unwrap();
+        let id = queue.submit(job("two\n")).unwrap();
+        assert!(id > failed);
+        assert_eq!(
+            Some(JobState::Failed),
+            queue.wait(id, Duration::from_secs(5))
+        );
+        drop(queue);
+
+        assert_eq!(saved, fs::read(&failed_path).unwrap());
+        assert!(job_path(&dir, id).with_extension("failed").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn wrong_model() {
+        let dir = temp_dir("model");
+        let queue = PrintQueue::open(Model::T88VI, &dir, &options(), || {
+            Err(io::ErrorKind::ConnectionRefused.into())
+        })
+        .unwrap();
+        assert!(queue.submit(job("one\n")).is_err());
+        drop(queue);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+// vim: foldmethod=marker
@@ -0,0 +1,744 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+use super::Error;
+
+/// Kinds of status which can be requested in real time with `DLE EOT`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum StatusKind {
+    /// General printer status, see [PrinterStatus].
+    Printer = 1,
+
+    /// Reasons the printer is offline, see [OfflineStatus].
+    OfflineCause = 2,
+
+    /// Reasons the printer is in an error state, see [ErrorStatus].
+    ErrorCause = 3,
+
+    /// State of the paper roll sensors, see [PaperStatus].
+    PaperSensor = 4,
+}
+
+/// Kinds of status which can be requested with `GS r`. Unlike `DLE EOT`,
+/// these requests are processed in order with the rest of the data sent
+/// to the printer, so the response only arrives once everything before it
+/// has been processed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum TransmitStatusKind {
+    /// State of the paper roll sensors, see [PaperStatus::parse_transmitted].
+    Paper = 1,
+
+    /// State of the drawer kick-out connector.
+    Drawer = 2,
+}
+
+/// Check the bits which are fixed in every `DLE EOT` response. Bits 1 and
+/// 4 are always set, and bits 0 and 7 are always clear.
+fn check_fixed_bits(byte: u8) -> Result<(), Error> {
+    if byte & 0b1001_0011 != 0b0001_0010 {
+        return Err(Error::InvalidStatus);
+    }
+    Ok(())
+}
+
+/// Check if the bit at index `bit` is set in `byte`.
+fn bit(byte: u8, bit: u8) -> bool {
+    byte & (1 << bit) != 0
+}
+
+/// Return a byte with each bit in `bits` set if its flag is true.
+fn bits(bits: &[(u8, bool)]) -> u8 {
+    bits.iter()
+        .filter(|(_, set)| *set)
+        .fold(0, |byte, (bit, _)| byte | (1 << bit))
+}
+
+/// Write the names of the conditions which are true, separated by
+/// commas, or `none` if there aren't any.
+fn summary(
+    f: &mut std::fmt::Formatter<'_>,
+    conditions: &[(bool, &str)],
+    none: &str,
+) -> std::fmt::Result {
+    let names: Vec<&str> = conditions
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, name)| *name)
+        .collect();
+    if names.is_empty() {
+        return f.write_str(none);
+    }
+    f.write_str(&names.join(", "))
+}
+
+/// Printer status, as returned by [StatusKind::Printer].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PrinterStatus {
+    /// True if pin 3 of the drawer kick-out connector is high.
+    pub drawer_pin3_high: bool,
+
+    /// True if the printer is offline.
+    pub offline: bool,
+
+    /// True if the printer is waiting to be brought back online.
+    pub waiting_for_recovery: bool,
+
+    /// True if the paper feed button is being pressed.
+    pub feed_button_pressed: bool,
+}
+
+impl PrinterStatus {
+    /// Parse a response to a [StatusKind::Printer] request.
+    pub fn parse(byte: u8) -> Result<Self, Error> {
+        check_fixed_bits(byte)?;
+        Ok(Self {
+            drawer_pin3_high: bit(byte, 2),
+            offline: bit(byte, 3),
+            waiting_for_recovery: bit(byte, 5),
+            feed_button_pressed: bit(byte, 6),
+        })
+    }
+
+    /// Return the state of the drawer kick-out connector.
+    pub fn drawer(&self) -> DrawerStatus {
+        DrawerStatus {
+            pin3_high: self.drawer_pin3_high,
+        }
+    }
+
+    /// Return the `DLE EOT` response for this status.
+    pub(crate) fn to_byte(self) -> u8 {
+        0b0001_0010
+            | bits(&[
+                (2, self.drawer_pin3_high),
+                (3, self.offline),
+                (5, self.waiting_for_recovery),
+                (6, self.feed_button_pressed),
+            ])
+    }
+}
+
+impl std::fmt::Display for PrinterStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.offline { "offline" } else { "online" })?;
+        for (set, name) in [
+            (self.waiting_for_recovery, "waiting for recovery"),
+            (self.feed_button_pressed, "feed button pressed"),
+        ] {
+            if set {
+                write!(f, ", {}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Causes of the printer being offline, as returned by
+/// [StatusKind::OfflineCause].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OfflineStatus {
+    /// True if the cover is open.
+    pub cover_open: bool,
+
+    /// True if paper is being fed by the paper feed button.
+    pub feeding_by_button: bool,
+
+    /// True if printing stopped because the paper ran out.
+    pub paper_end_stop: bool,
+
+    /// True if an error has occurred.
+    pub error: bool,
+}
+
+impl OfflineStatus {
+    /// Parse a response to a [StatusKind::OfflineCause] request.
+    pub fn parse(byte: u8) -> Result<Self, Error> {
+        check_fixed_bits(byte)?;
+        Ok(Self {
+            cover_open: bit(byte, 2),
+            feeding_by_button: bit(byte, 3),
+            paper_end_stop: bit(byte, 5),
+            error: bit(byte, 6),
+        })
+    }
+
+    /// Return the `DLE EOT` response for this status.
+    pub(crate) fn to_byte(self) -> u8 {
+        0b0001_0010
+            | bits(&[
+                (2, self.cover_open),
+                (3, self.feeding_by_button),
+                (5, self.paper_end_stop),
+                (6, self.error),
+            ])
+    }
+}
+
+impl std::fmt::Display for OfflineStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        summary(
+            f,
+            &[
+                (self.cover_open, "cover open"),
+                (self.feeding_by_button, "feeding by button"),
+                (self.paper_end_stop, "stopped at paper end"),
+                (self.error, "error"),
+            ],
+            "no offline causes",
+        )
+    }
+}
+
+/// Causes of an error, as returned by [StatusKind::ErrorCause].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ErrorStatus {
+    /// True if a recoverable error has occurred.
+    pub recoverable: bool,
+
+    /// True if the autocutter has failed, such as from a paper jam.
+    pub autocutter: bool,
+
+    /// True if an unrecoverable error has occurred.
+    pub unrecoverable: bool,
+
+    /// True if an error which will automatically recover has occurred,
+    /// such as the print head overheating.
+    pub auto_recoverable: bool,
+}
+
+impl ErrorStatus {
+    /// Parse a response to a [StatusKind::ErrorCause] request.
+    pub fn parse(byte: u8) -> Result<Self, Error> {
+        check_fixed_bits(byte)?;
+        Ok(Self {
+            recoverable: bit(byte, 2),
+            autocutter: bit(byte, 3),
+            unrecoverable: bit(byte, 5),
+            auto_recoverable: bit(byte, 6),
+        })
+    }
+
+    /// Return true if any error is being reported.
+    pub fn any(&self) -> bool {
+        self.recoverable || self.autocutter || self.unrecoverable || self.auto_recoverable
+    }
+
+    /// Return the error bits shared by the `DLE EOT` response and the
+    /// second byte of an ASB frame.
+    fn error_bits(self) -> u8 {
+        bits(&[
+            (2, self.recoverable),
+            (3, self.autocutter),
+            (5, self.unrecoverable),
+            (6, self.auto_recoverable),
+        ])
+    }
+
+    /// Return the `DLE EOT` response for this status.
+    pub(crate) fn to_byte(self) -> u8 {
+        0b0001_0010 | self.error_bits()
+    }
+}
+
+impl std::fmt::Display for ErrorStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        summary(
+            f,
+            &[
+                (self.recoverable, "recoverable error"),
+                (self.autocutter, "autocutter error"),
+                (self.unrecoverable, "unrecoverable error"),
+                (self.auto_recoverable, "auto-recoverable error"),
+            ],
+            "no errors",
+        )
+    }
+}
+
+/// Paper roll sensor state, as returned by [StatusKind::PaperSensor].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PaperStatus {
+    /// True if the paper roll is nearly empty.
+    pub near_end: bool,
+
+    /// True if the paper roll has run out.
+    pub paper_end: bool,
+}
+
+impl PaperStatus {
+    /// Parse a response to a [StatusKind::PaperSensor] request.
+    pub fn parse(byte: u8) -> Result<Self, Error> {
+        check_fixed_bits(byte)?;
+        Ok(Self {
+            near_end: byte & 0b0000_1100 != 0,
+            paper_end: byte & 0b0110_0000 != 0,
+        })
+    }
+
+    /// Parse a response to a [TransmitStatusKind::Paper] request.
+    pub fn parse_transmitted(byte: u8) -> Result<Self, Error> {
+        if byte & 0b1001_0000 != 0 {
+            return Err(Error::InvalidStatus);
+        }
+        Ok(Self {
+            near_end: byte & 0b0000_0011 != 0,
+            paper_end: byte & 0b0000_1100 != 0,
+        })
+    }
+
+    /// Return the `DLE EOT` response for this status.
+    pub(crate) fn to_byte(self) -> u8 {
+        0b0001_0010 | (self.near_end as u8 * 0b0000_1100) | (self.paper_end as u8 * 0b0110_0000)
+    }
+
+    /// Return the `GS r` response for this status, which is also the third
+    /// byte of an ASB frame.
+    pub(crate) fn to_transmitted_byte(self) -> u8 {
+        (self.near_end as u8 * 0b0000_0011) | (self.paper_end as u8 * 0b0000_1100)
+    }
+}
+
+impl std::fmt::Display for PaperStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.paper_end {
+            "paper out"
+        } else if self.near_end {
+            "paper near end"
+        } else {
+            "paper ok"
+        })
+    }
+}
+
+/// State of the drawer kick-out connector, as returned by
+/// [TransmitStatusKind::Drawer], or included in a [PrinterStatus] or
+/// [AsbStatus].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DrawerStatus {
+    /// True if pin 3 of the drawer kick-out connector is high. Whether
+    /// this means the drawer is open or closed depends on the drawer, but
+    /// most report high when open.
+    pub pin3_high: bool,
+}
+
+impl DrawerStatus {
+    /// Parse a response to a [TransmitStatusKind::Drawer] request.
+    pub fn parse(byte: u8) -> Result<Self, Error> {
+        if byte & 0b1001_0000 != 0 {
+            return Err(Error::InvalidStatus);
+        }
+        Ok(Self {
+            pin3_high: bit(byte, 0),
+        })
+    }
+
+    /// Return the `GS r` response for this status.
+    pub(crate) fn to_byte(self) -> u8 {
+        self.pin3_high as u8
+    }
+}
+
+impl std::fmt::Display for DrawerStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.pin3_high {
+            "drawer pin 3 high"
+        } else {
+            "drawer pin 3 low"
+        })
+    }
+}
+
+/// Response to a real-time status request.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RealTimeStatus {
+    /// Response to [StatusKind::Printer].
+    Printer(PrinterStatus),
+
+    /// Response to [StatusKind::OfflineCause].
+    Offline(OfflineStatus),
+
+    /// Response to [StatusKind::ErrorCause].
+    Error(ErrorStatus),
+
+    /// Response to [StatusKind::PaperSensor].
+    Paper(PaperStatus),
+}
+
+impl RealTimeStatus {
+    /// Return the byte the printer sends for this status.
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            RealTimeStatus::Printer(status) => status.to_byte(),
+            RealTimeStatus::Offline(status) => status.to_byte(),
+            RealTimeStatus::Error(status) => status.to_byte(),
+            RealTimeStatus::Paper(status) => status.to_byte(),
+        }
+    }
+
+    /// Parse the byte returned by the printer in response to a real-time
+    /// status request of the provided [StatusKind].
+    pub fn parse(kind: StatusKind, byte: u8) -> Result<Self, Error> {
+        Ok(match kind {
+            StatusKind::Printer => RealTimeStatus::Printer(PrinterStatus::parse(byte)?),
+            StatusKind::OfflineCause => RealTimeStatus::Offline(OfflineStatus::parse(byte)?),
+            StatusKind::ErrorCause => RealTimeStatus::Error(ErrorStatus::parse(byte)?),
+            StatusKind::PaperSensor => RealTimeStatus::Paper(PaperStatus::parse(byte)?),
+        })
+    }
+}
+
+/// Status changes which the printer will report automatically, once
+/// Automatic Status Back (ASB) is enabled with `GS a`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct AsbFlags {
+    /// Report changes to the drawer kick-out connector pin 3.
+    pub drawer: bool,
+
+    /// Report the printer going online or offline.
+    pub online: bool,
+
+    /// Report errors.
+    pub error: bool,
+
+    /// Report changes to the paper roll sensors.
+    pub paper: bool,
+}
+
+impl AsbFlags {
+    /// Report every kind of status change.
+    pub fn all() -> Self {
+        Self {
+            drawer: true,
+            online: true,
+            error: true,
+            paper: true,
+        }
+    }
+
+    /// Return the flags as the `GS a` parameter byte.
+    pub(crate) fn bits(&self) -> u8 {
+        (self.drawer as u8)
+            | (self.online as u8) << 1
+            | (self.error as u8) << 2
+            | (self.paper as u8) << 3
+    }
+}
+
+/// Status frame sent by the printer whenever the status changes, once
+/// Automatic Status Back (ASB) has been enabled.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AsbStatus {
+    /// True if pin 3 of the drawer kick-out connector is high.
+    pub drawer_pin3_high: bool,
+
+    /// True if the printer is offline.
+    pub offline: bool,
+
+    /// True if the cover is open.
+    pub cover_open: bool,
+
+    /// True if paper is being fed by the paper feed button.
+    pub feeding_by_button: bool,
+
+    /// Errors the printer is reporting.
+    pub error: ErrorStatus,
+
+    /// State of the paper roll sensors.
+    pub paper: PaperStatus,
+}
+
+impl std::fmt::Display for RealTimeStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RealTimeStatus::Printer(status) => status.fmt(f),
+            RealTimeStatus::Offline(status) => status.fmt(f),
+            RealTimeStatus::Error(status) => status.fmt(f),
+            RealTimeStatus::Paper(status) => status.fmt(f),
+        }
+    }
+}
+
+impl AsbStatus {
+    /// Return true if `byte` could be the first byte of an ASB frame. This
+    /// can be used to find the start of a frame when reading a stream of
+    /// responses from the printer.
+    pub fn is_frame_start(byte: u8) -> bool {
+        byte & 0b1001_0011 == 0b0001_0000
+    }
+
+    /// Parse a 4 byte ASB status frame.
+    pub fn parse(frame: &[u8; 4]) -> Result<Self, Error> {
+        if !Self::is_frame_start(frame[0]) || frame[1..].iter().any(|b| b & 0b1001_0000 != 0) {
+            return Err(Error::InvalidStatus);
+        }
+
+        let [b1, b2, b3, _] = *frame;
+        Ok(Self {
+            drawer_pin3_high: bit(b1, 2),
+            offline: bit(b1, 3),
+            cover_open: bit(b1, 5),
+            feeding_by_button: bit(b1, 6),
+            error: ErrorStatus {
+                recoverable: bit(b2, 2),
+                autocutter: bit(b2, 3),
+                unrecoverable: bit(b2, 5),
+                auto_recoverable: bit(b2, 6),
+            },
+            paper: PaperStatus {
+                near_end: b3 & 0b0000_0011 != 0,
+                paper_end: b3 & 0b0000_1100 != 0,
+            },
+        })
+    }
+
+    /// Return the state of the drawer kick-out connector.
+    pub fn drawer(&self) -> DrawerStatus {
+        DrawerStatus {
+            pin3_high: self.drawer_pin3_high,
+        }
+    }
+
+    /// Return the 4 byte ASB frame for this status.
+    pub(crate) fn to_frame(self) -> [u8; 4] {
+        [
+            0b0001_0000
+                | bits(&[
+                    (2, self.drawer_pin3_high),
+                    (3, self.offline),
+                    (5, self.cover_open),
+                    (6, self.feeding_by_button),
+                ]),
+            self.error.error_bits(),
+            self.paper.to_transmitted_byte(),
+            0,
+        ]
+    }
+}
+
+impl std::fmt::Display for AsbStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.offline { "offline" } else { "online" })?;
+        for (set, name) in [
+            (self.cover_open, "cover open"),
+            (self.feeding_by_button, "feeding by button"),
+        ] {
+            if set {
+                write!(f, ", {}", name)?;
+            }
+        }
+        if self.error.any() {
+            write!(f, ", {}", self.error)?;
+        }
+        write!(f, ", {}, {}", self.paper, self.drawer())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asb_flags_bits() {
+        assert_eq!(0x00, AsbFlags::default().bits());
+        assert_eq!(0x0f, AsbFlags::all().bits());
+        assert_eq!(
+            0x0a,
+            AsbFlags {
+                online: true,
+                paper: true,
+                ..Default::default()
+            }
+            .bits()
+        );
+    }
+
+    #[test]
+    fn parse_asb_status() {
+        let status = AsbStatus::parse(&[0x10, 0x00, 0x00, 0x00]).unwrap();
+        assert!(!status.offline);
+        assert!(!status.cover_open);
+        assert!(!status.paper.paper_end);
+
+        let status = AsbStatus::parse(&[0x38, 0x08, 0x0f, 0x00]).unwrap();
+        assert!(status.offline);
+        assert!(status.cover_open);
+        assert!(status.error.autocutter);
+        assert!(status.paper.near_end);
+        assert!(status.paper.paper_end);
+
+        assert_eq!(
+            Err(Error::InvalidStatus),
+            AsbStatus::parse(&[0x12, 0x00, 0x00, 0x00])
+        );
+        assert_eq!(
+            Err(Error::InvalidStatus),
+            AsbStatus::parse(&[0x10, 0x10, 0x00, 0x00])
+        );
+    }
+
+    #[test]
+    fn parse_printer_status() {
+        assert_eq!(
+            RealTimeStatus::Printer(PrinterStatus {
+                drawer_pin3_high: false,
+                offline: false,
+                waiting_for_recovery: false,
+                feed_button_pressed: false,
+            }),
+            RealTimeStatus::parse(StatusKind::Printer, 0x12).unwrap()
+        );
+        assert_eq!(
+            PrinterStatus {
+                drawer_pin3_high: true,
+                offline: true,
+                waiting_for_recovery: false,
+                feed_button_pressed: false,
+            },
+            PrinterStatus::parse(0x1e).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_offline_status() {
+        let status = OfflineStatus::parse(0x36).unwrap();
+        assert!(status.cover_open);
+        assert!(!status.feeding_by_button);
+        assert!(status.paper_end_stop);
+        assert!(!status.error);
+    }
+
+    #[test]
+    fn parse_error_status() {
+        let status = ErrorStatus::parse(0x1a).unwrap();
+        assert!(!status.recoverable);
+        assert!(status.autocutter);
+        assert!(!status.unrecoverable);
+        assert!(!status.auto_recoverable);
+    }
+
+    #[test]
+    fn parse_paper_status() {
+        assert_eq!(
+            PaperStatus {
+                near_end: false,
+                paper_end: false
+            },
+            PaperStatus::parse(0x12).unwrap()
+        );
+        assert_eq!(
+            PaperStatus {
+                near_end: true,
+                paper_end: true
+            },
+            PaperStatus::parse(0x7e).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_transmitted_paper_status() {
+        assert_eq!(
+            PaperStatus {
+                near_end: false,
+                paper_end: false
+            },
+            PaperStatus::parse_transmitted(0x00).unwrap()
+        );
+        assert_eq!(
+            PaperStatus {
+                near_end: true,
+                paper_end: true
+            },
+            PaperStatus::parse_transmitted(0x0f).unwrap()
+        );
+        assert_eq!(
+            Err(Error::InvalidStatus),
+            PaperStatus::parse_transmitted(0x10)
+        );
+    }
+
+    #[test]
+    fn parse_drawer_status() {
+        assert_eq!(
+            DrawerStatus { pin3_high: true },
+            DrawerStatus::parse(0x01).unwrap()
+        );
+        assert_eq!(
+            DrawerStatus { pin3_high: false },
+            DrawerStatus::parse(0x00).unwrap()
+        );
+        assert_eq!(Err(Error::InvalidStatus), DrawerStatus::parse(0x80));
+    }
+
+    #[test]
+    fn status_round_trip() {
+        for byte in 0..=0xff {
+            for kind in [
+                StatusKind::Printer,
+                StatusKind::OfflineCause,
+                StatusKind::ErrorCause,
+            ] {
+                if let Ok(status) = RealTimeStatus::parse(kind, byte) {
+                    assert_eq!(byte, status.to_byte());
+                }
+            }
+            if let Ok(status) = PaperStatus::parse_transmitted(byte) {
+                assert_eq!(
+                    status,
+                    PaperStatus::parse_transmitted(status.to_transmitted_byte()).unwrap()
+                );
+            }
+        }
+
+        let frame = [0x38, 0x08, 0x0f, 0x00];
+        assert_eq!(frame, AsbStatus::parse(&frame).unwrap().to_frame());
+    }
+
+    #[test]
+    fn status_summary() {
+        assert_eq!("online", PrinterStatus::parse(0x12).unwrap().to_string());
+        assert_eq!(
+            "offline, waiting for recovery",
+            PrinterStatus::parse(0x3a).unwrap().to_string()
+        );
+        assert_eq!(
+            "cover open, stopped at paper end",
+            OfflineStatus::parse(0x36).unwrap().to_string()
+        );
+        assert_eq!("no errors", ErrorStatus::parse(0x12).unwrap().to_string());
+        assert_eq!("paper out", PaperStatus::parse(0x7e).unwrap().to_string());
+        assert_eq!(
+            "offline, cover open, autocutter error, paper out, drawer pin 3 low",
+            AsbStatus::parse(&[0x38, 0x08, 0x0f, 0x00])
+                .unwrap()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn parse_invalid_status() {
+        for byte in [0x00, 0x13, 0x92, 0xff] {
+            assert_eq!(Err(Error::InvalidStatus), PrinterStatus::parse(byte));
+        }
+    }
+}
+
+// vim: foldmethod=marker
@@ -0,0 +1,252 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+use super::codepage::Encoder;
+use super::{Command, Model};
+
+/// A run of text printed with a single set of character styles, as part
+/// of [StyledText].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    /// Text to print.
+    pub text: String,
+
+    /// Print the text in bold.
+    pub bold: bool,
+
+    /// Underline the text.
+    pub underline: bool,
+
+    /// Print the text white on black.
+    pub reverse: bool,
+
+    /// Character width multiplier, from 1 through 8.
+    pub width: u8,
+
+    /// Character height multiplier, from 1 through 8.
+    pub height: u8,
+}
+
+impl Span {
+    /// Create a new span of plain, normal sized text.
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_owned(),
+            bold: false,
+            underline: false,
+            reverse: false,
+            width: 1,
+            height: 1,
+        }
+    }
+
+    /// Print this span in bold.
+    pub fn bold(self) -> Self {
+        Self { bold: true, ..self }
+    }
+
+    /// Underline this span.
+    pub fn underline(self) -> Self {
+        Self {
+            underline: true,
+            ..self
+        }
+    }
+
+    /// Print this span white on black.
+    pub fn reverse(self) -> Self {
+        Self {
+            reverse: true,
+            ..self
+        }
+    }
+
+    /// Scale the characters of this span. Both `width` and `height` are
+    /// multipliers between 1 (normal size) and 8.
+    pub fn size(self, width: u8, height: u8) -> Self {
+        Self {
+            width,
+            height,
+            ..self
+        }
+    }
+}
+
+impl From<&str> for Span {
+    fn from(text: &str) -> Self {
+        Self::new(text)
+    }
+}
+
+/// Text built from [Span]s which each carry their own style, such as a
+/// bold label followed by a plain value.
+///
+/// When printed, only the styles which change between spans are sent to
+/// the printer, and every style is back to normal afterwards. Spans may
+/// contain line feeds.
+///
+/// ```rust
+/// # use epson::{Span, StyledText};
+/// let text = StyledText::new()
+///     .span(Span::new("Total: ").bold())
+///     .span("$5.75\n");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StyledText {
+    /// Spans of the text, printed in order.
+    pub spans: Vec<Span>,
+}
+
+impl StyledText {
+    /// Create new, empty, styled text.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a span to the end of the text.
+    pub fn span(mut self, span: impl Into<Span>) -> Self {
+        self.spans.push(span.into());
+        self
+    }
+
+    /// Return the commands which print this text on `model`, starting
+    /// from (and returning to) unstyled, normal sized text. Text is
+    /// converted into the model's code pages.
+    pub fn commands(&self, model: Model) -> Vec<Command> {
+        let mut encoder = Encoder::new(model);
+        self.encode(|text| encoder.text(text))
+    }
+
+    /// Return the commands which print this text, using `encode` to turn
+    /// the text of each span into commands.
+    pub(crate) fn encode(&self, mut encode: impl FnMut(&str) -> Vec<Command>) -> Vec<Command> {
+        let plain = Span::new("");
+        let mut commands = vec![];
+        let mut current = &plain;
+
+        for span in self.spans.iter().chain(std::iter::once(&plain)) {
+            if span.bold != current.bold {
+                commands.push(Command::Emphasize(span.bold));
+            }
+            if span.underline != current.underline {
+                commands.push(Command::Underline(span.underline));
+            }
+            if span.reverse != current.reverse {
+                commands.push(Command::Reverse(span.reverse));
+            }
+            if (span.width, span.height) != (current.width, current.height) {
+                commands.push(Command::TextSize {
+                    width: span.width,
+                    height: span.height,
+                });
+            }
+            commands.extend(encode(&span.text));
+            current = span;
+        }
+        commands
+    }
+}
+
+impl From<Vec<Span>> for StyledText {
+    fn from(spans: Vec<Span>) -> Self {
+        Self { spans }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CodePage;
+
+    fn text(s: &str) -> Command {
+        Command::Text(s.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn styled_minimal_toggles() {
+        let styled = StyledText::new()
+            .span(Span::new("a").bold())
+            .span(Span::new("b").bold().underline())
+            .span(Span::new("c").underline())
+            .span("d\n");
+        assert_eq!(
+            vec![
+                Command::Emphasize(true),
+                text("a"),
+                Command::Underline(true),
+                text("b"),
+                Command::Emphasize(false),
+                text("c"),
+                Command::Underline(false),
+                text("d\n"),
+            ],
+            styled.commands(Model::T20II)
+        );
+    }
+
+    #[test]
+    fn styled_resets() {
+        let styled = StyledText::new().span(Span::new("big").reverse().size(2, 2));
+        assert_eq!(
+            vec![
+                Command::Reverse(true),
+                Command::TextSize {
+                    width: 2,
+                    height: 2
+                },
+                text("big"),
+                Command::Reverse(false),
+                Command::TextSize {
+                    width: 1,
+                    height: 1
+                },
+            ],
+            styled.commands(Model::T20II)
+        );
+    }
+
+    #[test]
+    fn styled_plain() {
+        assert_eq!(
+            vec![text("plain")],
+            StyledText::from(vec!["plain".into()]).commands(Model::T20II)
+        );
+        assert!(StyledText::new().commands(Model::T20II).is_empty());
+    }
+
+    #[test]
+    fn styled_code_page() {
+        let styled = StyledText::new()
+            .span(Span::new("Caf").bold())
+            .span("\u{e9}");
+        assert_eq!(
+            vec![
+                Command::Emphasize(true),
+                text("Caf"),
+                Command::Emphasize(false),
+                Command::CodePage(CodePage::Pc437),
+                Command::Text(vec![0x82]),
+            ],
+            styled.commands(Model::T20II)
+        );
+    }
+}
+
+// vim: foldmethod=marker
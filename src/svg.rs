@@ -0,0 +1,1182 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Rasterize SVG images at the printer's resolution, so that vector logos
+//! print sharp at exactly the width of the paper, rather than being
+//! scaled from a bitmap.
+//!
+//! This is a small renderer for the subset of SVG that logos and
+//! generated receipts use, rather than a complete implementation:
+//!
+//! | Supported                                   | Not supported                 |
+//! | ------------------------------------------- | ----------------------------- |
+//! | `path`, `rect`, `circle`, `ellipse`, `line`, `polyline`, `polygon` | `text`, `image`, `use` |
+//! | `g` and nested `svg`, with `transform`       | gradients, patterns, filters  |
+//! | `fill`, `stroke`, `stroke-width`, `fill-rule` | clipping paths and masks     |
+//! | `opacity`, `fill-opacity`, `stroke-opacity`  | CSS stylesheets and selectors |
+//! | presentation attributes and `style="…"`     | dashes, miter and bevel joins |
+//!
+//! Text should be converted to paths before printing. Colors are printed
+//! as shades of grey, and elements which aren't supported are skipped.
+//!
+//! ```rust
+//! # use epson::Model;
+//! let svg = r#"<svg viewBox="0 0 10 10"><circle cx="5" cy="5" r="4"/></svg>"#;
+//! let img = epson::svg::render(svg, Model::T20II.get_max_image_width() as u32).unwrap();
+//! assert_eq!((576, 576), img.dimensions());
+//! ```
+
+use super::raster::{self, FillRule, Line, SAMPLES};
+use super::Error;
+use image::GrayImage;
+
+/// Number of line segments each Bézier curve is flattened into.
+const CURVE_STEPS: usize = 16;
+
+/// Tallest image which will be rendered, in dots.
+const MAX_HEIGHT: u32 = 65535;
+
+/// Elements whose contents are never drawn directly.
+const SKIPPED: &[&str] = &[
+    "defs",
+    "clipPath",
+    "mask",
+    "marker",
+    "pattern",
+    "symbol",
+    "linearGradient",
+    "radialGradient",
+    "filter",
+    "style",
+    "script",
+    "title",
+    "desc",
+    "metadata",
+    "text",
+    "foreignObject",
+];
+
+/// An affine transform, `[a, b, c, d, e, f]`, mapping `(x, y)` to
+/// `(a * x + c * y + e, b * x + d * y + f)`.
+type Transform = [f32; 6];
+
+const IDENTITY: Transform = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+fn multiply(m: &Transform, n: &Transform) -> Transform {
+    [
+        m[0] * n[0] + m[2] * n[1],
+        m[1] * n[0] + m[3] * n[1],
+        m[0] * n[2] + m[2] * n[3],
+        m[1] * n[2] + m[3] * n[3],
+        m[0] * n[4] + m[2] * n[5] + m[4],
+        m[1] * n[4] + m[3] * n[5] + m[5],
+    ]
+}
+
+fn apply(m: &Transform, (x, y): (f32, f32)) -> (f32, f32) {
+    (m[0] * x + m[2] * y + m[4], m[1] * x + m[3] * y + m[5])
+}
+
+/// Inherited drawing state of an element.
+#[derive(Clone, Debug)]
+struct State {
+    transform: Transform,
+    fill: Option<u8>,
+    stroke: Option<u8>,
+    stroke_width: f32,
+    fill_rule: FillRule,
+    fill_opacity: f32,
+    stroke_opacity: f32,
+    opacity: f32,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            transform: IDENTITY,
+            fill: Some(0),
+            stroke: None,
+            stroke_width: 1.0,
+            fill_rule: FillRule::NonZero,
+            fill_opacity: 1.0,
+            stroke_opacity: 1.0,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl State {
+    /// Apply a presentation attribute, or property from a `style`
+    /// attribute.
+    fn set(&mut self, name: &str, value: &str) {
+        let value = value.trim();
+        match name {
+            "fill" => self.fill = color(value).unwrap_or(self.fill),
+            "stroke" => self.stroke = color(value).unwrap_or(self.stroke),
+            "stroke-width" => {
+                if let Some(width) = length(value) {
+                    self.stroke_width = width;
+                }
+            }
+            "fill-rule" => {
+                self.fill_rule = match value {
+                    "evenodd" => FillRule::EvenOdd,
+                    _ => FillRule::NonZero,
+                }
+            }
+            "fill-opacity" => self.fill_opacity = opacity(value).unwrap_or(self.fill_opacity),
+            "stroke-opacity" => self.stroke_opacity = opacity(value).unwrap_or(self.stroke_opacity),
+            // Group opacity is approximated by fading each child.
+            "opacity" => self.opacity *= opacity(value).unwrap_or(1.0),
+            "transform" => {
+                if let Some(t) = transform(value) {
+                    self.transform = multiply(&self.transform, &t);
+                }
+            }
+            "style" => {
+                for property in value.split(';') {
+                    if let Some((name, value)) = property.split_once(':') {
+                        self.set(name.trim(), value);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parse an opacity, clamped to 0 through 1.
+fn opacity(value: &str) -> Option<f32> {
+    Some(value.parse::<f32>().ok()?.clamp(0.0, 1.0))
+}
+
+/// Parse a paint into the luma it prints as, where `Some(None)` is
+/// `none`, and `None` is a value which can't be parsed.
+fn color(value: &str) -> Option<Option<u8>> {
+    let luma = |r: f32, g: f32, b: f32| Some(Some((0.299 * r + 0.587 * g + 0.114 * b) as u8));
+    let value = value.to_ascii_lowercase();
+    if value == "none" || value == "transparent" {
+        return Some(None);
+    }
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits: Vec<f32> = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as f32))
+            .collect::<Option<_>>()?;
+        return match digits[..] {
+            [r, g, b] => luma(r * 17.0, g * 17.0, b * 17.0),
+            [r1, r2, g1, g2, b1, b2] => luma(r1 * 16.0 + r2, g1 * 16.0 + g2, b1 * 16.0 + b2),
+            _ => None,
+        };
+    }
+    if let Some(args) = value
+        .strip_prefix("rgb(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let channels: Vec<f32> = args
+            .split(',')
+            .map(|c| {
+                let c = c.trim();
+                match c.strip_suffix('%') {
+                    Some(pct) => pct.parse::<f32>().ok().map(|p| p * 2.55),
+                    None => c.parse().ok(),
+                }
+            })
+            .collect::<Option<_>>()?;
+        return match channels[..] {
+            [r, g, b] => luma(
+                r.clamp(0.0, 255.0),
+                g.clamp(0.0, 255.0),
+                b.clamp(0.0, 255.0),
+            ),
+            _ => None,
+        };
+    }
+    match value.as_str() {
+        "white" => luma(255.0, 255.0, 255.0),
+        "silver" => luma(192.0, 192.0, 192.0),
+        "gray" | "grey" => luma(128.0, 128.0, 128.0),
+        "red" => luma(255.0, 0.0, 0.0),
+        "maroon" => luma(128.0, 0.0, 0.0),
+        "yellow" => luma(255.0, 255.0, 0.0),
+        "orange" => luma(255.0, 165.0, 0.0),
+        "lime" => luma(0.0, 255.0, 0.0),
+        "green" => luma(0.0, 128.0, 0.0),
+        "aqua" | "cyan" => luma(0.0, 255.0, 255.0),
+        "blue" => luma(0.0, 0.0, 255.0),
+        "navy" => luma(0.0, 0.0, 128.0),
+        "purple" => luma(128.0, 0.0, 128.0),
+        "fuchsia" | "magenta" => luma(255.0, 0.0, 255.0),
+        // Anything else, such as black, currentColor or a gradient, is
+        // printed black, so that it shows up.
+        _ => Some(Some(0)),
+    }
+}
+
+/// Parse a length in user units (CSS pixels). Percentages can't be
+/// resolved, and are treated as missing.
+fn length(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| c.is_ascii_alphabetic() || c == '%')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f32 = number.trim().parse().ok()?;
+    let scale = match unit {
+        "" | "px" => 1.0,
+        "pt" => 96.0 / 72.0,
+        "pc" => 16.0,
+        "mm" => 96.0 / 25.4,
+        "cm" => 96.0 / 2.54,
+        "in" => 96.0,
+        _ => return None,
+    };
+    Some(number * scale)
+}
+
+/// Reads numbers, and arc flags, out of path data and attribute lists.
+struct Numbers<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Numbers<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input: input.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn separators(&mut self) {
+        while let Some(b' ' | b'\t' | b'\r' | b'\n' | b',') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    /// Return the next non-separator byte, without consuming it.
+    fn peek_token(&mut self) -> Option<u8> {
+        self.separators();
+        self.peek()
+    }
+
+    fn number(&mut self) -> Option<f32> {
+        self.separators();
+        let start = self.pos;
+        if let Some(b'+' | b'-') = self.peek() {
+            self.pos += 1;
+        }
+        let mut dot = false;
+        while let Some(b) = self.peek() {
+            match b {
+                b'0'..=b'9' => {}
+                b'.' if !dot => dot = true,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            let mark = self.pos;
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if !matches!(self.peek(), Some(b'0'..=b'9')) {
+                self.pos = mark;
+            }
+            while let Some(b'0'..=b'9') = self.peek() {
+                self.pos += 1;
+            }
+        }
+        let number = std::str::from_utf8(&self.input[start..self.pos])
+            .ok()?
+            .parse()
+            .ok();
+        if number.is_none() {
+            self.pos = start;
+        }
+        number
+    }
+
+    /// Arc flags are a single `0` or `1`, which may be run together with
+    /// the next number.
+    fn flag(&mut self) -> Option<bool> {
+        self.separators();
+        let flag = match self.peek()? {
+            b'0' => false,
+            b'1' => true,
+            _ => return None,
+        };
+        self.pos += 1;
+        Some(flag)
+    }
+}
+
+/// Parse a `transform` attribute.
+fn transform(value: &str) -> Option<Transform> {
+    let mut result = IDENTITY;
+    let mut rest = value.trim();
+    while !rest.is_empty() {
+        let (name, after) = rest.split_once('(')?;
+        let (args, after) = after.split_once(')')?;
+        let mut numbers = Numbers::new(args);
+        let mut args = vec![];
+        while let Some(n) = numbers.number() {
+            args.push(n);
+        }
+        let t = match (name.trim(), &args[..]) {
+            ("matrix", &[a, b, c, d, e, f]) => [a, b, c, d, e, f],
+            ("translate", &[x]) => [1.0, 0.0, 0.0, 1.0, x, 0.0],
+            ("translate", &[x, y]) => [1.0, 0.0, 0.0, 1.0, x, y],
+            ("scale", &[s]) => [s, 0.0, 0.0, s, 0.0, 0.0],
+            ("scale", &[x, y]) => [x, 0.0, 0.0, y, 0.0, 0.0],
+            ("rotate", &[a]) => rotate(a),
+            ("rotate", &[a, cx, cy]) => multiply(
+                &multiply(&[1.0, 0.0, 0.0, 1.0, cx, cy], &rotate(a)),
+                &[1.0, 0.0, 0.0, 1.0, -cx, -cy],
+            ),
+            ("skewX", &[a]) => [1.0, 0.0, a.to_radians().tan(), 1.0, 0.0, 0.0],
+            ("skewY", &[a]) => [1.0, a.to_radians().tan(), 0.0, 1.0, 0.0, 0.0],
+            _ => return None,
+        };
+        result = multiply(&result, &t);
+        rest = after.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+    }
+    Some(result)
+}
+
+fn rotate(degrees: f32) -> Transform {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    [cos, sin, -sin, cos, 0.0, 0.0]
+}
+
+/// An outline, as a list of subpaths, each a list of points and whether
+/// it was closed.
+#[derive(Default)]
+struct Path {
+    subpaths: Vec<(Vec<(f32, f32)>, bool)>,
+}
+
+impl Path {
+    fn move_to(&mut self, p: (f32, f32)) {
+        self.subpaths.push((vec![p], false));
+    }
+
+    fn line_to(&mut self, p: (f32, f32)) {
+        match self.subpaths.last_mut() {
+            Some((points, false)) => points.push(p),
+            _ => {
+                // Drawing after a close starts again from the same point.
+                let start = self.current().unwrap_or(p);
+                self.subpaths.push((vec![start, p], false));
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some((points, closed)) = self.subpaths.last_mut() {
+            *closed = true;
+            let start = points[0];
+            self.subpaths.push((vec![start], false));
+        }
+    }
+
+    fn current(&self) -> Option<(f32, f32)> {
+        self.subpaths
+            .last()
+            .and_then(|(points, _)| points.last().copied())
+    }
+
+    fn cubic(&mut self, p0: (f32, f32), c1: (f32, f32), c2: (f32, f32), p: (f32, f32)) {
+        for step in 1..=CURVE_STEPS {
+            let t = step as f32 / CURVE_STEPS as f32;
+            let u = 1.0 - t;
+            let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+            self.line_to((
+                a * p0.0 + b * c1.0 + c * c2.0 + d * p.0,
+                a * p0.1 + b * c1.1 + c * c2.1 + d * p.1,
+            ));
+        }
+    }
+
+    fn quad(&mut self, p0: (f32, f32), c: (f32, f32), p: (f32, f32)) {
+        for step in 1..=CURVE_STEPS {
+            let t = step as f32 / CURVE_STEPS as f32;
+            let u = 1.0 - t;
+            self.line_to((
+                u * u * p0.0 + 2.0 * u * t * c.0 + t * t * p.0,
+                u * u * p0.1 + 2.0 * u * t * c.1 + t * t * p.1,
+            ));
+        }
+    }
+
+    /// Add an elliptical arc from `p0` to `p`, as described in the SVG
+    /// implementation notes.
+    #[allow(clippy::too_many_arguments)]
+    fn arc(
+        &mut self,
+        p0: (f32, f32),
+        rx: f32,
+        ry: f32,
+        angle: f32,
+        large: bool,
+        sweep: bool,
+        p: (f32, f32),
+    ) {
+        let (mut rx, mut ry) = (rx.abs(), ry.abs());
+        if rx == 0.0 || ry == 0.0 || p0 == p {
+            self.line_to(p);
+            return;
+        }
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let (dx, dy) = ((p0.0 - p.0) / 2.0, (p0.1 - p.1) / 2.0);
+        let (x1, y1) = (cos * dx + sin * dy, -sin * dx + cos * dy);
+
+        let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+        if lambda > 1.0 {
+            rx *= lambda.sqrt();
+            ry *= lambda.sqrt();
+        }
+        let num = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+        let den = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+        let mut coef = (num / den).max(0.0).sqrt();
+        if large == sweep {
+            coef = -coef;
+        }
+        let (cx1, cy1) = (coef * rx * y1 / ry, -coef * ry * x1 / rx);
+        let (cx, cy) = (
+            cos * cx1 - sin * cy1 + (p0.0 + p.0) / 2.0,
+            sin * cx1 + cos * cy1 + (p0.1 + p.1) / 2.0,
+        );
+
+        let angle_between = |(ux, uy): (f32, f32), (vx, vy): (f32, f32)| {
+            (ux * vy - uy * vx).atan2(ux * vx + uy * vy)
+        };
+        let start_vector = ((x1 - cx1) / rx, (y1 - cy1) / ry);
+        let theta = angle_between((1.0, 0.0), start_vector);
+        let mut delta = angle_between(start_vector, ((-x1 - cx1) / rx, (-y1 - cy1) / ry));
+        if !sweep && delta > 0.0 {
+            delta -= std::f32::consts::TAU;
+        } else if sweep && delta < 0.0 {
+            delta += std::f32::consts::TAU;
+        }
+
+        let steps = ((delta.abs() / (std::f32::consts::PI / 16.0)).ceil() as usize).max(1);
+        for step in 1..=steps {
+            let t = theta + delta * step as f32 / steps as f32;
+            let (st, ct) = t.sin_cos();
+            self.line_to((
+                cx + rx * ct * cos - ry * st * sin,
+                cy + rx * ct * sin + ry * st * cos,
+            ));
+        }
+    }
+
+    /// Add a whole ellipse as a closed subpath.
+    fn ellipse(&mut self, cx: f32, cy: f32, rx: f32, ry: f32) {
+        if rx <= 0.0 || ry <= 0.0 {
+            return;
+        }
+        self.move_to((cx + rx, cy));
+        self.arc((cx + rx, cy), rx, ry, 0.0, false, true, (cx - rx, cy));
+        self.arc((cx - rx, cy), rx, ry, 0.0, false, true, (cx + rx, cy));
+        self.close();
+    }
+}
+
+/// Parse path data. Parsing stops at the first error, keeping everything
+/// before it, as the SVG specification asks.
+fn path_data(d: &str) -> Path {
+    let mut path = Path::default();
+    let mut numbers = Numbers::new(d);
+    let mut start = (0.0, 0.0);
+    let mut current = (0.0, 0.0);
+    // Reflected control point for the S and T commands.
+    let mut last_cubic: Option<(f32, f32)> = None;
+    let mut last_quad: Option<(f32, f32)> = None;
+    let mut command = None;
+
+    while let Some(next) = numbers.peek_token() {
+        if next.is_ascii_alphabetic() {
+            numbers.pos += 1;
+            command = Some(next);
+        }
+        let Some(cmd) = command else {
+            break;
+        };
+        let relative = cmd.is_ascii_lowercase();
+        let offset = |p: (f32, f32)| {
+            if relative {
+                (current.0 + p.0, current.1 + p.1)
+            } else {
+                p
+            }
+        };
+        let point = |numbers: &mut Numbers| Some(offset((numbers.number()?, numbers.number()?)));
+
+        let (cubic, quad) = match cmd.to_ascii_uppercase() {
+            b'M' => {
+                let Some(p) = point(&mut numbers) else {
+                    break;
+                };
+                path.move_to(p);
+                start = p;
+                current = p;
+                // Further pairs are implicit line-tos.
+                command = Some(if relative { b'l' } else { b'L' });
+                (None, None)
+            }
+            b'L' => {
+                let Some(p) = point(&mut numbers) else {
+                    break;
+                };
+                path.line_to(p);
+                current = p;
+                (None, None)
+            }
+            b'H' | b'V' => {
+                let Some(n) = numbers.number() else {
+                    break;
+                };
+                current = match (cmd.to_ascii_uppercase(), relative) {
+                    (b'H', true) => (current.0 + n, current.1),
+                    (b'H', false) => (n, current.1),
+                    (_, true) => (current.0, current.1 + n),
+                    (_, false) => (current.0, n),
+                };
+                path.line_to(current);
+                (None, None)
+            }
+            b'C' | b'S' => {
+                let c1 = match cmd.to_ascii_uppercase() {
+                    b'C' => point(&mut numbers),
+                    _ => Some(
+                        last_cubic
+                            .map_or(current, |c| (2.0 * current.0 - c.0, 2.0 * current.1 - c.1)),
+                    ),
+                };
+                let (Some(c1), Some(c2), Some(p)) = (c1, point(&mut numbers), point(&mut numbers))
+                else {
+                    break;
+                };
+                path.cubic(current, c1, c2, p);
+                current = p;
+                (Some(c2), None)
+            }
+            b'Q' | b'T' => {
+                let c = match cmd.to_ascii_uppercase() {
+                    b'Q' => point(&mut numbers),
+                    _ => Some(
+                        last_quad
+                            .map_or(current, |c| (2.0 * current.0 - c.0, 2.0 * current.1 - c.1)),
+                    ),
+                };
+                let (Some(c), Some(p)) = (c, point(&mut numbers)) else {
+                    break;
+                };
+                path.quad(current, c, p);
+                current = p;
+                (None, Some(c))
+            }
+            b'A' => {
+                let (Some(rx), Some(ry), Some(angle), Some(large), Some(sweep), Some(p)) = (
+                    numbers.number(),
+                    numbers.number(),
+                    numbers.number(),
+                    numbers.flag(),
+                    numbers.flag(),
+                    point(&mut numbers),
+                ) else {
+                    break;
+                };
+                path.arc(current, rx, ry, angle, large, sweep, p);
+                current = p;
+                (None, None)
+            }
+            b'Z' => {
+                path.close();
+                current = start;
+                command = None;
+                (None, None)
+            }
+            _ => break,
+        };
+        last_cubic = cubic;
+        last_quad = quad;
+    }
+    path
+}
+
+/// Build the outline of an element, in its own user units.
+fn shape(name: &str, attr: impl Fn(&str) -> Option<f32>, d: Option<&str>) -> Option<Path> {
+    let mut path = Path::default();
+    let get = |name| attr(name).unwrap_or(0.0);
+    match name {
+        "path" => return Some(path_data(d?)),
+        "rect" => {
+            let (x, y, w, h) = (get("x"), get("y"), get("width"), get("height"));
+            if w <= 0.0 || h <= 0.0 {
+                return None;
+            }
+            let (rx, ry) = match (attr("rx"), attr("ry")) {
+                (Some(rx), None) => (rx, rx),
+                (None, Some(ry)) => (ry, ry),
+                (rx, ry) => (rx.unwrap_or(0.0), ry.unwrap_or(0.0)),
+            };
+            let (rx, ry) = (rx.clamp(0.0, w / 2.0), ry.clamp(0.0, h / 2.0));
+            path.move_to((x + rx, y));
+            path.line_to((x + w - rx, y));
+            path.arc((x + w - rx, y), rx, ry, 0.0, false, true, (x + w, y + ry));
+            path.line_to((x + w, y + h - ry));
+            path.arc(
+                (x + w, y + h - ry),
+                rx,
+                ry,
+                0.0,
+                false,
+                true,
+                (x + w - rx, y + h),
+            );
+            path.line_to((x + rx, y + h));
+            path.arc((x + rx, y + h), rx, ry, 0.0, false, true, (x, y + h - ry));
+            path.line_to((x, y + ry));
+            path.arc((x, y + ry), rx, ry, 0.0, false, true, (x + rx, y));
+            path.close();
+        }
+        "circle" => path.ellipse(get("cx"), get("cy"), get("r"), get("r")),
+        "ellipse" => path.ellipse(get("cx"), get("cy"), get("rx"), get("ry")),
+        "line" => {
+            path.move_to((get("x1"), get("y1")));
+            path.line_to((get("x2"), get("y2")));
+        }
+        _ => return None,
+    }
+    Some(path)
+}
+
+/// Parse the `points` of a `polyline` or `polygon`.
+fn points(value: &str, close: bool) -> Path {
+    let mut path = Path::default();
+    let mut numbers = Numbers::new(value);
+    while let (Some(x), Some(y)) = (numbers.number(), numbers.number()) {
+        match path.current() {
+            None => path.move_to((x, y)),
+            Some(_) => path.line_to((x, y)),
+        }
+    }
+    if close {
+        path.close();
+    }
+    path
+}
+
+/// Return the lines of a polygon, made to wind clockwise, so that
+/// overlapping pieces of a stroke add up under the nonzero rule.
+fn clockwise(points: &[(f32, f32)], out: &mut Vec<(f32, f32, f32, f32)>) {
+    let area: f32 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.0 * b.1 - b.0 * a.1)
+        .sum();
+    let n = points.len();
+    for i in 0..n {
+        let (a, b) = (points[i], points[(i + 1) % n]);
+        if area >= 0.0 {
+            out.push((a.0, a.1, b.0, b.1));
+        } else {
+            out.push((b.0, b.1, a.0, a.1));
+        }
+    }
+}
+
+/// Build the outline of the stroke of `path`, with butt caps and round
+/// joins, in the same units as the path.
+fn stroke(path: &Path, width: f32) -> Vec<(f32, f32, f32, f32)> {
+    let half = width / 2.0;
+    let mut out = vec![];
+    for (points, closed) in &path.subpaths {
+        let mut points = points.clone();
+        points.dedup();
+        if *closed && points.len() > 2 && points.first() == points.last() {
+            points.pop();
+        }
+        let segments = match (closed, points.len()) {
+            (_, 0 | 1) => continue,
+            (true, n) if n > 2 => n,
+            (_, n) => n - 1,
+        };
+        for i in 0..segments {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+            let len = (dx * dx + dy * dy).sqrt();
+            let (nx, ny) = (-dy / len * half, dx / len * half);
+            clockwise(
+                &[
+                    (a.0 + nx, a.1 + ny),
+                    (b.0 + nx, b.1 + ny),
+                    (b.0 - nx, b.1 - ny),
+                    (a.0 - nx, a.1 - ny),
+                ],
+                &mut out,
+            );
+        }
+        // Round joins, everywhere two segments meet.
+        let joins = if *closed && points.len() > 2 {
+            0..points.len()
+        } else {
+            1..points.len() - 1
+        };
+        for i in joins {
+            let (x, y) = points[i];
+            let circle: Vec<_> = (0..16)
+                .map(|k| {
+                    let (s, c) = (k as f32 * std::f32::consts::TAU / 16.0).sin_cos();
+                    (x + c * half, y + s * half)
+                })
+                .collect();
+            clockwise(&circle, &mut out);
+        }
+    }
+    out
+}
+
+/// Paint `lines` (in user units) onto the image in `luma`.
+fn paint(
+    img: &mut GrayImage,
+    transform: &Transform,
+    lines: impl IntoIterator<Item = (f32, f32, f32, f32)>,
+    rule: FillRule,
+    luma: u8,
+    alpha: f32,
+) {
+    let lines: Vec<Line> = lines
+        .into_iter()
+        .map(|(x0, y0, x1, y1)| {
+            let (x0, y0) = apply(transform, (x0, y0));
+            let (x1, y1) = apply(transform, (x1, y1));
+            [x0, y0, x1, y1]
+        })
+        .collect();
+    raster::fill(img, &lines, rule, |pixel, covered| {
+        let a = alpha * covered as f32 / (SAMPLES * SAMPLES) as f32;
+        *pixel = (*pixel as f32 * (1.0 - a) + luma as f32 * a).round() as u8;
+    });
+}
+
+/// Fill and stroke `path` with `state`.
+fn draw(img: &mut GrayImage, state: &State, path: &Path) {
+    if let Some(luma) = state.fill {
+        let lines = path.subpaths.iter().flat_map(|(points, _)| {
+            // Every subpath is filled as if it were closed.
+            points
+                .iter()
+                .zip(points.iter().cycle().skip(1))
+                .map(|(a, b)| (a.0, a.1, b.0, b.1))
+        });
+        let alpha = state.fill_opacity * state.opacity;
+        paint(img, &state.transform, lines, state.fill_rule, luma, alpha);
+    }
+    if let (Some(luma), true) = (state.stroke, state.stroke_width > 0.0) {
+        let alpha = state.stroke_opacity * state.opacity;
+        let lines = stroke(path, state.stroke_width);
+        paint(img, &state.transform, lines, FillRule::NonZero, luma, alpha);
+    }
+}
+
+/// A tag read from the document: its name, attributes, and whether it
+/// opens (and doesn't also close) an element.
+enum Tag<'a> {
+    Start(&'a str, Vec<(&'a str, String)>, bool),
+    End,
+}
+
+/// Reads the tags out of an XML document, skipping everything else.
+struct Tags<'a> {
+    input: &'a str,
+}
+
+impl<'a> Iterator for Tags<'a> {
+    type Item = Result<Tag<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let start = self.input.find('<')?;
+            let rest = &self.input[start..];
+            for (open, close) in [
+                ("<!--", "-->"),
+                ("<![CDATA[", "]]>"),
+                ("<?", "?>"),
+                ("<!", ">"),
+            ] {
+                if rest.starts_with(open) {
+                    let Some(end) = rest.find(close) else {
+                        return Some(Err(Error::InvalidSvg));
+                    };
+                    self.input = &rest[end + close.len()..];
+                    break;
+                }
+            }
+            if self.input.len() < rest.len() {
+                continue;
+            }
+            let Some(end) = rest.find('>') else {
+                return Some(Err(Error::InvalidSvg));
+            };
+            self.input = &rest[end + 1..];
+            let body = &rest[1..end];
+            if body.starts_with('/') {
+                return Some(Ok(Tag::End));
+            }
+            let (body, open) = match body.strip_suffix('/') {
+                Some(body) => (body, false),
+                None => (body, true),
+            };
+            return Some(tag(body).map(|(name, attrs)| Tag::Start(name, attrs, open)));
+        }
+    }
+}
+
+/// Split the inside of a start tag into its name (without any namespace
+/// prefix) and its attributes.
+#[allow(clippy::type_complexity)]
+fn tag(body: &str) -> Result<(&str, Vec<(&str, String)>), Error> {
+    let body = body.trim();
+    let split = body.find(char::is_whitespace).unwrap_or(body.len());
+    let (name, mut rest) = body.split_at(split);
+    let name = name.rsplit(':').next().unwrap_or(name);
+    let mut attrs = vec![];
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return Ok((name, attrs));
+        }
+        let (key, after) = rest.split_once('=').ok_or(Error::InvalidSvg)?;
+        let after = after.trim_start();
+        let quote = after.chars().next().filter(|q| *q == '"' || *q == '\'');
+        let quote = quote.ok_or(Error::InvalidSvg)?;
+        let (value, after) = after[1..].split_once(quote).ok_or(Error::InvalidSvg)?;
+        attrs.push((key.trim(), unescape(value)));
+        rest = after;
+    }
+}
+
+/// Replace the character and entity references in an attribute value.
+fn unescape(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..semi];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity.strip_prefix("#x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => entity.strip_prefix('#').and_then(|n| n.parse().ok()),
+            }
+            .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Work out the size of the image, and the transform from the root
+/// `svg` element's user units to dots, scaling it to `width` dots wide.
+fn viewport(attrs: &[(&str, String)], width: u32) -> Result<(u32, Transform), Error> {
+    let get = |name| attrs.iter().find(|(k, _)| *k == name).map(|(_, v)| v);
+    let view_box = match get("viewBox") {
+        Some(value) => {
+            let mut numbers = Numbers::new(value);
+            let mut view_box = [0.0; 4];
+            for n in view_box.iter_mut() {
+                *n = numbers.number().ok_or(Error::InvalidSvg)?;
+            }
+            view_box
+        }
+        None => {
+            let w = get("width").and_then(|w| length(w));
+            let h = get("height").and_then(|h| length(h));
+            [
+                0.0,
+                0.0,
+                w.ok_or(Error::InvalidSvg)?,
+                h.ok_or(Error::InvalidSvg)?,
+            ]
+        }
+    };
+    let [x, y, w, h] = view_box;
+    if w <= 0.0 || h <= 0.0 || !w.is_finite() || !h.is_finite() {
+        return Err(Error::InvalidSvg);
+    }
+    let scale = width as f32 / w;
+    let height = (h * scale).ceil();
+    if height > MAX_HEIGHT as f32 {
+        return Err(Error::ImageTooLarge);
+    }
+    Ok((
+        (height as u32).max(1),
+        [scale, 0.0, 0.0, scale, -x * scale, -y * scale],
+    ))
+}
+
+/// Render an SVG document as a greyscale image `width` dots wide, on a
+/// white background, keeping its aspect ratio.
+pub fn render(svg: &str, width: u32) -> Result<GrayImage, Error> {
+    if width == 0 {
+        return Err(Error::InvalidParameter);
+    }
+    let mut img: Option<GrayImage> = None;
+    let mut stack: Vec<State> = vec![];
+    let mut skip = 0;
+
+    for tag in (Tags { input: svg }) {
+        let (name, attrs, open) = match tag? {
+            Tag::End => {
+                if skip > 0 {
+                    skip -= 1;
+                } else {
+                    stack.pop();
+                }
+                continue;
+            }
+            Tag::Start(name, attrs, open) => (name, attrs, open),
+        };
+        if skip > 0 || SKIPPED.contains(&name) {
+            skip += open as usize;
+            continue;
+        }
+
+        let mut state = match (&img, stack.last()) {
+            (None, _) => {
+                if name != "svg" {
+                    return Err(Error::InvalidSvg);
+                }
+                let (height, transform) = viewport(&attrs, width)?;
+                img = Some(GrayImage::from_pixel(width, height, image::Luma([255])));
+                State {
+                    transform,
+                    ..State::default()
+                }
+            }
+            (Some(_), Some(parent)) => parent.clone(),
+            // Elements after the root element has closed aren't drawn.
+            (Some(_), None) => break,
+        };
+        let get = |key: &str| {
+            attrs
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.as_str())
+        };
+        for (key, value) in &attrs {
+            if *key != "style" {
+                state.set(key, value);
+            }
+        }
+        if let Some(style) = get("style") {
+            state.set("style", style);
+        }
+
+        let path = match name {
+            "polyline" | "polygon" => get("points").map(|p| points(p, name == "polygon")),
+            _ => shape(name, |key| get(key).and_then(length), get("d")),
+        };
+        if let (Some(img), Some(path)) = (img.as_mut(), path) {
+            draw(img, &state, &path);
+        }
+        if open {
+            stack.push(state);
+        }
+    }
+    img.ok_or(Error::InvalidSvg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn luma(img: &GrayImage, x: u32, y: u32) -> u8 {
+        img.get_pixel(x, y).0[0]
+    }
+
+    #[test]
+    fn render_view_box() {
+        let img = render(
+            r##"<?xml version="1.0"?>
+            <!-- a logo -->
+            <svg xmlns="http://www.w3.org/2000/svg" width="20mm" viewBox="0 0 20 10">
+              <rect x="10" y="0" width="10" height="10" fill="#000"/>
+            </svg>"##,
+            40,
+        )
+        .unwrap();
+        assert_eq!((40, 20), img.dimensions());
+        assert_eq!(255, luma(&img, 10, 10));
+        assert_eq!(0, luma(&img, 30, 10));
+    }
+
+    #[test]
+    fn render_width_height() {
+        let img = render(r##"<svg width="10" height="5"/>"##, 20).unwrap();
+        assert_eq!((20, 10), img.dimensions());
+    }
+
+    #[test]
+    fn render_circle_and_group() {
+        let img = render(
+            r##"<svg viewBox="0 0 100 100">
+              <g fill="red" transform="translate(50 50)">
+                <circle r="40"/>
+                <circle r="10" fill="white"/>
+              </g>
+            </svg>"##,
+            100,
+        )
+        .unwrap();
+        // red is a mid grey
+        assert_eq!(76, luma(&img, 50, 25));
+        assert_eq!(255, luma(&img, 50, 50));
+        assert_eq!(255, luma(&img, 5, 5));
+    }
+
+    #[test]
+    fn render_path() {
+        // A square with a square hole, drawn with relative commands.
+        let img = render(
+            r##"<svg viewBox="0 0 10 10">
+              <path fill-rule="evenodd" d="M0 0h10v10H0z m3 3h4v4h-4z"/>
+            </svg>"##,
+            10,
+        )
+        .unwrap();
+        assert_eq!(0, luma(&img, 1, 1));
+        assert_eq!(255, luma(&img, 5, 5));
+    }
+
+    #[test]
+    fn render_curves() {
+        let img = render(
+            r##"<svg viewBox="0 0 10 10">
+              <path d="M0,5 C0,-1.6 10,-1.6 10,5 Q5,15 0,5 Z"/>
+              <path d="M0 0a1 1 0 0010 0" fill="none" stroke="black"/>
+            </svg>"##,
+            10,
+        )
+        .unwrap();
+        assert_eq!(0, luma(&img, 5, 4));
+        assert_eq!(0, luma(&img, 5, 7));
+    }
+
+    #[test]
+    fn render_stroke() {
+        let img = render(
+            r##"<svg viewBox="0 0 10 10">
+              <line x1="0" y1="5" x2="10" y2="5" stroke="#000" style="stroke-width: 2"/>
+              <polyline points="1,1 9,1" fill="none" stroke="black" stroke-width="0"/>
+            </svg>"##,
+            10,
+        )
+        .unwrap();
+        assert_eq!(0, luma(&img, 5, 4));
+        assert_eq!(0, luma(&img, 5, 5));
+        assert_eq!(255, luma(&img, 5, 2));
+        assert_eq!(255, luma(&img, 5, 1));
+    }
+
+    #[test]
+    fn render_skips_unsupported() {
+        let img = render(
+            r##"<svg viewBox="0 0 10 10">
+              <defs><rect id="r" width="10" height="10"/></defs>
+              <text x="0" y="5">HACK THE PLANET</text>
+              <use href="#r"/>
+            </svg>"##,
+            10,
+        )
+        .unwrap();
+        assert!(img.pixels().all(|p| p.0[0] == 255));
+    }
+
+    #[test]
+    fn render_invalid() {
+        assert_eq!(Err(Error::InvalidSvg), render("<html></html>", 10));
+        assert_eq!(Err(Error::InvalidSvg), render("<svg>", 10));
+        assert_eq!(
+            Err(Error::InvalidSvg),
+            render(r##"<svg viewBox="0 0 0 10">"##, 10)
+        );
+        assert_eq!(
+            Err(Error::ImageTooLarge),
+            render(r##"<svg viewBox="0 0 1 1000000">"##, 10)
+        );
+    }
+
+    #[test]
+    fn parse_transform() {
+        let t = transform("translate(10, 20) scale(2)").unwrap();
+        assert_eq!((12.0, 24.0), apply(&t, (1.0, 2.0)));
+        let t = transform("rotate(90 5 5)").unwrap();
+        let (x, y) = apply(&t, (10.0, 5.0));
+        assert!((x - 5.0).abs() < 1e-4 && (y - 10.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn parse_color() {
+        assert_eq!(Some(None), color("none"));
+        assert_eq!(Some(Some(255)), color("#FFF"));
+        assert_eq!(Some(Some(255)), color("rgb(100%, 100%, 100%)"));
+        assert_eq!(Some(Some(0)), color("black"));
+        assert_eq!(None, color("#12"));
+    }
+
+    #[test]
+    fn parse_numbers() {
+        let mut numbers = Numbers::new("1.5.5-2e1,3e");
+        assert_eq!(Some(1.5), numbers.number());
+        assert_eq!(Some(0.5), numbers.number());
+        assert_eq!(Some(-20.0), numbers.number());
+        assert_eq!(Some(3.0), numbers.number());
+        assert_eq!(None, numbers.number());
+    }
+}
+
+// vim: foldmethod=marker
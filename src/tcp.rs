@@ -0,0 +1,329 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+#[cfg(feature = "tokio")]
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use std::{
+    io,
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+#[cfg(feature = "tokio")]
+use tokio::io::AsyncWrite;
+
+/// Options for connecting to a printer over TCP, used by
+/// [crate::Writer::connect_tcp] and `AsyncWriter::connect_tcp`. The
+/// defaults make sure a printer which is switched off or unplugged
+/// results in an error, rather than hanging.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TcpOptions {
+    /// How long to wait for the printer to accept the connection.
+    pub connect_timeout: Duration,
+
+    /// How long a write may block, such as when the printer is out of
+    /// paper and its buffer is full, before failing. If this is `None`,
+    /// writes may block forever.
+    pub write_timeout: Option<Duration>,
+
+    /// How long a read, such as of a status byte, may block before
+    /// failing. If this is `None`, reads may block forever. The
+    /// `AsyncWriter` never reads, so this only applies to [crate::Writer].
+    pub read_timeout: Option<Duration>,
+
+    /// How long the connection may be idle before TCP keepalive probes are
+    /// sent, to notice a printer which has gone away. If this is `None`,
+    /// keepalive is off. Keepalive is only set on unix platforms, with the
+    /// `keepalive` feature, which sets it through `libc`.
+    pub keepalive: Option<Duration>,
+
+    /// Send commands as soon as they're written, rather than waiting to
+    /// batch them into fewer packets.
+    pub nodelay: bool,
+}
+
+impl Default for TcpOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(5),
+            write_timeout: Some(Duration::from_secs(30)),
+            read_timeout: Some(Duration::from_secs(5)),
+            keepalive: Some(Duration::from_secs(30)),
+            nodelay: true,
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "keepalive"))]
+fn set_keepalive(stream: &impl std::os::unix::io::AsRawFd, idle: Duration) -> io::Result<()> {
+    let fd = stream.as_raw_fd();
+    let set = |level, name, value: libc::c_int| {
+        // SAFETY: fd is an open socket, and value is a c_int of the
+        // length provided.
+        let ret = unsafe {
+            libc::setsockopt(
+                fd,
+                level,
+                name,
+                &value as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    };
+
+    set(libc::SOL_SOCKET, libc::SO_KEEPALIVE, 1)?;
+    let secs = idle.as_secs().clamp(1, i32::MAX as u64) as libc::c_int;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        set(libc::IPPROTO_TCP, libc::TCP_KEEPIDLE, secs)?;
+        set(libc::IPPROTO_TCP, libc::TCP_KEEPINTVL, secs)?;
+    }
+    #[cfg(target_vendor = "apple")]
+    set(libc::IPPROTO_TCP, libc::TCP_KEEPALIVE, secs)?;
+    let _ = secs;
+    Ok(())
+}
+
+#[cfg(not(all(unix, feature = "keepalive")))]
+fn set_keepalive<S>(_: &S, _: Duration) -> io::Result<()> {
+    Ok(())
+}
+
+/// Connect to `addr`, trying each address it resolves to in turn, and
+/// apply `options` to the connection.
+pub(crate) fn connect(addr: impl ToSocketAddrs, options: &TcpOptions) -> io::Result<TcpStream> {
+    let mut last_err = io::Error::new(io::ErrorKind::InvalidInput, "no addresses to connect to");
+    for addr in addr.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, options.connect_timeout) {
+            Ok(stream) => {
+                stream.set_write_timeout(options.write_timeout)?;
+                stream.set_read_timeout(options.read_timeout)?;
+                stream.set_nodelay(options.nodelay)?;
+                if let Some(idle) = options.keepalive {
+                    set_keepalive(&stream, idle)?;
+                }
+                return Ok(stream);
+            }
+            Err(err) => last_err = err,
+        }
+    }
+    Err(last_err)
+}
+
+/// Connect to `addr` like [connect], without blocking the runtime. The
+/// write timeout is kept by the returned [AsyncTcpStream], since the
+/// socket's own timeouts don't apply to non-blocking writes.
+#[cfg(feature = "tokio")]
+pub(crate) async fn connect_async(
+    addr: impl tokio::net::ToSocketAddrs,
+    options: &TcpOptions,
+) -> io::Result<AsyncTcpStream> {
+    let mut last_err = io::Error::new(io::ErrorKind::InvalidInput, "no addresses to connect to");
+    for addr in tokio::net::lookup_host(addr).await? {
+        let connect = tokio::net::TcpStream::connect(addr);
+        match tokio::time::timeout(options.connect_timeout, connect).await {
+            Ok(Ok(stream)) => {
+                stream.set_nodelay(options.nodelay)?;
+                if let Some(idle) = options.keepalive {
+                    set_keepalive(&stream, idle)?;
+                }
+                return Ok(AsyncTcpStream {
+                    stream,
+                    write_timeout: options.write_timeout,
+                    deadline: None,
+                });
+            }
+            Ok(Err(err)) => last_err = err,
+            Err(_) => last_err = io::Error::new(io::ErrorKind::TimedOut, "connection timed out"),
+        }
+    }
+    Err(last_err)
+}
+
+/// A [tokio::net::TcpStream] whose writes fail once they've made no
+/// progress for the write timeout, such as when the printer is out of
+/// paper and its buffer is full.
+#[cfg(feature = "tokio")]
+pub(crate) struct AsyncTcpStream {
+    stream: tokio::net::TcpStream,
+    write_timeout: Option<Duration>,
+    deadline: Option<Pin<Box<tokio::time::Sleep>>>,
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncTcpStream {
+    /// Pass on the result of a write, or fail it if it has been pending
+    /// for longer than the write timeout.
+    fn timeout<T>(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll: Poll<io::Result<T>>,
+    ) -> Poll<io::Result<T>> {
+        let Some(timeout) = self.write_timeout.filter(|_| poll.is_pending()) else {
+            self.deadline = None;
+            return poll;
+        };
+        let deadline = self
+            .deadline
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+        match deadline.as_mut().poll(cx) {
+            Poll::Ready(()) => {
+                self.deadline = None;
+                Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "write timed out",
+                )))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncWrite for AsyncTcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.stream).poll_write(cx, buf);
+        this.timeout(cx, poll)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.stream).poll_flush(cx);
+        this.timeout(cx, poll)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.stream).poll_shutdown(cx);
+        this.timeout(cx, poll)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn connect_options() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let stream = connect(addr, &TcpOptions::default()).unwrap();
+        assert!(stream.nodelay().unwrap());
+        assert_eq!(
+            Some(Duration::from_secs(30)),
+            stream.write_timeout().unwrap()
+        );
+        assert_eq!(Some(Duration::from_secs(5)), stream.read_timeout().unwrap());
+
+        let stream = connect(
+            addr,
+            &TcpOptions {
+                write_timeout: None,
+                keepalive: None,
+                nodelay: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(!stream.nodelay().unwrap());
+        assert_eq!(None, stream.write_timeout().unwrap());
+    }
+
+    #[cfg(feature = "tokio")]
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn connect_async() {
+        use std::io::Read;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let runtime = runtime();
+        runtime.block_on(async {
+            let mut pos =
+                crate::AsyncWriter::connect_tcp(crate::Model::T20II, addr, &TcpOptions::default())
+                    .await
+                    .unwrap();
+            pos.write_all(b"hi").await.unwrap();
+            pos.flush().await.unwrap();
+        });
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut bytes = [0; 4];
+        stream.read_exact(&mut bytes).unwrap();
+        assert_eq!(*b"\x1b@hi", bytes);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn connect_async_write_timeout() {
+        use tokio::io::AsyncWriteExt;
+
+        // The listener never reads, so writes stall once the socket
+        // buffers are full.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let options = TcpOptions {
+            write_timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        let err = runtime().block_on(async {
+            let mut stream = super::connect_async(addr, &options).await.unwrap();
+            let chunk = vec![0; 64 * 1024];
+            loop {
+                if let Err(err) = stream.write_all(&chunk).await {
+                    return err;
+                }
+            }
+        });
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+        drop(listener);
+    }
+
+    #[test]
+    fn connect_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        assert!(connect(addr, &TcpOptions::default()).is_err());
+    }
+}
+
+// vim: foldmethod=marker
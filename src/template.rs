@@ -0,0 +1,536 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Receipt templates, so that merchants can change what their receipts
+//! say without changing code.
+//!
+//! A [Template] is text with a small, Jinja-like, syntax, which is
+//! rendered with JSON data into a list of [Command]s:
+//!
+//! | Syntax                               | Meaning                              |
+//! | ------------------------------------ | ------------------------------------ |
+//! | `{{ order.id }}`                     | Insert a value from the data         |
+//! | `{{ total \| currency("$") }}`       | Insert a value, passed through filters |
+//! | `{% for item in items %}…{% endfor %}` | Repeat for each entry of an array  |
+//! | `{% if note %}…{% else %}…{% endif %}` | Only print if a value is set       |
+//! | `{% bold %}…{% endbold %}`           | Bold text (also `underline`, `reverse`) |
+//! | `{% align center %}`                 | Justify the following lines          |
+//! | `{% size 2 2 %}`                     | Scale the following text             |
+//! | `{% feed 3 %}`, `{% cut %}`          | Feed or cut the paper                |
+//!
+//! The filters are:
+//!
+//! | Filter             | Meaning                                          |
+//! | ------------------ | ------------------------------------------------ |
+//! | `currency(symbol)` | Format a number with two decimals and a symbol   |
+//! | `upper`            | Convert to upper case                            |
+//! | `pad(width)`       | Left justify, padded with spaces to `width`      |
+//! | `right(width)`     | Right justify to `width`                         |
+//! | `center(width)`    | Center within `width`                            |
+//!
+//! Widths default to the number of columns on the paper. A line feed right
+//! after a `{% … %}` tag is dropped, so tags can sit on their own lines.
+//!
+//! ```rust
+//! # use epson::{template::Template, Model};
+//! let template = Template::new(
+//!     "{% for item in items %}
+//! {{ item.name | pad(20) }}{{ item.price | currency(\"$\") | right(10) }}
+//! {% endfor %}
+//! {% cut %}
+//! ",
+//! )
+//! .unwrap();
+//! let commands = template
+//!     .render(r#"{"items": [{"name": "Coffee", "price": 3.5}]}"#, Model::T20II)
+//!     .unwrap();
+//! ```
+
+use super::codepage::Encoder;
+use super::json::Value;
+use super::width::{text_width, truncate};
+use super::{Alignment, Command, Error, Model};
+
+/// A parsed receipt template. See the [module](self) documentation for
+/// the syntax.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Text(String),
+    Expr(Vec<String>, Vec<Filter>),
+    For(String, Vec<String>, Vec<Node>),
+    If(Vec<String>, Vec<Node>, Vec<Node>),
+    Command(Command),
+    Cut,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Filter {
+    Currency(String),
+    Upper,
+    Pad(Option<usize>),
+    Right(Option<usize>),
+    Center(Option<usize>),
+}
+
+enum Token {
+    Text(String),
+    Expr(String),
+    Tag(String),
+}
+
+impl Template {
+    /// Parse a template, returning [Error::InvalidTemplate] if it isn't
+    /// valid.
+    pub fn new(source: &str) -> Result<Self, Error> {
+        let mut tokens = tokenize(source)?.into_iter();
+        match parse(&mut tokens, &[])? {
+            (nodes, None) => Ok(Self { nodes }),
+            _ => Err(Error::InvalidTemplate),
+        }
+    }
+
+    /// Render the template with the JSON `data`, laid out for `model`.
+    /// Returns [Error::InvalidTemplate] if the data isn't valid JSON, or
+    /// is missing a value the template uses.
+    pub fn render(&self, data: &str, model: Model) -> Result<Vec<Command>, Error> {
+        let data = Value::parse(data).map_err(|_| Error::InvalidTemplate)?;
+        let mut renderer = Renderer {
+            model,
+            root: &data,
+            scope: vec![],
+            text: String::new(),
+            commands: vec![],
+            encoder: Encoder::new(model),
+        };
+        renderer.nodes(&self.nodes)?;
+        renderer.flush();
+        Ok(renderer.commands)
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = vec![];
+    let mut rest = source;
+
+    while let Some(start) = [rest.find("{{"), rest.find("{%")]
+        .into_iter()
+        .flatten()
+        .min()
+    {
+        if start > 0 {
+            tokens.push(Token::Text(rest[..start].to_owned()));
+        }
+        let close = if rest[start..].starts_with("{{") {
+            "}}"
+        } else {
+            "%}"
+        };
+        let end = start
+            + 2
+            + rest[start + 2..]
+                .find(close)
+                .ok_or(Error::InvalidTemplate)?;
+        let inner = rest[start + 2..end].trim().to_owned();
+        rest = &rest[end + 2..];
+
+        if close == "}}" {
+            tokens.push(Token::Expr(inner));
+        } else {
+            tokens.push(Token::Tag(inner));
+            rest = rest.strip_prefix('\n').unwrap_or(rest);
+        }
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest.to_owned()));
+    }
+    Ok(tokens)
+}
+
+/// Parse nodes until one of the `ends` tags, which is returned along with
+/// the nodes. `None` means the end of the template was reached.
+fn parse(
+    tokens: &mut impl Iterator<Item = Token>,
+    ends: &[&str],
+) -> Result<(Vec<Node>, Option<String>), Error> {
+    let mut nodes = vec![];
+
+    while let Some(token) = tokens.next() {
+        let tag = match token {
+            Token::Text(text) => {
+                nodes.push(Node::Text(text));
+                continue;
+            }
+            Token::Expr(expr) => {
+                nodes.push(parse_expr(&expr)?);
+                continue;
+            }
+            Token::Tag(tag) => tag,
+        };
+
+        let words: Vec<&str> = tag.split_whitespace().collect();
+        nodes.push(match words[..] {
+            [end] if ends.contains(&end) => return Ok((nodes, Some(end.to_owned()))),
+            ["for", var, "in", path] => match parse(tokens, &["endfor"])? {
+                (body, Some(_)) => Node::For(var.to_owned(), parse_path(path)?, body),
+                _ => return Err(Error::InvalidTemplate),
+            },
+            ["if", path] => {
+                let path = parse_path(path)?;
+                match parse(tokens, &["else", "endif"])? {
+                    (body, Some(end)) if end == "endif" => Node::If(path, body, vec![]),
+                    (body, Some(_)) => match parse(tokens, &["endif"])? {
+                        (otherwise, Some(_)) => Node::If(path, body, otherwise),
+                        _ => return Err(Error::InvalidTemplate),
+                    },
+                    _ => return Err(Error::InvalidTemplate),
+                }
+            }
+            ["bold"] => Node::Command(Command::Emphasize(true)),
+            ["endbold"] => Node::Command(Command::Emphasize(false)),
+            ["underline"] => Node::Command(Command::Underline(true)),
+            ["endunderline"] => Node::Command(Command::Underline(false)),
+            ["reverse"] => Node::Command(Command::Reverse(true)),
+            ["endreverse"] => Node::Command(Command::Reverse(false)),
+            ["align", align] => Node::Command(Command::Justification(match align {
+                "left" => Alignment::Left,
+                "center" => Alignment::Center,
+                "right" => Alignment::Right,
+                _ => return Err(Error::InvalidTemplate),
+            })),
+            ["size", width, height] => Node::Command(Command::TextSize {
+                width: width.parse().map_err(|_| Error::InvalidTemplate)?,
+                height: height.parse().map_err(|_| Error::InvalidTemplate)?,
+            }),
+            ["feed", lines] => Node::Command(Command::Feed(
+                lines.parse().map_err(|_| Error::InvalidTemplate)?,
+            )),
+            ["cut"] => Node::Cut,
+            _ => return Err(Error::InvalidTemplate),
+        });
+    }
+    Ok((nodes, None))
+}
+
+fn parse_path(path: &str) -> Result<Vec<String>, Error> {
+    let path: Vec<String> = path.trim().split('.').map(str::to_owned).collect();
+    if path
+        .iter()
+        .any(|part| part.is_empty() || !part.chars().all(|c| c.is_alphanumeric() || c == '_'))
+    {
+        return Err(Error::InvalidTemplate);
+    }
+    Ok(path)
+}
+
+/// Split `s` on `sep`, except inside of double quotes.
+fn split_unquoted(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == sep && !quoted {
+            parts.push(&s[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+fn parse_expr(expr: &str) -> Result<Node, Error> {
+    let mut parts = split_unquoted(expr, '|').into_iter();
+    let path = parse_path(parts.next().unwrap_or_default())?;
+    let filters = parts.map(parse_filter).collect::<Result<_, _>>()?;
+    Ok(Node::Expr(path, filters))
+}
+
+fn parse_filter(filter: &str) -> Result<Filter, Error> {
+    let filter = filter.trim();
+    let (name, arg) = match filter.split_once('(') {
+        Some((name, rest)) => {
+            let arg = rest.strip_suffix(')').ok_or(Error::InvalidTemplate)?.trim();
+            (name.trim(), Some(arg))
+        }
+        None => (filter, None),
+    };
+    let string = || match arg {
+        None => Ok(String::new()),
+        Some(arg) => arg
+            .strip_prefix('"')
+            .and_then(|arg| arg.strip_suffix('"'))
+            .map(str::to_owned)
+            .ok_or(Error::InvalidTemplate),
+    };
+    let width = || match arg {
+        None => Ok(None),
+        Some(arg) => arg.parse().map(Some).map_err(|_| Error::InvalidTemplate),
+    };
+
+    Ok(match name {
+        "currency" => Filter::Currency(string()?),
+        "upper" if arg.is_none() => Filter::Upper,
+        "pad" => Filter::Pad(width()?),
+        "right" => Filter::Right(width()?),
+        "center" => Filter::Center(width()?),
+        _ => return Err(Error::InvalidTemplate),
+    })
+}
+
+struct Renderer<'a> {
+    model: Model,
+    root: &'a Value,
+    scope: Vec<(&'a str, &'a Value)>,
+    text: String,
+    commands: Vec<Command>,
+    encoder: Encoder,
+}
+
+impl<'a> Renderer<'a> {
+    fn flush(&mut self) {
+        if !self.text.is_empty() {
+            let text = std::mem::take(&mut self.text);
+            self.commands.extend(self.encoder.text(&text));
+        }
+    }
+
+    fn command(&mut self, cmd: Command) {
+        self.flush();
+        self.encoder.track(&cmd);
+        self.commands.push(cmd);
+    }
+
+    fn lookup(&self, path: &[String]) -> Result<&'a Value, Error> {
+        let (first, rest) = path.split_first().ok_or(Error::InvalidTemplate)?;
+        let mut value = self
+            .scope
+            .iter()
+            .rev()
+            .find(|(name, _)| name == first)
+            .map(|(_, value)| *value)
+            .or_else(|| self.root.get(first))
+            .ok_or(Error::InvalidTemplate)?;
+        for part in rest {
+            value = value.get(part).ok_or(Error::InvalidTemplate)?;
+        }
+        Ok(value)
+    }
+
+    fn nodes(&mut self, nodes: &'a [Node]) -> Result<(), Error> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => self.text.push_str(text),
+                Node::Expr(path, filters) => {
+                    let mut text = to_string(self.lookup(path)?)?;
+                    for filter in filters {
+                        text = self.filter(filter, text)?;
+                    }
+                    self.text.push_str(&text);
+                }
+                Node::For(var, path, body) => {
+                    let items = self
+                        .lookup(path)?
+                        .as_array()
+                        .ok_or(Error::InvalidTemplate)?;
+                    for item in items {
+                        self.scope.push((var, item));
+                        self.nodes(body)?;
+                        self.scope.pop();
+                    }
+                }
+                Node::If(path, body, otherwise) => {
+                    let set = self.lookup(path).map(truthy).unwrap_or(false);
+                    self.nodes(if set { body } else { otherwise })?;
+                }
+                Node::Command(cmd) => self.command(cmd.clone()),
+                Node::Cut => self.command(self.model.get_cut_command().command()),
+            }
+        }
+        Ok(())
+    }
+
+    fn filter(&self, filter: &Filter, text: String) -> Result<String, Error> {
+        let columns = self.model.get_columns();
+        let pad = |width: &Option<usize>, text: &str| {
+            let text = truncate(text, width.unwrap_or(columns));
+            let room = width.unwrap_or(columns) - text_width(text);
+            (text.to_owned(), room)
+        };
+
+        Ok(match filter {
+            Filter::Currency(symbol) => {
+                let amount: f64 = text.trim().parse().map_err(|_| Error::InvalidTemplate)?;
+                let sign = if amount < 0.0 { "-" } else { "" };
+                format!("{}{}{:.2}", sign, symbol, amount.abs())
+            }
+            Filter::Upper => text.to_uppercase(),
+            Filter::Pad(width) => {
+                let (text, room) = pad(width, &text);
+                format!("{}{}", text, " ".repeat(room))
+            }
+            Filter::Right(width) => {
+                let (text, room) = pad(width, &text);
+                format!("{}{}", " ".repeat(room), text)
+            }
+            Filter::Center(width) => {
+                let (text, room) = pad(width, &text);
+                format!(
+                    "{}{}{}",
+                    " ".repeat(room / 2),
+                    text,
+                    " ".repeat(room - room / 2)
+                )
+            }
+        })
+    }
+}
+
+fn to_string(value: &Value) -> Result<String, Error> {
+    Ok(match value {
+        Value::Null => String::new(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => (*n as i64).to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.clone(),
+        Value::Array(_) | Value::Object(_) => return Err(Error::InvalidTemplate),
+    })
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => *n != 0.0,
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CutMode;
+
+    fn text(s: &str) -> Command {
+        Command::Text(s.as_bytes().to_vec())
+    }
+
+    fn render(template: &str, data: &str) -> Result<Vec<Command>, Error> {
+        Template::new(template)?.render(data, Model::T20II)
+    }
+
+    #[test]
+    fn template_code_page() {
+        assert_eq!(
+            vec![
+                Command::CodePage(crate::CodePage::Pc437),
+                Command::Text(b"Caf\x82\n".to_vec()),
+            ],
+            render("{{ name }}\n", r#"{"name": "Caf\u00e9"}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn template_values() {
+        assert_eq!(
+            vec![text("Order 42 for Ada\n")],
+            render(
+                "Order {{ order.id }} for {{ order.name }}\n",
+                r#"{"order": {"id": 42, "name": "Ada"}}"#
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn template_filters() {
+        assert_eq!(
+            vec![text("COFFEE    |  $3.50| -$1.25 |")],
+            render(
+                r#"{{ name | upper | pad(10) }}|{{ price | currency("$") | right(7) }}|{{ discount | currency("$") | center(8) }}|"#,
+                r#"{"name": "Coffee", "price": 3.5, "discount": -1.25}"#
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            vec![text(&format!("{}x", " ".repeat(47)))],
+            render("{{ x | right }}", r#"{"x": "x"}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn template_blocks() {
+        let template = "{% for item in items %}
+{% if item.bold %}
+{% bold %}{{ item.name }}{% endbold %}
+{% else %}
+{{ item.name }}
+{% endif %}
+{% endfor %}
+{% cut %}
+";
+        assert_eq!(
+            vec![
+                text("a\n"),
+                Command::Emphasize(true),
+                text("b"),
+                Command::Emphasize(false),
+                text("c\n"),
+                Command::CutWith(CutMode::Full, None),
+            ],
+            render(
+                template,
+                r#"{"items": [{"name": "a"}, {"name": "b", "bold": true}, {"name": "c"}]}"#
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn template_invalid() {
+        for template in [
+            "{{ x",
+            "{% for x in y %}",
+            "{% endfor %}",
+            "{% sparkles %}",
+            "{% align up %}",
+            "{{ x | sparkles }}",
+            "{{ x | pad(wide) }}",
+            "{{ x. }}",
+        ] {
+            assert_eq!(Err(Error::InvalidTemplate), Template::new(template));
+        }
+        assert_eq!(Err(Error::InvalidTemplate), render("{{ x }}", "{}"));
+        assert_eq!(Err(Error::InvalidTemplate), render("{{ x }}", "nope"));
+        assert_eq!(
+            Err(Error::InvalidTemplate),
+            render("{{ x | currency }}", r#"{"x": "free"}"#)
+        );
+    }
+}
+
+// vim: foldmethod=marker
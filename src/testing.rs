@@ -0,0 +1,934 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Helpers for testing code which drives a printer, without needing one.
+
+use super::{
+    decode, AsbFlags, AsbStatus, Command, Decoded, Decoder, ErrorStatus, InfoKind,
+    MaintenanceCounter, OfflineStatus, PaperStatus, PrinterInfo, PrinterStatus, RealTimeStatus,
+    StatusKind, TransmitStatusKind,
+};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+/// Writer which records everything written to it, for checking what an
+/// application sent to the printer in unit tests.
+///
+/// Cloning a CaptureWriter returns another handle to the same recording,
+/// so one handle can be given to a [super::Writer], and the other used to
+/// check what was written.
+///
+/// ```rust
+/// # use epson::{testing::CaptureWriter, Command, CutMode, Model, Writer};
+/// let capture = CaptureWriter::new();
+/// let mut pos = Writer::open(Model::T20II, Box::new(capture.clone())).unwrap();
+/// pos.feed(2).unwrap();
+/// pos.cut().unwrap();
+///
+/// capture.assert_commands(&[
+///     Command::Init,
+///     Command::Feed(2),
+///     Command::CutWith(CutMode::Full, None),
+/// ]);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CaptureWriter {
+    bytes: Arc<Mutex<Vec<u8>>>,
+}
+
+impl CaptureWriter {
+    /// Create a new, empty, CaptureWriter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return every byte written so far.
+    pub fn bytes(&self) -> Vec<u8> {
+        self.lock().clone()
+    }
+
+    /// Forget everything written so far.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Return everything written so far, decoded.
+    pub fn decoded(&self) -> Vec<Decoded> {
+        decode(&self.lock())
+    }
+
+    /// Return every command written so far, leaving out text.
+    pub fn commands(&self) -> Vec<Command> {
+        self.decoded()
+            .into_iter()
+            .filter_map(|decoded| match decoded {
+                Decoded::Command(cmd) => Some(cmd),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Return all of the text written so far, including line feeds.
+    pub fn text(&self) -> String {
+        let text: Vec<u8> = self
+            .decoded()
+            .into_iter()
+            .filter_map(|decoded| match decoded {
+                Decoded::Text(text) => Some(text),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        String::from_utf8_lossy(&text).into_owned()
+    }
+
+    /// Assert that exactly these commands have been written so far,
+    /// ignoring any text. Commands which encode to the same bytes are
+    /// treated as the same command, so `DoubleWidth(true)` matches
+    /// `TextSize { width: 2, height: 1 }`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the commands don't match, or if an expected command
+    /// can't be encoded.
+    #[track_caller]
+    pub fn assert_commands(&self, expected: &[Command]) {
+        let expected: Vec<u8> = expected
+            .iter()
+            .flat_map(|cmd| cmd.as_bytes().expect("expected command can't be encoded"))
+            .collect();
+        let expected: Vec<Command> = decode(&expected)
+            .into_iter()
+            .filter_map(|decoded| match decoded {
+                Decoded::Command(cmd) => Some(cmd),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(expected, self.commands());
+    }
+
+    /// Assert that exactly these bytes have been written so far.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bytes don't match.
+    #[track_caller]
+    pub fn assert_bytes(&self, expected: &[u8]) {
+        assert_eq!(expected, &self.bytes()[..]);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<u8>> {
+        self.bytes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Write for CaptureWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.lock().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for CaptureWriter {
+    fn poll_write(
+        mut self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        std::task::Poll::Ready(self.write(buf))
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+/// Fake bidirectional printer, for testing code which reads status or
+/// information back from the printer.
+///
+/// The MockPrinter keeps track of the text it has printed, whether the
+/// cash drawer is open, and how much paper is left, and answers `DLE EOT`
+/// and `GS r` status requests, `GS ( H` process ID requests, `GS I`
+/// information requests and `GS g` maintenance counter requests. Once Automatic Status Back is enabled,
+/// it also sends ASB frames whenever the enabled statuses change. Answers are read back with [std::io::Read].
+///
+/// Status requests are answered from the MockPrinter's state, unless a
+/// response has been scripted with [MockPrinter::script_status], in which
+/// case the scripted responses are used first, in order.
+///
+/// Like [CaptureWriter], cloning a MockPrinter returns another handle to
+/// the same printer.
+///
+/// ```rust
+/// # use epson::{testing::MockPrinter, RealTimeStatus, StatusKind, Command};
+/// # use std::io::{Read, Write};
+/// let mut printer = MockPrinter::new().with_paper_lines(1);
+/// printer.write_all(b"one\ntwo\n").unwrap();
+/// printer
+///     .write_all(&Command::RealTimeStatusRequest(StatusKind::PaperSensor).as_bytes().unwrap())
+///     .unwrap();
+///
+/// let mut status = [0u8];
+/// printer.read_exact(&mut status).unwrap();
+/// match RealTimeStatus::parse(StatusKind::PaperSensor, status[0]).unwrap() {
+///     RealTimeStatus::Paper(paper) => assert!(paper.paper_end),
+///     _ => unreachable!(),
+/// }
+/// assert_eq!("one\n", printer.printed_text());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MockPrinter {
+    inner: Arc<Mutex<MockState>>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    decoder: Decoder,
+    received: Vec<u8>,
+    printed: Vec<u8>,
+    responses: VecDeque<u8>,
+    lines: u32,
+    paper_lines: Option<u32>,
+    drawer_open: bool,
+    cover_open: bool,
+    status: Vec<(StatusKind, VecDeque<u8>)>,
+    info: Vec<(InfoKind, Vec<u8>)>,
+    counters: Vec<(u16, u32)>,
+    error: Option<ErrorStatus>,
+    asb: AsbFlags,
+    last_asb: Option<[u8; 4]>,
+}
+
+impl MockPrinter {
+    /// Create a new MockPrinter, with an endless roll of paper.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run out of paper after printing this many lines.
+    pub fn with_paper_lines(self, lines: u32) -> Self {
+        self.lock().paper_lines = Some(lines);
+        self
+    }
+
+    /// Answer `GS I` requests for this kind of information.
+    pub fn with_info(self, info: PrinterInfo) -> Self {
+        let (kind, response) = match info {
+            PrinterInfo::ModelId(id) => (InfoKind::ModelId, vec![id]),
+            PrinterInfo::TypeId(type_id) => (
+                InfoKind::TypeId,
+                vec![
+                    type_id.multibyte as u8
+                        | (type_id.autocutter as u8) << 1
+                        | (type_id.customer_display as u8) << 2,
+                ],
+            ),
+            PrinterInfo::VersionId(id) => (InfoKind::VersionId, vec![id]),
+            PrinterInfo::FirmwareVersion(text) => (InfoKind::FirmwareVersion, text_response(&text)),
+            PrinterInfo::MakerName(text) => (InfoKind::MakerName, text_response(&text)),
+            PrinterInfo::ModelName(text) => (InfoKind::ModelName, text_response(&text)),
+            PrinterInfo::SerialNumber(text) => (InfoKind::SerialNumber, text_response(&text)),
+        };
+
+        let mut state = self.lock();
+        state.info.retain(|(k, _)| *k != kind);
+        state.info.push((kind, response));
+        drop(state);
+        self
+    }
+
+    /// Set the value of a maintenance counter.
+    pub fn with_counter(self, counter: MaintenanceCounter, cumulative: bool, value: u32) -> Self {
+        let id = counter.id(cumulative);
+        let mut state = self.lock();
+        state.counters.retain(|(i, _)| *i != id);
+        state.counters.push((id, value));
+        drop(state);
+        self
+    }
+
+    /// Answer the next `DLE EOT` request of this kind with `byte`, rather
+    /// than the printer's state. Scripting several responses for a kind
+    /// answers requests with each of them in turn.
+    pub fn script_status(&self, kind: StatusKind, byte: u8) {
+        let mut state = self.lock();
+        match state.status.iter_mut().find(|(k, _)| *k == kind) {
+            Some((_, script)) => script.push_back(byte),
+            None => state.status.push((kind, VecDeque::from([byte]))),
+        }
+    }
+
+    /// Open or close the cash drawer. The drawer is opened by any drawer
+    /// kick command.
+    pub fn set_drawer_open(&self, open: bool) {
+        let mut state = self.lock();
+        state.drawer_open = open;
+        state.report();
+    }
+
+    /// Open or close the printer cover. The printer is offline while the
+    /// cover is open.
+    pub fn set_cover_open(&self, open: bool) {
+        let mut state = self.lock();
+        state.cover_open = open;
+        state.report();
+    }
+
+    /// Report `error`, such as an autocutter jam. The printer is offline,
+    /// and prints nothing, while reporting an error. Recoverable and
+    /// autocutter errors are cleared by `DLE ENQ` recovery commands.
+    pub fn set_error(&self, error: ErrorStatus) {
+        let mut state = self.lock();
+        state.error = Some(error).filter(ErrorStatus::any);
+        state.report();
+    }
+
+    /// Return the error the printer is reporting, if any.
+    pub fn error(&self) -> Option<ErrorStatus> {
+        self.lock().error
+    }
+
+    /// Load a new roll of paper, good for this many lines.
+    pub fn refill_paper(&self, lines: u32) {
+        let mut state = self.lock();
+        state.paper_lines = Some(state.lines + lines);
+        state.report();
+    }
+
+    /// Return true if the cash drawer is open.
+    pub fn drawer_open(&self) -> bool {
+        self.lock().drawer_open
+    }
+
+    /// Return true if the printer has run out of paper.
+    pub fn paper_out(&self) -> bool {
+        self.lock().paper_out()
+    }
+
+    /// Return the number of lines printed or fed so far.
+    pub fn lines_printed(&self) -> u32 {
+        self.lock().lines
+    }
+
+    /// Return every byte written to the printer so far.
+    pub fn received(&self) -> Vec<u8> {
+        self.lock().received.clone()
+    }
+
+    /// Return the text which was printed, leaving out anything sent after
+    /// the paper ran out.
+    pub fn printed_text(&self) -> String {
+        String::from_utf8_lossy(&self.lock().printed).into_owned()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Frame a text response the way the printer does.
+fn text_response(text: &str) -> Vec<u8> {
+    [&[0x5f], text.as_bytes(), &[0x00]].concat()
+}
+
+impl MockState {
+    fn paper_out(&self) -> bool {
+        self.paper_lines.is_some_and(|lines| self.lines >= lines)
+    }
+
+    fn feed(&mut self, lines: u32) {
+        self.lines = match self.paper_lines {
+            Some(max) => (self.lines + lines).min(max),
+            None => self.lines + lines,
+        };
+    }
+
+    fn handle(&mut self, decoded: Decoded) {
+        match decoded {
+            Decoded::Text(text) => {
+                for b in text {
+                    if self.paper_out() || self.cover_open || self.error.is_some() {
+                        break;
+                    }
+                    self.printed.push(b);
+                    if b == b'\n' {
+                        self.feed(1);
+                    }
+                }
+            }
+            Decoded::Command(Command::Feed(lines)) => self.feed(lines as u32),
+            Decoded::Command(Command::CashDrawer { .. })
+            | Decoded::Command(Command::RealTimeDrawerPulse { .. }) => self.drawer_open = true,
+            Decoded::Command(Command::RealTimeStatusRequest(kind)) => {
+                let byte = self.status(kind);
+                self.responses.push_back(byte);
+            }
+            Decoded::Command(Command::TransmitStatus(kind)) => {
+                let asb = self.asb_status();
+                let byte = match kind {
+                    TransmitStatusKind::Paper => asb.paper.to_transmitted_byte(),
+                    TransmitStatusKind::Drawer => asb.drawer().to_byte(),
+                };
+                self.responses.push_back(byte);
+            }
+            Decoded::Command(Command::RecoverAndRestart)
+            | Decoded::Command(Command::RecoverAndCancel) => {
+                self.error = self.error.filter(|e| e.unrecoverable || e.auto_recoverable);
+            }
+            Decoded::Command(Command::RequestProcessId(id)) => {
+                self.responses.extend([0x37, 0x22]);
+                self.responses.extend(id);
+                self.responses.push_back(0x00);
+            }
+            Decoded::Command(Command::EnableAsb(flags)) => {
+                // The printer reports its status as soon as ASB is enabled.
+                self.asb = flags;
+                self.last_asb = None;
+            }
+            Decoded::Command(Command::RequestPrinterId(kind)) => {
+                if let Some((_, response)) = self.info.iter().find(|(k, _)| *k == kind) {
+                    self.responses.extend(response.clone());
+                }
+            }
+            Decoded::Command(Command::RequestMaintenanceCounter {
+                counter,
+                cumulative,
+            }) => {
+                let id = counter.id(cumulative);
+                let value = self
+                    .counters
+                    .iter()
+                    .find(|(i, _)| *i == id)
+                    .map_or(0, |(_, value)| *value);
+                self.responses.extend(text_response(&value.to_string()));
+            }
+            Decoded::Command(Command::ResetMaintenanceCounter(counter)) => {
+                let id = counter.id(false);
+                self.counters.retain(|(i, _)| *i != id);
+            }
+            _ => {}
+        }
+    }
+
+    /// Return the `DLE EOT` response for the kind of status.
+    fn status(&mut self, kind: StatusKind) -> u8 {
+        if let Some((_, script)) = self.status.iter_mut().find(|(k, _)| *k == kind) {
+            if let Some(byte) = script.pop_front() {
+                return byte;
+            }
+        }
+
+        let asb = self.asb_status();
+        match kind {
+            StatusKind::Printer => RealTimeStatus::Printer(PrinterStatus {
+                drawer_pin3_high: asb.drawer_pin3_high,
+                offline: asb.offline,
+                waiting_for_recovery: false,
+                feed_button_pressed: false,
+            }),
+            StatusKind::OfflineCause => RealTimeStatus::Offline(OfflineStatus {
+                cover_open: asb.cover_open,
+                feeding_by_button: false,
+                paper_end_stop: asb.paper.paper_end,
+                error: asb.error.any(),
+            }),
+            StatusKind::ErrorCause => RealTimeStatus::Error(asb.error),
+            StatusKind::PaperSensor => RealTimeStatus::Paper(asb.paper),
+        }
+        .to_byte()
+    }
+
+    /// Return the printer's state, as it would be reported by ASB.
+    fn asb_status(&self) -> AsbStatus {
+        let paper_out = self.paper_out();
+        AsbStatus {
+            drawer_pin3_high: self.drawer_open,
+            offline: paper_out || self.cover_open || self.error.is_some(),
+            cover_open: self.cover_open,
+            feeding_by_button: false,
+            error: self.error.unwrap_or(ErrorStatus {
+                recoverable: false,
+                autocutter: false,
+                unrecoverable: false,
+                auto_recoverable: false,
+            }),
+            paper: PaperStatus {
+                near_end: false,
+                paper_end: paper_out,
+            },
+        }
+    }
+
+    /// Send an ASB frame if ASB is enabled, and anything it was enabled
+    /// for has changed since the last frame.
+    fn report(&mut self) {
+        let frame = self.asb_status().to_frame();
+        let flags = self.asb;
+        let mask = [
+            if flags.drawer { 0b0000_0100 } else { 0 } | if flags.online { 0b0110_1000 } else { 0 },
+            if flags.error { 0xff } else { 0 },
+            if flags.paper { 0xff } else { 0 },
+            0,
+        ];
+        let masked = |frame: [u8; 4]| -> [u8; 4] { std::array::from_fn(|i| frame[i] & mask[i]) };
+        if flags == AsbFlags::default()
+            || self
+                .last_asb
+                .is_some_and(|last| masked(last) == masked(frame))
+        {
+            return;
+        }
+        self.last_asb = Some(frame);
+        self.responses.extend(frame);
+    }
+}
+
+impl Write for MockPrinter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut state = self.lock();
+        state.received.extend_from_slice(buf);
+        state.decoder.push(buf);
+        while let Some(decoded) = state.decoder.next() {
+            state.handle(decoded);
+            state.report();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for MockPrinter {
+    /// Read back responses from the printer. When there is nothing to
+    /// read, this fails with `ErrorKind::WouldBlock` rather than blocking,
+    /// like a non-blocking socket.
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut state = self.lock();
+        if state.responses.is_empty() && !buf.is_empty() {
+            return Err(std::io::ErrorKind::WouldBlock.into());
+        }
+        let len = buf.len().min(state.responses.len());
+        for (b, response) in buf.iter_mut().zip(state.responses.drain(..len)) {
+            *b = response;
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for MockPrinter {
+    fn poll_write(
+        mut self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        std::task::Poll::Ready(self.write(buf))
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncRead for MockPrinter {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        let len = self.read(buf.initialize_unfilled())?;
+        buf.advance(len);
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        document::Style, Alignment, CapabilityProfile, CutCommand, CutMode, Model, Writer,
+    };
+
+    fn capture() -> (CaptureWriter, Writer) {
+        let capture = CaptureWriter::new();
+        let pos = Writer::open(Model::T20II, Box::new(capture.clone())).unwrap();
+        (capture, pos)
+    }
+
+    #[test]
+    fn capture_text_and_commands() {
+        let (capture, mut pos) = capture();
+        pos.emphasize(true).unwrap();
+        pos.write_all(b"Hello\n").unwrap();
+        pos.double_width(true).unwrap();
+
+        capture.assert_commands(&[
+            Command::Init,
+            Command::Emphasize(true),
+            Command::DoubleWidth(true),
+        ]);
+        assert_eq!("Hello\n", capture.text());
+    }
+
+    #[test]
+    fn capture_clear() {
+        let (capture, mut pos) = capture();
+        capture.clear();
+        pos.feed(1).unwrap();
+        capture.assert_bytes(&[0x1b, b'd', 0x01]);
+    }
+
+    #[test]
+    fn capture_cut_override() {
+        let model = Model::Custom(CapabilityProfile::new().cut_modes(&[CutMode::Partial]));
+        let capture = CaptureWriter::new();
+        let mut pos = Writer::open(model, Box::new(capture.clone())).unwrap();
+        assert!(pos.cut().is_err());
+
+        pos.set_cut_command(Some(CutCommand::EscM));
+        pos.cut().unwrap();
+        capture.assert_commands(&[Command::Init, Command::LegacyPartialCut]);
+
+        let model = Model::Custom(CapabilityProfile::new().cut_modes(&[CutMode::Full]));
+        let mut pos = Writer::open(model, Box::new(CaptureWriter::new())).unwrap();
+        pos.set_cut_command(Some(CutCommand::EscM));
+        assert!(pos.cut().is_err());
+    }
+
+    #[test]
+    fn capture_split_double_width() {
+        let (capture, mut pos) = capture();
+        pos.double_width(true).unwrap();
+        capture.clear();
+        pos.print_split("Total", "$5.75").unwrap();
+        let columns = Model::T20II.get_columns() / 2;
+        let line = format!("Total{}$5.75\n", " ".repeat(columns - 10));
+        capture.assert_bytes(line.as_bytes());
+    }
+
+    #[test]
+    fn capture_wrapped_restores_style() {
+        let (capture, mut pos) = capture();
+        pos.emphasize(true).unwrap();
+        pos.justify(Alignment::Center).unwrap();
+        capture.clear();
+        let style = Style {
+            underline: true,
+            width: 2,
+            ..Default::default()
+        };
+        pos.print_wrapped("Hi", &style).unwrap();
+        capture.assert_commands(&[
+            Command::Emphasize(false),
+            Command::Underline(true),
+            Command::Justification(Alignment::Left),
+            Command::TextSize {
+                width: 2,
+                height: 1,
+            },
+            Command::Text(b"Hi".to_vec()),
+            Command::Text(b"\n".to_vec()),
+            Command::Emphasize(true),
+            Command::Underline(false),
+            Command::Justification(Alignment::Center),
+            Command::TextSize {
+                width: 1,
+                height: 1,
+            },
+        ]);
+    }
+
+    #[test]
+    fn capture_style_without_text_size() {
+        let capture = CaptureWriter::new();
+        let mut pos = Writer::open(Model::Generic, Box::new(capture.clone())).unwrap();
+        capture.clear();
+        let style = Style {
+            height: 2,
+            ..Default::default()
+        };
+        pos.print_wrapped("Hi", &style).unwrap();
+        capture.assert_commands(&[
+            Command::LegacyPrintMode {
+                double_width: false,
+                double_height: true,
+            },
+            Command::Text(b"Hi".to_vec()),
+            Command::Text(b"\n".to_vec()),
+            Command::LegacyPrintMode {
+                double_width: false,
+                double_height: false,
+            },
+        ]);
+    }
+
+    #[test]
+    fn capture_unicode() {
+        let capture = CaptureWriter::new();
+        let mut pos = Writer::open(Model::T88VII, Box::new(capture.clone())).unwrap();
+        pos.set_unicode().unwrap();
+        capture.clear();
+        pos.print_text("Caf\u{e9} \u{2603}").unwrap();
+        pos.print_split("\u{e9}", "$1").unwrap();
+        let line = format!("\u{e9}{}$1\n", " ".repeat(Model::T88VII.get_columns() - 3));
+        capture.assert_bytes(format!("Caf\u{e9} \u{2603}{}", line).as_bytes());
+
+        // Initializing the printer turns UTF-8 back off.
+        pos.init().unwrap();
+        capture.clear();
+        pos.print_text("\u{e9}").unwrap();
+        capture.assert_bytes(&[0x82]);
+    }
+
+    #[test]
+    fn capture_control_characters() {
+        let (capture, mut pos) = capture();
+        capture.clear();
+        pos.print_line("Bob\x1bp\x00\x19\x19\tx").unwrap();
+        capture.assert_bytes(b"Bob?p???\tx\n");
+    }
+
+    #[test]
+    fn capture_print_line() {
+        let (capture, mut pos) = capture();
+        capture.clear();
+        pos.print_line("HACK THE PLANET").unwrap();
+        capture.assert_commands(&[Command::Text(b"HACK THE PLANET\n".to_vec())]);
+    }
+
+    #[test]
+    fn capture_write_fmt() {
+        let (capture, mut pos) = capture();
+        writeln!(pos, "Total: {:>6}", "5.75").unwrap();
+        std::fmt::Write::write_str(&mut pos, "Caf\u{e9}\n").unwrap();
+        // é is 0x82 in the default code page, PC437.
+        assert!(capture.bytes().ends_with(b"Total:   5.75\nCaf\x82\n"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn capture_mismatch() {
+        let (capture, mut pos) = capture();
+        pos.feed(1).unwrap();
+        capture.assert_commands(&[Command::Init, Command::Feed(2)]);
+    }
+    fn send(printer: &mut MockPrinter, cmd: Command) {
+        printer.write_all(&cmd.as_bytes().unwrap()).unwrap();
+    }
+
+    fn read_all(printer: &mut MockPrinter) -> Vec<u8> {
+        let mut out = vec![];
+        let mut buf = [0; 64];
+        while let Ok(n) = printer.read(&mut buf) {
+            out.extend_from_slice(&buf[..n]);
+        }
+        out
+    }
+
+    #[test]
+    fn mock_status() {
+        let mut printer = MockPrinter::new();
+        send(
+            &mut printer,
+            Command::RealTimeStatusRequest(StatusKind::Printer),
+        );
+        assert_eq!(vec![0x12], read_all(&mut printer));
+
+        send(
+            &mut printer,
+            Command::CashDrawer {
+                pin: crate::DrawerPin::Pin2,
+                on_ms: 100,
+                off_ms: 100,
+            },
+        );
+        printer.set_cover_open(true);
+        send(
+            &mut printer,
+            Command::RealTimeStatusRequest(StatusKind::Printer),
+        );
+        send(
+            &mut printer,
+            Command::RealTimeStatusRequest(StatusKind::OfflineCause),
+        );
+        assert_eq!(vec![0x1e, 0x16], read_all(&mut printer));
+    }
+
+    #[test]
+    fn mock_transmit_status() {
+        let mut printer = MockPrinter::new().with_paper_lines(1);
+        send(
+            &mut printer,
+            Command::TransmitStatus(TransmitStatusKind::Paper),
+        );
+        printer.write_all(b"one\n").unwrap();
+        send(
+            &mut printer,
+            Command::TransmitStatus(TransmitStatusKind::Paper),
+        );
+        printer.set_drawer_open(true);
+        send(
+            &mut printer,
+            Command::TransmitStatus(TransmitStatusKind::Drawer),
+        );
+        assert_eq!(vec![0x00, 0x0c, 0x01], read_all(&mut printer));
+    }
+
+    #[test]
+    fn mock_asb() {
+        let mut printer = MockPrinter::new().with_paper_lines(1);
+        send(
+            &mut printer,
+            Command::EnableAsb(AsbFlags {
+                online: true,
+                paper: true,
+                ..Default::default()
+            }),
+        );
+        assert_eq!(vec![0x10, 0x00, 0x00, 0x00], read_all(&mut printer));
+
+        // Drawer changes weren't asked for.
+        printer.set_drawer_open(true);
+        assert!(read_all(&mut printer).is_empty());
+
+        printer.write_all(b"one\n").unwrap();
+        let frame = read_all(&mut printer);
+        let status = AsbStatus::parse(&frame.try_into().unwrap()).unwrap();
+        assert!(status.offline);
+        assert!(status.paper.paper_end);
+        assert!(status.drawer().pin3_high);
+
+        send(&mut printer, Command::EnableAsb(AsbFlags::default()));
+        printer.set_cover_open(true);
+        assert!(read_all(&mut printer).is_empty());
+    }
+
+    #[test]
+    fn mock_recovery() {
+        let mut printer = MockPrinter::new();
+        printer.set_error(ErrorStatus {
+            recoverable: false,
+            autocutter: true,
+            unrecoverable: false,
+            auto_recoverable: false,
+        });
+        printer.write_all(b"lost\n").unwrap();
+        send(
+            &mut printer,
+            Command::RealTimeStatusRequest(StatusKind::ErrorCause),
+        );
+        assert_eq!(vec![0x1a], read_all(&mut printer));
+
+        send(&mut printer, Command::RecoverAndRestart);
+        printer.write_all(b"printed\n").unwrap();
+        assert_eq!(None, printer.error());
+        assert_eq!("printed\n", printer.printed_text());
+    }
+
+    #[test]
+    fn mock_scripted_status() {
+        let mut printer = MockPrinter::new();
+        printer.script_status(StatusKind::ErrorCause, 0x1a);
+        printer.script_status(StatusKind::ErrorCause, 0x32);
+        for _ in 0..3 {
+            send(
+                &mut printer,
+                Command::RealTimeStatusRequest(StatusKind::ErrorCause),
+            );
+        }
+        assert_eq!(vec![0x1a, 0x32, 0x12], read_all(&mut printer));
+    }
+
+    #[test]
+    fn mock_paper_out() {
+        let mut printer = MockPrinter::new().with_paper_lines(3);
+        printer.write_all(b"one\n").unwrap();
+        send(&mut printer, Command::Feed(1));
+        printer.write_all(b"two\nthree\n").unwrap();
+        assert!(printer.paper_out());
+        assert_eq!("one\ntwo\n", printer.printed_text());
+
+        printer.refill_paper(10);
+        assert!(!printer.paper_out());
+    }
+
+    #[test]
+    fn mock_info() {
+        let mut printer = MockPrinter::new()
+            .with_info(PrinterInfo::ModelName("TM-T20II".to_owned()))
+            .with_counter(MaintenanceCounter::Cuts, true, 1234);
+
+        send(&mut printer, Command::RequestPrinterId(InfoKind::ModelName));
+        assert_eq!(
+            PrinterInfo::ModelName("TM-T20II".to_owned()),
+            PrinterInfo::parse(InfoKind::ModelName, &read_all(&mut printer)).unwrap()
+        );
+
+        // Unknown information isn't answered.
+        send(
+            &mut printer,
+            Command::RequestPrinterId(InfoKind::SerialNumber),
+        );
+        assert!(read_all(&mut printer).is_empty());
+
+        send(
+            &mut printer,
+            Command::RequestMaintenanceCounter {
+                counter: MaintenanceCounter::Cuts,
+                cumulative: true,
+            },
+        );
+        assert_eq!(
+            Ok(1234),
+            MaintenanceCounter::parse_value(&read_all(&mut printer))
+        );
+    }
+}
+
+// vim: foldmethod=marker
@@ -0,0 +1,610 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Render text as an image with a TrueType font, so that any character
+//! the font has can be printed, even on printers without Unicode support.
+//!
+//! Fonts are loaded from the bytes of a `.ttf` file with
+//! [Font::from_bytes]; the crate doesn't bundle one. Glyphs are drawn
+//! from their outlines, so color emoji fonts, which store bitmaps, are not
+//! supported, and text is drawn left to right without shaping.
+//!
+//! ```rust,no_run
+//! # use epson::{textimage::Font, Model};
+//! let font = Font::from_bytes(std::fs::read("NotoSansCJK.ttf").unwrap()).unwrap();
+//! let img = epson::textimage::render(&font, "こんにちは", 48.0, Model::T20II);
+//! ```
+
+use super::raster::{self, FillRule, Line, SAMPLES};
+use super::{Error, Model};
+
+/// Size to draw text at to roughly match the printer's own Font A.
+pub const FONT_A_SIZE: f32 = 24.0;
+
+/// Number of line segments each curve is flattened into.
+const CURVE_STEPS: usize = 8;
+
+/// A TrueType font, read from a `.ttf` file.
+#[derive(Clone)]
+pub struct Font {
+    data: Vec<u8>,
+    units_per_em: f32,
+    ascender: f32,
+    descender: f32,
+    line_gap: f32,
+    long_loca: bool,
+    num_glyphs: u16,
+    num_h_metrics: u16,
+    cmap: usize,
+    cmap_format: u16,
+    loca: usize,
+    glyf: usize,
+    hmtx: usize,
+}
+
+impl std::fmt::Debug for Font {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Font")
+            .field("units_per_em", &self.units_per_em)
+            .field("num_glyphs", &self.num_glyphs)
+            .finish()
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn i16_at(data: &[u8], offset: usize) -> Option<i16> {
+    u16_at(data, offset).map(|v| v as i16)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+impl Font {
+    /// Load a font from the contents of a TrueType (`.ttf`) file. Returns
+    /// [Error::InvalidFont] if the font can't be read, or has no Unicode
+    /// character map.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, Error> {
+        Self::parse(data).ok_or(Error::InvalidFont)
+    }
+
+    fn parse(data: Vec<u8>) -> Option<Self> {
+        let num_tables = u16_at(&data, 4)? as usize;
+        let table = |tag: &[u8]| {
+            (0..num_tables)
+                .map(|i| 12 + 16 * i)
+                .find(|&record| data.get(record..record + 4) == Some(tag))
+                .and_then(|record| u32_at(&data, record + 8))
+                .map(|offset| offset as usize)
+        };
+        let head = table(b"head")?;
+        let hhea = table(b"hhea")?;
+        let maxp = table(b"maxp")?;
+        let cmap = table(b"cmap")?;
+
+        let mut subtable = None;
+        for i in 0..u16_at(&data, cmap + 2)? as usize {
+            let record = cmap + 4 + 8 * i;
+            let platform = u16_at(&data, record)?;
+            let encoding = u16_at(&data, record + 2)?;
+            let offset = cmap + u32_at(&data, record + 4)? as usize;
+            let format = u16_at(&data, offset)?;
+            if platform != 0 && !(platform == 3 && (encoding == 1 || encoding == 10)) {
+                continue;
+            }
+            match format {
+                12 => {
+                    subtable = Some((offset, format));
+                    break;
+                }
+                4 if subtable.is_none() => subtable = Some((offset, format)),
+                _ => {}
+            }
+        }
+        let (cmap, cmap_format) = subtable?;
+
+        let font = Self {
+            units_per_em: u16_at(&data, head + 18)? as f32,
+            long_loca: i16_at(&data, head + 50)? != 0,
+            ascender: i16_at(&data, hhea + 4)? as f32,
+            descender: i16_at(&data, hhea + 6)? as f32,
+            line_gap: i16_at(&data, hhea + 8)? as f32,
+            num_h_metrics: u16_at(&data, hhea + 34)?,
+            num_glyphs: u16_at(&data, maxp + 4)?,
+            cmap,
+            cmap_format,
+            loca: table(b"loca")?,
+            glyf: table(b"glyf")?,
+            hmtx: table(b"hmtx")?,
+            data,
+        };
+        if font.units_per_em == 0.0 || font.num_h_metrics == 0 {
+            return None;
+        }
+        Some(font)
+    }
+
+    /// Return true if the font has a glyph for `c`.
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.glyph_index(c) != 0
+    }
+
+    /// Return the glyph for `c`, or 0 (the missing glyph) if there isn't
+    /// one.
+    fn glyph_index(&self, c: char) -> u16 {
+        let c = c as u32;
+        let d = &self.data;
+        let glyph = match self.cmap_format {
+            4 => (|| {
+                let segments = u16_at(d, self.cmap + 6)? as usize / 2;
+                let ends = self.cmap + 14;
+                let starts = ends + 2 * segments + 2;
+                let deltas = starts + 2 * segments;
+                let ranges = deltas + 2 * segments;
+                for i in 0..segments {
+                    if c > u16_at(d, ends + 2 * i)? as u32 {
+                        continue;
+                    }
+                    let start = u16_at(d, starts + 2 * i)? as u32;
+                    if c < start {
+                        return None;
+                    }
+                    let delta = u16_at(d, deltas + 2 * i)?;
+                    let range = u16_at(d, ranges + 2 * i)? as usize;
+                    if range == 0 {
+                        return Some((c as u16).wrapping_add(delta));
+                    }
+                    let offset = ranges + 2 * i + range + 2 * (c - start) as usize;
+                    return match u16_at(d, offset)? {
+                        0 => None,
+                        glyph => Some(glyph.wrapping_add(delta)),
+                    };
+                }
+                None
+            })(),
+            _ => (|| {
+                for i in 0..u32_at(d, self.cmap + 12)? as usize {
+                    let group = self.cmap + 16 + 12 * i;
+                    let start = u32_at(d, group)?;
+                    if c >= start && c <= u32_at(d, group + 4)? {
+                        return u16::try_from(u32_at(d, group + 8)? + c - start).ok();
+                    }
+                }
+                None
+            })(),
+        };
+        glyph.filter(|&g| g < self.num_glyphs).unwrap_or(0)
+    }
+
+    /// Return the horizontal advance of a glyph, in font units.
+    fn advance(&self, glyph: u16) -> f32 {
+        let metric = glyph.min(self.num_h_metrics - 1) as usize;
+        u16_at(&self.data, self.hmtx + 4 * metric).unwrap_or(0) as f32
+    }
+
+    fn glyph_range(&self, glyph: u16) -> Option<(usize, usize)> {
+        let offset = |g: usize| {
+            if self.long_loca {
+                u32_at(&self.data, self.loca + 4 * g).map(|o| o as usize)
+            } else {
+                u16_at(&self.data, self.loca + 2 * g).map(|o| o as usize * 2)
+            }
+        };
+        let glyph = glyph as usize;
+        Some((self.glyf + offset(glyph)?, self.glyf + offset(glyph + 1)?))
+    }
+
+    /// Add the outline of a glyph, in font units, to `out`.
+    fn outline(&self, glyph: u16, depth: usize, out: &mut Vec<Line>) -> Option<()> {
+        let (start, end) = self.glyph_range(glyph)?;
+        if end <= start || depth > 8 {
+            return Some(());
+        }
+        let d = &self.data;
+        let contours = i16_at(d, start)?;
+
+        if contours < 0 {
+            let mut p = start + 10;
+            loop {
+                let flags = u16_at(d, p)?;
+                let child = u16_at(d, p + 2)?;
+                p += 4;
+                let (mut dx, mut dy) = if flags & 0x01 != 0 {
+                    p += 4;
+                    (i16_at(d, p - 4)? as f32, i16_at(d, p - 2)? as f32)
+                } else {
+                    p += 2;
+                    (*d.get(p - 2)? as i8 as f32, *d.get(p - 1)? as i8 as f32)
+                };
+                if flags & 0x02 == 0 {
+                    // Components positioned by matching points aren't
+                    // supported, so they're drawn unmoved.
+                    (dx, dy) = (0.0, 0.0);
+                }
+                let f2dot14 = |o| i16_at(d, o).map(|v| v as f32 / 16384.0);
+                let (a, b, c, e) = if flags & 0x08 != 0 {
+                    p += 2;
+                    let scale = f2dot14(p - 2)?;
+                    (scale, 0.0, 0.0, scale)
+                } else if flags & 0x40 != 0 {
+                    p += 4;
+                    (f2dot14(p - 4)?, 0.0, 0.0, f2dot14(p - 2)?)
+                } else if flags & 0x80 != 0 {
+                    p += 8;
+                    (
+                        f2dot14(p - 8)?,
+                        f2dot14(p - 6)?,
+                        f2dot14(p - 4)?,
+                        f2dot14(p - 2)?,
+                    )
+                } else {
+                    (1.0, 0.0, 0.0, 1.0)
+                };
+
+                let mut lines = vec![];
+                self.outline(child, depth + 1, &mut lines)?;
+                out.extend(lines.into_iter().map(|[x0, y0, x1, y1]| {
+                    [
+                        a * x0 + c * y0 + dx,
+                        b * x0 + e * y0 + dy,
+                        a * x1 + c * y1 + dx,
+                        b * x1 + e * y1 + dy,
+                    ]
+                }));
+                if flags & 0x20 == 0 {
+                    return Some(());
+                }
+            }
+        }
+
+        let contours = contours as usize;
+        let ends = (0..contours)
+            .map(|i| u16_at(d, start + 10 + 2 * i).map(|e| e as usize))
+            .collect::<Option<Vec<_>>>()?;
+        let points = ends.last().map_or(0, |e| e + 1);
+        let mut p = start + 10 + 2 * contours;
+        p += 2 + u16_at(d, p)? as usize;
+
+        let mut flags = Vec::with_capacity(points);
+        while flags.len() < points {
+            let flag = *d.get(p)?;
+            p += 1;
+            flags.push(flag);
+            if flag & 0x08 != 0 {
+                let repeat = *d.get(p)?;
+                p += 1;
+                flags.extend(std::iter::repeat_n(flag, repeat as usize));
+            }
+        }
+        flags.truncate(points);
+
+        let coordinates = |short: u8, same: u8, p: &mut usize| {
+            let mut value = 0i32;
+            let mut values = Vec::with_capacity(points);
+            for flag in &flags {
+                if flag & short != 0 {
+                    let delta = *d.get(*p)? as i32;
+                    *p += 1;
+                    value += if flag & same != 0 { delta } else { -delta };
+                } else if flag & same == 0 {
+                    value += i16_at(d, *p)? as i32;
+                    *p += 2;
+                }
+                values.push(value as f32);
+            }
+            Some(values)
+        };
+        let xs = coordinates(0x02, 0x10, &mut p)?;
+        let ys = coordinates(0x04, 0x20, &mut p)?;
+
+        let mut first = 0;
+        for last in ends {
+            let contour: Vec<_> = (first..=last.min(points - 1))
+                .map(|i| (xs[i], ys[i], flags[i] & 0x01 != 0))
+                .collect();
+            flatten(&contour, out);
+            first = last + 1;
+        }
+        Some(())
+    }
+}
+
+/// Turn a closed contour of on and off curve points into lines.
+fn flatten(points: &[(f32, f32, bool)], out: &mut Vec<Line>) {
+    if points.len() < 2 {
+        return;
+    }
+    let mid = |a: (f32, f32), b: (f32, f32)| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+    let n = points.len();
+
+    // Start on a point on the curve, making one up between the first and
+    // last points if they're both off of it.
+    let (start, sequence): ((f32, f32), Vec<_>) = match points.iter().position(|p| p.2) {
+        Some(i) => (
+            (points[i].0, points[i].1),
+            (1..=n).map(|k| points[(i + k) % n]).collect(),
+        ),
+        None => {
+            let start = mid(
+                (points[n - 1].0, points[n - 1].1),
+                (points[0].0, points[0].1),
+            );
+            let mut sequence = points.to_vec();
+            sequence.push((start.0, start.1, true));
+            (start, sequence)
+        }
+    };
+
+    let mut quad = |from: (f32, f32), ctrl: (f32, f32), to: (f32, f32)| {
+        let mut prev = from;
+        for step in 1..=CURVE_STEPS {
+            let t = step as f32 / CURVE_STEPS as f32;
+            let u = 1.0 - t;
+            let next = (
+                u * u * from.0 + 2.0 * u * t * ctrl.0 + t * t * to.0,
+                u * u * from.1 + 2.0 * u * t * ctrl.1 + t * t * to.1,
+            );
+            out.push([prev.0, prev.1, next.0, next.1]);
+            prev = next;
+        }
+    };
+
+    let mut current = start;
+    let mut ctrl: Option<(f32, f32)> = None;
+    for (x, y, on) in sequence {
+        if on {
+            match ctrl.take() {
+                Some(c) => quad(current, c, (x, y)),
+                None => quad(current, current, (x, y)),
+            }
+            current = (x, y);
+        } else {
+            if let Some(c) = ctrl {
+                let m = mid(c, (x, y));
+                quad(current, c, m);
+                current = m;
+            }
+            ctrl = Some((x, y));
+        }
+    }
+}
+
+/// Draw lines, already in pixels, onto `img`, darkening the pixels they
+/// enclose using the nonzero winding rule.
+fn fill(img: &mut image::GrayImage, lines: &[Line]) {
+    raster::fill(img, lines, FillRule::NonZero, |pixel, covered| {
+        let value = 255 - (255 * covered / (SAMPLES * SAMPLES)) as u8;
+        *pixel = (*pixel).min(value);
+    });
+}
+
+/// Render `text` in `font` as an image as wide as `model` can print, with
+/// characters `size` dots tall. Line feeds start a new line, and text
+/// which doesn't fit is wrapped at spaces where possible.
+pub fn render(font: &Font, text: &str, size: f32, model: Model) -> image::GrayImage {
+    let width = model.get_max_image_width() as f32;
+    let scale = size / font.units_per_em;
+    let space = font.advance(font.glyph_index(' ')) * scale;
+
+    // Lay out each glyph as (glyph, x) on its line.
+    let mut lines: Vec<Vec<(u16, f32)>> = vec![];
+    for paragraph in text.lines() {
+        let mut line = vec![];
+        let mut x = 0.0;
+        for word in paragraph.split(' ') {
+            let glyphs: Vec<_> = word
+                .chars()
+                .map(|c| {
+                    let glyph = font.glyph_index(c);
+                    (glyph, font.advance(glyph) * scale)
+                })
+                .collect();
+            let word_width: f32 = glyphs.iter().map(|(_, advance)| advance).sum();
+            if x > 0.0 && word_width > 0.0 && x + space + word_width > width {
+                lines.push(std::mem::take(&mut line));
+                x = 0.0;
+            } else if x > 0.0 || !line.is_empty() {
+                x += space;
+            }
+            for (glyph, advance) in glyphs {
+                if x > 0.0 && x + advance > width {
+                    lines.push(std::mem::take(&mut line));
+                    x = 0.0;
+                }
+                line.push((glyph, x));
+                x += advance;
+            }
+        }
+        lines.push(line);
+    }
+
+    let line_height = ((font.ascender - font.descender + font.line_gap) * scale).ceil();
+    let height = (line_height as u32 * lines.len() as u32).max(1);
+    let mut img = image::GrayImage::from_pixel(width as u32, height, image::Luma([255]));
+
+    let mut outline = vec![];
+    for (row, line) in lines.iter().enumerate() {
+        let baseline = row as f32 * line_height + font.ascender * scale;
+        for &(glyph, x) in line {
+            outline.clear();
+            if font.outline(glyph, 0, &mut outline).is_none() {
+                continue;
+            }
+            for line in outline.iter_mut() {
+                *line = [
+                    x + line[0] * scale,
+                    baseline - line[1] * scale,
+                    x + line[2] * scale,
+                    baseline - line[3] * scale,
+                ];
+            }
+            fill(&mut img, &outline);
+        }
+    }
+    img
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a tiny font with a 1000 unit em, where 'A' is a square of
+    /// straight lines and 'B' a circle-ish shape of curves.
+    fn test_font() -> Vec<u8> {
+        fn glyph(points: &[(i16, i16)], on: u8) -> Vec<u8> {
+            let mut g = vec![];
+            g.extend(1i16.to_be_bytes());
+            g.extend([0u8; 8]);
+            g.extend((points.len() as u16 - 1).to_be_bytes());
+            g.extend(0u16.to_be_bytes());
+            g.extend(std::iter::repeat_n(on, points.len()));
+            let (mut x, mut y) = (0, 0);
+            for (px, _) in points {
+                g.extend((px - x).to_be_bytes());
+                x = *px;
+            }
+            for (_, py) in points {
+                g.extend((py - y).to_be_bytes());
+                y = *py;
+            }
+            g
+        }
+        let square = [(100, 0), (900, 0), (900, 800), (100, 800)];
+        let glyphs = [vec![], glyph(&square, 1), glyph(&square, 0)];
+
+        let mut glyf = vec![];
+        let mut loca = vec![];
+        for g in &glyphs {
+            loca.extend((glyf.len() as u32).to_be_bytes());
+            glyf.extend(g);
+        }
+        loca.extend((glyf.len() as u32).to_be_bytes());
+
+        let mut head = vec![0u8; 54];
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+        head[50..52].copy_from_slice(&1i16.to_be_bytes());
+        let mut hhea = vec![0u8; 36];
+        hhea[4..6].copy_from_slice(&800i16.to_be_bytes());
+        hhea[6..8].copy_from_slice(&(-200i16).to_be_bytes());
+        hhea[34..36].copy_from_slice(&1u16.to_be_bytes());
+        let mut maxp = vec![0u8; 6];
+        maxp[4..6].copy_from_slice(&3u16.to_be_bytes());
+        let hmtx = [1000u16.to_be_bytes(), 0u16.to_be_bytes()].concat();
+
+        let mut cmap = vec![];
+        for v in [0u16, 1, 3, 1, 0, 12, 4, 32, 0, 4, 0, 0, 0] {
+            cmap.extend(v.to_be_bytes());
+        }
+        for v in [
+            0x42u16,
+            0xffff,
+            0,
+            0x41,
+            0xffff,
+            0u16.wrapping_sub(0x40),
+            1,
+            0,
+            0,
+        ] {
+            cmap.extend(v.to_be_bytes());
+        }
+
+        let tables: [(&[u8], Vec<u8>); 7] = [
+            (b"cmap", cmap),
+            (b"glyf", glyf),
+            (b"head", head),
+            (b"hhea", hhea),
+            (b"hmtx", hmtx),
+            (b"loca", loca),
+            (b"maxp", maxp),
+        ];
+        let mut font = vec![0, 1, 0, 0];
+        font.extend((tables.len() as u16).to_be_bytes());
+        font.extend([0u8; 6]);
+        let mut offset = 12 + 16 * tables.len();
+        for (tag, data) in &tables {
+            font.extend(*tag);
+            font.extend([0u8; 4]);
+            font.extend((offset as u32).to_be_bytes());
+            font.extend((data.len() as u32).to_be_bytes());
+            offset += data.len();
+        }
+        for (_, data) in tables {
+            font.extend(data);
+        }
+        font
+    }
+
+    #[test]
+    fn font_glyphs() {
+        let font = Font::from_bytes(test_font()).unwrap();
+        assert!(font.has_glyph('A'));
+        assert!(font.has_glyph('B'));
+        assert!(!font.has_glyph('C'));
+        assert!(!font.has_glyph('é'));
+        assert_eq!(
+            Err(Error::InvalidFont),
+            Font::from_bytes(vec![0; 12]).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn render_square() {
+        let font = Font::from_bytes(test_font()).unwrap();
+        let img = render(&font, "A", 20.0, Model::T20II);
+        assert_eq!((576, 20), img.dimensions());
+        assert_eq!(0, img.get_pixel(10, 8).0[0]);
+        assert_eq!(255, img.get_pixel(1, 8).0[0]);
+        assert_eq!(255, img.get_pixel(10, 18).0[0]);
+        assert_eq!(255, img.get_pixel(30, 8).0[0]);
+    }
+
+    #[test]
+    fn render_curves() {
+        let font = Font::from_bytes(test_font()).unwrap();
+        let img = render(&font, "B", 20.0, Model::T20II);
+        // All off curve points make a rounded shape inside the square.
+        assert_eq!(0, img.get_pixel(10, 8).0[0]);
+        assert_eq!(255, img.get_pixel(2, 0).0[0]);
+        assert_eq!(255, img.get_pixel(17, 15).0[0]);
+    }
+
+    #[test]
+    fn render_wraps() {
+        let font = Font::from_bytes(test_font()).unwrap();
+        let words = "AB ".repeat(10);
+        let img = render(&font, &words, 100.0, Model::T20II);
+        // Two glyphs a word, and two words fit across 576 dots.
+        assert_eq!(500, img.height());
+        assert_eq!(
+            100,
+            render(&font, "ABABAB", 100.0, Model::T20II).height() / 2
+        );
+    }
+}
+
+// vim: foldmethod=marker
@@ -0,0 +1,50 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Connections to printers which aren't just a socket or a file.
+//!
+//! Each transport is behind its own feature, and hands back a type
+//! implementing `std::io::Write` (and `std::io::Read`, for printers which
+//! send status back) which can be passed to [crate::Writer::open].
+//!
+//! # Printing over TLS
+//!
+//! Printers and print servers which require TLS, such as on a TLS-wrapped
+//! port 9100, can be reached with `tls::TlsStream` with the `tls` feature,
+//! which uses `rustls`. To use another TLS library, such as one linked
+//! against the system's OpenSSL, wrap the connection with it, and pass
+//! the stream to [crate::Writer::open].
+
+#[cfg(all(feature = "bluetooth", target_os = "linux"))]
+pub mod bluetooth;
+#[cfg(all(feature = "cups", unix))]
+pub mod cups;
+#[cfg(feature = "lpd")]
+pub mod lpd;
+#[cfg(all(feature = "serial", unix))]
+pub mod serial;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(all(feature = "usb", target_os = "linux"))]
+pub mod usb;
+#[cfg(all(feature = "winspool", windows))]
+pub mod winspool;
+
+// vim: foldmethod=marker
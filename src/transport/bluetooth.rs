@@ -0,0 +1,291 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Portable printers, such as the TM-P20 and TM-P80, connected over
+//! Bluetooth.
+//!
+//! Printers are reached over RFCOMM (the Serial Port Profile) using the
+//! Linux Bluetooth socket API, by the address of a printer which has
+//! already been paired with the host, for instance with `bluetoothctl`.
+//! Portable printers drop the connection when they sleep or go out of
+//! range, so a [BluetoothPrinter] reconnects and retries a write when the
+//! connection has been lost.
+//!
+//! ```rust,no_run
+//! # use epson::{Model, transport::bluetooth::{BluetoothConfig, BluetoothPrinter}};
+//! # use std::io::Write;
+//! let addr = "00:01:90:AA:BB:CC".parse().unwrap();
+//! let printer = BluetoothPrinter::connect(addr, &BluetoothConfig::default()).unwrap();
+//! let mut pos = epson::Writer::open(Model::Generic, Box::new(printer)).unwrap();
+//! pos.write_all(b"HACK THE PLANET\n").unwrap();
+//! ```
+
+use super::super::Error;
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    str::FromStr,
+    time::Duration,
+};
+
+const BTPROTO_RFCOMM: libc::c_int = 3;
+
+/// `struct sockaddr_rc` from `<bluetooth/rfcomm.h>`.
+#[repr(C)]
+struct SockaddrRc {
+    rc_family: libc::sa_family_t,
+    rc_bdaddr: [u8; 6],
+    rc_channel: u8,
+}
+
+/// Address of a Bluetooth device, such as `00:01:90:AA:BB:CC`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BdAddr(pub [u8; 6]);
+
+impl FromStr for BdAddr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut addr = [0u8; 6];
+        let mut parts = s.split(':');
+        for byte in addr.iter_mut() {
+            let part = parts.next().ok_or(Error::InvalidParameter)?;
+            if part.len() != 2 {
+                return Err(Error::InvalidParameter);
+            }
+            *byte = u8::from_str_radix(part, 16).map_err(|_| Error::InvalidParameter)?;
+        }
+        if parts.next().is_some() {
+            return Err(Error::InvalidParameter);
+        }
+        Ok(Self(addr))
+    }
+}
+
+impl std::fmt::Display for BdAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(
+            f,
+            "{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",
+            a, b, c, d, e, g
+        )
+    }
+}
+
+impl BdAddr {
+    fn sockaddr(&self, channel: u8) -> SockaddrRc {
+        // The kernel wants the address least significant byte first.
+        let mut rc_bdaddr = self.0;
+        rc_bdaddr.reverse();
+        SockaddrRc {
+            rc_family: libc::AF_BLUETOOTH as libc::sa_family_t,
+            rc_bdaddr,
+            rc_channel: channel,
+        }
+    }
+}
+
+/// Settings for connecting to a Bluetooth printer.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BluetoothConfig {
+    /// RFCOMM channel the printer's Serial Port Profile listens on. Epson
+    /// portable printers use channel 1.
+    pub channel: u8,
+
+    /// How many times to try connecting again after the connection is
+    /// lost, before giving up.
+    pub reconnect_attempts: u32,
+
+    /// How long to wait between attempts to reconnect.
+    pub reconnect_delay: Duration,
+}
+
+impl Default for BluetoothConfig {
+    fn default() -> Self {
+        Self {
+            channel: 1,
+            reconnect_attempts: 3,
+            reconnect_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+fn open(addr: BdAddr, channel: u8) -> io::Result<File> {
+    // SAFETY: socket returns either a new file descriptor, which is then
+    // owned by fd, or -1.
+    let fd = unsafe { libc::socket(libc::AF_BLUETOOTH, libc::SOCK_STREAM, BTPROTO_RFCOMM) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let sockaddr = addr.sockaddr(channel);
+    // SAFETY: sockaddr is a valid sockaddr_rc of the provided length.
+    let ret = unsafe {
+        libc::connect(
+            fd.as_raw_fd(),
+            &sockaddr as *const SockaddrRc as *const libc::sockaddr,
+            std::mem::size_of::<SockaddrRc>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(File::from(fd))
+}
+
+/// Return true if an error means the printer has gone away, and it's worth
+/// connecting again.
+fn is_disconnect(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::TimedOut
+    ) || err.raw_os_error() == Some(libc::EHOSTDOWN)
+}
+
+/// An RFCOMM connection to a paired Bluetooth printer.
+#[derive(Debug)]
+pub struct BluetoothPrinter {
+    addr: BdAddr,
+    config: BluetoothConfig,
+    stream: Option<File>,
+}
+
+impl BluetoothPrinter {
+    /// Connect to the printer at `addr`, which must already be paired.
+    pub fn connect(addr: BdAddr, config: &BluetoothConfig) -> io::Result<Self> {
+        Ok(Self {
+            addr,
+            config: *config,
+            stream: Some(open(addr, config.channel)?),
+        })
+    }
+
+    /// Address of the printer.
+    pub fn addr(&self) -> BdAddr {
+        self.addr
+    }
+
+    /// Drop the current connection, if any, and connect again, trying up
+    /// to [BluetoothConfig::reconnect_attempts] times.
+    pub fn reconnect(&mut self) -> io::Result<()> {
+        self.stream = None;
+        let mut attempt = 0;
+        loop {
+            match open(self.addr, self.config.channel) {
+                Ok(stream) => {
+                    self.stream = Some(stream);
+                    return Ok(());
+                }
+                Err(err) if attempt >= self.config.reconnect_attempts => return Err(err),
+                Err(_) => {
+                    attempt += 1;
+                    std::thread::sleep(self.config.reconnect_delay);
+                }
+            }
+        }
+    }
+
+    /// Run `f` on the connection, reconnecting first if it was lost, and
+    /// reconnecting and trying once more if `f` fails because it was lost.
+    fn with_stream<T>(&mut self, f: impl Fn(&mut File) -> io::Result<T>) -> io::Result<T> {
+        if self.stream.is_none() {
+            self.reconnect()?;
+        }
+        match f(self.stream.as_mut().unwrap()) {
+            Err(err) if is_disconnect(&err) => {
+                self.reconnect()?;
+                f(self.stream.as_mut().unwrap())
+            }
+            result => result,
+        }
+    }
+}
+
+impl Write for BluetoothPrinter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.with_stream(|stream| stream.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.stream.as_mut() {
+            Some(stream) => stream.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Read for BluetoothPrinter {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+        stream.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_addr() {
+        let addr: BdAddr = "00:01:90:aa:BB:cc".parse().unwrap();
+        assert_eq!(BdAddr([0x00, 0x01, 0x90, 0xaa, 0xbb, 0xcc]), addr);
+        assert_eq!("00:01:90:AA:BB:CC", addr.to_string());
+
+        for bad in [
+            "",
+            "00:01:90:AA:BB",
+            "00:01:90:AA:BB:CC:DD",
+            "0:01:90:AA:BB:CC",
+        ] {
+            assert_eq!(Err(Error::InvalidParameter), bad.parse::<BdAddr>());
+        }
+    }
+
+    #[test]
+    fn sockaddr() {
+        let addr: BdAddr = "00:01:90:AA:BB:CC".parse().unwrap();
+        let sockaddr = addr.sockaddr(1);
+        assert_eq!(libc::AF_BLUETOOTH as libc::sa_family_t, sockaddr.rc_family);
+        assert_eq!([0xcc, 0xbb, 0xaa, 0x90, 0x01, 0x00], sockaddr.rc_bdaddr);
+        assert_eq!(1, sockaddr.rc_channel);
+        assert_eq!(10, std::mem::size_of::<SockaddrRc>());
+    }
+
+    #[test]
+    fn disconnect_errors() {
+        assert!(is_disconnect(&io::ErrorKind::BrokenPipe.into()));
+        assert!(is_disconnect(&io::Error::from_raw_os_error(
+            libc::EHOSTDOWN
+        )));
+        assert!(!is_disconnect(&io::ErrorKind::PermissionDenied.into()));
+    }
+}
+
+// vim: foldmethod=marker
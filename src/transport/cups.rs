@@ -0,0 +1,172 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Printing through a CUPS queue.
+//!
+//! Bytes written to a [CupsJob] are held until it's flushed, and then
+//! submitted to the queue as a single raw job with `lp`, so the spooler
+//! passes the ESC/POS commands through to the printer untouched. Any
+//! bytes which haven't been submitted when the job is dropped are
+//! submitted then, as `std::io::BufWriter` does.
+//!
+//! ```rust,no_run
+//! # use epson::{Model, transport::cups::CupsJob};
+//! # use std::io::Write;
+//! let job = CupsJob::new("receipts").title("Order 1234");
+//! let mut pos = epson::Writer::open(Model::T20II, Box::new(job)).unwrap();
+//! pos.write_all(b"HACK THE PLANET\n").unwrap();
+//! pos.cut().unwrap();
+//! pos.flush().unwrap();
+//! ```
+
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+/// A raw print job for a CUPS queue.
+#[derive(Debug)]
+pub struct CupsJob {
+    queue: String,
+    title: Option<String>,
+    program: PathBuf,
+    buf: Vec<u8>,
+}
+
+impl CupsJob {
+    /// Create a job for the CUPS queue named `queue`, which should be set
+    /// up as a raw queue, or one whose driver accepts raw jobs.
+    pub fn new(queue: &str) -> Self {
+        Self {
+            queue: queue.to_owned(),
+            title: None,
+            program: PathBuf::from("lp"),
+            buf: vec![],
+        }
+    }
+
+    /// Set the title the job is listed under in the queue.
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_owned());
+        self
+    }
+
+    /// Use the `lp` program at `path`, rather than the one on `$PATH`.
+    pub fn program(mut self, path: impl Into<PathBuf>) -> Self {
+        self.program = path.into();
+        self
+    }
+
+    /// Submit everything written since the last job was submitted.
+    fn submit(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+
+        let mut command = Command::new(&self.program);
+        command.args(["-d", &self.queue, "-o", "raw"]);
+        if let Some(title) = &self.title {
+            command.args(["-t", title]);
+        }
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        child.stdin.take().unwrap().write_all(&self.buf)?;
+
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "lp failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+impl Write for CupsJob {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    /// Submit everything written so far as one job.
+    fn flush(&mut self) -> io::Result<()> {
+        self.submit()
+    }
+}
+
+impl Drop for CupsJob {
+    fn drop(&mut self) {
+        let _ = self.submit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    #[test]
+    fn submit_job() {
+        let dir = std::env::temp_dir().join(format!("epson-cups-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let lp = dir.join("lp");
+        fs::write(
+            &lp,
+            format!(
+                "#!/bin/sh\necho \"$@\" > {0}/args\ncat > {0}/job\n",
+                dir.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&lp, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut job = CupsJob::new("receipts").title("Order").program(&lp);
+        job.write_all(b"\x1b@hello\n").unwrap();
+        job.flush().unwrap();
+        let args = fs::read_to_string(dir.join("args")).unwrap();
+        let bytes = fs::read(dir.join("job")).unwrap();
+
+        // Nothing more to submit.
+        fs::remove_file(dir.join("job")).unwrap();
+        job.flush().unwrap();
+        let resubmitted = dir.join("job").exists();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!("-d receipts -o raw -t Order\n", args);
+        assert_eq!(b"\x1b@hello\n".to_vec(), bytes);
+        assert!(!resubmitted);
+    }
+
+    #[test]
+    fn submit_failure() {
+        let mut job = CupsJob::new("receipts").program("false");
+        job.write_all(b"hello").unwrap();
+        assert!(job.flush().is_err());
+        job.buf.clear();
+    }
+}
+
+// vim: foldmethod=marker
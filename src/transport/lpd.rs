@@ -0,0 +1,262 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Printing to an LPD print server, as described in RFC 1179.
+//!
+//! Older EpsonNet interfaces and many print servers accept jobs over LPD
+//! on port 515, rather than as raw bytes on port 9100. Bytes written to
+//! an [LpdJob] are held until it's flushed, and then sent to the queue as
+//! a single job, with the `l` file type so the server passes the ESC/POS
+//! commands through untouched. Any bytes which haven't been sent when the
+//! job is dropped are sent then, as `std::io::BufWriter` does.
+//!
+//! ```rust,no_run
+//! # use epson::{Model, transport::lpd::LpdJob};
+//! # use std::io::Write;
+//! let job = LpdJob::new("192.168.0.12:515", "lp").title("Order 1234");
+//! let mut pos = epson::Writer::open(Model::T20II, Box::new(job)).unwrap();
+//! pos.write_all(b"HACK THE PLANET\n").unwrap();
+//! pos.cut().unwrap();
+//! pos.flush().unwrap();
+//! ```
+
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+/// A print job for a queue on an LPD print server.
+#[derive(Debug)]
+pub struct LpdJob {
+    addr: String,
+    queue: String,
+    title: String,
+    user: String,
+    host: String,
+    timeout: Duration,
+    job_number: u16,
+    buf: Vec<u8>,
+}
+
+impl LpdJob {
+    /// Create a job for the queue named `queue` on the LPD server at
+    /// `addr`, such as `192.168.0.12:515`. EpsonNet interfaces accept
+    /// any queue name.
+    pub fn new(addr: &str, queue: &str) -> Self {
+        Self {
+            addr: addr.to_owned(),
+            queue: queue.to_owned(),
+            title: "epson".to_owned(),
+            user: "epson".to_owned(),
+            host: "epson".to_owned(),
+            timeout: Duration::from_secs(10),
+            job_number: (std::process::id() % 1000) as u16,
+            buf: vec![],
+        }
+    }
+
+    /// Set the title the job is listed under in the queue.
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = title.to_owned();
+        self
+    }
+
+    /// Set the user name and host name the job is sent on behalf of.
+    pub fn user(mut self, user: &str, host: &str) -> Self {
+        self.user = user.to_owned();
+        self.host = host.to_owned();
+        self
+    }
+
+    /// Set how long to wait to connect to the server, and for each of its
+    /// replies.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Build the control file describing a job whose data file is named
+    /// `data_file`.
+    fn control_file(&self, data_file: &str) -> String {
+        format!(
+            "H{}\nP{}\nJ{}\nN{}\nl{}\nU{}\n",
+            self.host, self.user, self.title, self.title, data_file, data_file
+        )
+    }
+
+    /// Send everything written since the last job was sent.
+    fn submit(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+
+        let addr = self
+            .addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::AddrNotAvailable))?;
+        let mut stream = TcpStream::connect_timeout(&addr, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        let name = format!("{:03}{}", self.job_number, self.host);
+        let control = self.control_file(&format!("dfA{}", name));
+
+        // Receive a printer job.
+        stream.write_all(format!("\x02{}\n", self.queue).as_bytes())?;
+        ack(&mut stream)?;
+
+        // Receive control file, then receive data file.
+        for (kind, file, data) in [
+            (b'\x02', "cfA", control.as_bytes()),
+            (b'\x03', "dfA", self.buf.as_slice()),
+        ] {
+            writeln!(stream, "{}{} {}{}", kind as char, data.len(), file, name)?;
+            ack(&mut stream)?;
+            stream.write_all(data)?;
+            stream.write_all(&[0])?;
+            ack(&mut stream)?;
+        }
+
+        self.job_number = (self.job_number + 1) % 1000;
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+/// Read the server's reply to a command, which is a zero byte if it was
+/// accepted.
+fn ack(stream: &mut TcpStream) -> io::Result<()> {
+    let mut reply = [0u8; 1];
+    stream.read_exact(&mut reply)?;
+    if reply[0] != 0 {
+        return Err(io::Error::other(format!(
+            "LPD server refused the job ({})",
+            reply[0]
+        )));
+    }
+    Ok(())
+}
+
+impl Write for LpdJob {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    /// Send everything written so far as one job.
+    fn flush(&mut self) -> io::Result<()> {
+        self.submit()
+    }
+}
+
+impl Drop for LpdJob {
+    fn drop(&mut self) {
+        let _ = self.submit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader},
+        net::TcpListener,
+        thread,
+    };
+
+    /// Accept one job, replying to each command with `reply`, and return
+    /// everything received.
+    fn serve(listener: TcpListener, reply: u8) -> thread::JoinHandle<Vec<Vec<u8>>> {
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut received = vec![];
+            loop {
+                let mut line = vec![];
+                if reader.read_until(b'\n', &mut line).unwrap() == 0 {
+                    break;
+                }
+                writer.write_all(&[reply]).unwrap();
+                if reply != 0 {
+                    break;
+                }
+                let len = match line[0] {
+                    2 | 3 if !received.is_empty() => {
+                        let line = String::from_utf8_lossy(&line[1..]);
+                        line.split(' ').next().unwrap().parse::<usize>().unwrap()
+                    }
+                    _ => {
+                        received.push(line);
+                        continue;
+                    }
+                };
+                received.push(line);
+                let mut data = vec![0; len + 1];
+                reader.read_exact(&mut data).unwrap();
+                writer.write_all(&[0]).unwrap();
+                received.push(data);
+            }
+            received
+        })
+    }
+
+    #[test]
+    fn submit_job() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = serve(listener, 0);
+
+        let mut job = LpdJob::new(&addr, "lp").title("Order").user("till", "pos1");
+        job.job_number = 7;
+        job.write_all(b"\x1b@hello\n").unwrap();
+        job.flush().unwrap();
+        drop(job);
+
+        let received = server.join().unwrap();
+        assert_eq!(
+            vec![
+                b"\x02lp\n".to_vec(),
+                b"\x0250 cfA007pos1\n".to_vec(),
+                b"Hpos1\nPtill\nJOrder\nNOrder\nldfA007pos1\nUdfA007pos1\n\0".to_vec(),
+                b"\x038 dfA007pos1\n".to_vec(),
+                b"\x1b@hello\n\0".to_vec(),
+            ],
+            received
+        );
+    }
+
+    #[test]
+    fn refused_job() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = serve(listener, 1);
+
+        let mut job = LpdJob::new(&addr, "nope");
+        job.write_all(b"hello").unwrap();
+        assert!(job.flush().is_err());
+        job.buf.clear();
+        server.join().unwrap();
+    }
+}
+
+// vim: foldmethod=marker
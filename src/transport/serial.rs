@@ -0,0 +1,516 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Printers connected to an RS-232 serial port.
+//!
+//! The port is configured with termios to match the printer's DIP switch
+//! or memory switch settings, which for most TM printers default to
+//! 38400 baud, 8 data bits, no parity, and DTR/DSR flow control.
+//!
+//! ```rust,no_run
+//! # use epson::{Model, transport::serial::{SerialConfig, SerialPort}};
+//! # use std::io::Write;
+//! let port = SerialPort::open("/dev/ttyS0", &SerialConfig::default()).unwrap();
+//! let mut pos = epson::Writer::open(Model::T88VI, Box::new(port)).unwrap();
+//! pos.write_all(b"HACK THE PLANET\n").unwrap();
+//! ```
+//!
+//! With the `tokio` feature, [AsyncSerialPort] can be used with an
+//! [crate::AsyncWriter] in the same way. It runs the blocking reads and
+//! writes on tokio's blocking thread pool; see its documentation for what
+//! that means for callers.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::{fs::OpenOptionsExt, io::AsRawFd},
+    path::Path,
+    time::{Duration, Instant},
+};
+#[cfg(feature = "tokio")]
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// How the printer and host stop each other from sending data faster
+/// than it can be handled.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum FlowControl {
+    /// No flow control. Data can be lost if the printer's buffer fills.
+    None,
+
+    /// The printer drops DSR when its buffer is nearly full, and raises
+    /// it again once there is room. This is the default for TM printers.
+    #[default]
+    DtrDsr,
+
+    /// The printer sends XOFF when its buffer is nearly full, and XON
+    /// once there is room.
+    XonXoff,
+
+    /// RTS/CTS handshaking, for printers or adapters wired for it.
+    RtsCts,
+}
+
+/// Parity bit sent with each character.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum Parity {
+    /// No parity bit.
+    #[default]
+    None,
+
+    /// Even parity.
+    Even,
+
+    /// Odd parity.
+    Odd,
+}
+
+/// Settings for a serial port, which must match the printer's.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SerialConfig {
+    /// Speed of the port, in bits per second. One of 1200, 2400, 4800,
+    /// 9600, 19200, 38400, 57600 or 115200.
+    pub baud: u32,
+
+    /// Number of data bits, either 7 or 8.
+    pub data_bits: u8,
+
+    /// Parity bit sent with each character.
+    pub parity: Parity,
+
+    /// Number of stop bits, either 1 or 2.
+    pub stop_bits: u8,
+
+    /// Flow control used by the printer.
+    pub flow_control: FlowControl,
+
+    /// How long to wait for data to read, or for the printer to be ready
+    /// for more data, before giving up with [io::ErrorKind::TimedOut].
+    /// Read timeouts are limited to 25.5 seconds by the serial driver.
+    pub timeout: Duration,
+}
+
+impl Default for SerialConfig {
+    fn default() -> Self {
+        Self {
+            baud: 38400,
+            data_bits: 8,
+            parity: Parity::None,
+            stop_bits: 1,
+            flow_control: FlowControl::DtrDsr,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+fn invalid(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn check(ret: libc::c_int) -> io::Result<()> {
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn speed(baud: u32) -> io::Result<libc::speed_t> {
+    Ok(match baud {
+        1200 => libc::B1200,
+        2400 => libc::B2400,
+        4800 => libc::B4800,
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        _ => return Err(invalid("unsupported baud rate")),
+    })
+}
+
+/// Set up `termios` for raw, 8-bit clean I/O with the provided settings.
+fn configure(termios: &mut libc::termios, config: &SerialConfig) -> io::Result<()> {
+    let speed = speed(config.baud)?;
+
+    // SAFETY: termios is a valid, initialized struct.
+    unsafe {
+        libc::cfmakeraw(termios);
+        check(libc::cfsetispeed(termios, speed))?;
+        check(libc::cfsetospeed(termios, speed))?;
+    }
+
+    termios.c_cflag &= !(libc::CSIZE | libc::PARENB | libc::PARODD | libc::CSTOPB);
+    termios.c_cflag &= !libc::CRTSCTS;
+    termios.c_cflag |= libc::CLOCAL | libc::CREAD;
+    termios.c_cflag |= match config.data_bits {
+        7 => libc::CS7,
+        8 => libc::CS8,
+        _ => return Err(invalid("data bits must be 7 or 8")),
+    };
+    termios.c_cflag |= match config.parity {
+        Parity::None => 0,
+        Parity::Even => libc::PARENB,
+        Parity::Odd => libc::PARENB | libc::PARODD,
+    };
+    termios.c_cflag |= match config.stop_bits {
+        1 => 0,
+        2 => libc::CSTOPB,
+        _ => return Err(invalid("stop bits must be 1 or 2")),
+    };
+
+    termios.c_iflag &= !(libc::IXON | libc::IXOFF | libc::IXANY);
+    match config.flow_control {
+        FlowControl::XonXoff => termios.c_iflag |= libc::IXON | libc::IXOFF,
+        FlowControl::RtsCts => termios.c_cflag |= libc::CRTSCTS,
+        FlowControl::None | FlowControl::DtrDsr => {}
+    }
+
+    // Return from read as soon as there's any data, or after the timeout.
+    termios.c_cc[libc::VMIN] = 0;
+    termios.c_cc[libc::VTIME] = (config.timeout.as_millis() / 100).clamp(1, 255) as u8;
+    Ok(())
+}
+
+/// An open serial port connected to a printer.
+#[derive(Debug)]
+pub struct SerialPort {
+    file: File,
+    flow_control: FlowControl,
+    timeout: Duration,
+}
+
+impl SerialPort {
+    /// Open and configure the serial port at `path`, such as `/dev/ttyS0`
+    /// or `/dev/ttyUSB0`. DTR is raised, to tell the printer the host is
+    /// ready.
+    pub fn open(path: impl AsRef<Path>, config: &SerialConfig) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(path)?;
+        let fd = file.as_raw_fd();
+
+        // SAFETY: fd is an open file descriptor for the lifetime of file,
+        // and termios is fully written by tcgetattr before it's used.
+        unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            check(libc::tcgetattr(fd, &mut termios))?;
+            configure(&mut termios, config)?;
+            check(libc::tcsetattr(fd, libc::TCSANOW, &termios))?;
+        }
+
+        let port = Self {
+            file,
+            flow_control: config.flow_control,
+            timeout: config.timeout,
+        };
+        port.set_dtr(true)?;
+        Ok(port)
+    }
+
+    fn modem_bits(&self) -> io::Result<libc::c_int> {
+        let mut bits: libc::c_int = 0;
+        // SAFETY: TIOCMGET writes a c_int to the provided pointer.
+        check(unsafe { libc::ioctl(self.file.as_raw_fd(), libc::TIOCMGET, &mut bits) })?;
+        Ok(bits)
+    }
+
+    /// Raise or lower the DTR line.
+    pub fn set_dtr(&self, level: bool) -> io::Result<()> {
+        let request = if level {
+            libc::TIOCMBIS
+        } else {
+            libc::TIOCMBIC
+        };
+        let bits: libc::c_int = libc::TIOCM_DTR;
+        // SAFETY: TIOCMBIS and TIOCMBIC read a c_int from the pointer.
+        check(unsafe { libc::ioctl(self.file.as_raw_fd(), request, &bits) })
+    }
+
+    /// Return true if the printer is raising DSR, meaning it's ready to
+    /// receive data.
+    pub fn dsr(&self) -> io::Result<bool> {
+        Ok(self.modem_bits()? & libc::TIOCM_DSR != 0)
+    }
+
+    /// Wait until all written data has been sent to the printer.
+    pub fn drain(&self) -> io::Result<()> {
+        // SAFETY: fd is open for the lifetime of self.
+        check(unsafe { libc::tcdrain(self.file.as_raw_fd()) })
+    }
+
+    /// Return a second handle to the same port, so that status can be
+    /// read on one while the other is owned by a [crate::Writer].
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            file: self.file.try_clone()?,
+            flow_control: self.flow_control,
+            timeout: self.timeout,
+        })
+    }
+
+    /// With DTR/DSR flow control, wait for the printer to raise DSR.
+    fn wait_ready(&self) -> io::Result<()> {
+        if self.flow_control != FlowControl::DtrDsr {
+            return Ok(());
+        }
+        let start = Instant::now();
+        while !self.dsr()? {
+            if start.elapsed() >= self.timeout {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "printer did not raise DSR",
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        Ok(())
+    }
+}
+
+impl Write for SerialPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.wait_ready()?;
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.drain()
+    }
+}
+
+impl Read for SerialPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.file.read(buf)? {
+            0 if !buf.is_empty() => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "no data from printer",
+            )),
+            n => Ok(n),
+        }
+    }
+}
+
+/// An open serial port connected to a printer, for use with an
+/// [crate::AsyncWriter].
+///
+/// The port is driven through [tokio::fs::File], so each read and write
+/// is a blocking call handed to tokio's blocking thread pool, rather
+/// than the file descriptor being registered with the reactor as sockets
+/// are (with `AsyncFd`). That keeps the `serial` feature to tokio's `fs`
+/// support, without its `net` feature, and behaves the same with any
+/// tty driver, at the cost of some latency per call:
+///
+/// - This must be used from within a tokio runtime with the blocking
+///   pool available, which is the default for both runtime flavors.
+/// - Writes are buffered by `tokio::fs::File` and complete in the
+///   background, so call `flush` to wait until the data has reached the
+///   driver, and to see any error writing it.
+/// - A read holds a blocking pool thread until data arrives or
+///   [SerialConfig::timeout] passes, and then fails with
+///   [io::ErrorKind::TimedOut]. Dropping the read future doesn't cancel
+///   the call already running on that thread.
+/// - Waiting for DSR, with [FlowControl::DtrDsr], polls the line every
+///   10ms on a tokio timer, without holding a thread.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct AsyncSerialPort {
+    port: SerialPort,
+    file: tokio::fs::File,
+    wait: Option<(Instant, Pin<Box<tokio::time::Sleep>>)>,
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncSerialPort {
+    /// Open and configure the serial port at `path`, as with
+    /// [SerialPort::open].
+    pub fn open(path: impl AsRef<Path>, config: &SerialConfig) -> io::Result<Self> {
+        Self::from_port(SerialPort::open(path, config)?)
+    }
+
+    /// Use an already open [SerialPort] asynchronously.
+    pub fn from_port(port: SerialPort) -> io::Result<Self> {
+        let file = tokio::fs::File::from_std(port.file.try_clone()?);
+        Ok(Self {
+            port,
+            file,
+            wait: None,
+        })
+    }
+
+    /// Return the underlying [SerialPort], to control DTR or check DSR.
+    pub fn port(&self) -> &SerialPort {
+        &self.port
+    }
+
+    /// With DTR/DSR flow control, wait for the printer to raise DSR.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.port.flow_control != FlowControl::DtrDsr {
+            return Poll::Ready(Ok(()));
+        }
+        loop {
+            if self.port.dsr()? {
+                self.wait = None;
+                return Poll::Ready(Ok(()));
+            }
+            let (start, sleep) = self.wait.get_or_insert_with(|| {
+                (
+                    Instant::now(),
+                    Box::pin(tokio::time::sleep(Duration::from_millis(10))),
+                )
+            });
+            if start.elapsed() >= self.port.timeout {
+                self.wait = None;
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "printer did not raise DSR",
+                )));
+            }
+            ready!(sleep.as_mut().poll(cx));
+            sleep
+                .as_mut()
+                .reset(tokio::time::Instant::now() + Duration::from_millis(10));
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncWrite for AsyncSerialPort {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.poll_ready(cx))?;
+        Pin::new(&mut self.file).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.file).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.file).poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncRead for AsyncSerialPort {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut self.file).poll_read(cx, buf))?;
+        if buf.filled().len() == filled && buf.remaining() > 0 {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "no data from printer",
+            )));
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configured(config: &SerialConfig) -> io::Result<libc::termios> {
+        // SAFETY: termios is plain data, and all zeroes is a valid value.
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        configure(&mut termios, config)?;
+        Ok(termios)
+    }
+
+    #[test]
+    fn configure_defaults() {
+        let termios = configured(&SerialConfig::default()).unwrap();
+        assert_eq!(libc::B38400, unsafe { libc::cfgetospeed(&termios) });
+        assert_eq!(libc::CS8, termios.c_cflag & libc::CSIZE);
+        assert_eq!(0, termios.c_cflag & (libc::PARENB | libc::CSTOPB));
+        assert_eq!(0, termios.c_cflag & libc::CRTSCTS);
+        assert_eq!(0, termios.c_iflag & (libc::IXON | libc::IXOFF));
+        assert_eq!(50, termios.c_cc[libc::VTIME]);
+    }
+
+    #[test]
+    fn configure_options() {
+        let termios = configured(&SerialConfig {
+            baud: 9600,
+            data_bits: 7,
+            parity: Parity::Odd,
+            stop_bits: 2,
+            flow_control: FlowControl::XonXoff,
+            timeout: Duration::from_secs(60),
+        })
+        .unwrap();
+        assert_eq!(libc::B9600, unsafe { libc::cfgetispeed(&termios) });
+        assert_eq!(libc::CS7, termios.c_cflag & libc::CSIZE);
+        assert_eq!(
+            libc::PARENB | libc::PARODD | libc::CSTOPB,
+            termios.c_cflag & (libc::PARENB | libc::PARODD | libc::CSTOPB)
+        );
+        assert_eq!(
+            libc::IXON | libc::IXOFF,
+            termios.c_iflag & (libc::IXON | libc::IXOFF)
+        );
+        assert_eq!(255, termios.c_cc[libc::VTIME]);
+
+        let termios = configured(&SerialConfig {
+            flow_control: FlowControl::RtsCts,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(libc::CRTSCTS, termios.c_cflag & libc::CRTSCTS);
+    }
+
+    #[test]
+    fn configure_invalid() {
+        for config in [
+            SerialConfig {
+                baud: 12345,
+                ..Default::default()
+            },
+            SerialConfig {
+                data_bits: 6,
+                ..Default::default()
+            },
+            SerialConfig {
+                stop_bits: 3,
+                ..Default::default()
+            },
+        ] {
+            assert_eq!(
+                io::ErrorKind::InvalidInput,
+                configured(&config).err().unwrap().kind()
+            );
+        }
+    }
+}
+
+// vim: foldmethod=marker
@@ -0,0 +1,253 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! USB connected printers, through the Linux `usblp` driver.
+//!
+//! The kernel claims the bulk OUT and IN endpoints of any USB printer
+//! class device, and exposes them as `/dev/usb/lpN`: writes go to the
+//! printer, and reads return whatever it sent back, such as status bytes.
+//! Printers are found by walking `/sys/bus/usb/devices` for Epson's
+//! vendor ID, so no OS print queue is needed.
+//!
+//! ```rust,no_run
+//! # use epson::{Model, transport::usb::UsbPrinter};
+//! # use std::io::Write;
+//! let printer = UsbPrinter::first().unwrap();
+//! let mut pos = epson::Writer::open(Model::T20II, Box::new(printer)).unwrap();
+//! pos.write_all(b"HACK THE PLANET\n").unwrap();
+//! ```
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// USB vendor ID assigned to Seiko Epson.
+pub const EPSON_VENDOR_ID: u16 = 0x04b8;
+
+const SYSFS_DEVICES: &str = "/sys/bus/usb/devices";
+const DEV_USB: &str = "/dev/usb";
+
+/// A USB printer found by [discover].
+#[derive(Clone, Debug, PartialEq)]
+pub struct UsbDevice {
+    /// USB vendor ID, which is [EPSON_VENDOR_ID] for Epson printers.
+    pub vendor_id: u16,
+
+    /// USB product ID, which identifies the printer model.
+    pub product_id: u16,
+
+    /// Product name reported by the printer, such as "TM-T20II".
+    pub product: Option<String>,
+
+    /// Serial number reported by the printer, if any.
+    pub serial: Option<String>,
+
+    /// Path to the `usblp` device node for this printer.
+    pub path: PathBuf,
+}
+
+/// Find all attached Epson USB printers that have been bound to the
+/// `usblp` driver.
+pub fn discover() -> io::Result<Vec<UsbDevice>> {
+    discover_in(Path::new(SYSFS_DEVICES), Path::new(DEV_USB))
+}
+
+fn read_attr(dir: &Path, name: &str) -> Option<String> {
+    fs::read_to_string(dir.join(name))
+        .ok()
+        .map(|v| v.trim().to_owned())
+}
+
+fn read_id(dir: &Path, name: &str) -> Option<u16> {
+    u16::from_str_radix(&read_attr(dir, name)?, 16).ok()
+}
+
+/// Find the name of the `lpN` node under one of the device's interfaces.
+/// Newer kernels put it under `usbmisc`, and older ones under `usb`.
+fn find_lp(sysfs: &Path, device: &str) -> Option<String> {
+    let prefix = format!("{}:", device);
+    for interface in fs::read_dir(sysfs).ok()?.flatten() {
+        if !interface.file_name().to_string_lossy().starts_with(&prefix) {
+            continue;
+        }
+        for class in ["usbmisc", "usb"] {
+            let Ok(entries) = fs::read_dir(interface.path().join(class)) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if name.starts_with("lp") {
+                    return Some(name);
+                }
+            }
+        }
+    }
+    None
+}
+
+fn discover_in(sysfs: &Path, dev: &Path) -> io::Result<Vec<UsbDevice>> {
+    let mut devices = vec![];
+    for entry in fs::read_dir(sysfs)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.contains(':') {
+            continue;
+        }
+        let dir = entry.path();
+        if read_id(&dir, "idVendor") != Some(EPSON_VENDOR_ID) {
+            continue;
+        }
+        let (Some(product_id), Some(lp)) = (read_id(&dir, "idProduct"), find_lp(sysfs, &name))
+        else {
+            continue;
+        };
+        devices.push(UsbDevice {
+            vendor_id: EPSON_VENDOR_ID,
+            product_id,
+            product: read_attr(&dir, "product"),
+            serial: read_attr(&dir, "serial"),
+            path: dev.join(lp),
+        });
+    }
+    devices.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(devices)
+}
+
+/// An open connection to a USB printer. Bytes written are sent to the
+/// printer's bulk OUT endpoint, and reads return bytes from its bulk IN
+/// endpoint, such as responses to [crate::Writer::request_status].
+#[derive(Debug)]
+pub struct UsbPrinter {
+    file: File,
+}
+
+impl UsbPrinter {
+    /// Open the printer described by a [UsbDevice] returned from
+    /// [discover].
+    pub fn open(device: &UsbDevice) -> io::Result<Self> {
+        Self::open_path(&device.path)
+    }
+
+    /// Open a `usblp` device node, such as `/dev/usb/lp0`, directly.
+    pub fn open_path(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(Self { file })
+    }
+
+    /// Open the first Epson printer returned by [discover]. An error of
+    /// kind [io::ErrorKind::NotFound] is returned if there isn't one.
+    pub fn first() -> io::Result<Self> {
+        match discover()?.first() {
+            Some(device) => Self::open(device),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no Epson USB printer found",
+            )),
+        }
+    }
+
+    /// Return a second handle to the same printer, so that status can be
+    /// read on one while the other is owned by a [crate::Writer].
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            file: self.file.try_clone()?,
+        })
+    }
+}
+
+impl Write for UsbPrinter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Read for UsbPrinter {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: PathBuf, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn discover_sysfs() {
+        let root = std::env::temp_dir().join(format!("epson-usb-{}", std::process::id()));
+        let sysfs = root.join("devices");
+
+        // An Epson printer on a newer kernel.
+        write(sysfs.join("1-2/idVendor"), "04b8\n");
+        write(sysfs.join("1-2/idProduct"), "0e15\n");
+        write(sysfs.join("1-2/product"), "TM-T20II\n");
+        fs::create_dir_all(sysfs.join("1-2:1.0/usbmisc/lp1")).unwrap();
+
+        // An Epson printer on an older kernel, without a serial number.
+        write(sysfs.join("1-1/idVendor"), "04b8\n");
+        write(sysfs.join("1-1/idProduct"), "0202\n");
+        write(sysfs.join("1-1/serial"), "ABC123\n");
+        fs::create_dir_all(sysfs.join("1-1:1.0/usb/lp0")).unwrap();
+
+        // Someone else's printer.
+        write(sysfs.join("2-1/idVendor"), "04f9\n");
+        write(sysfs.join("2-1/idProduct"), "0027\n");
+        fs::create_dir_all(sysfs.join("2-1:1.0/usbmisc/lp2")).unwrap();
+
+        // An Epson device without usblp bound, such as a scanner.
+        write(sysfs.join("3-1/idVendor"), "04b8\n");
+        write(sysfs.join("3-1/idProduct"), "0142\n");
+
+        let devices = discover_in(&sysfs, Path::new("/dev/usb")).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            vec![
+                UsbDevice {
+                    vendor_id: EPSON_VENDOR_ID,
+                    product_id: 0x0202,
+                    product: None,
+                    serial: Some("ABC123".to_owned()),
+                    path: PathBuf::from("/dev/usb/lp0"),
+                },
+                UsbDevice {
+                    vendor_id: EPSON_VENDOR_ID,
+                    product_id: 0x0e15,
+                    product: Some("TM-T20II".to_owned()),
+                    serial: None,
+                    path: PathBuf::from("/dev/usb/lp1"),
+                },
+            ],
+            devices
+        );
+    }
+}
+
+// vim: foldmethod=marker
@@ -0,0 +1,192 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Printing through the Windows print spooler (`winspool`).
+//!
+//! Most Windows installs only expose a TM printer as a named printer in
+//! the spooler. Bytes written to a [WinspoolJob] are held until it's
+//! flushed, and then sent to the printer as a single `RAW` document with
+//! `WritePrinter`, so the driver passes the ESC/POS commands through
+//! untouched. Any bytes which haven't been sent when the job is dropped
+//! are sent then, as `std::io::BufWriter` does.
+//!
+//! ```rust,ignore
+//! let job = WinspoolJob::open("EPSON TM-T20II Receipt")?.title("Order 1234");
+//! let mut pos = epson::Writer::open(Model::T20II, Box::new(job))?;
+//! pos.write_all(b"HACK THE PLANET\n")?;
+//! pos.cut()?;
+//! pos.flush()?;
+//! ```
+
+use std::{
+    ffi::{c_void, OsStr},
+    io::{self, Write},
+    os::windows::ffi::OsStrExt,
+    ptr,
+};
+
+type Handle = *mut c_void;
+type Bool = i32;
+
+/// `DOC_INFO_1W` from `winspool.h`.
+#[repr(C)]
+struct DocInfo1 {
+    doc_name: *const u16,
+    output_file: *const u16,
+    datatype: *const u16,
+}
+
+#[link(name = "winspool")]
+extern "system" {
+    fn OpenPrinterW(name: *const u16, printer: *mut Handle, default: *const c_void) -> Bool;
+    fn ClosePrinter(printer: Handle) -> Bool;
+    fn StartDocPrinterW(printer: Handle, level: u32, doc_info: *const DocInfo1) -> u32;
+    fn EndDocPrinter(printer: Handle) -> Bool;
+    fn StartPagePrinter(printer: Handle) -> Bool;
+    fn EndPagePrinter(printer: Handle) -> Bool;
+    fn WritePrinter(printer: Handle, buf: *const c_void, len: u32, written: *mut u32) -> Bool;
+}
+
+/// Convert `s` to a NUL terminated UTF-16 string.
+fn wide(s: &str) -> Vec<u16> {
+    OsStr::new(s).encode_wide().chain(Some(0)).collect()
+}
+
+fn check(ret: Bool) -> io::Result<()> {
+    if ret == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// A raw print job for a printer managed by the Windows print spooler.
+#[derive(Debug)]
+pub struct WinspoolJob {
+    printer: Handle,
+    title: String,
+    buf: Vec<u8>,
+}
+
+// SAFETY: printer handles aren't tied to the thread that opened them.
+unsafe impl Send for WinspoolJob {}
+
+impl WinspoolJob {
+    /// Open the printer named `name`, as listed in "Printers & scanners".
+    pub fn open(name: &str) -> io::Result<Self> {
+        let name = wide(name);
+        let mut printer = ptr::null_mut();
+        // SAFETY: name is NUL terminated, and printer is written on success.
+        check(unsafe { OpenPrinterW(name.as_ptr(), &mut printer, ptr::null()) })?;
+        Ok(Self {
+            printer,
+            title: "epson".to_owned(),
+            buf: vec![],
+        })
+    }
+
+    /// Set the title the job is listed under in the print queue.
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = title.to_owned();
+        self
+    }
+
+    /// Send everything written since the last job was sent.
+    fn submit(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+
+        let title = wide(&self.title);
+        let datatype = wide("RAW");
+        let info = DocInfo1 {
+            doc_name: title.as_ptr(),
+            output_file: ptr::null(),
+            datatype: datatype.as_ptr(),
+        };
+        // SAFETY: info and the strings it points to outlive the call.
+        if unsafe { StartDocPrinterW(self.printer, 1, &info) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let result = self.write_page();
+        // SAFETY: a document was started above.
+        let ended = check(unsafe { EndDocPrinter(self.printer) });
+        result.and(ended)?;
+        self.buf.clear();
+        Ok(())
+    }
+
+    fn write_page(&self) -> io::Result<()> {
+        // SAFETY: the printer handle is open, and buf is valid for its
+        // length for each call.
+        unsafe {
+            check(StartPagePrinter(self.printer))?;
+            let mut sent = 0;
+            while sent < self.buf.len() {
+                let chunk = &self.buf[sent..];
+                let len = chunk.len().min(u32::MAX as usize) as u32;
+                let mut written = 0;
+                check(WritePrinter(
+                    self.printer,
+                    chunk.as_ptr() as *const c_void,
+                    len,
+                    &mut written,
+                ))?;
+                if written == 0 {
+                    return Err(io::ErrorKind::WriteZero.into());
+                }
+                sent += written as usize;
+            }
+            check(EndPagePrinter(self.printer))
+        }
+    }
+}
+
+impl Write for WinspoolJob {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    /// Send everything written so far as one job.
+    fn flush(&mut self) -> io::Result<()> {
+        self.submit()
+    }
+}
+
+impl Drop for WinspoolJob {
+    fn drop(&mut self) {
+        let _ = self.submit();
+        // SAFETY: the handle was opened in open, and isn't used again.
+        unsafe { ClosePrinter(self.printer) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wide_strings() {
+        assert_eq!(vec![0x52, 0x41, 0x57, 0], wide("RAW"));
+        assert_eq!(vec![0xe9, 0], wide("é"));
+    }
+}
+
+// vim: foldmethod=marker
@@ -0,0 +1,117 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+use super::Error;
+
+/// Height of a user-defined character, in bytes. Glyphs are defined for
+/// the default 12x24 font, so each column is 24 dots, or 3 bytes, tall.
+pub(crate) const GLYPH_HEIGHT_BYTES: u8 = 3;
+
+/// Maximum width of a user-defined character, in dots.
+const GLYPH_MAX_WIDTH: usize = 12;
+
+/// A single user-defined character, which can be uploaded to the printer
+/// with `ESC &` and used in place of a character in the active code page.
+///
+/// Glyphs are 24 dots tall and up to 12 dots wide, matching the printer's
+/// default font.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserGlyph {
+    width: u8,
+
+    /// Column-major pixel data, 3 bytes per column, with the most
+    /// significant bit at the top.
+    data: Vec<u8>,
+}
+
+impl UserGlyph {
+    /// Build a glyph from a small image. The image may be no larger than
+    /// 12x24 pixels; dark pixels are printed.
+    pub fn from_image(img: &image::GrayImage) -> Result<Self, Error> {
+        let (width, height) = img.dimensions();
+        if width as usize > GLYPH_MAX_WIDTH || height > GLYPH_HEIGHT_BYTES as u32 * 8 {
+            return Err(Error::ImageTooLarge);
+        }
+
+        let mut data = vec![];
+        for x in 0..width {
+            for byte in 0..GLYPH_HEIGHT_BYTES as u32 {
+                let mut block: u8 = 0;
+                for bit in 0..8 {
+                    if let Some(pixel) = img.get_pixel_checked(x, byte * 8 + bit) {
+                        if pixel.0[0] <= 128 {
+                            block |= 1 << (7 - bit)
+                        }
+                    }
+                }
+                data.push(block);
+            }
+        }
+
+        Ok(Self {
+            width: width as u8,
+            data,
+        })
+    }
+
+    /// Build a glyph from a raw bit pattern. Each entry is a single column
+    /// of 24 dots, top to bottom, with the most significant bit of the
+    /// first byte at the top.
+    pub fn from_columns(columns: &[[u8; 3]]) -> Result<Self, Error> {
+        if columns.len() > GLYPH_MAX_WIDTH {
+            return Err(Error::ImageTooLarge);
+        }
+
+        Ok(Self {
+            width: columns.len() as u8,
+            data: columns.iter().flatten().copied().collect(),
+        })
+    }
+
+    /// Width of this glyph, in dots.
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    /// Raw column-major data of this glyph.
+    pub(crate) fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_glyph_from_image() {
+        let mut img: image::GrayImage = image::ImageBuffer::from_pixel(2, 24, image::Luma([255]));
+        img.put_pixel(0, 0, image::Luma([0]));
+        img.put_pixel(1, 23, image::Luma([0]));
+        let glyph = UserGlyph::from_image(&img).unwrap();
+        assert_eq!(2, glyph.width());
+        assert_eq!(&[0x80, 0x00, 0x00, 0x00, 0x00, 0x01], glyph.data());
+
+        let img: image::GrayImage = image::ImageBuffer::new(13, 24);
+        assert_eq!(Err(Error::ImageTooLarge), UserGlyph::from_image(&img));
+    }
+}
+
+// vim: foldmethod=marker
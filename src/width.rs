@@ -0,0 +1,241 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! How many printer columns text takes up. Full width characters, such
+//! as CJK ideographs, take two columns, and combining marks take none.
+
+/// Ranges of characters which are East Asian Wide or Fullwidth, and so
+/// take up two columns. This is a close approximation of Unicode's
+/// `EastAsianWidth.txt`, including emoji which default to emoji
+/// presentation.
+const WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115f),
+    (0x231a, 0x231b),
+    (0x2329, 0x232a),
+    (0x23e9, 0x23ec),
+    (0x23f0, 0x23f0),
+    (0x23f3, 0x23f3),
+    (0x25fd, 0x25fe),
+    (0x2614, 0x2615),
+    (0x2648, 0x2653),
+    (0x267f, 0x267f),
+    (0x2693, 0x2693),
+    (0x26a1, 0x26a1),
+    (0x26aa, 0x26ab),
+    (0x26bd, 0x26be),
+    (0x26c4, 0x26c5),
+    (0x26ce, 0x26ce),
+    (0x26d4, 0x26d4),
+    (0x26ea, 0x26ea),
+    (0x26f2, 0x26f3),
+    (0x26f5, 0x26f5),
+    (0x26fa, 0x26fa),
+    (0x26fd, 0x26fd),
+    (0x2705, 0x2705),
+    (0x270a, 0x270b),
+    (0x2728, 0x2728),
+    (0x274c, 0x274c),
+    (0x274e, 0x274e),
+    (0x2753, 0x2755),
+    (0x2757, 0x2757),
+    (0x2795, 0x2797),
+    (0x27b0, 0x27b0),
+    (0x27bf, 0x27bf),
+    (0x2b1b, 0x2b1c),
+    (0x2b50, 0x2b50),
+    (0x2b55, 0x2b55),
+    (0x2e80, 0x303e),
+    (0x3041, 0x33ff),
+    (0x3400, 0x4dbf),
+    (0x4e00, 0x9fff),
+    (0xa000, 0xa4cf),
+    (0xa960, 0xa97f),
+    (0xac00, 0xd7a3),
+    (0xf900, 0xfaff),
+    (0xfe10, 0xfe19),
+    (0xfe30, 0xfe6f),
+    (0xff00, 0xff60),
+    (0xffe0, 0xffe6),
+    (0x16fe0, 0x16fe4),
+    (0x17000, 0x18cff),
+    (0x1b000, 0x1b2ff),
+    (0x1f004, 0x1f004),
+    (0x1f0cf, 0x1f0cf),
+    (0x1f18e, 0x1f18e),
+    (0x1f191, 0x1f19a),
+    (0x1f200, 0x1f251),
+    (0x1f300, 0x1f64f),
+    (0x1f680, 0x1f6ff),
+    (0x1f900, 0x1f9ff),
+    (0x1fa70, 0x1faff),
+    (0x20000, 0x2fffd),
+    (0x30000, 0x3fffd),
+];
+
+/// Ranges of characters which don't take up a column of their own, such
+/// as combining marks, joiners and variation selectors.
+const ZERO: &[(u32, u32)] = &[
+    (0x0300, 0x036f),
+    (0x0483, 0x0489),
+    (0x0591, 0x05bd),
+    (0x05bf, 0x05bf),
+    (0x05c1, 0x05c2),
+    (0x05c4, 0x05c5),
+    (0x05c7, 0x05c7),
+    (0x0610, 0x061a),
+    (0x064b, 0x065f),
+    (0x0670, 0x0670),
+    (0x06d6, 0x06dc),
+    (0x06df, 0x06e4),
+    (0x06e7, 0x06e8),
+    (0x06ea, 0x06ed),
+    (0x0e31, 0x0e31),
+    (0x0e34, 0x0e3a),
+    (0x0e47, 0x0e4e),
+    (0x1160, 0x11ff),
+    (0x1ab0, 0x1aff),
+    (0x1dc0, 0x1dff),
+    (0x200b, 0x200f),
+    (0x20d0, 0x20ff),
+    (0x3099, 0x309a),
+    (0xfe00, 0xfe0f),
+    (0xfe20, 0xfe2f),
+    (0x1f3fb, 0x1f3ff),
+    (0xe0100, 0xe01ef),
+];
+
+/// Zero width joiner, which joins the characters on either side of it
+/// into one, such as in family emoji.
+const ZWJ: char = '\u{200d}';
+
+fn in_ranges(ranges: &[(u32, u32)], c: char) -> bool {
+    let c = c as u32;
+    ranges
+        .binary_search_by(|&(start, end)| {
+            if end < c {
+                std::cmp::Ordering::Less
+            } else if start > c {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+/// Return the number of columns `c` takes on its own.
+fn char_width(c: char) -> usize {
+    if c.is_control() || in_ranges(ZERO, c) {
+        0
+    } else if in_ranges(WIDE, c) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Split `text` into the groups of characters which print as one, such
+/// as a letter and its accents, or emoji joined with a zero width joiner.
+pub(crate) fn clusters(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let mut chars = rest.char_indices();
+        let (_, first) = chars.next()?;
+        let mut joined = first == ZWJ;
+        let mut end = rest.len();
+        for (i, c) in chars {
+            if !(joined || c == ZWJ || char_width(c) == 0 && !c.is_control()) {
+                end = i;
+                break;
+            }
+            joined = c == ZWJ;
+        }
+        let (cluster, tail) = rest.split_at(end);
+        rest = tail;
+        Some(cluster)
+    })
+}
+
+/// Return the number of columns `text` takes up when printed, where full
+/// width characters, such as CJK ideographs, take two.
+///
+/// ```rust
+/// assert_eq!(5, epson::text_width("hello"));
+/// assert_eq!(4, epson::text_width("日本"));
+/// assert_eq!(1, epson::text_width("e\u{301}"));
+/// ```
+pub fn text_width(text: &str) -> usize {
+    clusters(text)
+        .map(|cluster| cluster.chars().map(char_width).max().unwrap_or(0))
+        .sum()
+}
+
+/// Return the longest start of `text` that fits in `columns`, without
+/// splitting a cluster.
+pub(crate) fn truncate(text: &str, columns: usize) -> &str {
+    let mut used = 0;
+    let mut end = 0;
+    for cluster in clusters(text) {
+        used += text_width(cluster);
+        if used > columns {
+            break;
+        }
+        end += cluster.len();
+    }
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn width_of_text() {
+        assert_eq!(0, text_width(""));
+        assert_eq!(11, text_width("hello world"));
+        assert_eq!(4, text_width("café"));
+        assert_eq!(4, text_width("cafe\u{301}"));
+        assert_eq!(10, text_width("こんにちは"));
+        assert_eq!(4, text_width("ｱｲｳｴ"));
+        assert_eq!(6, text_width("ＡＢＣ"));
+        assert_eq!(8, text_width("한국어 A"));
+        assert_eq!(2, text_width("👍🏽"));
+        assert_eq!(2, text_width("👨\u{200d}👩\u{200d}👧"));
+    }
+
+    #[test]
+    fn width_clusters() {
+        assert_eq!(
+            vec!["a", "e\u{301}", "日", "👨\u{200d}👩"],
+            clusters("ae\u{301}日👨\u{200d}👩").collect::<Vec<_>>()
+        );
+        assert_eq!(vec!["\n", "a"], clusters("\na").collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn width_truncate() {
+        assert_eq!("日本", truncate("日本語", 5));
+        assert_eq!("日本語", truncate("日本語", 6));
+        assert_eq!("", truncate("日本語", 1));
+        assert_eq!("ae\u{301}", truncate("ae\u{301}b", 2));
+    }
+}
+
+// vim: foldmethod=marker
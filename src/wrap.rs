@@ -0,0 +1,206 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+use super::width::{clusters, text_width, truncate};
+use super::{document::Style, Model};
+
+/// Wrap `text` into lines which fit across the paper of `model` when
+/// printed in `style`, breaking at spaces where possible.
+///
+/// Lines are measured with [crate::text_width], so full width characters
+/// take two columns. Line feeds in `text` start a new line, and words
+/// longer than a whole line are split between characters, never inside of
+/// one. Each returned line is without its line feed.
+///
+/// ```rust
+/// # use epson::{document::Style, wrap_text, Model};
+/// let style = Style { width: 2, ..Default::default() };
+/// for line in wrap_text("The quick brown fox jumps over the lazy dog", Model::T20II, &style) {
+///     assert!(epson::text_width(&line) <= 24);
+/// }
+/// ```
+pub fn wrap_text(text: &str, model: Model, style: &Style) -> Vec<String> {
+    let columns = (model.get_columns() / style.width.max(1) as usize).max(1);
+    let mut lines = vec![];
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        let mut len = 0;
+
+        for mut word in paragraph.split_whitespace() {
+            let mut word_len = text_width(word);
+            if len > 0 && len + 1 + word_len <= columns {
+                line.push(' ');
+                line.push_str(word);
+                len += 1 + word_len;
+                continue;
+            }
+            if len > 0 {
+                lines.push(std::mem::take(&mut line));
+            }
+            while word_len > columns {
+                let mut head = truncate(word, columns);
+                if head.is_empty() {
+                    head = clusters(word).next().unwrap_or_default();
+                }
+                lines.push(head.to_owned());
+                word = &word[head.len()..];
+                word_len = text_width(word);
+            }
+            line.push_str(word);
+            len = word_len;
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// How a separator printed with [crate::Writer::print_rule] looks.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum RuleStyle {
+    /// A line of dashes.
+    #[default]
+    Dashes,
+
+    /// A line of equals signs.
+    Double,
+
+    /// A solid line one dot tall, printed as an image.
+    Line,
+}
+
+impl RuleStyle {
+    /// Return the text of this rule across `columns` characters, or `None`
+    /// if it's printed as an image.
+    pub(crate) fn text(&self, columns: usize) -> Option<String> {
+        let c = match self {
+            RuleStyle::Dashes => '-',
+            RuleStyle::Double => '=',
+            RuleStyle::Line => return None,
+        };
+        Some(format!("{}\n", c.to_string().repeat(columns)))
+    }
+}
+
+/// Lay out `left` and `right` on a line `columns` wide, with spaces in
+/// between. `left` is shortened if both don't fit.
+pub(crate) fn split_line(left: &str, right: &str, columns: usize) -> String {
+    let right_len = text_width(right);
+    let room = columns.saturating_sub(right_len + 1);
+    let left = truncate(left, room);
+    let gap = columns.saturating_sub(text_width(left) + right_len).max(1);
+    format!("{}{}{}", left, " ".repeat(gap), right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PaperWidth;
+
+    fn narrow() -> Model {
+        Model::T20II.with_paper_width(PaperWidth::Mm58)
+    }
+
+    #[test]
+    fn wrap_words() {
+        let columns = narrow().get_columns();
+        let text = "word ".repeat(columns);
+        let lines = wrap_text(&text, narrow(), &Style::default());
+        assert!(lines.len() > 1);
+        for line in &lines {
+            assert!(line.len() <= columns);
+            assert!(!line.starts_with(' ') && !line.ends_with(' '));
+        }
+        assert_eq!(
+            text.split_whitespace().count(),
+            lines.join(" ").split(' ').count()
+        );
+    }
+
+    #[test]
+    fn wrap_text_size() {
+        let style = Style {
+            width: 2,
+            ..Default::default()
+        };
+        let text = "x".repeat(Model::T20II.get_columns());
+        assert_eq!(
+            vec!["x".repeat(24), "x".repeat(24)],
+            wrap_text(&text, Model::T20II, &style)
+        );
+    }
+
+    #[test]
+    fn wrap_multibyte() {
+        let text = "é".repeat(Model::T20II.get_columns() + 1);
+        let lines = wrap_text(&text, Model::T20II, &Style::default());
+        assert_eq!(vec!["é".repeat(48), "é".to_owned()], lines);
+    }
+
+    #[test]
+    fn split_line_pads() {
+        assert_eq!("Coffee     $3.50", split_line("Coffee", "$3.50", 16));
+        assert_eq!(
+            "A very lo $3.50",
+            split_line("A very long item", "$3.50", 15)
+        );
+    }
+
+    #[test]
+    fn rule_text() {
+        assert_eq!(Some("----\n".to_owned()), RuleStyle::Dashes.text(4));
+        assert_eq!(Some("====\n".to_owned()), RuleStyle::Double.text(4));
+        assert_eq!(None, RuleStyle::Line.text(4));
+    }
+
+    #[test]
+    fn wrap_full_width() {
+        let text = "日本語".repeat(10);
+        let lines = wrap_text(&text, Model::T20II, &Style::default());
+        assert_eq!(vec!["日本語".repeat(8), "日本語".repeat(2)], lines);
+
+        let style = Style {
+            width: 8,
+            ..Default::default()
+        };
+        let lines = wrap_text(
+            "日本語 abc",
+            Model::T20II.with_paper_width(PaperWidth::Mm58),
+            &style,
+        );
+        assert_eq!(vec!["日本", "語", "abc"], lines);
+    }
+
+    #[test]
+    fn split_line_full_width() {
+        assert_eq!("日本      ¥350", split_line("日本", "¥350", 14));
+        assert_eq!("日 ¥350", split_line("日本", "¥350", 7));
+    }
+
+    #[test]
+    fn wrap_line_feeds() {
+        assert_eq!(
+            vec!["one", "", "two"],
+            wrap_text("one\n\ntwo\n", Model::T20II, &Style::default())
+        );
+    }
+}
+
+// vim: foldmethod=marker
@@ -18,8 +18,28 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE. }}}
 
-use super::{Alignment, CharacterSet, Command, Error as EpsonError, Model};
-use std::io::Write;
+#[cfg(feature = "bidi")]
+use super::bidi;
+#[cfg(feature = "textimage")]
+use super::codepage::encodable;
+use super::codepage::Encoder;
+use super::document::{Style, StyleSheet};
+use super::epson_image::{bands, fit_width, IMAGE_BAND_HEIGHT};
+#[cfg(feature = "svg")]
+use super::svg;
+use super::tcp;
+#[cfg(feature = "textimage")]
+use super::textimage;
+use super::wrap::split_line;
+use super::{
+    wrap_text, Alignment, AsbFlags, Barcode, CharacterSet, CodePage, Color, ColorConversion,
+    ColumnImageMode, Command, CustomizeValue, CutCommand, CutMode, DrawerPin, EffectColor,
+    Error as EpsonError, ImageAdjustments, ImageOptions, ImageScale, InfoKind, JobResult,
+    KanjiCode, KanjiStyle, MaintenanceCounter, MaxWidthBehavior, Model, NvKey, PaperLayout,
+    PrintJob, QrCode, Receipt, RuleStyle, StatusKind, StyledText, TcpOptions, TransmitStatusKind,
+    UserGlyph,
+};
+use std::{io::Write, net::ToSocketAddrs, time::Instant};
 
 /// All errors that can be returned from the sync code in the Epson module.
 #[derive(Debug)]
@@ -59,21 +79,54 @@ type Result<T> = std::result::Result<T, Error>;
 pub struct Writer {
     w: Box<dyn Write>,
     model: Model,
+    cut_command: Option<CutCommand>,
+    code_page: CodePage,
+    character_set: CharacterSet,
+    style_sheet: StyleSheet,
+    style: Style,
+    style_stack: Vec<(Style, CodePage)>,
 }
 
 impl Writer {
     /// Create a new Writer
     pub fn open(model: Model, w: Box<dyn Write>) -> Result<Self> {
-        let mut r = Self { w, model };
+        let mut r = Self {
+            w,
+            model,
+            cut_command: None,
+            code_page: CodePage::Pc437,
+            character_set: CharacterSet::Raw,
+            style_sheet: StyleSheet::new(),
+            style: Style::default(),
+            style_stack: vec![],
+        };
         r.init()?;
         Ok(r)
     }
 
+    /// Connect to the printer at `addr` over TCP, usually on port 9100,
+    /// and create a new Writer for it. Unlike connecting a `TcpStream`
+    /// directly, this fails instead of hanging if the printer is switched
+    /// off or unplugged, depending on the provided [TcpOptions].
+    pub fn connect_tcp(
+        model: Model,
+        addr: impl ToSocketAddrs,
+        options: &TcpOptions,
+    ) -> Result<Self> {
+        let stream = tcp::connect(addr, options)?;
+        Self::open(model, Box::new(stream))
+    }
+
     /// initialize the epson printer
-    fn init(&mut self) -> Result<()> {
+    pub(crate) fn init(&mut self) -> Result<()> {
         self.write_command(Command::Init)
     }
 
+    /// Return the model this writer was opened for.
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
     /// Set unicode mode on the printer, if supported.
     pub fn set_unicode(&mut self) -> Result<()> {
         self.character_set(CharacterSet::Unicode)
@@ -90,9 +143,47 @@ impl Writer {
         self.write_command(Command::CharacterSet(c))
     }
 
-    /// cut the printer paper
+    /// Switch the [CodePage] used to print bytes above 0x7F. Models only
+    /// support some code pages, see [Model::get_code_pages].
+    pub fn code_page(&mut self, page: CodePage) -> Result<()> {
+        if !self.model.supports_code_page(page) {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::CodePage(page))
+    }
+
+    /// cut the printer paper, using the [CutCommand] the model expects
     pub fn cut(&mut self) -> Result<()> {
-        self.write_command(Command::Cut)
+        let cut = self
+            .cut_command
+            .unwrap_or_else(|| self.model.get_cut_command());
+        if !self.model.supports_cut_mode(cut.mode()) {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(cut.command())
+    }
+
+    /// Override the [CutCommand] used by `cut`, rather than the one the
+    /// [Model] expects. Passing `None` goes back to the model's choice.
+    pub fn set_cut_command(&mut self, command: Option<CutCommand>) {
+        self.cut_command = command;
+    }
+
+    /// Partially cut the printer paper, leaving a bit attached.
+    pub fn partial_cut(&mut self) -> Result<()> {
+        if !self.model.supports_cut_mode(CutMode::Partial) {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::CutWith(CutMode::Partial, None))
+    }
+
+    /// Feed the paper to the cutting position plus `feed` motion units,
+    /// and then cut it using the provided [CutMode].
+    pub fn cut_with_feed(&mut self, mode: CutMode, feed: u8) -> Result<()> {
+        if !self.model.supports_cut_mode(mode) {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::CutWith(mode, Some(feed)))
     }
 
     /// If true, text printed after this command will be underlined. If false,
@@ -119,22 +210,371 @@ impl Writer {
         self.write_command(Command::DoubleStrike(state))
     }
 
+    /// If true, rotate the text printed after this command by 90 degrees
+    /// clockwise. If false, return to normal printing. Not all models
+    /// support rotated text.
+    pub fn rotate90(&mut self, state: bool) -> Result<()> {
+        if !self.model.supports_rotation() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::Rotate90(state))
+    }
+
+    /// If true, smooth the text printed after this command, which makes
+    /// scaled up text look a lot less jagged. If false, disable smoothing.
+    pub fn smoothing(&mut self, state: bool) -> Result<()> {
+        self.write_command(Command::Smoothing(state))
+    }
+
+    /// Select the [Color] of the text printed after this command. This is
+    /// only supported on two-color models.
+    pub fn print_color(&mut self, color: Color) -> Result<()> {
+        if !self.model.supports_two_color() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::PrintColor(color))
+    }
+
+    /// If true, enable Kanji mode, where multibyte character codes are
+    /// printed as Kanji. If false, return to single byte characters. Only
+    /// models with a Kanji ROM support this.
+    pub fn kanji_mode(&mut self, state: bool) -> Result<()> {
+        if !self.model.supports_kanji() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::KanjiMode(state))
+    }
+
+    /// Select the [KanjiCode] used to encode Kanji characters.
+    pub fn kanji_code(&mut self, code: KanjiCode) -> Result<()> {
+        if !self.model.supports_kanji() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::KanjiCodeSystem(code))
+    }
+
+    /// Set the [KanjiStyle] used to print Kanji characters.
+    pub fn kanji_style(&mut self, style: KanjiStyle) -> Result<()> {
+        if !self.model.supports_kanji() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::KanjiStyle(style))
+    }
+
+    /// Select the [EffectColor] of the text printed after this command.
+    /// Only supported on models with character effects.
+    pub fn character_color(&mut self, color: EffectColor) -> Result<()> {
+        self.write_effect_command(Command::CharacterColor(color))
+    }
+
+    /// Select the background [EffectColor] of the text printed after this
+    /// command. Only supported on models with character effects.
+    pub fn background_color(&mut self, color: EffectColor) -> Result<()> {
+        self.write_effect_command(Command::BackgroundColor(color))
+    }
+
+    /// Print a shadow of the provided [EffectColor] behind the text printed
+    /// after this command, or remove it if `None`. Only supported on models
+    /// with character effects.
+    pub fn shadow(&mut self, color: Option<EffectColor>) -> Result<()> {
+        self.write_effect_command(Command::Shadow(color))
+    }
+
     /// Set the horizontal justification of the text printed after this
     /// command.
     pub fn justify(&mut self, alignment: Alignment) -> Result<()> {
         self.write_command(Command::Justification(alignment))
     }
 
+    /// Scale the text printed after this command. Both `width` and `height`
+    /// are multipliers between 1 (normal size) and 8.
+    pub fn set_text_size(&mut self, width: u8, height: u8) -> Result<()> {
+        self.write_command(Command::TextSize { width, height })
+    }
+
+    /// If true, print the text after this command twice as wide. This
+    /// resets the text height to normal.
+    pub fn double_width(&mut self, state: bool) -> Result<()> {
+        self.write_command(if self.model.supports_text_size() {
+            Command::DoubleWidth(state)
+        } else {
+            Command::LegacyPrintMode {
+                double_width: state,
+                double_height: false,
+            }
+        })
+    }
+
+    /// If true, print the text after this command twice as tall. This
+    /// resets the text width to normal.
+    pub fn double_height(&mut self, state: bool) -> Result<()> {
+        self.write_command(if self.model.supports_text_size() {
+            Command::DoubleHeight(state)
+        } else {
+            Command::LegacyPrintMode {
+                double_width: false,
+                double_height: state,
+            }
+        })
+    }
+
+    /// If true, print the text after this command twice as wide and twice
+    /// as tall, otherwise return to normal size text.
+    pub fn double_size(&mut self, state: bool) -> Result<()> {
+        self.write_command(if self.model.supports_text_size() {
+            let size = if state { 2 } else { 1 };
+            Command::TextSize {
+                width: size,
+                height: size,
+            }
+        } else {
+            Command::LegacyPrintMode {
+                double_width: state,
+                double_height: state,
+            }
+        })
+    }
+
+    /// Reset the spacing between lines to the printer's default.
+    pub fn default_line_spacing(&mut self) -> Result<()> {
+        self.write_command(Command::DefaultLineSpacing)
+    }
+
+    /// Set the spacing between lines to the provided number of motion
+    /// units (typically dots).
+    pub fn line_spacing(&mut self, spacing: u8) -> Result<()> {
+        self.write_command(Command::LineSpacing(spacing))
+    }
+
+    /// Set the horizontal tab stops, in columns from the start of the line.
+    /// Stops must be ascending, and no more than 32 may be set.
+    pub fn set_tab_stops(&mut self, stops: Vec<u8>) -> Result<()> {
+        self.write_command(Command::SetTabStops(stops))
+    }
+
+    /// Move to the next horizontal tab stop.
+    pub fn tab(&mut self) -> Result<()> {
+        self.write_command(Command::HorizontalTab)
+    }
+
+    /// Select the kind of paper loaded in a label printer.
+    pub fn set_paper_layout(&mut self, layout: PaperLayout) -> Result<()> {
+        self.write_label_command(Command::SetPaperLayout(layout))
+    }
+
+    /// Feed label or black mark paper to the start of the next label.
+    pub fn feed_to_label(&mut self) -> Result<()> {
+        self.write_label_command(Command::FeedToLabelOrigin)
+    }
+
+    /// Feed label or black mark paper to the cutting position, such as
+    /// before calling [Self::cut].
+    pub fn feed_to_cut_position(&mut self) -> Result<()> {
+        self.write_label_command(Command::FeedToCutPosition)
+    }
+
+    /// Feed label paper to the label peeling position.
+    pub fn feed_to_peel_position(&mut self) -> Result<()> {
+        if !self.model.supports_label_peeler() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_label_command(Command::FeedToPeelPosition)
+    }
+
     /// Feed the specified number of lines out of the printer.
     pub fn feed(&mut self, count: u8) -> Result<()> {
         self.write_command(Command::Feed(count))
     }
 
-    /// Set the printer speed to the provided value.
+    /// Feed the paper by the specified number of motion units (typically
+    /// dots).
+    pub fn feed_units(&mut self, units: u8) -> Result<()> {
+        self.write_command(Command::FeedUnits(units))
+    }
+
+    /// Feed the paper by the specified distance in millimeters, converted
+    /// to dots using [Model::mm_to_dots].
+    pub fn feed_mm(&mut self, mm: f32) -> Result<()> {
+        if !mm.is_finite() || mm < 0.0 {
+            return Err(EpsonError::InvalidParameter.into());
+        }
+
+        let mut dots = self.model.mm_to_dots(mm);
+        while dots > 0 {
+            let units = dots.min(u8::MAX as u32);
+            self.write_command(Command::FeedUnits(units as u8))?;
+            dots -= units;
+        }
+        Ok(())
+    }
+
+    /// Feed the specified number of lines backwards. Most models can only
+    /// reverse feed a few lines; see [Model::get_max_reverse_feed].
+    pub fn reverse_feed(&mut self, count: u8) -> Result<()> {
+        let max = self.model.get_max_reverse_feed();
+        if max == 0 {
+            return Err(EpsonError::Unsupported.into());
+        }
+        if count > max {
+            return Err(EpsonError::InvalidParameter.into());
+        }
+        self.write_command(Command::ReverseFeed(count))
+    }
+
+    /// Set the printer speed to the provided value. This will return
+    /// an error if the speed is outside of [Model::get_speed_range].
     pub fn speed(&mut self, speed: u8) -> Result<()> {
+        if !self.model.get_speed_range().contains(&speed) {
+            return Err(EpsonError::InvalidParameter.into());
+        }
+        self.speed_unchecked(speed)
+    }
+
+    /// Set the printer speed to the provided value, without any model
+    /// checks. Only use this if you know your printer's firmware accepts
+    /// the value.
+    pub fn speed_unchecked(&mut self, speed: u8) -> Result<()> {
         self.write_command(Command::Speed(speed))
     }
 
+    /// Open the cash drawer attached to the provided pin of the drawer
+    /// kick-out connector, using a 50ms pulse.
+    pub fn open_drawer(&mut self, pin: DrawerPin) -> Result<()> {
+        if !self.model.supports_cash_drawer() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::CashDrawer {
+            pin,
+            on_ms: 50,
+            off_ms: 250,
+        })
+    }
+
+    /// Open the cash drawer attached to the provided pin right away, even if
+    /// the printer is busy printing. `duration` is the length of the pulse
+    /// in 100ms units, from 1 through 8.
+    pub fn pulse_drawer(&mut self, pin: DrawerPin, duration: u8) -> Result<()> {
+        if !self.model.supports_cash_drawer() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::RealTimeDrawerPulse { pin, duration })
+    }
+
+    /// Sound the printer's built-in buzzer, playing `pattern` (1 through 10)
+    /// `repetitions` times. Not all models have a buzzer.
+    pub fn buzzer(&mut self, pattern: u8, repetitions: u8) -> Result<()> {
+        if !self.model.supports_buzzer() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::Buzzer {
+            pattern,
+            repetitions,
+        })
+    }
+
+    /// Upload a [UserGlyph] to the printer, to be printed in place of the
+    /// character `code` (between 32 and 126) once enabled with
+    /// [Self::user_glyphs].
+    pub fn define_glyph(&mut self, code: u8, glyph: UserGlyph) -> Result<()> {
+        self.write_command(Command::DefineUserGlyphs {
+            first: code,
+            glyphs: vec![glyph],
+        })
+    }
+
+    /// If true, print user-defined characters in place of the built-in
+    /// ones. If false, go back to the built-in character set.
+    pub fn user_glyphs(&mut self, state: bool) -> Result<()> {
+        self.write_command(Command::UserGlyphs(state))
+    }
+
+    /// Remove the user-defined character for `code`.
+    pub fn clear_glyph(&mut self, code: u8) -> Result<()> {
+        self.write_command(Command::ClearUserGlyph(code))
+    }
+
+    /// Record the commands written by `f` as a macro stored in the printer,
+    /// rather than printing them. The macro can then be replayed with
+    /// [Self::execute_macro].
+    pub fn record_macro<F>(&mut self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Self) -> Result<()>,
+    {
+        self.write_command(Command::MacroStart)?;
+        let ret = f(self);
+        self.write_command(Command::MacroEnd)?;
+        ret
+    }
+
+    /// Execute the stored macro `count` times, waiting `interval` (in
+    /// 100ms units) between each, or for the FEED button to be pressed
+    /// if `button` is true.
+    pub fn execute_macro(&mut self, count: u8, interval: u8, button: bool) -> Result<()> {
+        self.write_command(Command::MacroExecute {
+            count,
+            interval,
+            button,
+        })
+    }
+
+    /// Set the print density, relative to the standard density, from -6
+    /// (lightest) through 6 (darkest). This lasts until the printer is
+    /// reset.
+    pub fn density(&mut self, density: i8) -> Result<()> {
+        self.write_command(Command::Density(density))
+    }
+
+    /// Store the provided [CustomizeValue]s in the printer's non-volatile
+    /// memory, entering and leaving user setting mode around them.
+    ///
+    /// Leaving user setting mode resets the printer, so this should be done
+    /// before printing anything. NV memory has a limited number of write
+    /// cycles, so don't do this for every receipt.
+    pub fn set_customize_values(&mut self, values: &[CustomizeValue]) -> Result<()> {
+        for value in values {
+            value.setting()?;
+        }
+
+        self.write_command(Command::EnterUserSetting)?;
+        for value in values {
+            self.write_command(Command::SetCustomizeValue(*value))?;
+        }
+        self.write_command(Command::ExitUserSetting)
+    }
+
+    /// Print a greyscale image, prepared and printed as described by the
+    /// provided [ImageOptions]. The other `print_image` methods are
+    /// shorthand for common options.
+    pub fn print_image_with(
+        &mut self,
+        mut img: image::GrayImage,
+        options: ImageOptions,
+    ) -> Result<()> {
+        options.adjustments.apply(&mut img);
+
+        let max_width = self.model.get_max_image_width() as u32 / options.scale.factors().0 as u32;
+        let mut img = match options.max_width_behavior {
+            MaxWidthBehavior::Error => img,
+            MaxWidthBehavior::Fit => fit_width(img, max_width),
+            MaxWidthBehavior::Crop if img.width() > max_width => {
+                image::imageops::crop_imm(&img, 0, 0, max_width, img.height()).to_image()
+            }
+            MaxWidthBehavior::Crop => img,
+        };
+        self.model.check_image_scaled(&img, options.scale)?;
+        options.dither.apply(&mut img);
+        let img = options.padding.apply(img)?;
+
+        if let Some(align) = options.align {
+            self.justify(align)?;
+        }
+        self.write_image(img, options.scale)?;
+        if options.align.is_some() {
+            self.justify(Alignment::Left)?;
+        }
+        Ok(())
+    }
+
     /// Print a greyscale image.
     ///
     /// Currently, this image must have a width that's 8 bit aligned,
@@ -142,22 +582,551 @@ impl Writer {
     /// width of the image is constrained by the underling printer model
     /// provided to `Self::open`.
     pub fn print_image(&mut self, img: image::GrayImage) -> Result<()> {
-        self.model.check_image(&img)?;
-        self.print_image_unchecked(img)
+        self.print_image_with(img, ImageOptions::default())
+    }
+
+    /// Print a greyscale image, which the printer enlarges by `scale`. This
+    /// is useful for small logos, which can be sent at their native size
+    /// rather than being resampled first.
+    pub fn print_image_scaled(&mut self, img: image::GrayImage, scale: ImageScale) -> Result<()> {
+        self.print_image_with(
+            img,
+            ImageOptions {
+                scale,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Print `text` in `style`, wrapped at word boundaries to fit the
+    /// paper with [wrap_text]. The previous style is restored afterwards,
+    /// as with [Self::push_style] and [Self::pop_style].
+    pub fn print_wrapped(&mut self, text: &str, style: &Style) -> Result<()> {
+        self.push_style();
+        self.set_style(style)?;
+        for line in wrap_text(text, self.model, style) {
+            self.write_text(&line)?;
+            self.write_all(b"\n")?;
+        }
+        self.pop_style()
+    }
+
+    /// Print `text`, converted into the current [CodePage]. When a
+    /// character isn't in the current code page, this switches to one of
+    /// the model's code pages which has it, and characters which none of
+    /// them have are printed as a question mark.
+    pub fn print_text(&mut self, text: &str) -> Result<()> {
+        #[cfg(feature = "bidi")]
+        let text = &bidi::visual(text);
+        self.write_text(text)
+    }
+
+    /// Print `text` like [Self::print_text], followed by a line feed.
+    pub fn print_line(&mut self, text: &str) -> Result<()> {
+        self.print_text(&format!("{}\n", text))
+    }
+
+    /// Print formatted text like [Self::print_text], so that
+    /// `write!(pos, "Total: {:>10}\n", total)` goes through the same code
+    /// page conversion. This takes precedence over `std::io::Write`'s
+    /// `write_fmt`, which would send the formatted text as raw bytes.
+    pub fn write_fmt(&mut self, args: std::fmt::Arguments<'_>) -> Result<()> {
+        match args.as_str() {
+            Some(text) => self.print_text(text),
+            None => self.print_text(&args.to_string()),
+        }
+    }
+
+    /// Return an [Encoder] which carries on from the printer's current
+    /// [CodePage] and [CharacterSet].
+    fn encoder(&self) -> Encoder {
+        let unicode = self.character_set == CharacterSet::Unicode;
+        Encoder::resume(self.model, self.code_page, unicode)
+    }
+
+    /// Send `text` converted into the current [CodePage], switching code
+    /// pages as needed, or as UTF-8 once [Self::set_unicode] is on.
+    fn write_text(&mut self, text: &str) -> Result<()> {
+        for cmd in self.encoder().text(text) {
+            self.write_command(cmd)?;
+        }
+        Ok(())
+    }
+
+    /// Print `text` like [Self::print_text], except that lines with
+    /// characters none of the model's code pages have are drawn with
+    /// `font` and printed as an image instead. The whole line becomes an
+    /// image, so that it stays on one line.
+    #[cfg(feature = "textimage")]
+    pub fn print_text_with_fallback(&mut self, text: &str, font: &textimage::Font) -> Result<()> {
+        #[cfg(feature = "bidi")]
+        let text = &bidi::visual(text);
+        let pages = self.model.get_code_pages();
+        for line in text.split_inclusive('\n') {
+            if encodable(line, &pages) {
+                self.write_text(line)?;
+                continue;
+            }
+            let img = textimage::render(
+                font,
+                line.trim_end_matches('\n'),
+                textimage::FONT_A_SIZE,
+                self.model,
+            );
+            self.print_image(img)?;
+        }
+        Ok(())
+    }
+
+    /// Set the [StyleSheet] used by [Self::apply_style].
+    pub fn set_style_sheet(&mut self, sheet: StyleSheet) {
+        self.style_sheet = sheet;
+    }
+
+    /// Switch to the style called `name` in the [StyleSheet], sending only
+    /// what differs from the current style. The previous style comes back
+    /// with [Self::restore_style]. Returns [EpsonError::InvalidParameter]
+    /// if there's no style by that name.
+    pub fn apply_style(&mut self, name: &str) -> Result<()> {
+        let style = self
+            .style_sheet
+            .get(name)
+            .ok_or(EpsonError::InvalidParameter)?
+            .clone();
+        self.push_style();
+        self.set_style(&style)
+    }
+
+    /// Go back to the style in use before the last [Self::apply_style].
+    pub fn restore_style(&mut self) -> Result<()> {
+        self.pop_style()
+    }
+
+    /// Return the current text style, as tracked from the commands sent
+    /// so far. Bytes sent with `write_all` aren't tracked.
+    pub fn style(&self) -> &Style {
+        &self.style
+    }
+
+    /// Switch to `style`, sending only the commands for what differs from
+    /// the current style.
+    pub fn set_style(&mut self, style: &Style) -> Result<()> {
+        for cmd in style.commands_from(&self.style.clone(), self.model) {
+            self.write_command(cmd)?;
+        }
+        Ok(())
+    }
+
+    /// Save the current style and [CodePage], to be restored with
+    /// [Self::pop_style].
+    pub fn push_style(&mut self) {
+        self.style_stack.push((self.style.clone(), self.code_page));
+    }
+
+    /// Go back to the style and [CodePage] saved by the last
+    /// [Self::push_style], sending only the commands for what differs.
+    pub fn pop_style(&mut self) -> Result<()> {
+        let Some((style, page)) = self.style_stack.pop() else {
+            return Ok(());
+        };
+        self.set_style(&style)?;
+        if page != self.code_page {
+            self.code_page(page)?;
+        }
+        Ok(())
+    }
+
+    /// Go back to the default style: normal sized, left justified, text
+    /// without emphasis or underline.
+    pub fn reset_style(&mut self) -> Result<()> {
+        self.set_style(&Style::default())
+    }
+
+    /// Print `text` in the style called `name`, then restore the previous
+    /// style.
+    pub fn print_with_style(&mut self, name: &str, text: &str) -> Result<()> {
+        self.apply_style(name)?;
+        self.print_text(text)?;
+        self.restore_style()
+    }
+
+    /// Characters across the paper at the current text width.
+    fn columns(&self) -> usize {
+        self.model.get_columns() / self.style.width.max(1) as usize
+    }
+
+    /// Print `left` and `right` on one line, pushed to either edge of the
+    /// paper, such as a label and a price. `left` is shortened if both
+    /// don't fit across the line at the current text width.
+    pub fn print_split(&mut self, left: &str, right: &str) -> Result<()> {
+        let line = split_line(left, right, self.columns());
+        self.write_text(&format!("{}\n", line))
+    }
+
+    /// Print a separator across the full width of the paper.
+    pub fn print_rule(&mut self, style: RuleStyle) -> Result<()> {
+        match style.text(self.columns()) {
+            Some(text) => self.write_text(&text),
+            None => {
+                let width = self.model.get_max_image_width() as u32;
+                self.print_image(image::GrayImage::new(width, 1))
+            }
+        }
+    }
+
+    /// Print `text` as an image, drawn with a TrueType `font` at `size`
+    /// dots tall. This can print any character the font has, whatever
+    /// the printer supports.
+    #[cfg(feature = "textimage")]
+    pub fn print_text_image(
+        &mut self,
+        font: &textimage::Font,
+        text: &str,
+        size: f32,
+    ) -> Result<()> {
+        self.print_image(textimage::render(font, text, size, self.model))
+    }
+
+    /// Print an SVG image, rendered at the full printable width of the
+    /// paper. See [svg] for the parts of SVG which are supported.
+    #[cfg(feature = "svg")]
+    pub fn print_svg(&mut self, svg: &str) -> Result<()> {
+        let width = self.model.get_max_image_width() as u32;
+        self.print_image(svg::render(svg, width)?)
+    }
+
+    /// Print [StyledText], sending only the style changes between its
+    /// spans.
+    pub fn print_styled(&mut self, text: &StyledText) -> Result<()> {
+        let mut encoder = self.encoder();
+        for cmd in text.encode(|text| encoder.text(text)) {
+            self.write_command(cmd)?;
+        }
+        Ok(())
+    }
+
+    /// Send a [PrintJob] to the printer in a single write, and flush it.
+    /// The job must have been built for the same model as this writer.
+    pub fn submit(&mut self, job: &PrintJob) -> Result<JobResult> {
+        if job.model() != self.model {
+            return Err(EpsonError::Unsupported.into());
+        }
+        let start = Instant::now();
+        self.write_all(job.as_bytes())?;
+        self.flush()?;
+        for cmd in job.commands() {
+            self.track(cmd);
+        }
+        Ok(JobResult {
+            commands: job.commands().len(),
+            bytes: job.as_bytes().len(),
+            elapsed: start.elapsed(),
+        })
+    }
+
+    /// Print a [Receipt], checking it against the configured model first.
+    pub fn print_receipt(&mut self, receipt: &Receipt) -> Result<()> {
+        for cmd in receipt.build()? {
+            self.write_command(cmd)?;
+        }
+        Ok(())
+    }
+
+    /// Print a color image, such as an RGB or RGBA image loaded from disk,
+    /// converting it to greyscale with the provided [ColorConversion].
+    pub fn print_color_image(
+        &mut self,
+        img: &image::DynamicImage,
+        conversion: ColorConversion,
+    ) -> Result<()> {
+        self.print_image(conversion.convert(img))
+    }
+
+    /// Print a greyscale image, after applying the provided
+    /// [ImageAdjustments] to lighten or darken it.
+    pub fn print_image_adjusted(
+        &mut self,
+        img: image::GrayImage,
+        adjustments: ImageAdjustments,
+    ) -> Result<()> {
+        self.print_image_with(
+            img,
+            ImageOptions {
+                adjustments,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Print a greyscale image, scaling it down to the configured model's
+    /// maximum image width first if it's too wide. The aspect ratio is
+    /// kept, so the image will get shorter too.
+    pub fn print_image_fit(&mut self, img: image::GrayImage) -> Result<()> {
+        self.print_image_with(
+            img,
+            ImageOptions {
+                max_width_behavior: MaxWidthBehavior::Fit,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Print an already packed 1-bit raster image, such as a framebuffer
+    /// or a logo stored in ROM, without going through an image. Each row
+    /// is `width_bytes` bytes, most significant bit on the left, and set
+    /// bits are printed black.
+    pub fn print_raster_bits(
+        &mut self,
+        width_bytes: u16,
+        height: u16,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        if width_bytes as usize * 8 > self.model.get_max_image_width() {
+            return Err(EpsonError::ImageTooLarge.into());
+        }
+        self.write_command(Command::RasterBits {
+            width_bytes,
+            height,
+            data,
+        })
+    }
+
+    /// Print a two-color image on models with two-color paper, from a
+    /// plane printed in black and a plane printed in red. Both planes must
+    /// be the same size. See [crate::split_two_color] to build the planes
+    /// from a color image.
+    pub fn print_two_color_image(
+        &mut self,
+        black: image::GrayImage,
+        red: image::GrayImage,
+    ) -> Result<()> {
+        if !self.model.supports_two_color() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        if black.dimensions() != red.dimensions() {
+            return Err(EpsonError::InvalidParameter.into());
+        }
+        self.model.check_image(&black)?;
+
+        self.write_command(Command::StoreGraphics {
+            color: Color::Black,
+            image: black,
+        })?;
+        self.write_command(Command::StoreGraphics {
+            color: Color::Red,
+            image: red,
+        })?;
+        self.write_command(Command::PrintGraphics)
     }
 
     /// Print a grayscale image, without any model checks. This will let you
     /// do all sorts of invalid things. Don't use this if you can avoid it,
     /// it may result in trash being printed.
     pub fn print_image_unchecked(&mut self, img: image::GrayImage) -> Result<()> {
-        self.write_command(Command::Image(img))
+        self.write_image(img, ImageScale::Normal)
+    }
+
+    /// Print the provided [Barcode].
+    pub fn print_barcode(&mut self, barcode: Barcode) -> Result<()> {
+        self.write_command(Command::Barcode(barcode))
+    }
+
+    /// Print the provided [QrCode]. This will return an error if the
+    /// configured Model does not support the requested [crate::QrModel].
+    pub fn print_qr(&mut self, qr: QrCode) -> Result<()> {
+        if !self.model.supports_qr_model(qr.model) {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(Command::QrCode(qr))
+    }
+
+    /// Store a greyscale image in the printer's non-volatile memory under
+    /// the provided [NvKey]. The image is checked against the model just
+    /// like [Self::print_image].
+    ///
+    /// NV memory has a limited number of write cycles, so this should be
+    /// done once (for instance, at install time), not once per receipt.
+    pub fn upload_nv_logo(&mut self, key: NvKey, img: image::GrayImage) -> Result<()> {
+        self.model.check_image(&img)?;
+        self.write_command(Command::DefineNvLogo { key, image: img })
+    }
+
+    /// Print an image previously stored with [Self::upload_nv_logo].
+    pub fn print_nv_logo(&mut self, key: NvKey, scale: ImageScale) -> Result<()> {
+        self.write_command(Command::PrintNvLogo { key, scale })
+    }
+
+    /// Delete an image previously stored with [Self::upload_nv_logo].
+    pub fn delete_nv_logo(&mut self, key: NvKey) -> Result<()> {
+        self.write_command(Command::DeleteNvLogo(key))
+    }
+
+    /// Print a greyscale image as column-format `ESC *` bit images, rather
+    /// than a raster image. This is slower, but some older or generic
+    /// printers only render `ESC *` reliably.
+    ///
+    /// Single density modes print every column twice as wide, so the image
+    /// should be half as wide as it would be otherwise.
+    pub fn print_image_columns(
+        &mut self,
+        img: image::GrayImage,
+        mode: ColumnImageMode,
+    ) -> Result<()> {
+        self.model.check_image(&img)?;
+        self.write_command(Command::ColumnImage(mode, img))
+    }
+
+    /// Define a downloaded bit image in the printer's RAM, which can then
+    /// be printed any number of times with [Self::print_downloaded_image]
+    /// until the printer is reset. The image may be no more than 2040 dots
+    /// wide and 384 dots tall, and, with each side rounded up to a multiple
+    /// of 8, no more than 98304 dots in total.
+    pub fn define_downloaded_image(&mut self, img: image::GrayImage) -> Result<()> {
+        self.model.check_image(&img)?;
+        self.write_command(Command::DefineDownloadedImage(img))
+    }
+
+    /// Print the image defined with [Self::define_downloaded_image].
+    pub fn print_downloaded_image(&mut self, scale: ImageScale) -> Result<()> {
+        self.write_command(Command::PrintDownloadedImage(scale))
+    }
+
+    /// Ask the printer to send back a real-time status byte of the provided
+    /// [StatusKind]. The response must be read from the underlying
+    /// transport, and can be parsed with [crate::RealTimeStatus::parse].
+    pub fn request_status(&mut self, kind: StatusKind) -> Result<()> {
+        self.write_command(Command::RealTimeStatusRequest(kind))
+    }
+
+    /// Ask the printer to send back a status byte of the provided
+    /// [TransmitStatusKind], once everything sent before it has been
+    /// processed. The response must be read from the underlying transport.
+    pub fn transmit_status(&mut self, kind: TransmitStatusKind) -> Result<()> {
+        self.write_command(Command::TransmitStatus(kind))
+    }
+
+    /// Ask the printer to send back `id`, four ASCII digits, once
+    /// everything sent before it has finished printing. The response must
+    /// be read from the underlying transport.
+    pub fn request_process_id(&mut self, id: [u8; 4]) -> Result<()> {
+        self.write_command(Command::RequestProcessId(id))
+    }
+
+    /// Enable Automatic Status Back (ASB) for the provided [AsbFlags], after
+    /// which the printer will send a 4 byte frame whenever that status
+    /// changes. Frames must be read from the underlying transport, and can
+    /// be parsed with [crate::AsbStatus::parse]. Pass the default flags to
+    /// disable ASB.
+    pub fn enable_asb(&mut self, flags: AsbFlags) -> Result<()> {
+        self.write_command(Command::EnableAsb(flags))
+    }
+
+    /// Ask the printer to send back information of the provided [InfoKind].
+    /// The response must be read from the underlying transport, and can be
+    /// parsed with [crate::PrinterInfo::parse].
+    pub fn request_printer_id(&mut self, kind: InfoKind) -> Result<()> {
+        self.write_command(Command::RequestPrinterId(kind))
+    }
+
+    /// Ask the printer to send back the value of a [MaintenanceCounter]. If
+    /// `cumulative` is true, the lifetime value is requested. The response
+    /// must be read from the underlying transport, and can be parsed with
+    /// [MaintenanceCounter::parse_value].
+    pub fn request_maintenance_counter(
+        &mut self,
+        counter: MaintenanceCounter,
+        cumulative: bool,
+    ) -> Result<()> {
+        self.write_command(Command::RequestMaintenanceCounter {
+            counter,
+            cumulative,
+        })
+    }
+
+    /// Reset a [MaintenanceCounter] to zero. Cumulative counters can't be
+    /// reset.
+    pub fn reset_maintenance_counter(&mut self, counter: MaintenanceCounter) -> Result<()> {
+        self.write_command(Command::ResetMaintenanceCounter(counter))
+    }
+
+    /// Cancel the current job, by clearing the printer's receive and print
+    /// buffers in real time. Anything sent but not yet printed is lost.
+    pub fn cancel(&mut self) -> Result<()> {
+        self.write_command(Command::ClearBuffers)
+    }
+
+    /// Recover from a recoverable error, such as a cleared cutter jam or a
+    /// closed cover, and restart printing from the line where the error
+    /// occurred. If `cancel` is true, the interrupted job is cancelled
+    /// instead, by clearing the receive and print buffers.
+    pub fn recover(&mut self, cancel: bool) -> Result<()> {
+        self.write_command(if cancel {
+            Command::RecoverAndCancel
+        } else {
+            Command::RecoverAndRestart
+        })
+    }
+
+    /// Delete all the print data in the page mode print area.
+    pub fn cancel_page_data(&mut self) -> Result<()> {
+        self.write_command(Command::CancelPageData)
+    }
+
+    /// Send an image as a series of `GS v 0` raster images, each no taller
+    /// than [IMAGE_BAND_HEIGHT], which print back to back without a gap.
+    fn write_image(&mut self, img: image::GrayImage, scale: ImageScale) -> Result<()> {
+        if img.height() <= IMAGE_BAND_HEIGHT {
+            return self.write_command(Command::Image(img, scale));
+        }
+        for band in bands(&img, IMAGE_BAND_HEIGHT) {
+            self.write_command(Command::Image(band, scale))?;
+        }
+        Ok(())
+    }
+
+    /// Send a character effect command, if the model supports them.
+    fn write_effect_command(&mut self, cmd: Command) -> Result<()> {
+        if !self.model.supports_character_effects() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(cmd)
+    }
+
+    /// Send a label paper command, if the model supports label paper.
+    fn write_label_command(&mut self, cmd: Command) -> Result<()> {
+        if !self.model.supports_label_paper() {
+            return Err(EpsonError::Unsupported.into());
+        }
+        self.write_command(cmd)
     }
 
     /// Send a raw command to the Epson printer.
     fn write_command(&mut self, cmd: Command) -> Result<()> {
         self.write_all(&cmd.as_bytes()?)?;
+        self.track(&cmd);
         Ok(())
     }
+
+    /// Keep track of the style, [CodePage] and [CharacterSet] `cmd`
+    /// leaves the printer in.
+    fn track(&mut self, cmd: &Command) {
+        self.style.track(cmd);
+        match *cmd {
+            Command::Init => {
+                self.code_page = CodePage::Pc437;
+                self.character_set = CharacterSet::Raw;
+            }
+            Command::CodePage(page) => self.code_page = page,
+            Command::CharacterSet(set) => self.character_set = set,
+            _ => {}
+        }
+    }
+}
+
+impl std::fmt::Write for Writer {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.print_text(s).map_err(|_| std::fmt::Error)
+    }
 }
 
 impl Write for Writer {
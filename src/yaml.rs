@@ -0,0 +1,685 @@
+// {{{ Copyright (c) Paul R. Tagliamonte <paultag@gmail.com>, 2016,2024
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE. }}}
+
+//! Just enough of a YAML reader to load hand-edited configuration, such
+//! as [crate::Document]s, into the same [Value]s the JSON reader returns.
+//!
+//! This reads block mappings and sequences, flow collections (`[a, b]`
+//! and `{a: 1}`), plain, single and double quoted scalars, literal and
+//! folded block scalars (`|` and `>`), and comments. Scalars are resolved
+//! with the YAML 1.2 core schema, so `yes` is a string, not a boolean.
+//! Anchors, aliases, tags, multi-line plain scalars and multiple
+//! documents aren't supported, and are rejected rather than misread.
+
+use super::json::{ParseError, Value, MAX_DEPTH};
+
+/// Parse a single YAML document.
+pub(crate) fn parse(input: &str) -> Result<Value, ParseError> {
+    let mut parser = Parser {
+        lines: input.lines().map(str::to_owned).collect(),
+        pos: 0,
+        depth: 0,
+    };
+    if let Some((0, "---")) = parser.peek()?.as_ref().map(|(i, l)| (*i, l.as_str())) {
+        parser.pos += 1;
+    }
+    let value = parser.node(0)?;
+    match parser.peek()? {
+        None => Ok(value),
+        Some(_) => Err(ParseError),
+    }
+}
+
+/// Return the characters of `line` which aren't inside a quoted scalar,
+/// with their byte offsets. Quotes only start a scalar at the start of a
+/// token, so an apostrophe in `Don't` is just a character.
+fn unquoted(line: &str) -> Vec<(usize, char)> {
+    let mut out = vec![];
+    let mut quote = None;
+    let mut prev = ' ';
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match quote {
+            Some('"') if c == '\\' => {
+                chars.next();
+            }
+            Some('\'') if c == '\'' && chars.peek().map(|(_, c)| *c) == Some('\'') => {
+                chars.next();
+            }
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if (c == '"' || c == '\'') && (prev.is_whitespace() || "[{,".contains(prev)) => {
+                quote = Some(c)
+            }
+            None => out.push((i, c)),
+        }
+        prev = c;
+    }
+    out
+}
+
+/// Remove a trailing comment, which starts with a `#` at the start of
+/// the line or after whitespace.
+fn strip_comment(line: &str) -> &str {
+    let end = unquoted(line)
+        .into_iter()
+        .find(|&(i, c)| c == '#' && (i == 0 || line[..i].ends_with(char::is_whitespace)))
+        .map_or(line.len(), |(i, _)| i);
+    line[..end].trim_end()
+}
+
+/// Split a mapping entry into its key and the rest of the line, if
+/// `line` is one.
+fn split_key(line: &str) -> Result<Option<(String, &str)>, ParseError> {
+    let mut depth = 0;
+    for (i, c) in unquoted(line) {
+        match c {
+            '[' | '{' => depth += 1,
+            ']' | '}' => depth -= 1,
+            ':' if depth == 0 && line[i + 1..].chars().next().is_none_or(char::is_whitespace) => {
+                let key = line[..i].trim();
+                let key = match key.chars().next() {
+                    Some('"' | '\'') => match scalar(key)? {
+                        Value::String(key) => key,
+                        _ => return Err(ParseError),
+                    },
+                    Some(_) => key.to_owned(),
+                    None => return Err(ParseError),
+                };
+                return Ok(Some((key, line[i + 1..].trim())));
+            }
+            _ => {}
+        }
+    }
+    Ok(None)
+}
+
+/// Return true if `line` is an entry of a block sequence.
+fn is_item(line: &str) -> bool {
+    line == "-" || line.starts_with("- ")
+}
+
+/// Return true if every flow collection opened in `text` is closed.
+fn balanced(text: &str) -> bool {
+    let depth: i32 = unquoted(text)
+        .into_iter()
+        .map(|(_, c)| match c {
+            '[' | '{' => 1,
+            ']' | '}' => -1,
+            _ => 0,
+        })
+        .sum();
+    depth <= 0
+}
+
+/// Resolve a plain scalar with the core schema.
+fn plain(text: &str) -> Value {
+    match text {
+        "" | "~" | "null" | "Null" | "NULL" => return Value::Null,
+        "true" | "True" | "TRUE" => return Value::Bool(true),
+        "false" | "False" | "FALSE" => return Value::Bool(false),
+        ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => {
+            return Value::Number(f64::INFINITY)
+        }
+        "-.inf" | "-.Inf" | "-.INF" => return Value::Number(f64::NEG_INFINITY),
+        ".nan" | ".NaN" | ".NAN" => return Value::Number(f64::NAN),
+        _ => {}
+    }
+    let radix = |prefix, radix| {
+        text.strip_prefix(prefix)
+            .and_then(|digits| i64::from_str_radix(digits, radix).ok())
+    };
+    if let Some(n) = radix("0x", 16).or_else(|| radix("0o", 8)) {
+        return Value::Number(n as f64);
+    }
+    // Rust also parses words such as "inf" and "NaN", which are strings
+    // in YAML.
+    let numeric = text.chars().any(|c| c.is_ascii_digit())
+        && text
+            .chars()
+            .all(|c| c.is_ascii_digit() || "+-.eE".contains(c));
+    match text.parse() {
+        Ok(n) if numeric => Value::Number(n),
+        _ => Value::String(text.to_owned()),
+    }
+}
+
+/// Parse a double quoted scalar at the start of `text`, returning it and
+/// the number of bytes it took up.
+fn double_quoted(text: &str) -> Result<(String, usize), ParseError> {
+    let mut out = String::new();
+    let mut chars = text.char_indices().skip(1);
+    let hex = |chars: &mut std::iter::Skip<std::str::CharIndices>, len| {
+        let mut n = 0;
+        for _ in 0..len {
+            let (_, c) = chars.next().ok_or(ParseError)?;
+            n = n * 16 + c.to_digit(16).ok_or(ParseError)?;
+        }
+        char::from_u32(n).ok_or(ParseError)
+    };
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((out, i + 1)),
+            '\\' => {
+                let (_, c) = chars.next().ok_or(ParseError)?;
+                out.push(match c {
+                    '0' => '\0',
+                    'a' => '\u{7}',
+                    'b' => '\u{8}',
+                    't' | '\t' => '\t',
+                    'n' => '\n',
+                    'v' => '\u{b}',
+                    'f' => '\u{c}',
+                    'r' => '\r',
+                    'e' => '\u{1b}',
+                    ' ' | '"' | '/' | '\\' => c,
+                    'N' => '\u{85}',
+                    '_' => '\u{a0}',
+                    'x' => hex(&mut chars, 2)?,
+                    'u' => hex(&mut chars, 4)?,
+                    'U' => hex(&mut chars, 8)?,
+                    _ => return Err(ParseError),
+                });
+            }
+            c => out.push(c),
+        }
+    }
+    Err(ParseError)
+}
+
+/// Parse a single quoted scalar at the start of `text`, returning it and
+/// the number of bytes it took up.
+fn single_quoted(text: &str) -> Result<(String, usize), ParseError> {
+    let mut out = String::new();
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != '\'' {
+            out.push(c);
+        } else if chars.peek().map(|(_, c)| *c) == Some('\'') {
+            chars.next();
+            out.push('\'');
+        } else {
+            return Ok((out, i + 1));
+        }
+    }
+    Err(ParseError)
+}
+
+/// Parse a whole line's worth of scalar, which isn't a flow collection
+/// or block scalar.
+fn scalar(text: &str) -> Result<Value, ParseError> {
+    let quoted = match text.chars().next() {
+        Some('"') => double_quoted(text)?,
+        Some('\'') => single_quoted(text)?,
+        Some('&' | '*' | '!' | '%' | '@' | '`') => return Err(ParseError),
+        _ => return Ok(plain(text)),
+    };
+    match text[quoted.1..].trim() {
+        "" => Ok(Value::String(quoted.0)),
+        _ => Err(ParseError),
+    }
+}
+
+/// Reads a flow collection, such as `[Coffee, "$3.50"]`.
+struct Flow<'a> {
+    text: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+impl Flow<'_> {
+    fn peek(&mut self) -> Option<char> {
+        self.text[self.pos..].trim_start().chars().next()
+    }
+
+    /// Skip whitespace, and consume `c` if it's next.
+    fn eat(&mut self, c: char) -> bool {
+        self.pos = self.text.len() - self.text[self.pos..].trim_start().len();
+        let found = self.text[self.pos..].starts_with(c);
+        if found {
+            self.pos += c.len_utf8();
+        }
+        found
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        match self.peek().ok_or(ParseError)? {
+            '[' | '{' => {
+                if self.depth == MAX_DEPTH {
+                    return Err(ParseError);
+                }
+                self.depth += 1;
+                let value = self.collection();
+                self.depth -= 1;
+                value
+            }
+            '"' | '\'' => {
+                self.eat(' ');
+                let rest = &self.text[self.pos..];
+                let (value, len) = match rest.starts_with('"') {
+                    true => double_quoted(rest)?,
+                    false => single_quoted(rest)?,
+                };
+                self.pos += len;
+                Ok(Value::String(value))
+            }
+            _ => {
+                let rest = &self.text[self.pos..];
+                let end = rest
+                    .char_indices()
+                    .find(|&(i, c)| {
+                        ",[]{}".contains(c)
+                            || (c == ':'
+                                && rest[i + 1..]
+                                    .chars()
+                                    .next()
+                                    .is_none_or(|c| c.is_whitespace() || ",[]{}".contains(c)))
+                    })
+                    .map_or(rest.len(), |(i, _)| i);
+                self.pos += end;
+                match rest[..end].trim() {
+                    text if text.starts_with(['&', '*', '!', '%', '@', '`']) => Err(ParseError),
+                    text => Ok(plain(text)),
+                }
+            }
+        }
+    }
+
+    fn collection(&mut self) -> Result<Value, ParseError> {
+        let mapping = self.eat('{');
+        if !mapping {
+            self.eat('[');
+        }
+        let close = if mapping { '}' } else { ']' };
+        let mut items = vec![];
+        let mut entries = vec![];
+        loop {
+            if self.eat(close) {
+                break;
+            }
+            if mapping {
+                let key = match self.value()? {
+                    Value::String(key) => key,
+                    Value::Null => String::new(),
+                    _ => return Err(ParseError),
+                };
+                let value = if self.eat(':') {
+                    match self.peek() {
+                        Some(',') | Some('}') => Value::Null,
+                        _ => self.value()?,
+                    }
+                } else {
+                    Value::Null
+                };
+                if key.is_empty() || entries.iter().any(|(k, _)| *k == key) {
+                    return Err(ParseError);
+                }
+                entries.push((key, value));
+            } else {
+                items.push(self.value()?);
+            }
+            if !self.eat(',') {
+                if !self.eat(close) {
+                    return Err(ParseError);
+                }
+                break;
+            }
+        }
+        Ok(match mapping {
+            true => Value::Object(entries),
+            false => Value::Array(items),
+        })
+    }
+}
+
+struct Parser {
+    lines: Vec<String>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    /// Skip blank lines and comments, and return the indentation and
+    /// content of the next line, without consuming it.
+    fn peek(&mut self) -> Result<Option<(usize, String)>, ParseError> {
+        while let Some(line) = self.lines.get(self.pos) {
+            let content = strip_comment(line);
+            let trimmed = content.trim_start();
+            if trimmed.is_empty() {
+                self.pos += 1;
+                continue;
+            }
+            let indent = &content[..content.len() - trimmed.len()];
+            if indent.contains('\t') {
+                return Err(ParseError);
+            }
+            if trimmed == "..." || (indent.is_empty() && trimmed.starts_with("---")) {
+                // Only a single document is supported.
+                return match trimmed {
+                    "..."
+                        if self.lines[self.pos + 1..]
+                            .iter()
+                            .all(|line| strip_comment(line).trim().is_empty()) =>
+                    {
+                        Ok(None)
+                    }
+                    "---"
+                        if self.lines[..self.pos]
+                            .iter()
+                            .all(|line| strip_comment(line).trim().is_empty()) =>
+                    {
+                        Ok(Some((0, "---".to_owned())))
+                    }
+                    _ => Err(ParseError),
+                };
+            }
+            if indent.is_empty() && trimmed.starts_with('%') {
+                return Err(ParseError);
+            }
+            return Ok(Some((indent.len(), trimmed.to_owned())));
+        }
+        Ok(None)
+    }
+
+    /// Parse one level deeper, limiting how deep documents may nest.
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Value, ParseError>,
+    ) -> Result<Value, ParseError> {
+        if self.depth == MAX_DEPTH {
+            return Err(ParseError);
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    /// Parse the node starting on the next line, which must be indented
+    /// by at least `min_indent`, or is null.
+    fn node(&mut self, min_indent: usize) -> Result<Value, ParseError> {
+        let Some((indent, content)) = self.peek()? else {
+            return Ok(Value::Null);
+        };
+        if indent < min_indent {
+            return Ok(Value::Null);
+        }
+        if is_item(&content) {
+            self.nested(|p| p.sequence(indent))
+        } else if split_key(&content)?.is_some() {
+            self.nested(|p| p.mapping(indent))
+        } else {
+            self.pos += 1;
+            self.inline(content, min_indent)
+        }
+    }
+
+    fn sequence(&mut self, indent: usize) -> Result<Value, ParseError> {
+        let mut items = vec![];
+        while let Some((next, content)) = self.peek()? {
+            if next != indent || !is_item(&content) {
+                break;
+            }
+            let rest = content[1..].trim_start();
+            if rest.is_empty() {
+                self.pos += 1;
+            } else {
+                // Carry on as if the rest of the line, such as the first
+                // entry of a mapping, were on a line of its own.
+                let column = indent + content.len() - rest.len();
+                self.lines[self.pos] = format!("{}{}", " ".repeat(column), rest);
+            }
+            items.push(self.node(indent + 1)?);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<Value, ParseError> {
+        let mut entries: Vec<(String, Value)> = vec![];
+        while let Some((next, content)) = self.peek()? {
+            if next < indent {
+                break;
+            }
+            let (key, rest) = match (next == indent, split_key(&content)?) {
+                (true, Some(entry)) => entry,
+                _ => return Err(ParseError),
+            };
+            self.pos += 1;
+            let value = if rest.is_empty() {
+                match self.peek()? {
+                    // A sequence may sit at the same indentation as its key.
+                    Some((next, content)) if next == indent && is_item(&content) => {
+                        self.nested(|p| p.sequence(indent))?
+                    }
+                    _ => self.node(indent + 1)?,
+                }
+            } else {
+                self.inline(rest.to_owned(), indent + 1)?
+            };
+            if entries.iter().any(|(k, _)| *k == key) {
+                return Err(ParseError);
+            }
+            entries.push((key, value));
+        }
+        Ok(Value::Object(entries))
+    }
+
+    /// Parse a value written after a key or sequence dash, whose block
+    /// scalar or continued flow collection must be indented by at least
+    /// `min_indent`.
+    fn inline(&mut self, mut text: String, min_indent: usize) -> Result<Value, ParseError> {
+        match text.chars().next() {
+            Some('[' | '{') => {
+                while !balanced(&text) {
+                    match self.peek()? {
+                        Some((indent, content)) if indent >= min_indent => {
+                            text.push(' ');
+                            text.push_str(&content);
+                            self.pos += 1;
+                        }
+                        _ => return Err(ParseError),
+                    }
+                }
+                let mut flow = Flow {
+                    text: &text,
+                    pos: 0,
+                    depth: self.depth,
+                };
+                let value = flow.value()?;
+                match flow.peek() {
+                    None => Ok(value),
+                    Some(_) => Err(ParseError),
+                }
+            }
+            Some('|' | '>') => self.block_scalar(&text, min_indent),
+            _ => scalar(&text),
+        }
+    }
+
+    /// Parse a literal (`|`) or folded (`>`) block scalar, with the
+    /// header `header`, from the lines which follow.
+    fn block_scalar(&mut self, header: &str, min_indent: usize) -> Result<Value, ParseError> {
+        let folded = header.starts_with('>');
+        let chomp = match &header[1..] {
+            "" => None,
+            "-" => Some(false),
+            "+" => Some(true),
+            _ => return Err(ParseError),
+        };
+
+        let mut lines = vec![];
+        let mut block_indent = None;
+        while let Some(line) = self.lines.get(self.pos) {
+            let content = line.trim_start_matches(' ');
+            let indent = line.len() - content.len();
+            if content.trim().is_empty() {
+                lines.push("");
+                self.pos += 1;
+                continue;
+            }
+            let block_indent = *block_indent.get_or_insert(indent);
+            if indent < block_indent.max(min_indent) {
+                break;
+            }
+            lines.push(&line[block_indent..]);
+            self.pos += 1;
+        }
+        if block_indent.is_some_and(|indent| indent < min_indent) {
+            return Err(ParseError);
+        }
+
+        let content = lines
+            .iter()
+            .rposition(|line| !line.is_empty())
+            .map_or(0, |i| i + 1);
+        let mut text = String::new();
+        for (i, line) in lines[..content].iter().enumerate() {
+            if i > 0 {
+                let joined = folded
+                    && !line.is_empty()
+                    && !lines[i - 1].is_empty()
+                    && !line.starts_with(' ')
+                    && !lines[i - 1].starts_with(' ');
+                text.push(if joined { ' ' } else { '\n' });
+            }
+            text.push_str(line);
+        }
+        if folded {
+            // Blank lines between folded lines stand for one line break
+            // each, rather than two.
+            text = text.replace("\n\n", "\n");
+        }
+        match chomp {
+            _ if content == 0 => {}
+            Some(false) => {}
+            None => text.push('\n'),
+            Some(true) => text.push_str(&"\n".repeat(lines.len() - content + 1)),
+        }
+        Ok(Value::String(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_owned())
+    }
+
+    #[test]
+    fn yaml_block() {
+        let value = parse(
+            "---
+# A receipt
+name: Cafe   # trailing comment
+items:
+  - name: Coffee
+    price: 3.5
+  - {name: Bagel, price: 2.25}
+tags:
+- 'it''s'
+- \"caf\\u00e9 #1\"
+empty:
+flags: [true, False, ~, 0x1f, yes]
+",
+        )
+        .unwrap();
+        assert_eq!(Some(&string("Cafe")), value.get("name"));
+        let items = value.get("items").and_then(Value::as_array).unwrap();
+        assert_eq!(Some(&Value::Number(3.5)), items[0].get("price"));
+        assert_eq!(Some(&string("Bagel")), items[1].get("name"));
+        assert_eq!(
+            Some(&Value::Array(vec![string("it's"), string("caf\u{e9} #1")])),
+            value.get("tags")
+        );
+        assert_eq!(Some(&Value::Null), value.get("empty"));
+        assert_eq!(
+            Some(&Value::Array(vec![
+                Value::Bool(true),
+                Value::Bool(false),
+                Value::Null,
+                Value::Number(31.0),
+                string("yes"),
+            ])),
+            value.get("flags")
+        );
+    }
+
+    #[test]
+    fn yaml_flow_continued() {
+        let value = parse("rows: [[Coffee, \"$3.50\"],\n       [Bagel, $2.25]]\n").unwrap();
+        assert_eq!(
+            Some(&Value::Array(vec![
+                Value::Array(vec![string("Coffee"), string("$3.50")]),
+                Value::Array(vec![string("Bagel"), string("$2.25")]),
+            ])),
+            value.get("rows")
+        );
+    }
+
+    #[test]
+    fn yaml_block_scalars() {
+        let value = parse(
+            "literal: |
+  Thanks for
+  visiting!
+
+folded: >-
+  Thanks for
+  visiting!
+plain: Don't panic
+",
+        )
+        .unwrap();
+        assert_eq!(
+            Some(&string("Thanks for\nvisiting!\n")),
+            value.get("literal")
+        );
+        assert_eq!(Some(&string("Thanks for visiting!")), value.get("folded"));
+        assert_eq!(Some(&string("Don't panic")), value.get("plain"));
+    }
+
+    #[test]
+    fn yaml_nested_sequences() {
+        assert_eq!(
+            Ok(Value::Array(vec![
+                Value::Array(vec![Value::Number(1.0), Value::Number(2.0)]),
+                Value::Number(3.0),
+            ])),
+            parse("- - 1\n  - 2\n- 3\n")
+        );
+    }
+
+    #[test]
+    fn yaml_invalid() {
+        assert_eq!(Err(ParseError), parse("a: 1\n  b: 2\n"));
+        assert_eq!(Err(ParseError), parse("a: 1\na: 2\n"));
+        assert_eq!(Err(ParseError), parse("a: [1, 2\n"));
+        assert_eq!(Err(ParseError), parse("a: &anchor 1\n"));
+        assert_eq!(Err(ParseError), parse("a: 1\n---\nb: 2\n"));
+        assert_eq!(Err(ParseError), parse("a:\n\t- 1\n"));
+        assert_eq!(Err(ParseError), parse("a: \"unterminated\n"));
+        assert_eq!(Err(ParseError), parse(&"- ".repeat(100_000)));
+        assert_eq!(Err(ParseError), parse(&"[".repeat(100_000)));
+    }
+}
+
+// vim: foldmethod=marker